@@ -5,12 +5,21 @@
 //! Handles authentication and connection to NATS server.
 //! Used by both provider and consumer modules.
 
-use std::collections::HashSet;
-
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Debug,
+    future::Future,
+    pin::Pin,
+    str::FromStr,
+    sync::Arc,
+};
+
+use futures::Stream;
+use thiserror::Error;
 use tokio::sync::broadcast;
 use tracing::{debug, error};
 
-use crate::oauth2::OAuth2Credentials;
+use crate::oauth2::{ClientAuthStyle, OAuth2Credentials};
 
 type Result<T> = core::result::Result<T, async_nats::Error>;
 
@@ -21,7 +30,7 @@ pub const DEFAULT_U_OS_NATS_ADDRESS: &str = "nats://127.0.0.1:49360";
 pub const DEFAULT_U_OS_OAUTH2_ENDPOINT: &str = "https://127.0.0.1/oauth2/token";
 
 /// Access permissions for the NATS connection.
-/// Internally gets converted to Oauth2 scopes.
+/// Internally gets converted to Oauth2 scopes, see [`AuthenticationSettingsBuilder::with_scope_mapping`].
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub enum NatsPermission {
     /// Read-only access to the variable hub for consumers.
@@ -44,18 +53,228 @@ impl NatsPermission {
     }
 }
 
+/// Error returned when parsing an unknown `NatsPermission` scope string.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[error("Unknown NATS permission `{0}`")]
+pub struct UnknownNatsPermissionError(pub String);
+
+impl TryFrom<&str> for NatsPermission {
+    type Error = UnknownNatsPermissionError;
+
+    /// Parses an Oauth2 scope string as returned by [`NatsPermission::as_str`].
+    fn try_from(value: &str) -> core::result::Result<Self, Self::Error> {
+        match value {
+            "hub.variables.readonly" => Ok(NatsPermission::VariableHubRead),
+            "hub.variables.readwrite" => Ok(NatsPermission::VariableHubReadWrite),
+            "hub.variables.provide" => Ok(NatsPermission::VariableHubProvide),
+            _ => Err(UnknownNatsPermissionError(value.to_owned())),
+        }
+    }
+}
+
+impl FromStr for NatsPermission {
+    type Err = UnknownNatsPermissionError;
+
+    fn from_str(s: &str) -> core::result::Result<Self, Self::Err> {
+        Self::try_from(s)
+    }
+}
+
+/// Error returned when connecting to a NATS server address that is obviously malformed.
+///
+/// Returned boxed by the constructors of [`AuthenticatedNatsConnection`], e.g. [`AuthenticatedNatsConnection::new`],
+/// and can be detected via `downcast_ref`.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[error("Invalid NATS server address `{address}`: {reason}")]
+pub struct InvalidServerAddressError {
+    /// The address as it was passed by the user.
+    pub address: String,
+    /// Describes why the address is invalid.
+    pub reason: String,
+}
+
+/// Error of an established [`AuthenticatedNatsConnection`], see [`AuthenticatedNatsConnection::new_with_error_stream`].
+///
+/// The connection recovers from most of these errors by itself, e.g. by reconnecting.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[allow(missing_docs)]
+pub enum ConnectionError {
+    /// Requesting the `OAuth2` token or the credentials failed, contains the reason.
+    ///
+    /// The NATS client additionally reports the failed connection attempt as [`Self::Client`].
+    #[error("Authentication failed: {0}")]
+    Authentication(String),
+    /// The server rejected the credentials or the permissions of the client.
+    #[error("Authorization violation")]
+    AuthorizationViolation,
+    /// A subscription could not keep up and messages were dropped, contains the subscription id.
+    #[error("Slow consumer detected for subscription {0}")]
+    SlowConsumer(u64),
+    #[error("Server error: {0}")]
+    Server(String),
+    /// The client reported an error, e.g. because a connection attempt failed.
+    #[error("Client error: {0}")]
+    Client(String),
+    /// The client gave up reconnecting, the connection is unusable afterwards.
+    #[error("Maximum number of reconnects reached")]
+    MaxReconnects,
+}
+
+impl ConnectionError {
+    /// Maps a raw NATS event to an error, returns `None` for events that are no errors.
+    fn from_event(event: &async_nats::Event) -> Option<Self> {
+        match event {
+            async_nats::Event::ServerError(async_nats::ServerError::AuthorizationViolation) => {
+                Some(Self::AuthorizationViolation)
+            }
+            async_nats::Event::ServerError(async_nats::ServerError::SlowConsumer(sid))
+            | async_nats::Event::SlowConsumer(sid) => Some(Self::SlowConsumer(*sid)),
+            async_nats::Event::ServerError(async_nats::ServerError::Other(error)) => {
+                Some(Self::Server(error.clone()))
+            }
+            async_nats::Event::ClientError(async_nats::ClientError::MaxReconnects) => {
+                Some(Self::MaxReconnects)
+            }
+            async_nats::Event::ClientError(async_nats::ClientError::Other(error)) => {
+                Some(Self::Client(error.clone()))
+            }
+            async_nats::Event::Connected
+            | async_nats::Event::Disconnected
+            | async_nats::Event::LameDuckMode
+            | async_nats::Event::Draining
+            | async_nats::Event::Closed => None,
+        }
+    }
+}
+
+/// Number of connection errors that are buffered for slow receivers.
+const CONNECTION_ERROR_BUFFER: usize = 128;
+
+/// Schemes of NATS server addresses that are supported by the NATS client.
+const SUPPORTED_SERVER_ADDRESS_SCHEMES: [&str; 2] = ["nats", "tls"];
+
+/// Checks that all comma separated addresses in `address` are NATS URLs with a supported scheme and a host.
+fn validate_server_address(address: &str) -> core::result::Result<(), InvalidServerAddressError> {
+    let invalid = |reason: String| InvalidServerAddressError {
+        address: address.to_owned(),
+        reason,
+    };
+
+    if address.trim().is_empty() {
+        return Err(invalid("The address is empty".to_owned()));
+    }
+
+    for server in address.split(',').map(str::trim) {
+        if !server.contains("://") {
+            return Err(invalid(format!(
+                "`{server}` has no scheme, use e.g. `nats://{server}`"
+            )));
+        }
+
+        let url = reqwest::Url::parse(server).map_err(|e| invalid(format!("`{server}`: {e}")))?;
+
+        if !SUPPORTED_SERVER_ADDRESS_SCHEMES.contains(&url.scheme()) {
+            return Err(invalid(format!(
+                "`{server}` has the unsupported scheme `{}`, expected `nats://` or `tls://`",
+                url.scheme()
+            )));
+        }
+
+        if url.host_str().map_or(true, str::is_empty) {
+            return Err(invalid(format!("`{server}` has no host")));
+        }
+    }
+
+    Ok(())
+}
+
 /// A set of NATS permissions.
 pub type NatsPermissionList = HashSet<String>;
 
+/// Future returned by a [`CredentialsProvider`].
+pub type CredentialsFuture = Pin<Box<dyn Future<Output = OAuth2Credentials> + Send>>;
+
+/// Callback that supplies fresh `OAuth2` credentials each time the connection (re)authenticates.
+///
+/// This allows rotating secrets without restarting the process.
+#[derive(Clone)]
+pub struct CredentialsProvider(Arc<dyn Fn() -> CredentialsFuture + Send + Sync>);
+
+impl CredentialsProvider {
+    /// Calls the provider callback and returns the current credentials.
+    pub async fn get_credentials(&self) -> OAuth2Credentials {
+        (self.0)().await
+    }
+}
+
+impl Debug for CredentialsProvider {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("CredentialsProvider")
+    }
+}
+
 /// Determines how the connection authenticates to the NATS server.
 #[derive(Clone, Debug)]
 pub struct AuthenticationSettings {
     /// A list of oauth2 scopes that are used to authenticate the client.
+    ///
+    /// Scopes of a [`NatsPermission`] are stored with their built-in name and replaced
+    /// according to [`Self::scope_mapping`] when requesting a token, see [`Self::oauth2_scopes`].
     pub permissions: NatsPermissionList,
+    /// Overrides the oauth2 scope that is requested for a permission.
+    ///
+    /// Permissions without an entry use the built-in scope of [`NatsPermission::as_str`].
+    pub scope_mapping: HashMap<NatsPermission, String>,
     /// Address of the oauth2 endpoint.
     pub oauth2_endpoint: String,
     /// `OAuth2` credentials used to authenticate the client.
     pub creds: Option<OAuth2Credentials>,
+    /// Determines how the credentials are sent to the oauth2 endpoint.
+    pub client_auth_style: ClientAuthStyle,
+    /// Optional callback that supplies credentials on every (re)connect.
+    ///
+    /// If set, this takes precedence over [`Self::creds`].
+    pub creds_provider: Option<CredentialsProvider>,
+    /// Optional free-form description of the application, e.g. its name.
+    ///
+    /// Appended to the NATS connection name, see [`AuthenticatedNatsConnection::get_connection_name`].
+    pub client_description: Option<String>,
+    /// Optional version of the application.
+    ///
+    /// Appended to the NATS connection name, see [`AuthenticatedNatsConnection::get_connection_name`].
+    pub client_version: Option<String>,
+}
+
+impl AuthenticationSettings {
+    /// Returns the oauth2 scopes that are requested for the [`Self::permissions`], after applying the [`Self::scope_mapping`].
+    #[must_use]
+    pub fn oauth2_scopes(&self) -> Vec<&str> {
+        self.permissions
+            .iter()
+            .map(|scope| {
+                NatsPermission::try_from(scope.as_str())
+                    .ok()
+                    .and_then(|permission| self.scope_mapping.get(&permission))
+                    .unwrap_or(scope)
+                    .as_str()
+            })
+            .collect()
+    }
+
+    /// Builds the NATS connection name from `client_name`, the client description and the client version.
+    fn connection_name(&self, client_name: &str) -> String {
+        let tags: Vec<&str> = [&self.client_description, &self.client_version]
+            .into_iter()
+            .flatten()
+            .map(String::as_str)
+            .collect();
+
+        if tags.is_empty() {
+            client_name.to_owned()
+        } else {
+            format!("{client_name} ({})", tags.join(" "))
+        }
+    }
 }
 
 /// Helper struct to build the authentication settings.
@@ -72,12 +291,35 @@ impl AuthenticationSettingsBuilder {
         Self {
             settings: AuthenticationSettings {
                 permissions: NatsPermissionList::from([permission.as_str().to_owned()]),
+                scope_mapping: HashMap::new(),
                 oauth2_endpoint: DEFAULT_U_OS_OAUTH2_ENDPOINT.to_string(),
                 creds: None,
+                client_auth_style: ClientAuthStyle::default(),
+                creds_provider: None,
+                client_description: None,
+                client_version: None,
             },
         }
     }
 
+    /// Creates a builder for a provider that also reads variables of other providers, e.g. a gateway.
+    ///
+    /// Same as `new(NatsPermission::VariableHubProvide).add_permission(NatsPermission::VariableHubRead)`.
+    #[must_use]
+    pub fn provider_and_consumer() -> Self {
+        Self::new(NatsPermission::VariableHubProvide)
+            .add_permission(NatsPermission::VariableHubRead)
+    }
+
+    /// Creates a builder for a provider that also reads and writes variables of other providers.
+    ///
+    /// Same as `new(NatsPermission::VariableHubProvide).add_permission(NatsPermission::VariableHubReadWrite)`.
+    #[must_use]
+    pub fn provider_and_read_write_consumer() -> Self {
+        Self::new(NatsPermission::VariableHubProvide)
+            .add_permission(NatsPermission::VariableHubReadWrite)
+    }
+
     /// Allows to add multiple permissions at once.
     ///
     /// This is useful if the connection should be shared between e.g. a provider and a consumer.
@@ -89,6 +331,18 @@ impl AuthenticationSettingsBuilder {
         self
     }
 
+    /// Overrides the oauth2 scope names of the permissions.
+    ///
+    /// This is needed if the identity provider of a deployment uses different scope names for the same capabilities.
+    /// Permissions without an entry keep their built-in scope name, see [`NatsPermission::as_str`].
+    /// The mapping only affects the token request, [`AuthenticatedNatsConnection::get_permissions`]
+    /// still reports the built-in names.
+    #[must_use]
+    pub fn with_scope_mapping(mut self, mapping: HashMap<NatsPermission, String>) -> Self {
+        self.settings.scope_mapping = mapping;
+        self
+    }
+
     /// Allows to specificy oauth2 credentials.
     ///
     /// This is always needed for providers. For consumers,
@@ -100,6 +354,56 @@ impl AuthenticationSettingsBuilder {
         self
     }
 
+    /// Allows to specify a callback that supplies the oauth2 credentials.
+    ///
+    /// The callback is invoked every time the connection requests a new token, e.g. on each reconnect.
+    /// This is useful if credentials rotate and are fetched from an external source.
+    /// If set, the static credentials from [`Self::with_credentials`] are ignored.
+    ///
+    /// The NATS client name is taken from the credentials that are returned when the connection is created
+    /// and can not change afterwards.
+    #[must_use]
+    pub fn with_credentials_provider<F, Fut>(mut self, provider: F) -> Self
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = OAuth2Credentials> + Send + 'static,
+    {
+        self.settings.creds_provider = Some(CredentialsProvider(Arc::new(move || {
+            Box::pin(provider()) as CredentialsFuture
+        })));
+        self
+    }
+
+    /// Allows to specify how the credentials are sent to the oauth2 endpoint.
+    ///
+    /// Defaults to [`ClientAuthStyle::Basic`]. Some identity providers only accept [`ClientAuthStyle::Post`].
+    #[must_use]
+    pub fn with_client_auth_style(mut self, auth_style: ClientAuthStyle) -> Self {
+        self.settings.client_auth_style = auth_style;
+        self
+    }
+
+    /// Sets a free-form description of the application, e.g. its name.
+    ///
+    /// The description is appended to the NATS connection name, so server-side tooling
+    /// can identify which application a connection belongs to.
+    /// It does not affect the client name, the provider id or permissions.
+    #[must_use]
+    pub fn with_client_description(mut self, description: impl Into<String>) -> Self {
+        self.settings.client_description = Some(description.into());
+        self
+    }
+
+    /// Sets the version of the application, e.g. `env!("CARGO_PKG_VERSION")`.
+    ///
+    /// Like [`Self::with_client_description`], the version is appended to the NATS connection name.
+    /// This helps to tell connections apart during fleet upgrades.
+    #[must_use]
+    pub fn with_client_version(mut self, version: impl Into<String>) -> Self {
+        self.settings.client_version = Some(version.into());
+        self
+    }
+
     /// Allows to specifiy a different oauth2 endpoint address.
     ///
     /// Useful e.g. if the oauth endpoint is on another device.
@@ -115,10 +419,51 @@ impl AuthenticationSettingsBuilder {
     pub fn build(self) -> AuthenticationSettings {
         self.settings
     }
+
+    /// Same as [`Self::build`], but fails if the settings are obviously broken.
+    ///
+    /// Checks for an empty permission set, static credentials with an empty client id
+    /// (which would silently connect unauthenticated) and an unparsable `OAuth2` endpoint.
+    /// Credentials supplied via [`Self::with_credentials_provider`] can not be checked in advance.
+    pub fn build_validated(
+        self,
+    ) -> core::result::Result<AuthenticationSettings, AuthSettingsError> {
+        if self.settings.permissions.is_empty() {
+            return Err(AuthSettingsError::EmptyPermissions);
+        }
+
+        if let Some(creds) = &self.settings.creds {
+            if creds.client_id.is_empty() {
+                return Err(AuthSettingsError::EmptyClientId(creds.client_name.clone()));
+            }
+        }
+
+        if let Err(e) = reqwest::Url::parse(&self.settings.oauth2_endpoint) {
+            return Err(AuthSettingsError::InvalidOAuth2Endpoint {
+                endpoint: self.settings.oauth2_endpoint,
+                reason: e.to_string(),
+            });
+        }
+
+        Ok(self.settings)
+    }
+}
+
+/// Error returned by [`AuthenticationSettingsBuilder::build_validated`].
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[allow(missing_docs)]
+pub enum AuthSettingsError {
+    #[error("No NATS permissions specified")]
+    EmptyPermissions,
+    #[error("Credentials for client `{0}` have an empty client id, the connection would be unauthenticated")]
+    EmptyClientId(String),
+    #[error("Invalid OAuth2 endpoint `{endpoint}`: {reason}")]
+    InvalidOAuth2Endpoint { endpoint: String, reason: String },
 }
 
 /// The authentication method used to connect to the NATS server.
 #[derive(Debug)]
+#[allow(clippy::large_enum_variant)] // Only created once per connection, boxing would change the public API
 pub enum NatsAuthenticationMethod {
     /// Client wont authenticate at all and registers via name "_UNAUTHENTICATED".
     Unauthenticated,
@@ -144,29 +489,86 @@ pub struct AuthenticatedNatsConnection {
     event_sender: broadcast::Sender<async_nats::Event>,
     nats_permissions: Option<NatsPermissionList>,
     client_name: String,
+    connection_name: String,
 }
 
 impl AuthenticatedNatsConnection {
     /// Tries to connect and authenticate to the NATS server.
     ///
     /// If no `client_name` is suppied in the settings, "_UNAUTHENTICATED" is used.
+    /// If a credentials provider is set, it is called once to determine the client name.
     ///
     /// The constructor will wait until the first connection event is received by the nats client.
     /// No internal timeout is used. If you want, you can use `tokio::timeout` to limit the time for connection.
+    ///
+    /// Fails early with an [`InvalidServerAddressError`] if the address is not a `nats://` or `tls://` URL.
     pub async fn new(
         nats_server_addr: impl Into<String>,
         auth_settings: &AuthenticationSettings,
     ) -> Result<Self> {
-        let client_name = auth_settings
-            .creds
-            .as_ref()
-            .map(|creds| creds.client_name.clone());
+        let (error_sender, _) = broadcast::channel(CONNECTION_ERROR_BUFFER);
+        Self::new_impl(nats_server_addr, auth_settings, true, error_sender).await
+    }
 
-        Self::connect_with_auth_method(
+    /// Same as [`Self::new()`], but additionally returns a stream of all errors of the connection.
+    ///
+    /// The stream reports failed token requests, errors sent by the server and errors of the NATS client as typed
+    /// [`ConnectionError`]s, so there is no need to decode the raw events of [`Self::get_events()`].
+    /// If the receiver falls behind by more than 128 errors, the oldest ones are skipped.
+    /// The stream ends once the NATS client is dropped.
+    ///
+    /// In contrast to [`Self::new()`], this does not wait for the first connection, as it would wait forever
+    /// if e.g. the token request keeps failing. Instead, the NATS client keeps trying to connect in the background
+    /// and the stream reports why the attempts fail. Requests that are sent before the connection is established
+    /// are buffered by the NATS client. Use [`Self::get_events()`] to wait for the connection if needed.
+    pub async fn new_with_error_stream(
+        nats_server_addr: impl Into<String>,
+        auth_settings: &AuthenticationSettings,
+    ) -> Result<(Self, impl Stream<Item = ConnectionError> + Send + Unpin)> {
+        //must subscribe before connecting, as otherwise we may miss errors of the first connection attempts
+        let (error_sender, error_receiver) = broadcast::channel(CONNECTION_ERROR_BUFFER);
+        let con = Self::new_impl(nats_server_addr, auth_settings, false, error_sender).await?;
+
+        let errors = futures::stream::unfold(error_receiver, |mut error_receiver| async move {
+            loop {
+                match error_receiver.recv().await {
+                    Ok(error) => return Some((error, error_receiver)),
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        debug!("Skipped {skipped} connection errors of a slow receiver");
+                    }
+                    Err(broadcast::error::RecvError::Closed) => return None,
+                }
+            }
+        });
+
+        Ok((con, Box::pin(errors)))
+    }
+
+    async fn new_impl(
+        nats_server_addr: impl Into<String>,
+        auth_settings: &AuthenticationSettings,
+        wait_for_con: bool,
+        error_sender: broadcast::Sender<ConnectionError>,
+    ) -> Result<Self> {
+        let nats_server_addr = nats_server_addr.into();
+        //Check before calling the credentials provider, which may take a while
+        validate_server_address(&nats_server_addr)?;
+
+        let client_name = if let Some(creds_provider) = &auth_settings.creds_provider {
+            Some(creds_provider.get_credentials().await.client_name)
+        } else {
+            auth_settings
+                .creds
+                .as_ref()
+                .map(|creds| creds.client_name.clone())
+        };
+
+        Self::connect_impl(
             nats_server_addr,
             client_name,
             NatsAuthenticationMethod::OAuth2Client(auth_settings.clone()),
-            true,
+            wait_for_con,
+            error_sender,
         )
         .await
     }
@@ -176,12 +578,35 @@ impl AuthenticatedNatsConnection {
     /// This is useful if a certain authorization method should be used.
     ///
     /// Usually, this is not needed for providers and consumers and they should use [`Self::new()`] instead.
+    ///
+    /// Fails early with an [`InvalidServerAddressError`] if the address is not a `nats://` or `tls://` URL.
     pub async fn connect_with_auth_method(
         nats_server_addr: impl Into<String>,
         client_name: Option<impl Into<String>>,
         auth_method: NatsAuthenticationMethod,
         wait_for_con: bool,
     ) -> Result<Self> {
+        let (error_sender, _) = broadcast::channel(CONNECTION_ERROR_BUFFER);
+        Self::connect_impl(
+            nats_server_addr,
+            client_name,
+            auth_method,
+            wait_for_con,
+            error_sender,
+        )
+        .await
+    }
+
+    async fn connect_impl(
+        nats_server_addr: impl Into<String>,
+        client_name: Option<impl Into<String>>,
+        auth_method: NatsAuthenticationMethod,
+        wait_for_con: bool,
+        error_sender: broadcast::Sender<ConnectionError>,
+    ) -> Result<Self> {
+        let nats_server_addr = nats_server_addr.into();
+        validate_server_address(&nats_server_addr)?;
+
         let mut client_name =
             client_name.map_or_else(|| "_UNAUTHENTICATED".to_string(), Into::into);
 
@@ -189,17 +614,27 @@ impl AuthenticatedNatsConnection {
             client_name = "_UNAUTHENTICATED".to_string();
         }
 
+        let connection_name =
+            if let NatsAuthenticationMethod::OAuth2Client(auth_settings) = &auth_method {
+                auth_settings.connection_name(&client_name)
+            } else {
+                client_name.clone()
+            };
+
         let (event_sender, _) = broadcast::channel(128);
 
         //must subscribe to nats events before trying to connect, as otherwise we may miss the connect event
         let event_receiver = event_sender.subscribe();
 
-        let nats_client = Self::connect_to_nats(
+        //The connect future of the NATS client is large, so it is boxed to keep the futures of all callers small
+        let nats_client = Box::pin(Self::connect_to_nats(
             &auth_method,
-            nats_server_addr.into(),
+            nats_server_addr,
             &client_name,
+            &connection_name,
             event_sender.clone(),
-        )
+            error_sender,
+        ))
         .await?;
 
         let nats_permissions =
@@ -216,6 +651,7 @@ impl AuthenticatedNatsConnection {
             event_sender,
             nats_permissions,
             client_name,
+            connection_name,
         };
 
         if wait_for_con {
@@ -245,12 +681,48 @@ impl AuthenticatedNatsConnection {
         &self.client_name
     }
 
+    /// Gets the name of the NATS connection, as it is shown by server-side tooling.
+    ///
+    /// This is the client name, followed by the client description and version in parentheses if they were set
+    /// in the auth settings, e.g. `my_client (My App 1.2.3)`.
+    #[must_use]
+    pub fn get_connection_name(&self) -> &str {
+        &self.connection_name
+    }
+
     /// Returns the nats client.
     #[must_use]
     pub fn get_client(&self) -> &async_nats::Client {
         &self.nats_client
     }
 
+    /// Returns a `JetStream` context that uses this connection, e.g. for durable application specific messaging.
+    ///
+    /// The Data Hub protocol only uses core NATS, so this is not required to interact with the hub.
+    /// Note that the `JetStream` subjects must be covered by the permissions of the connection.
+    #[cfg(feature = "jetstream")]
+    #[must_use]
+    pub fn jetstream(&self) -> async_nats::jetstream::Context {
+        async_nats::jetstream::new(self.nats_client.clone())
+    }
+
+    /// Returns the current state of the NATS connection.
+    ///
+    /// In contrast to [`Self::get_events`], this does not require to keep an event receiver around,
+    /// which makes it useful for synchronous checks, e.g. in a health endpoint.
+    #[must_use]
+    pub fn connection_state(&self) -> async_nats::connection::State {
+        self.nats_client.connection_state()
+    }
+
+    /// Returns true if the NATS connection is currently established.
+    ///
+    /// See [`Self::connection_state`] for details.
+    #[must_use]
+    pub fn is_connected(&self) -> bool {
+        self.connection_state() == async_nats::connection::State::Connected
+    }
+
     /// Allows to subscribe to nats events and react to them.
     /// This simply forwards nats events to the caller.
     #[must_use]
@@ -267,7 +739,10 @@ impl AuthenticatedNatsConnection {
         &self.nats_permissions
     }
 
-    fn setup_nats_auth(auth_method: &NatsAuthenticationMethod) -> async_nats::ConnectOptions {
+    fn setup_nats_auth(
+        auth_method: &NatsAuthenticationMethod,
+        error_sender: &broadcast::Sender<ConnectionError>,
+    ) -> async_nats::ConnectOptions {
         match auth_method {
             NatsAuthenticationMethod::Unauthenticated => async_nats::ConnectOptions::new(),
             NatsAuthenticationMethod::UsernameAndPassword { username, password } => {
@@ -279,51 +754,74 @@ impl AuthenticatedNatsConnection {
                 async_nats::ConnectOptions::new().token(token.clone())
             }
             NatsAuthenticationMethod::OAuth2Client(auth_settings) => {
-                Self::setup_oauth2_client_auth(auth_settings)
+                Self::setup_oauth2_client_auth(auth_settings, error_sender)
             }
         }
     }
 
     fn setup_oauth2_client_auth(
         auth_settings: &AuthenticationSettings,
+        error_sender: &broadcast::Sender<ConnectionError>,
     ) -> async_nats::ConnectOptions {
         let token_endpoint = auth_settings.oauth2_endpoint.clone();
+        let auth_style = auth_settings.client_auth_style;
 
-        let scope_list = auth_settings
-            .permissions
-            .iter()
-            .map(std::string::ToString::to_string)
-            .collect::<Vec<_>>()
-            .join(" ");
+        let scope_list = auth_settings.oauth2_scopes().join(" ");
+
+        if let Some(creds_provider) = auth_settings.creds_provider.clone() {
+            let error_sender = error_sender.clone();
+            return async_nats::ConnectOptions::with_auth_callback(move |_| {
+                let creds_provider = creds_provider.clone();
+                let token_endpoint = token_endpoint.clone();
+                let scope_list = scope_list.clone();
+                let error_sender = error_sender.clone();
+
+                async move {
+                    //The auth callback future must be Sync, which user futures usually are not,
+                    //so the credentials provider is polled in a separate task.
+                    let creds = tokio::spawn(async move { creds_provider.get_credentials().await })
+                        .await
+                        .map_err(|e| {
+                            Self::report_auth_error(
+                                &error_sender,
+                                async_nats::AuthError::new(format!(
+                                    "Credentials provider failed: {e}"
+                                )),
+                            )
+                        })?;
+
+                    //Same behavior as static credentials without client id: connect without token
+                    if creds.client_id.is_empty() {
+                        return Ok(async_nats::Auth::new());
+                    }
+
+                    debug!("Requesting token for client id: {}", creds.client_id);
+                    Self::request_auth_token(&creds, &token_endpoint, &scope_list, auth_style)
+                        .await
+                        .map_err(|e| Self::report_auth_error(&error_sender, e))
+                }
+            })
+            .retry_on_initial_connect();
+        }
 
         if let Some(creds) = auth_settings.creds.clone() {
             if creds.client_id.is_empty() {
                 return async_nats::ConnectOptions::new();
             }
 
+            let error_sender = error_sender.clone();
             async_nats::ConnectOptions::with_auth_callback(move |_| {
                 debug!("Requesting token for client id: {}", creds.client_id);
 
                 let creds = creds.clone();
                 let token_endpoint = token_endpoint.clone();
                 let scope_list = scope_list.clone();
+                let error_sender = error_sender.clone();
 
                 async move {
-                    let result = creds.request_token(&token_endpoint, &scope_list).await;
-
-                    match result {
-                        Ok(token_response) => {
-                            let mut auth = async_nats::Auth::new();
-                            auth.token = Some(token_response.access_token);
-                            Ok(auth)
-                        }
-                        Err(e) => {
-                            let error_text = format!("Error requesting token: {e}");
-
-                            error!(error_text);
-                            Err(async_nats::AuthError::new(error_text))
-                        }
-                    }
+                    Self::request_auth_token(&creds, &token_endpoint, &scope_list, auth_style)
+                        .await
+                        .map_err(|e| Self::report_auth_error(&error_sender, e))
                 }
             })
             .retry_on_initial_connect()
@@ -332,22 +830,65 @@ impl AuthenticatedNatsConnection {
         }
     }
 
+    /// Forwards a failed authentication to the error stream and returns the error unchanged.
+    fn report_auth_error(
+        error_sender: &broadcast::Sender<ConnectionError>,
+        error: async_nats::AuthError,
+    ) -> async_nats::AuthError {
+        error_sender
+            .send(ConnectionError::Authentication(error.to_string()))
+            .ok();
+        error
+    }
+
+    /// Requests an oauth2 token and converts it to NATS auth data.
+    async fn request_auth_token(
+        creds: &OAuth2Credentials,
+        token_endpoint: &str,
+        scope_list: &str,
+        auth_style: ClientAuthStyle,
+    ) -> core::result::Result<async_nats::Auth, async_nats::AuthError> {
+        let result = creds
+            .request_token_with_auth_style(token_endpoint, scope_list, auth_style)
+            .await;
+
+        match result {
+            Ok(token_response) => {
+                let mut auth = async_nats::Auth::new();
+                auth.token = Some(token_response.access_token);
+                Ok(auth)
+            }
+            Err(e) => {
+                let error_text = format!("Error requesting token: {e}");
+
+                error!(error_text);
+                Err(async_nats::AuthError::new(error_text))
+            }
+        }
+    }
+
     async fn connect_to_nats(
         auth_method: &NatsAuthenticationMethod,
         nats_hostname: String,
         client_name: &str,
+        connection_name: &str,
         event_sender: broadcast::Sender<async_nats::Event>,
+        error_sender: broadcast::Sender<ConnectionError>,
     ) -> Result<async_nats::Client> {
-        let connection_options = Self::setup_nats_auth(auth_method);
+        let connection_options = Self::setup_nats_auth(auth_method, &error_sender);
 
         let connection_options = connection_options
-            .name(client_name)
+            .name(connection_name)
             .custom_inbox_prefix(format!("_INBOX.{client_name}"));
 
         let connection_options = connection_options
             .event_callback(move |event| {
                 let event_sender = event_sender.clone();
+                let error_sender = error_sender.clone();
                 async move {
+                    if let Some(error) = ConnectionError::from_event(&event) {
+                        error_sender.send(error).ok();
+                    }
                     event_sender.send(event).ok();
                 }
             })
@@ -5,26 +5,51 @@
 //! This module provides a high-level API for interacting with a variable hub provider
 //! by abstacting the low-level API details via easy to use rust types.
 
-use std::{collections::HashSet, sync::Arc};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    future::Future,
+    marker::PhantomData,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    task::{Context, Poll},
+    time::Duration,
+};
 
-use futures::{Stream, StreamExt};
+use futures::{
+    future::Either,
+    stream::{Fuse, FusedStream},
+    Stream, StreamExt,
+};
+use rustc_hash::FxHashMap;
 use std::convert::Into;
 use thiserror::Error;
-use tracing::error;
+use tokio::{
+    sync::{broadcast, mpsc, oneshot},
+    task::JoinHandle,
+    time::{Instant, Interval, MissedTickBehavior},
+};
+use tracing::{error, warn};
 
 use crate::{
-    dh_types::{VariableDefinition, VariableID, VariableValue},
+    dh_types::{
+        self, FromVariableValue, ProviderStatus, TimestampValue, VariableAccessType,
+        VariableDefinition, VariableID, VariableType, VariableValue,
+    },
     generated::weidmueller::ucontrol::hub::{
-        ProviderDefinitionState, ReadVariablesQueryRequestT, TimestampT, VariableListT,
-        VariableQuality, VariableT, VariableValueT, VariablesChangedEventT, WriteVariablesCommandT,
+        ProviderDefinitionState, ProviderDefinitionT, ReadVariablesQueryRequestT,
+        ReadVariablesQueryResponseT, TimestampT, VariableListT, VariableQuality, VariableT,
+        VariableValueT, VariablesChangedEventT, WriteVariablesCommandT,
     },
 };
 
 use super::{
     connected_nats_provider::{self, ConnectedNatsProvider, ConnectedNatsProviderState},
-    consumer_types::{self, VariableState},
+    consumer_types::{self, FilterMode, OverflowStrategy, SubscriptionBuffer, VariableState},
     dh_consumer::{self, DataHubConsumer},
-    variable_key::VariableKey,
+    variable_key::{matches_key_pattern, VariableKey, VariableKeyHash},
 };
 
 /// Error type for the connected data hub provider.
@@ -37,6 +62,24 @@ pub enum Error {
     TypeConversion(#[from] consumer_types::Error),
     #[error("{0}")]
     LowLevelApi(#[from] connected_nats_provider::Error),
+    #[error("The subscription task crashed during setup")]
+    SubscriptionTaskCrashed,
+    #[error("The key '{0}' belongs to a folder, not to a variable")]
+    FolderKey(String),
+    #[error("The wait was cancelled")]
+    Cancelled,
+    #[error("The wait or read timed out")]
+    Timeout,
+    #[error("The subscription ended, as the NATS connection was closed")]
+    SubscriptionEnded,
+    #[error("The provider does not support reading historical values")]
+    HistoryUnsupported,
+    #[error(
+        "The provider definition changed while writing, resolve the variable keys again and retry"
+    )]
+    DefinitionChangedDuringWrite,
+    #[error("The poll interval must not be zero")]
+    ZeroPollInterval,
 }
 
 /// Result type for the connected data hub provider.
@@ -62,18 +105,444 @@ pub enum ProviderEvent {
     Offline,
     /// The provider is online but has an invalid definition.
     Invalid,
+    /// A variable ID now belongs to a different variable key than before.
+    ///
+    /// Cached IDs of `old_key` must be resolved again, as they now refer to `new_key`.
+    /// Only emitted if enabled via [`DataHubProviderConnection::set_detect_id_remapping`].
+    IdRemapped {
+        /// The reused variable ID.
+        id: VariableID,
+        /// The key the ID belonged to before.
+        old_key: String,
+        /// The key the ID belongs to now.
+        new_key: String,
+    },
+    /// The provider changed its overall status, see [`ProviderStatus`].
+    ///
+    /// Use [`DataHubProviderConnection::read_provider_status`] to get the status before the first change.
+    StatusChanged(ProviderStatus),
+    /// The NATS connection of the consumer was lost, the client tries to reconnect in the background.
+    ///
+    /// Unlike [`ProviderEvent::Offline`], this says nothing about the provider itself,
+    /// only that no events and variable changes are received until [`ProviderEvent::NatsReconnected`].
+    NatsDisconnected,
+    /// The NATS connection of the consumer was (re-)established.
+    ///
+    /// Events and variable changes published while disconnected are lost,
+    /// so cached states should be read again if they must be up to date.
+    NatsReconnected,
+}
+
+impl From<Option<ProviderDefinitionT>> for ProviderEvent {
+    fn from(provider_definition: Option<ProviderDefinitionT>) -> Self {
+        match provider_definition {
+            Some(provider_def) => {
+                if provider_def.state == ProviderDefinitionState::OK {
+                    let new_variable_defs = provider_def
+                        .variable_definitions
+                        .unwrap_or_default()
+                        .into_iter()
+                        .map(Into::into)
+                        .collect();
+                    ProviderEvent::DefinitionChanged(new_variable_defs)
+                } else {
+                    ProviderEvent::Invalid
+                }
+            }
+            None => ProviderEvent::Offline,
+        }
+    }
+}
+
+/// Handle of a subscription created by [`DataHubProviderConnection::on_variable_change`].
+///
+/// Dropping the handle cancels the subscription.
+#[derive(Debug)]
+pub struct SubscriptionHandle {
+    task: JoinHandle<()>,
+}
+
+/// Used to cancel the subscription
+impl Drop for SubscriptionHandle {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// Stream of variable changes returned by [`DataHubProviderConnection::subscribe_variables_buffered`].
+///
+/// Yields the same items as [`DataHubProviderConnection::subscribe_variables_with_filter`]
+/// and additionally counts the change events that were dropped by the buffer.
+/// Dropping the stream cancels the subscription.
+pub struct BufferedSubscription {
+    changes: Fuse<BoxedChangesStream>,
+    dropped_changes: Arc<AtomicU64>,
+    forwarding_task: JoinHandle<()>,
+}
+
+impl BufferedSubscription {
+    /// Returns the number of change events that were dropped because the buffer was full.
+    ///
+    /// This is always 0 for [`OverflowStrategy::Wait`].
+    #[must_use]
+    pub fn dropped_changes(&self) -> u64 {
+        self.dropped_changes.load(Ordering::Relaxed)
+    }
+}
+
+impl Stream for BufferedSubscription {
+    type Item = Vec<(VariableID, VariableState)>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.changes.poll_next_unpin(cx)
+    }
+}
+
+impl FusedStream for BufferedSubscription {
+    fn is_terminated(&self) -> bool {
+        self.changes.is_terminated()
+    }
+}
+
+/// Used to stop the forwarding task
+impl Drop for BufferedSubscription {
+    fn drop(&mut self) {
+        self.forwarding_task.abort();
+    }
+}
+
+/// Type erased stream of variable changes, so that both overflow strategies can share [`BufferedSubscription`].
+type BoxedChangesStream = Pin<Box<dyn Stream<Item = Vec<(VariableID, VariableState)>> + Send>>;
+
+/// Sending side of the buffer of a [`BufferedSubscription`], depending on the [`OverflowStrategy`].
+enum SubscriptionBufferSender {
+    /// A broadcast channel overwrites the oldest items if it is full and tells the receiver how many it lost.
+    DropOldest(broadcast::Sender<Vec<(VariableID, VariableState)>>),
+    Wait(mpsc::Sender<Vec<(VariableID, VariableState)>>),
+}
+
+/// A [`ProviderEvent`] together with the low level provider definition it was created from.
+///
+/// Returned by [`DataHubProviderConnection::subscribe_provider_events_raw`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct RawProviderEvent {
+    /// The converted high level event.
+    pub event: ProviderEvent,
+    /// The complete low level provider definition as received from the registry.
+    ///
+    /// This is `None` if the provider is offline.
+    pub provider_definition: Option<ProviderDefinitionT>,
+}
+
+/// Differences between a previous snapshot of variable definitions and the current provider definition.
+///
+/// Variables are matched by key, as variable IDs may change whenever the provider definition changes.
+/// All lists are sorted by variable key.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DefinitionDelta {
+    /// Variables that exist now, but did not exist in the previous snapshot.
+    pub added: Vec<VariableDefinition>,
+    /// Variables that existed in the previous snapshot, but no longer exist.
+    pub removed: Vec<VariableDefinition>,
+    /// Variables whose data type changed. Contains the current definitions.
+    pub type_changed: Vec<VariableDefinition>,
+}
+
+impl DefinitionDelta {
+    /// Returns true if no variable was added, removed or changed its data type.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.type_changed.is_empty()
+    }
+}
+
+/// Returned by [`DataHubProviderConnection::assert_schema`] if the provider does not match the expected schema.
+///
+/// All lists are sorted by variable key.
+#[derive(Error, Debug, Clone, Default, PartialEq, Eq)]
+#[error(
+    "Provider schema mismatch: missing keys {missing:?}, unexpected keys {unexpected:?}, type mismatches {type_mismatches:?}"
+)]
+pub struct SchemaMismatch {
+    /// Expected keys that the provider does not offer.
+    pub missing: Vec<String>,
+    /// Keys offered by the provider that were not expected.
+    pub unexpected: Vec<String>,
+    /// Keys that exist on both sides, but with a different data type.
+    pub type_mismatches: Vec<SchemaTypeMismatch>,
+}
+
+/// A variable whose data type differs from the expected schema.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SchemaTypeMismatch {
+    /// The variable key
+    pub key: String,
+    /// The data type that was expected
+    pub expected: VariableType,
+    /// The data type offered by the provider
+    pub actual: VariableType,
+}
+
+/// Handle to a single variable whose data type is bound to the rust type `T`.
+///
+/// Created via [`DataHubProviderConnection::typed_handle`], which checks the data type against the variable definition.
+/// Therefore, reads and writes use `T` directly instead of [`VariableValue`].
+///
+/// The handle refers to the variable by its key, so it stays valid if the provider definition changes.
+/// If the provider changes the data type of the variable in the meantime,
+/// reads fail with [`dh_types::Error::ValueTypeMismatch`] and writes are rejected.
+pub struct TypedVariable<'a, T> {
+    connection: &'a DataHubProviderConnection,
+    key: String,
+    _type: PhantomData<fn() -> T>,
+}
+
+impl<T> Clone for TypedVariable<'_, T> {
+    fn clone(&self) -> Self {
+        Self {
+            connection: self.connection,
+            key: self.key.clone(),
+            _type: PhantomData,
+        }
+    }
+}
+
+impl<T: FromVariableValue + Into<VariableValue>> TypedVariable<'_, T> {
+    /// Returns the key of the variable.
+    #[must_use]
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// Reads the current value of the variable.
+    ///
+    /// See [`DataHubProviderConnection::read_single_variable_typed()`] for details.
+    pub async fn read(&self) -> Result<T> {
+        self.connection.read_single_variable_typed(&self.key).await
+    }
+
+    /// Writes a new value to the variable.
+    ///
+    /// See [`DataHubProviderConnection::write_single_variable()`] for details.
+    pub async fn write(&self, value: T) -> Result<()> {
+        self.connection
+            .write_single_variable(&self.key, value)
+            .await
+    }
+}
+
+/// Selects the variables of a filtered subscription, resolved to IDs whenever the provider definition changes.
+#[derive(Debug, Clone)]
+enum VariableFilter {
+    /// Exact variable keys
+    Keys(Vec<VariableKeyHash>),
+    /// Wildcard patterns, see [`DataHubProviderConnection::subscribe_variables_with_pattern`]
+    Patterns(Vec<String>),
+}
+
+/// Delay before a failed re-read after a reconnect is retried.
+const REREAD_RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/// State of a stream returned by [`DataHubProviderConnection::subscribe_adaptive`]
+/// or [`DataHubProviderConnection::subscribe_resilient`].
+struct AdaptiveSubscription<S> {
+    events: S,
+    /// Timer for periodic polls, `None` if the variables are only re-read after reconnects
+    poll_timer: Option<Interval>,
+    /// Events of the NATS client, used to detect reconnects. `None` once the client is gone.
+    nats_events: Option<broadcast::Receiver<async_nats::Event>>,
+    /// Time at which the variables are re-read, set after a reconnect
+    pending_reread: Option<Instant>,
+    /// If set to true, change events that are older than or equal to the last yielded state are dropped
+    drop_stale_events: bool,
+    provider: Arc<ConnectedNatsProvider>,
+    filter: Option<VariableFilter>,
+    ignore_unknown_values: bool,
+    /// Fingerprint at the last poll, as variable IDs are only valid for a single provider definition
+    last_fingerprint: Option<u64>,
+    /// Last yielded state per variable, used to detect missed changes
+    last_seen: FxHashMap<VariableID, VariableState>,
+}
+
+impl<S> AdaptiveSubscription<S>
+where
+    S: Stream<Item = Vec<(VariableID, VariableState)>> + Unpin,
+{
+    /// Waits for the next event or missed changes found by polling or re-reading after a reconnect.
+    ///
+    /// Ends the stream once the event subscription ends.
+    async fn next_changes(mut self) -> Option<(Vec<(VariableID, VariableState)>, Self)> {
+        loop {
+            tokio::select! {
+                changes = self.events.next() => {
+                    let mut changes = changes?;
+                    if self.drop_stale_events {
+                        self.drop_stale(&mut changes);
+                        if changes.is_empty() {
+                            continue;
+                        }
+                    }
+                    for (id, state) in &changes {
+                        self.last_seen.insert(*id, state.clone());
+                    }
+                    return Some((changes, self));
+                },
+                () = next_poll(&mut self.poll_timer) => {
+                    match self.poll_missed_changes().await {
+                        Ok(missed) if !missed.is_empty() => return Some((missed, self)),
+                        Ok(_) => {}
+                        Err(e) => warn!(
+                            "Failed to poll variables of provider {}: {e}",
+                            self.provider.get_provider_id()
+                        ),
+                    }
+                },
+                event = next_nats_event(&mut self.nats_events) => match event {
+                    //Changes published while disconnected are lost. Lagging may hide a reconnect, so re-read in both cases.
+                    Ok(async_nats::Event::Connected) | Err(broadcast::error::RecvError::Lagged(_)) => {
+                        self.pending_reread = Some(Instant::now());
+                    }
+                    Ok(_) => {}
+                    Err(broadcast::error::RecvError::Closed) => self.nats_events = None,
+                },
+                () = reread_due(self.pending_reread) => {
+                    //Variables of an offline provider can not be read, wait until it is back
+                    if self.provider.get_fingerprint().is_none() {
+                        self.pending_reread = Some(Instant::now() + REREAD_RETRY_DELAY);
+                        continue;
+                    }
+
+                    match self.poll_missed_changes().await {
+                        Ok(missed) => {
+                            self.pending_reread = None;
+                            if !missed.is_empty() {
+                                return Some((missed, self));
+                            }
+                        }
+                        Err(e) => {
+                            warn!(
+                                "Failed to re-read variables of provider {} after reconnect: {e}",
+                                self.provider.get_provider_id()
+                            );
+                            self.pending_reread = Some(Instant::now() + REREAD_RETRY_DELAY);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Removes changes that are older than or equal to the last yielded state of the variable.
+    fn drop_stale(&mut self, changes: &mut Vec<(VariableID, VariableState)>) {
+        //IDs of the yielded states are meaningless after the definition changed
+        let fingerprint = self.provider.get_fingerprint();
+        if fingerprint != self.last_fingerprint {
+            self.last_seen.clear();
+            self.last_fingerprint = fingerprint;
+        }
+
+        changes.retain(|(id, state)| {
+            self.last_seen.get(id).map_or(true, |last| {
+                state != last && state.timestamp >= last.timestamp
+            })
+        });
+    }
+
+    /// Reads the current variable states and returns those that differ from the last yielded states.
+    async fn poll_missed_changes(&mut self) -> Result<Vec<(VariableID, VariableState)>> {
+        let fingerprint = self.provider.get_fingerprint();
+        if fingerprint != self.last_fingerprint {
+            self.last_seen.clear();
+            self.last_fingerprint = fingerprint;
+        }
+
+        if fingerprint.is_none() {
+            return Ok(Vec::new());
+        }
+
+        //Unknown keys are skipped, like in the event subscription
+        let ids = self.filter.as_ref().map(|filter| {
+            //Safety: Unwrap is ok here as we know that the mutex cant be poisoned during writing
+            #[allow(clippy::unwrap_used)]
+            let state = self.provider.get_state().read().unwrap();
+
+            DataHubProviderConnection::build_filter_set(filter, &state)
+                .into_iter()
+                .collect::<Vec<_>>()
+        });
+        if ids.as_ref().is_some_and(Vec::is_empty) {
+            return Ok(Vec::new());
+        }
+
+        //Always request timestamps, they are needed to detect missed changes
+        let low_level_data = self
+            .provider
+            .read_variables_unchecked(&ReadVariablesQueryRequestT { ids })
+            .await?;
+
+        let mut missed = Vec::new();
+        for (id, state) in
+            DataHubProviderConnection::map_read_response(low_level_data, self.ignore_unknown_values)
+        {
+            let changed = self.last_seen.get(&id).map_or(true, |last| {
+                let changed_fields = state.changed_fields(last);
+                changed_fields.value || changed_fields.timestamp
+            });
+
+            if changed {
+                self.last_seen.insert(id, state.clone());
+                missed.push((id, state));
+            }
+        }
+
+        Ok(missed)
+    }
+}
+
+/// Waits for the next tick of the poll timer, or forever if the stream does not poll.
+async fn next_poll(timer: &mut Option<Interval>) {
+    match timer {
+        Some(timer) => {
+            timer.tick().await;
+        }
+        None => std::future::pending().await,
+    }
+}
+
+/// Waits for the next event of the NATS client, or forever once the client is gone.
+async fn next_nats_event(
+    events: &mut Option<broadcast::Receiver<async_nats::Event>>,
+) -> std::result::Result<async_nats::Event, broadcast::error::RecvError> {
+    match events {
+        Some(events) => events.recv().await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Waits until the pending re-read is due, or forever if no re-read is pending.
+async fn reread_due(at: Option<Instant>) {
+    match at {
+        Some(at) => tokio::time::sleep_until(at).await,
+        None => std::future::pending().await,
+    }
 }
 
 /// Contains options for the [`DataHubProviderConnection`].
 struct Options {
     /// If set to true, unknown variable values will be ignored by read and subscribe methods.
     ignore_unknown_variable_values: bool,
+    /// If set to true, read requests ask the provider to omit timestamps and qualities.
+    values_only_reads: bool,
+    /// If set to true, provider event streams report variable IDs that were reassigned to a different key.
+    detect_id_remapping: bool,
 }
 
 impl Default for Options {
     fn default() -> Self {
         Self {
             ignore_unknown_variable_values: true,
+            values_only_reads: false,
+            detect_id_remapping: false,
         }
     }
 }
@@ -91,9 +560,25 @@ impl Default for Options {
 /// This allows you to use strings or other types that implement the [`VariableKeyLike`] trait.
 /// However, for maximum performance it is recommended to always create a variable key once and then reuse it for multiple calls.
 /// See documentation of [`VariableKey`] for more details.
+///
+/// The connection keeps the underlying NATS connection alive, even if the [`DataHubConsumer`] it was created from is dropped.
+/// Likewise, all streams returned by this connection keep the connection state alive,
+/// so they continue to yield values even if the consumer and the connection itself are dropped first.
+///
+/// The streams do not end while the NATS connection is open, even if the provider or the registry goes offline.
+/// They only end once the NATS connection is closed, e.g. via [`async_nats::Client::drain`], which distinguishes
+/// an idle stream from a dead one. All streams implement [`FusedStream`], so they keep returning `None` after they ended.
 pub struct DataHubProviderConnection {
-    connected_provider: ConnectedNatsProvider,
+    connected_provider: Arc<ConnectedNatsProvider>,
     opts: Options,
+    write_batch_sender: Option<mpsc::UnboundedSender<BatchedWrite>>,
+}
+
+/// A single variable write that is waiting to be flushed by the write batching task.
+struct BatchedWrite {
+    /// Fingerprint of the provider definition the variable id was resolved against.
+    fingerprint: u64,
+    var: VariableT,
 }
 
 impl DataHubProviderConnection {
@@ -123,8 +608,9 @@ impl DataHubProviderConnection {
             ConnectedNatsProvider::new(consumer.get_nats_consumer().clone(), provider_id).await?;
 
         Ok(Self {
-            connected_provider,
+            connected_provider: Arc::new(connected_provider),
             opts: Options::default(),
+            write_batch_sender: None,
         })
     }
 
@@ -137,13 +623,70 @@ impl DataHubProviderConnection {
         self.opts.ignore_unknown_variable_values = ignore;
     }
 
+    /// If `values_only` is set to true, read methods ask the provider to only send variable values.
+    ///
+    /// This reduces the payload size for constrained links, as the provider omits timestamps and qualities.
+    /// In this case, the returned [`VariableState::quality`] is always [`VariableQuality::BadOrUndefined`](crate::dh_types::VariableQuality::BadOrUndefined)
+    /// and [`VariableState::timestamp`] is the time of the read, so only the value should be used.
+    /// Providers that do not support this option still send full responses.
+    ///
+    /// By default, this is set to false. Subscriptions are not affected by this option.
+    pub fn set_values_only_reads(&mut self, values_only: bool) {
+        self.opts.values_only_reads = values_only;
+    }
+
+    /// If `detect` is set to true, [`Self::subscribe_provider_events`] reports variable IDs that
+    /// are reused for a different variable key via [`ProviderEvent::IdRemapped`].
+    ///
+    /// Providers may reassign IDs when their definition changes, so IDs that were cached by the application
+    /// could silently refer to a different variable afterwards. The key of an ID is remembered even if the
+    /// variable is removed in between, so reuse is also detected if the provider removes and re-adds its variables.
+    /// The remapping events are emitted right before the [`ProviderEvent::DefinitionChanged`] event that contains the new keys.
+    ///
+    /// By default, this is set to false.
+    /// Please note that you will have to resubscribe to streams to see the effect of changes to this option.
+    pub fn set_detect_id_remapping(&mut self, detect: bool) {
+        self.opts.detect_id_remapping = detect;
+    }
+
+    /// Enables batching of writes done via [`Self::write_single_variable`].
+    ///
+    /// Instead of sending each write immediately, writes are buffered per variable (last write wins)
+    /// and sent to the provider as a single write command at most `max_delay` after the first buffered write.
+    /// This is useful for interactive controls like sliders, which may produce many writes in quick succession.
+    ///
+    /// Permission and type checks are still performed immediately, but errors that occur while sending
+    /// the batched write command can only be logged. Buffered writes are dropped if the provider definition
+    /// changes before they are sent. Remaining writes are flushed when batching is disabled or the connection is dropped.
+    ///
+    /// Calling this again while batching is enabled replaces the previous batching task.
+    pub fn enable_write_batching(&mut self, max_delay: Duration) {
+        let (sender, receiver) = mpsc::unbounded_channel();
+
+        tokio::spawn(Self::write_batching_loop(
+            self.connected_provider.clone(),
+            receiver,
+            max_delay,
+        ));
+
+        self.write_batch_sender = Some(sender);
+    }
+
+    /// Disables batching of writes, see [`Self::enable_write_batching`].
+    ///
+    /// Writes that are still buffered will be flushed in the background.
+    pub fn disable_write_batching(&mut self) {
+        self.write_batch_sender = None;
+    }
+
     /// Waits until all specified variable keys are available from the provider.
     ///
     /// Some providers will register before all their variables are available and add them later.
     /// This method can be used to ensure that all specified variable keys are available before proceeding with further operations.
     ///
     /// Note that this will block forever if the provider never registers the specified variable keys.
-    /// There is no internal timeout, but you may wrap this within a timeout call.
+    /// There is no internal timeout, but you may wrap this within a timeout call
+    /// or use [`Self::wait_until_variable_keys_are_available_or_cancelled()`].
     ///
     /// This method will succeed even if the hub registry is currently offline, but may return an error if there is an issue
     /// with the NATS connection.
@@ -181,6 +724,78 @@ impl DataHubProviderConnection {
         Ok(())
     }
 
+    /// Same as [`Self::wait_until_variable_keys_are_available()`], but stops waiting once `cancel` completes.
+    ///
+    /// `cancel` can be any future without output, e.g. an async block that awaits the receiver of a oneshot channel
+    /// or the `cancelled()` future of a cancellation token.
+    /// This allows to stop waiting for keys that will never arrive, e.g. during a graceful shutdown.
+    /// The internal subscription to provider events is torn down before this method returns.
+    ///
+    /// ```no_run
+    /// # use u_os_hub_client::consumer::connected_dh_provider::DataHubProviderConnection;
+    /// # async fn example(dh_provider_con: &DataHubProviderConnection) {
+    /// let (shutdown_tx, shutdown_rx) = tokio::sync::oneshot::channel::<()>();
+    /// # drop(shutdown_tx);
+    /// let result = dh_provider_con
+    ///     .wait_until_variable_keys_are_available_or_cancelled(&["my_folder.my_var"], async {
+    ///         let _ = shutdown_rx.await;
+    ///     })
+    ///     .await;
+    /// # }
+    /// ```
+    ///
+    /// Returns [`Error::Cancelled`] if `cancel` completed before all keys were available.
+    pub async fn wait_until_variable_keys_are_available_or_cancelled(
+        &self,
+        keys: &[&str],
+        cancel: impl Future<Output = ()>,
+    ) -> Result<()> {
+        tokio::select! {
+            result = self.wait_until_variable_keys_are_available(keys) => result,
+            () = cancel => Err(Error::Cancelled),
+        }
+    }
+
+    /// Waits until the provider definition did not change for `quiet_period`.
+    ///
+    /// A single logical change of a provider can lead to multiple [`ProviderEvent`]s,
+    /// e.g. if the provider first clears its variables and adds the new ones afterwards.
+    /// This method can be used to avoid acting on such intermediate definitions.
+    ///
+    /// Returns at the earliest after `quiet_period`, even if the definition did not change at all.
+    /// Note that this will block forever if the provider keeps changing its definition faster than `quiet_period`.
+    /// There is no internal timeout, but you may wrap this within a timeout call.
+    ///
+    /// This method will succeed even if the hub registry is currently offline, but may return an error if there is an issue
+    /// with the NATS connection.
+    pub async fn wait_for_stable_definition(&self, quiet_period: Duration) -> Result<()> {
+        //Status changes and reconnects do not affect the definition
+        let mut provider_evt_stream =
+            Box::pin(self.subscribe_provider_events().await?.filter(|event| {
+                futures::future::ready(!matches!(
+                    event,
+                    ProviderEvent::StatusChanged(_)
+                        | ProviderEvent::NatsDisconnected
+                        | ProviderEvent::NatsReconnected
+                ))
+            }));
+
+        while let Ok(Some(_)) = tokio::time::timeout(quiet_period, provider_evt_stream.next()).await
+        {
+            //Every event restarts the quiet period
+        }
+
+        Ok(())
+    }
+
+    /// Reads the current status of the provider, see [`ProviderStatus`].
+    ///
+    /// Subscribe to [`ProviderEvent::StatusChanged`] via [`Self::subscribe_provider_events`] to get notified about changes.
+    /// Fails if the provider is offline or uses a client version without status support.
+    pub async fn read_provider_status(&self) -> Result<ProviderStatus> {
+        Ok(self.connected_provider.read_provider_status().await?)
+    }
+
     /// Allows access to the low level api
     #[must_use]
     pub fn get_connected_nats_provider(&self) -> &ConnectedNatsProvider {
@@ -194,7 +809,12 @@ impl DataHubProviderConnection {
     }
 
     /// Returns a stream of events for the connected provider.
-    /// This allows you to receive events when the provider goes offline or when the provider definition changes.
+    /// This allows you to receive events when the provider goes offline, when the provider definition changes
+    /// or when the provider changes its status.
+    ///
+    /// Additionally, the stream contains [`ProviderEvent::NatsDisconnected`] and [`ProviderEvent::NatsReconnected`]
+    /// when the NATS connection of the consumer goes down and comes back.
+    /// These are distinct from [`ProviderEvent::Offline`], which means that the registry reported the provider gone.
     ///
     /// This method will succeed even if the hub registry is currently offline, but may return an error if there is an issue
     /// with the NATS connection.
@@ -205,35 +825,155 @@ impl DataHubProviderConnection {
     /// Internally uses the low level api to receive the values.
     /// Each received value from the low level api will be converted to an easy to use rust type.
     /// If the low level api stream returned an error value, this value will be silenly ignored, but the subscription will not be cancelled.
-    pub async fn subscribe_provider_events(&self) -> Result<impl Stream<Item = ProviderEvent>> {
+    pub async fn subscribe_provider_events(
+        &self,
+    ) -> Result<impl FusedStream<Item = ProviderEvent>> {
+        //Subscribed first, so a reconnect during the subscription is not missed
+        let nats_events = futures::stream::unfold(self.nats_events(), |mut events| async move {
+            loop {
+                let event = match events.recv().await {
+                    Ok(async_nats::Event::Disconnected) => ProviderEvent::NatsDisconnected,
+                    Ok(async_nats::Event::Connected) => ProviderEvent::NatsReconnected,
+                    Ok(async_nats::Event::Closed) | Err(broadcast::error::RecvError::Closed) => {
+                        return None
+                    }
+                    //Lagging only drops events, the current state is signaled by the next one
+                    Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                };
+                return Some((event, events));
+            }
+        });
+
         let provider_def_events = self
             .connected_provider
             .subscribe_provider_definition()
             .await?;
 
-        let mapped_events = provider_def_events.filter_map(move |event| async {
-            let ll_event = event.ok()?;
-
-            let mapped_event = match ll_event.provider_definition {
-                Some(provider_def) => {
-                    if provider_def.state == ProviderDefinitionState::OK {
-                        let new_variable_defs = provider_def
-                            .variable_definitions
-                            .unwrap_or_default()
-                            .into_iter()
-                            .map(Into::into)
-                            .collect();
-                        ProviderEvent::DefinitionChanged(new_variable_defs)
-                    } else {
-                        ProviderEvent::Invalid
-                    }
+        //Keys of all IDs known so far, taken after subscribing so no definition change is missed
+        let mut known_keys = self.opts.detect_id_remapping.then(|| {
+            self.connected_provider
+                .get_all_variable_definitions()
+                .into_values()
+                .map(|var_def| (var_def.id, var_def.key))
+                .collect::<FxHashMap<_, _>>()
+        });
+
+        let mapped_events = provider_def_events
+            .filter_map(move |event| async {
+                let ll_event = event.ok()?;
+                Some(ll_event.provider_definition.map(|def| *def).into())
+            })
+            .flat_map(move |event: ProviderEvent| {
+                let mut events = Vec::new();
+
+                if let (Some(known_keys), ProviderEvent::DefinitionChanged(var_defs)) =
+                    (&mut known_keys, &event)
+                {
+                    events = Self::detect_id_remapping(known_keys, var_defs);
                 }
-                None => ProviderEvent::Offline,
-            };
 
-            Some(mapped_event)
+                events.push(event);
+                futures::stream::iter(events)
+            });
+
+        let status_events = self
+            .connected_provider
+            .subscribe_provider_status()
+            .await?
+            .map(ProviderEvent::StatusChanged);
+
+        //The stream ends with the subscriptions, even if the NATS client does not report the close
+        let subscription_events = futures::stream::select(mapped_events, status_events)
+            .map(Some)
+            .chain(futures::stream::once(async { None }));
+        let events = futures::stream::select(subscription_events, nats_events.map(Some))
+            .take_while(|event| futures::future::ready(event.is_some()))
+            .filter_map(futures::future::ready);
+
+        Ok(keep_alive(
+            Box::pin(events),
+            self.connected_provider.clone(),
+        ))
+    }
+
+    /// Returns a [`ProviderEvent::IdRemapped`] event for each ID in `var_defs` whose key differs from `known_keys`
+    /// and updates `known_keys` afterwards.
+    ///
+    /// IDs that are not part of `var_defs` are kept in `known_keys`, so reuse after a removal is detected as well.
+    fn detect_id_remapping(
+        known_keys: &mut FxHashMap<VariableID, String>,
+        var_defs: &[VariableDefinition],
+    ) -> Vec<ProviderEvent> {
+        let mut remapped = Vec::new();
+
+        for var_def in var_defs {
+            if let Some(old_key) = known_keys.insert(var_def.id, var_def.key.clone()) {
+                if old_key != var_def.key {
+                    remapped.push(ProviderEvent::IdRemapped {
+                        id: var_def.id,
+                        old_key,
+                        new_key: var_def.key.clone(),
+                    });
+                }
+            }
+        }
+
+        remapped
+    }
+
+    /// Same as [`Self::subscribe_provider_events`], but each event additionally contains
+    /// the complete low level provider definition.
+    ///
+    /// This is useful for tooling that needs information which is not part of the high level
+    /// [`VariableDefinition`], e.g. the fingerprint or the raw definition of variables with incompatible enum values.
+    pub async fn subscribe_provider_events_raw(
+        &self,
+    ) -> Result<impl FusedStream<Item = RawProviderEvent>> {
+        let provider_def_events = self
+            .connected_provider
+            .subscribe_provider_definition()
+            .await?;
+
+        let mapped_events = provider_def_events.filter_map(move |event| async {
+            let provider_definition = event.ok()?.provider_definition.map(|def| *def);
+
+            Some(RawProviderEvent {
+                event: provider_definition.clone().into(),
+                provider_definition,
+            })
         });
 
+        Ok(keep_alive(
+            Box::pin(mapped_events),
+            self.connected_provider.clone(),
+        ))
+    }
+
+    /// Returns a stream that signals if the provider is currently online.
+    ///
+    /// The stream first yields the current online state and afterwards yields `true` each time the provider
+    /// gets a new valid definition and `false` if the provider goes offline or becomes invalid.
+    ///
+    /// This is a simplified version of [`Self::subscribe_provider_events()`] and has the same error and subscription behavior.
+    pub async fn online_signal(&self) -> Result<impl FusedStream<Item = bool>> {
+        //sub must happen before reading the current state to make sure we dont miss any events
+        let provider_events = self.subscribe_provider_events().await?;
+        let is_online = self.connected_provider.is_online();
+
+        let mapped_events = futures::stream::once(async move { is_online }).chain(
+            provider_events.filter_map(|event| async move {
+                match event {
+                    ProviderEvent::DefinitionChanged(_) => Some(true),
+                    ProviderEvent::Offline | ProviderEvent::Invalid => Some(false),
+                    //Does not change the online state
+                    ProviderEvent::IdRemapped { .. }
+                    | ProviderEvent::StatusChanged(_)
+                    | ProviderEvent::NatsDisconnected
+                    | ProviderEvent::NatsReconnected => None,
+                }
+            }),
+        );
+
         Ok(Box::pin(mapped_events))
     }
 
@@ -253,9 +993,23 @@ impl DataHubProviderConnection {
         Ok(self.connected_provider.variable_id_from_key(key.into())?)
     }
 
+    /// Returns true if the variable key currently exists on the provider.
+    ///
+    /// This is a cheap lookup in the cached definition, so no request is sent to the provider.
+    /// Please note that the key may be removed at any time if the provider definition changes.
+    #[inline(always)]
+    #[must_use]
+    pub fn variable_exists<'a>(&self, key: impl VariableKeyLike<'a>) -> bool {
+        self.connected_provider.is_variable_key_valid(key.into())
+    }
+
     /// Returns a cached list of all variable definitions for this provider.
     ///
     /// The cached value will be updated internally once the provider definition changes.
+    ///
+    /// Variables with keys that violate the schema by exceeding
+    /// [`MAX_VARIABLE_KEY_LENGTH`](crate::provider::variable_definition_validator::MAX_VARIABLE_KEY_LENGTH) are dropped
+    /// when the definition is received, so they are neither listed here nor accessible via any other method.
     pub fn get_all_variable_definitions(&self) -> Result<Vec<VariableDefinition>> {
         let var_defs = self.connected_provider.get_all_variable_definitions();
 
@@ -264,40 +1018,293 @@ impl DataHubProviderConnection {
         Ok(result)
     }
 
-    /// Returns the cached variable definition for this variable.
+    /// Returns the cached variable IDs of this provider, sorted by variable key.
     ///
-    /// Will fail if the variable is unknown.
-    /// The cached value will be updated internally once the provider definition changes.
-    pub fn get_variable_definition<'a>(
-        &self,
-        var: impl VariableKeyLike<'a>,
-    ) -> Result<VariableDefinition> {
-        let id = self.variable_id_from_key(var)?;
-
-        let var_def = self.connected_provider.get_variable_definition(id)?;
-        Ok(var_def.into())
+    /// In contrast to [`Self::get_all_variable_definitions()`], the order is deterministic,
+    /// which is useful for diffable output or stable UIs.
+    /// If the provider is currently offline, the index is empty.
+    #[must_use]
+    pub fn get_variable_index(&self) -> BTreeMap<String, VariableID> {
+        self.connected_provider
+            .get_all_variable_definitions()
+            .into_values()
+            .map(|var_def| (var_def.key, var_def.id))
+            .collect()
     }
 
-    /// Reads the current state of a single variable from the provider.
-    ///
-    /// Will check if the supplied variable ID is valid before sending the read request.
+    /// Returns the cached definitions of all variables that consumers can write.
     ///
-    /// This method may fail if there is an issue with the nats connection, the provider is unavailable
-    /// or something goes wrong while deserializing flatbuffer payloads.
-    pub async fn read_single_variable<'a>(
+    /// Variables with an unknown access type are only included if `include_unknown_access_types` is set.
+    /// Writing to them is allowed for maximum compatibility, but the provider may still reject the write command.
+    pub fn writable_variables(
         &self,
-        var: impl VariableKeyLike<'a>,
-    ) -> Result<VariableState> {
-        let key = var.into();
-        let response_variable_list = self.read_variables(Some(&[key])).await?;
-
-        let state = response_variable_list
-            .into_iter()
-            .next()
-            .ok_or_else(|| connected_nats_provider::Error::InvalidVariableKey(key.to_string()))?
-            .1;
+        include_unknown_access_types: bool,
+    ) -> Result<Vec<VariableDefinition>> {
+        let mut var_defs = self.get_all_variable_definitions()?;
+
+        var_defs.retain(|var_def| match var_def.access_type {
+            VariableAccessType::ReadWrite => true,
+            VariableAccessType::ReadOnly => false,
+            VariableAccessType::Unknown(_) => include_unknown_access_types,
+        });
 
-        Ok(state)
+        Ok(var_defs)
+    }
+
+    /// Compares a previous snapshot of variable definitions with the cached definitions of this provider.
+    ///
+    /// This is useful after a reconnect to find out what changed without diffing the whole set manually.
+    /// The comparison is done client-side, so `old` should be the result of a previous call to [`Self::get_all_variable_definitions()`].
+    /// If the provider is currently offline, all variables of `old` will be reported as removed.
+    #[must_use]
+    pub fn definition_diff_since(&self, old: &[VariableDefinition]) -> DefinitionDelta {
+        let current: Vec<VariableDefinition> = self
+            .connected_provider
+            .get_all_variable_definitions()
+            .into_values()
+            .map(Into::into)
+            .collect();
+
+        let old_by_key: HashMap<&str, &VariableDefinition> =
+            old.iter().map(|def| (def.key.as_str(), def)).collect();
+        let current_by_key: HashMap<&str, &VariableDefinition> =
+            current.iter().map(|def| (def.key.as_str(), def)).collect();
+
+        let mut delta = DefinitionDelta::default();
+
+        for def in &current {
+            match old_by_key.get(def.key.as_str()) {
+                None => delta.added.push(def.clone()),
+                Some(old_def) if old_def.data_type != def.data_type => {
+                    delta.type_changed.push(def.clone());
+                }
+                Some(_) => {}
+            }
+        }
+
+        delta.removed = old
+            .iter()
+            .filter(|def| !current_by_key.contains_key(def.key.as_str()))
+            .cloned()
+            .collect();
+
+        delta.added.sort_by(|a, b| a.key.cmp(&b.key));
+        delta.removed.sort_by(|a, b| a.key.cmp(&b.key));
+        delta.type_changed.sort_by(|a, b| a.key.cmp(&b.key));
+
+        delta
+    }
+
+    /// Checks that the provider offers exactly the expected variables with the expected data types.
+    ///
+    /// Reports missing keys, unexpected extra keys and type mismatches at once.
+    /// This allows applications to fail on startup if a provider was reconfigured incompatibly,
+    /// instead of failing at the first read or write.
+    /// The check is done against the cached definitions, so no request is sent to the provider.
+    /// If the provider is currently offline, all expected keys will be reported as missing.
+    pub fn assert_schema(
+        &self,
+        expected: &[(&str, VariableType)],
+    ) -> core::result::Result<(), SchemaMismatch> {
+        let current = self.connected_provider.get_all_variable_definitions();
+        let current_by_key: HashMap<&str, VariableType> = current
+            .values()
+            .map(|def| (def.key.as_str(), def.data_type.into()))
+            .collect();
+        let expected_by_key: HashMap<&str, VariableType> = expected.iter().copied().collect();
+
+        let mut mismatch = SchemaMismatch::default();
+
+        for (key, expected_type) in &expected_by_key {
+            match current_by_key.get(key) {
+                None => mismatch.missing.push((*key).to_owned()),
+                Some(actual) if actual != expected_type => {
+                    mismatch.type_mismatches.push(SchemaTypeMismatch {
+                        key: (*key).to_owned(),
+                        expected: *expected_type,
+                        actual: *actual,
+                    });
+                }
+                Some(_) => {}
+            }
+        }
+
+        mismatch.unexpected = current_by_key
+            .keys()
+            .filter(|key| !expected_by_key.contains_key(*key))
+            .map(|key| (*key).to_owned())
+            .collect();
+
+        if mismatch.missing.is_empty()
+            && mismatch.unexpected.is_empty()
+            && mismatch.type_mismatches.is_empty()
+        {
+            return Ok(());
+        }
+
+        mismatch.missing.sort();
+        mismatch.unexpected.sort();
+        mismatch.type_mismatches.sort_by(|a, b| a.key.cmp(&b.key));
+
+        Err(mismatch)
+    }
+
+    /// Returns the cached variable definition for this variable.
+    ///
+    /// Will fail if the variable is unknown.
+    /// The cached value will be updated internally once the provider definition changes.
+    pub fn get_variable_definition<'a>(
+        &self,
+        var: impl VariableKeyLike<'a>,
+    ) -> Result<VariableDefinition> {
+        let id = self.variable_id_from_key(var)?;
+
+        let var_def = self.connected_provider.get_variable_definition(id)?;
+        Ok(var_def.into())
+    }
+
+    /// Reads the current state of a single variable from the provider.
+    ///
+    /// Will check if the supplied variable ID is valid before sending the read request.
+    ///
+    /// This method may fail if there is an issue with the nats connection, the provider is unavailable
+    /// or something goes wrong while deserializing flatbuffer payloads.
+    /// The read is only limited by the request timeout of the NATS client,
+    /// use [`Self::read_single_variable_with_timeout()`] for a hard deadline.
+    pub async fn read_single_variable<'a>(
+        &self,
+        var: impl VariableKeyLike<'a>,
+    ) -> Result<VariableState> {
+        let key = var.into();
+        let response_variable_list = self.read_variables(Some(&[key])).await?;
+
+        let state = response_variable_list
+            .into_iter()
+            .next()
+            .ok_or_else(|| connected_nats_provider::Error::InvalidVariableKey(key.to_string()))?
+            .1;
+
+        Ok(state)
+    }
+
+    /// Same as [`Self::read_single_variable()`], but fails with [`Error::Timeout`] if the read does not finish within `timeout`.
+    ///
+    /// This is useful e.g. for UI code, which must not wait for an unresponsive provider.
+    /// A response that arrives after the timeout is ignored.
+    pub async fn read_single_variable_with_timeout<'a>(
+        &self,
+        var: impl VariableKeyLike<'a>,
+        timeout: Duration,
+    ) -> Result<VariableState> {
+        tokio::time::timeout(timeout, self.read_single_variable(var))
+            .await
+            .map_err(|_| Error::Timeout)?
+    }
+
+    /// Reads the current state of the variable at `path`, whose segments are joined with `.` to form the variable key.
+    ///
+    /// E.g. `&["my_folder", "sub_folder", "my_var"]` reads the variable `my_folder.sub_folder.my_var`.
+    /// Fails with [`Error::FolderKey`] if the path leads to a folder, i.e. the key is a prefix of other variable keys.
+    /// Otherwise, this behaves like [`Self::read_single_variable()`].
+    #[allow(clippy::missing_panics_doc)] // See comment below
+    pub async fn read_by_path(&self, path: &[&str]) -> Result<VariableState> {
+        let key = path.join(".");
+
+        if self.variable_id_from_key(&key).is_err() {
+            let folder_prefix = format!("{key}.");
+
+            //Safety: Unwrap is ok here as we know that the mutex cant be poisoned during writing
+            #[allow(clippy::unwrap_used)]
+            let is_folder = self
+                .connected_provider
+                .get_state()
+                .read()
+                .unwrap()
+                .cur_variable_defs
+                .values()
+                .any(|var_def| var_def.key.starts_with(&folder_prefix));
+
+            if is_folder {
+                return Err(Error::FolderKey(key));
+            }
+        }
+
+        self.read_single_variable(&key).await
+    }
+
+    /// Same as [`Self::read_single_variable()`], but converts the value into the rust type `T`.
+    ///
+    /// Only the value is returned, quality and timestamp are discarded.
+    /// Fails with [`consumer_types::Error::ValueTypeMismatch`] if the value does not have the type of `T`.
+    pub async fn read_single_variable_typed<'a, T: FromVariableValue>(
+        &self,
+        var: impl VariableKeyLike<'a>,
+    ) -> Result<T> {
+        let state = self.read_single_variable(var).await?;
+
+        Ok(T::from_variable_value(state.value)?)
+    }
+
+    /// Creates a [`TypedVariable`] handle for the variable, which reads and writes values of the rust type `T`.
+    ///
+    /// Fails with [`consumer_types::Error::ValueTypeMismatch`] if the variable does not have the type of `T`,
+    /// so type errors show up once when the handle is created instead of at every read or write.
+    pub fn typed_handle<'a, T: FromVariableValue + Into<VariableValue>>(
+        &self,
+        var: impl VariableKeyLike<'a>,
+    ) -> Result<TypedVariable<'_, T>> {
+        let definition = self.get_variable_definition(var)?;
+
+        if definition.data_type != T::VARIABLE_TYPE {
+            return Err(consumer_types::Error::ValueTypeMismatch {
+                expected: T::VARIABLE_TYPE,
+                actual: Some(definition.data_type),
+            }
+            .into());
+        }
+
+        Ok(TypedVariable {
+            connection: self,
+            key: definition.key,
+            _type: PhantomData,
+        })
+    }
+
+    /// Reads the historical states of a variable between `from` and `to` (both inclusive), sorted by timestamp.
+    ///
+    /// This requires a provider that stores a history of its values and answers on
+    /// [`nats_subjects::read_history_query`](crate::nats_subjects::read_history_query).
+    /// For all other providers, this fails with [`Error::HistoryUnsupported`].
+    ///
+    /// The request contains a read request for the variable, while the range is sent as nanoseconds since the unix epoch
+    /// in the `History-From` and `History-To` NATS headers. The provider answers with a regular read response,
+    /// which may contain multiple states of the variable.
+    pub async fn read_history<'a>(
+        &self,
+        var: impl VariableKeyLike<'a>,
+        from: TimestampValue,
+        to: TimestampValue,
+    ) -> Result<Vec<VariableState>> {
+        let id = self.variable_id_from_key(var)?;
+
+        let low_level_data = self
+            .connected_provider
+            .read_history(id, from, to)
+            .await
+            .map_err(|e| match e {
+                connected_nats_provider::Error::HistoryUnsupported(_) => Error::HistoryUnsupported,
+                e => e.into(),
+            })?;
+
+        let mut states: Vec<VariableState> =
+            Self::map_read_response(low_level_data, self.opts.ignore_unknown_variable_values)
+                .into_iter()
+                .filter(|(state_id, _)| *state_id == id)
+                .map(|(_, state)| state)
+                .collect();
+        states.sort_by_key(|state| state.timestamp);
+
+        Ok(states)
     }
 
     /// Reads the current state of all provider variables.
@@ -331,41 +1338,69 @@ impl DataHubProviderConnection {
         };
 
         //Read low level result
-        //Safety: We can use the unchecked method here because we already know that all variable IDs are valid
-        let low_level_data = self
-            .connected_provider
-            .read_variables_unchecked(&var_ids)
-            .await?;
+        //Safety: We can use the unchecked methods here because we already know that all variable IDs are valid
+        let low_level_data = if self.opts.values_only_reads {
+            self.connected_provider
+                .read_variables_values_only_unchecked(&var_ids)
+                .await?
+        } else {
+            self.connected_provider
+                .read_variables_unchecked(&var_ids)
+                .await?
+        };
 
-        let base_timestamp = low_level_data.variables.base_timestamp.into();
+        Ok(Self::map_read_response(
+            low_level_data,
+            self.opts.ignore_unknown_variable_values,
+        ))
+    }
 
-        //map to user friendly data types
-        let result = low_level_data
-            .variables
-            .items
-            .unwrap_or_default()
-            .into_iter()
-            .filter_map(|ll_var| -> Option<(u32, VariableState)> {
-                //Flatbuffer deserializes unknown union values to NONE
-                if self.opts.ignore_unknown_variable_values && ll_var.value == VariableValueT::NONE
-                {
-                    return None;
-                }
+    /// Same as [`Self::read_variables()`], but fails with [`Error::Timeout`] if the read does not finish within `timeout`.
+    ///
+    /// See [`Self::read_single_variable_with_timeout()`] for details.
+    pub async fn read_variables_with_timeout<'a>(
+        &self,
+        filter: Option<&[impl VariableKeyLike<'a>]>,
+        timeout: Duration,
+    ) -> Result<Vec<(VariableID, VariableState)>> {
+        tokio::time::timeout(timeout, self.read_variables(filter))
+            .await
+            .map_err(|_| Error::Timeout)?
+    }
 
-                let map_entry = (ll_var.id, VariableState::new(ll_var, base_timestamp).ok()?);
+    /// Reads the current state of all provider variables and only returns variables with one of the given `qualities`.
+    ///
+    /// This is useful e.g. for alarm views that only show variables with a bad or uncertain quality.
+    /// Note that the quality is part of the variable state, so this method reads all variables and filters locally.
+    /// For huge providers, consider subscribing to the variable changes and tracking the qualities instead.
+    ///
+    /// The qualities are always requested from the provider, even if [`Self::set_values_only_reads`] is enabled.
+    ///
+    /// This method may fail if there is an issue with the nats connection, the provider is unavailable
+    /// or something goes wrong while deserializing flatbuffer payloads.
+    pub async fn read_variables_by_quality(
+        &self,
+        qualities: &[dh_types::VariableQuality],
+    ) -> Result<Vec<(VariableID, VariableState)>> {
+        self.connected_provider.check_online()?;
 
-                Some(map_entry)
-            })
-            .collect();
+        let low_level_data = self
+            .connected_provider
+            .read_variables_unchecked(&ReadVariablesQueryRequestT { ids: None })
+            .await?;
 
-        Ok(result)
+        let mut var_states =
+            Self::map_read_response(low_level_data, self.opts.ignore_unknown_variable_values);
+        var_states.retain(|(_, state)| qualities.contains(&state.quality));
+
+        Ok(var_states)
     }
 
     /// Same as [`Self::subscribe_variables_with_filter`], but only returns changes of a single variable.
     pub async fn subscribe_single_variable<'a>(
         &self,
         var: impl VariableKeyLike<'a>,
-    ) -> Result<impl Stream<Item = VariableState>> {
+    ) -> Result<impl FusedStream<Item = VariableState>> {
         let mapped_stream = self
             .subscribe_variables_with_filter(Some(vec![var]))
             .await?
@@ -374,6 +1409,79 @@ impl DataHubProviderConnection {
         Ok(Box::pin(mapped_stream))
     }
 
+    /// Same as [`Self::subscribe_single_variable`], but the stream first yields the current state of the variable,
+    /// followed by all subsequent changes.
+    ///
+    /// This uses [`Self::snapshot_and_subscribe`], so no change can get lost between reading and subscribing
+    /// and changes that are older than or equal to the current state are dropped.
+    ///
+    /// If the current state can not be read, e.g. because the provider is offline or the key does not exist yet,
+    /// the stream is still established and only yields changes, e.g. once the provider is back.
+    pub async fn subscribe_single_variable_with_current<'a>(
+        &self,
+        var: impl VariableKeyLike<'a>,
+    ) -> Result<impl FusedStream<Item = VariableState>> {
+        let stream = match self.snapshot_and_subscribe(Some(vec![var])).await {
+            Ok((snapshot, changes)) => {
+                let current = snapshot.into_iter().next().map(|(_id, state)| state);
+                let changes = changes.filter_map(|changed_vars| async move {
+                    changed_vars.into_iter().next().map(|(_id, state)| state)
+                });
+
+                Either::Left(futures::stream::iter(current).chain(changes))
+            }
+            Err(_) => Either::Right(self.subscribe_single_variable(var).await?),
+        };
+
+        Ok(Box::pin(stream))
+    }
+
+    /// Waits until the state of the variable satisfies `predicate` and returns that state.
+    ///
+    /// The current state is checked first, so this returns immediately if the condition is already met.
+    /// Otherwise, every change is checked until `timeout` elapses. As this uses
+    /// [`Self::subscribe_single_variable_with_current`], no transition between reading and subscribing can get lost.
+    ///
+    /// ```no_run
+    /// # use std::time::Duration;
+    /// # use u_os_hub_client::{consumer::connected_dh_provider::DataHubProviderConnection, dh_types::VariableValue};
+    /// # async fn example(dh_provider_con: &DataHubProviderConnection) {
+    /// let state = dh_provider_con
+    ///     .wait_for_value(
+    ///         "sensors.temperature",
+    ///         |state| matches!(state.value, VariableValue::Float64(value) if value > 50.0),
+    ///         Duration::from_secs(10),
+    ///     )
+    ///     .await
+    ///     .unwrap();
+    /// # }
+    /// ```
+    ///
+    /// Fails with [`Error::Timeout`] if the condition was not met in time
+    /// and with [`Error::SubscriptionEnded`] if the NATS connection was closed while waiting.
+    pub async fn wait_for_value<'a>(
+        &self,
+        var: impl VariableKeyLike<'a>,
+        predicate: impl Fn(&VariableState) -> bool,
+        timeout: Duration,
+    ) -> Result<VariableState> {
+        let wait = async {
+            let mut states = self.subscribe_single_variable_with_current(var).await?;
+
+            while let Some(state) = states.next().await {
+                if predicate(&state) {
+                    return Ok(state);
+                }
+            }
+
+            Err(Error::SubscriptionEnded)
+        };
+
+        tokio::time::timeout(timeout, wait)
+            .await
+            .map_err(|_| Error::Timeout)?
+    }
+
     /// Returns a stream of variable state changes for the connected provider.
     ///
     /// Only returns changes to the provided variable keys by implementing client side filtering.
@@ -392,28 +1500,276 @@ impl DataHubProviderConnection {
     /// Internally uses the low level api to receive the values.
     /// Each received value from the low level api will be converted to an easy to use rust type.
     /// If the low level api stream returned an error value, this value will be silenly ignored, but the subscription will not be cancelled.
-    #[allow(clippy::missing_panics_doc)] // See comment below
     pub async fn subscribe_variables_with_filter<'a>(
         &self,
         filter_list: Option<Vec<impl VariableKeyLike<'a>>>,
-    ) -> Result<impl Stream<Item = Vec<(VariableID, VariableState)>>> {
-        let mut last_fp = self.connected_provider.get_fingerprint();
-        let state_clone = self.connected_provider.get_state().clone();
+    ) -> Result<impl FusedStream<Item = Vec<(VariableID, VariableState)>>> {
+        self.subscribe_variables_with_filter_mode(filter_list, FilterMode::Include)
+            .await
+    }
+
+    /// Same as [`Self::subscribe_variables_with_filter`], but `filter_mode` defines whether
+    /// the `filter_list` contains the variables to include or to exclude.
+    ///
+    /// With [`FilterMode::Exclude`], changes of all variables except the listed ones are returned.
+    /// Like the include filter, the exclude filter is rebuilt when the provider definition changes,
+    /// so listed keys that are added later on are excluded as well.
+    /// If `filter_list` is set to None, all changes will be returned regardless of the filter mode.
+    pub async fn subscribe_variables_with_filter_mode<'a>(
+        &self,
+        filter_list: Option<Vec<impl VariableKeyLike<'a>>>,
+        filter_mode: FilterMode,
+    ) -> Result<impl FusedStream<Item = Vec<(VariableID, VariableState)>>> {
+        Self::subscribe_filtered(
+            &self.connected_provider,
+            Self::key_filter(filter_list),
+            filter_mode,
+            self.opts.ignore_unknown_variable_values,
+        )
+        .await
+    }
+
+    /// Same as [`Self::subscribe_variables_with_filter`], but selects the variables by wildcard patterns
+    /// instead of exact keys, e.g. `"my_folder.*"` or `"motor_?.speed"`.
+    ///
+    /// `*` matches any sequence of characters, including `.` separators, so `"my_folder.*"` also matches
+    /// variables in nested folders. `?` matches exactly one character. A variable is included if it matches any of the patterns.
+    ///
+    /// The patterns are matched against the keys of the current provider definition and matched again
+    /// whenever the provider definition changes, so variables that are added later on are included as well.
+    pub async fn subscribe_variables_with_pattern(
+        &self,
+        patterns: &[&str],
+    ) -> Result<impl FusedStream<Item = Vec<(VariableID, VariableState)>>> {
+        let filter = VariableFilter::Patterns(patterns.iter().map(ToString::to_string).collect());
+
+        Self::subscribe_filtered(
+            &self.connected_provider,
+            Some(filter),
+            FilterMode::Include,
+            self.opts.ignore_unknown_variable_values,
+        )
+        .await
+    }
+
+    /// Calls `callback` for each changed variable, see [`Self::subscribe_variables_with_filter`] for the filter semantics.
+    ///
+    /// This is an alternative to streams for event driven applications. The subscription is driven by a background task,
+    /// so the callback should return quickly. Changes are passed to the callback in the order they are received.
+    ///
+    /// The subscription is cancelled when the returned [`SubscriptionHandle`] is dropped.
+    /// Like streams, the subscription keeps the underlying connection alive until then.
+    pub async fn on_variable_change<'a>(
+        &self,
+        filter_list: Option<Vec<impl VariableKeyLike<'a>>>,
+        callback: impl Fn(VariableID, VariableState) + Send + 'static,
+    ) -> Result<SubscriptionHandle> {
+        let (setup_tx, setup_rx) = oneshot::channel();
+
+        let task = tokio::spawn(Self::variable_change_callback_loop(
+            self.connected_provider.clone(),
+            Self::key_filter(filter_list),
+            self.opts.ignore_unknown_variable_values,
+            callback,
+            setup_tx,
+        ));
+
+        match setup_rx.await {
+            Ok(Ok(())) => Ok(SubscriptionHandle { task }),
+            Ok(Err(e)) => Err(e),
+            Err(_) => Err(Error::SubscriptionTaskCrashed),
+        }
+    }
+
+    /// Same as [`Self::subscribe_variables_with_filter`], but received changes are buffered as configured by `buffer`.
+    ///
+    /// A background task receives the changes from the NATS client as fast as possible and stores them in a buffer
+    /// with the configured capacity, from which the returned stream yields them. If the stream is not polled fast enough
+    /// and the buffer is full, the [`OverflowStrategy`] decides whether the oldest changes are dropped or
+    /// receiving waits for the stream. Use [`BufferedSubscription::dropped_changes`] to detect data loss.
+    pub async fn subscribe_variables_buffered<'a>(
+        &self,
+        filter_list: Option<Vec<impl VariableKeyLike<'a>>>,
+        buffer: SubscriptionBuffer,
+    ) -> Result<BufferedSubscription> {
+        let capacity = buffer.capacity.max(1);
+        let dropped_changes = Arc::new(AtomicU64::new(0));
+
+        let (buffer_tx, changes): (_, BoxedChangesStream) = match buffer.overflow_strategy {
+            OverflowStrategy::DropOldest => {
+                let (tx, rx) = broadcast::channel(capacity);
+                let dropped_changes = dropped_changes.clone();
+
+                let changes = futures::stream::unfold(rx, move |mut rx| {
+                    let dropped_changes = dropped_changes.clone();
+                    async move {
+                        loop {
+                            match rx.recv().await {
+                                Ok(changed_vars) => return Some((changed_vars, rx)),
+                                Err(broadcast::error::RecvError::Lagged(dropped)) => {
+                                    dropped_changes.fetch_add(dropped, Ordering::Relaxed);
+                                }
+                                Err(broadcast::error::RecvError::Closed) => return None,
+                            }
+                        }
+                    }
+                });
+
+                (SubscriptionBufferSender::DropOldest(tx), Box::pin(changes))
+            }
+            OverflowStrategy::Wait => {
+                let (tx, rx) = mpsc::channel(capacity);
+
+                let changes = futures::stream::unfold(rx, |mut rx| async move {
+                    let changed_vars = rx.recv().await?;
+                    Some((changed_vars, rx))
+                });
+
+                (SubscriptionBufferSender::Wait(tx), Box::pin(changes))
+            }
+        };
+
+        let (setup_tx, setup_rx) = oneshot::channel();
+
+        let forwarding_task = tokio::spawn(Self::buffered_forwarding_loop(
+            self.connected_provider.clone(),
+            Self::key_filter(filter_list),
+            self.opts.ignore_unknown_variable_values,
+            buffer_tx,
+            setup_tx,
+        ));
+
+        match setup_rx.await {
+            Ok(Ok(())) => Ok(BufferedSubscription {
+                changes: changes.fuse(),
+                dropped_changes,
+                forwarding_task,
+            }),
+            Ok(Err(e)) => Err(e),
+            Err(_) => Err(Error::SubscriptionTaskCrashed),
+        }
+    }
+
+    /// Subscribes to variable changes and forwards them into `buffer_tx` until the receiver is gone or the task is aborted.
+    ///
+    /// The result of the subscription is reported via `setup_tx` before the first change is forwarded.
+    async fn buffered_forwarding_loop(
+        provider: Arc<ConnectedNatsProvider>,
+        filter: Option<VariableFilter>,
+        ignore_unknown_values: bool,
+        buffer_tx: SubscriptionBufferSender,
+        setup_tx: oneshot::Sender<Result<()>>,
+    ) {
+        let changes = match Self::subscribe_filtered(
+            &provider,
+            filter,
+            FilterMode::Include,
+            ignore_unknown_values,
+        )
+        .await
+        {
+            Ok(changes) => changes,
+            Err(e) => {
+                let _ = setup_tx.send(Err(e));
+                return;
+            }
+        };
+        let mut changes = Box::pin(changes);
+
+        if setup_tx.send(Ok(())).is_err() {
+            //Caller is gone, nobody can receive the changes
+            return;
+        }
+
+        while let Some(changed_vars) = changes.next().await {
+            let receiver_gone = match &buffer_tx {
+                SubscriptionBufferSender::DropOldest(tx) => tx.send(changed_vars).is_err(),
+                SubscriptionBufferSender::Wait(tx) => tx.send(changed_vars).await.is_err(),
+            };
+
+            if receiver_gone {
+                break;
+            }
+        }
+    }
+
+    /// Subscribes to variable changes and passes them to `callback` until the task is aborted.
+    ///
+    /// The result of the subscription is reported via `setup_tx` before the first change is processed.
+    async fn variable_change_callback_loop(
+        provider: Arc<ConnectedNatsProvider>,
+        filter: Option<VariableFilter>,
+        ignore_unknown_values: bool,
+        callback: impl Fn(VariableID, VariableState),
+        setup_tx: oneshot::Sender<Result<()>>,
+    ) {
+        let changes = match Self::subscribe_filtered(
+            &provider,
+            filter,
+            FilterMode::Include,
+            ignore_unknown_values,
+        )
+        .await
+        {
+            Ok(changes) => changes,
+            Err(e) => {
+                let _ = setup_tx.send(Err(e));
+                return;
+            }
+        };
+        let mut changes = Box::pin(changes);
+
+        if setup_tx.send(Ok(())).is_err() {
+            //Caller is gone, nobody can cancel the subscription later
+            return;
+        }
+
+        while let Some(changed_vars) = changes.next().await {
+            for (id, state) in changed_vars {
+                callback(id, state);
+            }
+        }
+    }
+
+    /// Hashes the keys of a filter list, so the filter does not borrow the keys.
+    fn key_filter<'a>(
+        filter_list: Option<Vec<impl VariableKeyLike<'a>>>,
+    ) -> Option<VariableFilter> {
+        filter_list.map(|filter_list| {
+            VariableFilter::Keys(
+                filter_list
+                    .iter()
+                    .map(|key| (*key).into().key_hash)
+                    .collect(),
+            )
+        })
+    }
+
+    /// Shared implementation of the filtered subscriptions.
+    ///
+    /// `filter` is resolved to IDs now and each time the provider definition changes.
+    async fn subscribe_filtered(
+        provider: &Arc<ConnectedNatsProvider>,
+        filter: Option<VariableFilter>,
+        filter_mode: FilterMode,
+        ignore_unknown_values: bool,
+    ) -> Result<impl FusedStream<Item = Vec<(VariableID, VariableState)>> + Send> {
+        let mut last_fp = provider.get_fingerprint();
+        let state_clone = provider.get_state().clone();
 
         //build initial filter set and remember initial fingerprint
-        let mut filter_set = filter_list.as_ref().map(|filter_list| {
+        let mut filter_set = filter.as_ref().map(|filter| {
             //Safety: Unwrap is ok here as we know that the mutex cant be poisoned during writing
             #[allow(clippy::unwrap_used)]
-            Self::build_filter_set(filter_list, &state_clone.read().unwrap())
+            Self::build_filter_set(filter, &state_clone.read().unwrap())
         });
 
         //Subscibe to all, unfiltered variable change events
-        let low_level_data = self.connected_provider.subscribe_variables().await?;
-        let ignore_unknown_values = self.opts.ignore_unknown_variable_values;
+        let low_level_data = provider.subscribe_variables().await?;
 
         //Filter variables and map to user friendly type
         let mapped_stream = low_level_data.filter_map(move |var_changed_evt| {
-            if let Some(filter_list) = &filter_list {
+            if let Some(filter) = &filter {
                 //check if we need to rebuild the filter because provider changed
 
                 //Safety: Unwrap is ok here as we know that the mutex cant be poisoned during writing
@@ -422,13 +1778,14 @@ impl DataHubProviderConnection {
 
                 let current_fp = readable_state.cur_fingerprint;
                 if current_fp.is_some() && current_fp != last_fp {
-                    filter_set = Some(Self::build_filter_set(filter_list, &readable_state));
+                    filter_set = Some(Self::build_filter_set(filter, &readable_state));
                     last_fp = current_fp;
                 }
             }
 
             let mapped_and_filtered_vars = Self::process_var_changed_evt(
                 filter_set.as_ref(),
+                filter_mode,
                 var_changed_evt,
                 ignore_unknown_values,
             );
@@ -436,7 +1793,245 @@ impl DataHubProviderConnection {
             async move { mapped_and_filtered_vars }
         });
 
-        Ok(Box::pin(mapped_stream))
+        //Keeps the internal event loop alive, which is required to rebuild the filter set
+        Ok(keep_alive(Box::pin(mapped_stream), provider.clone()))
+    }
+
+    /// Same as [`Self::subscribe_variables_with_filter`], but the stream first yields a snapshot of the current state
+    /// of all filtered variables, followed by all subsequent changes.
+    ///
+    /// See [`Self::snapshot_and_subscribe`] for details, which returns the snapshot separately.
+    ///
+    /// In contrast to [`Self::subscribe_variables_with_filter`], this method fails if the provider is currently unavailable,
+    /// as the snapshot can not be read.
+    pub async fn subscribe_variables_with_snapshot<'a>(
+        &self,
+        filter_list: Option<Vec<impl VariableKeyLike<'a>>>,
+    ) -> Result<impl FusedStream<Item = Vec<(VariableID, VariableState)>>> {
+        let (snapshot, changes) = self.snapshot_and_subscribe(filter_list).await?;
+
+        Ok(Box::pin(
+            futures::stream::once(async move { snapshot }).chain(changes),
+        ))
+    }
+
+    /// Reads the current state of all filtered variables and subscribes to their subsequent changes in one step.
+    ///
+    /// The subscription is established before the snapshot is read, so no change can get lost in between.
+    /// Changes that were received while reading the snapshot are dropped if they are older than or equal to the snapshot state
+    /// of the variable, so the stream neither repeats snapshot values nor goes back to an older state. This closes the gap
+    /// between calling [`Self::read_variables`] and subscribing, e.g. to initialize a UI and keep it up to date.
+    ///
+    /// The snapshot always contains quality and timestamp, regardless of [`Self::set_values_only_reads`].
+    /// Filtered keys that do not exist yet are not part of the snapshot, but their changes are part of the stream
+    /// once they are added to the provider.
+    ///
+    /// In contrast to [`Self::subscribe_variables_with_filter`], this method fails if the provider is currently unavailable,
+    /// as the snapshot can not be read.
+    #[allow(clippy::missing_panics_doc)] // See comment below
+    pub async fn snapshot_and_subscribe<'a>(
+        &self,
+        filter_list: Option<Vec<impl VariableKeyLike<'a>>>,
+    ) -> Result<(
+        Vec<(VariableID, VariableState)>,
+        impl FusedStream<Item = Vec<(VariableID, VariableState)>>,
+    )> {
+        let filter = Self::key_filter(filter_list);
+        let ignore_unknown_values = self.opts.ignore_unknown_variable_values;
+
+        //Subscribe before reading the snapshot, so no change gets lost
+        let changes = Self::subscribe_filtered(
+            &self.connected_provider,
+            filter.clone(),
+            FilterMode::Include,
+            ignore_unknown_values,
+        )
+        .await?;
+
+        self.connected_provider.check_online()?;
+
+        let (snapshot_fingerprint, ids) = {
+            //Safety: Unwrap is ok here as we know that the mutex cant be poisoned during writing
+            #[allow(clippy::unwrap_used)]
+            let readable_state = self.connected_provider.get_state().read().unwrap();
+
+            let ids = filter.map(|filter| {
+                Self::build_filter_set(&filter, &readable_state)
+                    .into_iter()
+                    .collect()
+            });
+
+            (readable_state.cur_fingerprint, ids)
+        };
+
+        //Safety: We can use the unchecked method here because the IDs were just resolved from the current definition
+        let low_level_data = self
+            .connected_provider
+            .read_variables_unchecked(&ReadVariablesQueryRequestT { ids })
+            .await?;
+        let snapshot = Self::map_read_response(low_level_data, ignore_unknown_values);
+
+        //Remember the snapshot states to drop changes that are already part of the snapshot
+        let mut snapshot_states: FxHashMap<VariableID, VariableState> =
+            snapshot.iter().cloned().collect();
+        let provider = self.connected_provider.clone();
+
+        let changes = changes.filter_map(move |changed_vars| {
+            //IDs of the snapshot are meaningless after the definition changed
+            if !snapshot_states.is_empty() && provider.get_fingerprint() != snapshot_fingerprint {
+                snapshot_states.clear();
+            }
+
+            if snapshot_states.is_empty() {
+                return futures::future::ready(Some(changed_vars));
+            }
+
+            let changed_vars: Vec<_> = changed_vars
+                .into_iter()
+                .filter(|(id, state)| match snapshot_states.get(id) {
+                    Some(snapshot_state)
+                        if state.timestamp < snapshot_state.timestamp
+                            || state == snapshot_state =>
+                    {
+                        false
+                    }
+                    Some(_) => {
+                        //Once a newer change arrived, all following changes are newer as well
+                        snapshot_states.remove(id);
+                        true
+                    }
+                    None => true,
+                })
+                .collect();
+
+            //Don't yield events that only contained snapshot values
+            futures::future::ready((!changed_vars.is_empty()).then_some(changed_vars))
+        });
+
+        Ok((snapshot, Box::pin(changes)))
+    }
+
+    /// Same as [`Self::subscribe_variables_with_filter`], but additionally polls the variables every `poll_interval`
+    /// to reconcile changes that the event subscription missed, e.g. on unreliable networks.
+    ///
+    /// The stream yields all received change events. In addition, it yields polled variable states
+    /// if their value or timestamp differs from the last state yielded for this variable.
+    /// If the poll does not reveal any missed changes, nothing is yielded.
+    /// The first poll yields all variables that did not receive an event until then.
+    ///
+    /// Polls are skipped while the provider is offline. Failed polls are logged and retried at the next interval.
+    ///
+    /// In addition to the periodic polls, the variables are re-read right after the NATS client reconnected,
+    /// see [`Self::subscribe_resilient`].
+    ///
+    /// Missed changes are detected via value and timestamp. If a provider does not set variable timestamps,
+    /// the timestamp of each poll differs, so polled states will be yielded on every poll.
+    ///
+    /// Fails with [`Error::ZeroPollInterval`] if `poll_interval` is zero.
+    pub async fn subscribe_adaptive<'a>(
+        &self,
+        filter_list: Option<Vec<impl VariableKeyLike<'a>>>,
+        poll_interval: Duration,
+    ) -> Result<impl FusedStream<Item = Vec<(VariableID, VariableState)>>> {
+        //tokio panics on an interval of zero
+        if poll_interval.is_zero() {
+            return Err(Error::ZeroPollInterval);
+        }
+
+        let filter = Self::key_filter(filter_list);
+        let events = Self::subscribe_filtered(
+            &self.connected_provider,
+            filter.clone(),
+            FilterMode::Include,
+            self.opts.ignore_unknown_variable_values,
+        )
+        .await?;
+
+        let mut poll_timer = tokio::time::interval(poll_interval);
+        poll_timer.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        poll_timer.tick().await; //skip first tick
+
+        let subscription = AdaptiveSubscription {
+            events: Box::pin(events),
+            poll_timer: Some(poll_timer),
+            nats_events: Some(self.nats_events()),
+            pending_reread: None,
+            drop_stale_events: false,
+            provider: self.connected_provider.clone(),
+            filter,
+            ignore_unknown_values: self.opts.ignore_unknown_variable_values,
+            last_fingerprint: None,
+            last_seen: FxHashMap::default(),
+        };
+
+        Ok(Box::pin(
+            futures::stream::unfold(subscription, AdaptiveSubscription::next_changes).fuse(),
+        ))
+    }
+
+    /// Same as [`Self::subscribe_variables_with_snapshot`], but the stream survives reconnects of the NATS client
+    /// without silently skipping changes.
+    ///
+    /// Change events that are published while the client is disconnected are lost. Therefore, the stream re-reads
+    /// the filtered variables right after each reconnect and yields the states that differ in value or timestamp
+    /// from the last state yielded for the variable. Intermediate values during the disconnect can not be recovered,
+    /// but the stream always converges to the current state of the provider.
+    /// Change events that are older than or equal to an already yielded state are dropped,
+    /// so the stream never goes back to an older state.
+    ///
+    /// If the re-read fails or the provider is offline, it is retried every second until it succeeds.
+    /// Like [`Self::subscribe_variables_with_snapshot`], this method fails if the provider is currently unavailable.
+    pub async fn subscribe_resilient<'a>(
+        &self,
+        filter_list: Option<Vec<impl VariableKeyLike<'a>>>,
+    ) -> Result<impl FusedStream<Item = Vec<(VariableID, VariableState)>>> {
+        let filter = Self::key_filter(filter_list);
+        //Subscribe to client events first, so no reconnect gets lost while subscribing
+        let nats_events = self.nats_events();
+
+        //Subscribe before reading the snapshot, so no change gets lost
+        let events = Self::subscribe_filtered(
+            &self.connected_provider,
+            filter.clone(),
+            FilterMode::Include,
+            self.opts.ignore_unknown_variable_values,
+        )
+        .await?;
+
+        self.connected_provider.check_online()?;
+
+        let mut subscription = AdaptiveSubscription {
+            events: Box::pin(events),
+            poll_timer: None,
+            nats_events: Some(nats_events),
+            pending_reread: None,
+            drop_stale_events: true,
+            provider: self.connected_provider.clone(),
+            filter,
+            ignore_unknown_values: self.opts.ignore_unknown_variable_values,
+            last_fingerprint: None,
+            last_seen: FxHashMap::default(),
+        };
+
+        //Nothing was yielded yet, so this returns the states of all filtered variables
+        let snapshot = subscription.poll_missed_changes().await?;
+
+        Ok(Box::pin(
+            futures::stream::once(async move { snapshot })
+                .chain(futures::stream::unfold(
+                    subscription,
+                    AdaptiveSubscription::next_changes,
+                ))
+                .fuse(),
+        ))
+    }
+
+    /// Subscribes to the events of the underlying NATS client, e.g. to detect reconnects.
+    fn nats_events(&self) -> broadcast::Receiver<async_nats::Event> {
+        self.connected_provider
+            .get_consumer()
+            .get_nats_con()
+            .get_events()
     }
 
     /// Sends a write command to the provider for a single variable.
@@ -451,17 +2046,57 @@ impl DataHubProviderConnection {
     /// It will also check if the variable is writable and if the value type matches the variable definitions.
     ///
     /// This method may fail if there is an issue with the nats connection or the provider is unavailable.
+    /// If the provider definition changes while the write is prepared, [`Error::DefinitionChangedDuringWrite`] is returned,
+    /// see [`Self::write_variables()`] for details.
     pub async fn write_single_variable<'a>(
         &self,
         var: impl VariableKeyLike<'a>,
         new_value: impl Into<VariableValue>,
     ) -> Result<()> {
-        self.write_variables(&[(var, new_value.into())]).await
+        let Some(write_batch_sender) = &self.write_batch_sender else {
+            return self.write_variables(&[(var, new_value.into())]).await;
+        };
+
+        let fingerprint = self.connected_provider.get_fingerprint().ok_or_else(|| {
+            connected_nats_provider::Error::ProviderOfflineOrInvalid(
+                self.get_provider_id().to_owned(),
+            )
+        })?;
+
+        let new_value: VariableValue = new_value.into();
+        let ll_var = VariableT {
+            id: self.variable_id_from_key(var)?,
+            value: (&new_value).into(),
+            //TODO: Only to be set by the provider. For now, add default filler. May revise flatbuffer api later
+            quality: VariableQuality::BAD,
+            timestamp: None,
+        };
+
+        //Check permissions and types now, so the caller gets errors immediately
+        self.connected_provider.check_variable_write(&ll_var)?;
+
+        if let Err(mpsc::error::SendError(batched_write)) = write_batch_sender.send(BatchedWrite {
+            fingerprint,
+            var: ll_var,
+        }) {
+            //Batching task is gone, fall back to writing directly
+            Self::flush_batched_writes(&self.connected_provider, vec![batched_write]).await?;
+        }
+
+        Ok(())
     }
 
     /// Same as [`Self::write_single_variable`], but allows to write multiple variables at once.
     ///
     /// This is more efficient than calling [`Self::write_single_variable`] multiple times.
+    ///
+    /// This method always writes immediately, even if write batching is enabled.
+    /// Therefore, it may overtake writes that are still buffered by [`Self::enable_write_batching`].
+    ///
+    /// The write command is bound to the provider definition the keys were resolved against.
+    /// If the definition changes in the meantime, the command is rejected with [`Error::DefinitionChangedDuringWrite`]
+    /// instead of being sent with outdated variable IDs. In this case, nothing was written and the caller should
+    /// resolve the keys again and retry, e.g. by simply calling this method again.
     pub async fn write_variables<'a>(
         &self,
         new_values: &[(impl VariableKeyLike<'a>, VariableValue)],
@@ -504,35 +2139,182 @@ impl DataHubProviderConnection {
         //Send write command - This checks write permissions and variable types internally
         self.connected_provider
             .write_variables(&write_command)
-            .await?;
+            .await
+            .map_err(Self::map_write_error)?;
+
+        Ok(())
+    }
+
+    /// Maps errors of a write command, see [`Self::write_variables`].
+    ///
+    /// A fingerprint mismatch means that the IDs were resolved against a definition that changed in the meantime,
+    /// all other errors are propagated unchanged.
+    fn map_write_error(e: connected_nats_provider::Error) -> Error {
+        match e {
+            connected_nats_provider::Error::ProviderFingerprintMismatch { .. } => {
+                Error::DefinitionChangedDuringWrite
+            }
+            e => e.into(),
+        }
+    }
+
+    /// Buffers incoming writes and flushes them as a single write command after `max_delay`.
+    ///
+    /// Exits after flushing the remaining writes when the sender is dropped.
+    async fn write_batching_loop(
+        provider: Arc<ConnectedNatsProvider>,
+        mut receiver: mpsc::UnboundedReceiver<BatchedWrite>,
+        max_delay: Duration,
+    ) {
+        //Wait for the first write of the next batch
+        while let Some(first_write) = receiver.recv().await {
+            let mut pending = FxHashMap::default();
+            pending.insert(first_write.var.id, first_write);
+
+            let deadline = tokio::time::sleep(max_delay);
+            tokio::pin!(deadline);
+
+            let mut closed = false;
+            loop {
+                tokio::select! {
+                    () = &mut deadline => break,
+                    write = receiver.recv() => {
+                        if let Some(write) = write {
+                            pending.insert(write.var.id, write);
+                        } else {
+                            closed = true;
+                            break;
+                        }
+                    }
+                }
+            }
+
+            let writes = pending.into_values().collect();
+            if let Err(e) = Self::flush_batched_writes(&provider, writes).await {
+                error!(
+                    "Failed to write batched variables to provider {}: {e}",
+                    provider.get_provider_id()
+                );
+            }
+
+            if closed {
+                break;
+            }
+        }
+    }
+
+    /// Sends the given writes as a single write command.
+    ///
+    /// Writes that were resolved against an outdated provider definition are dropped.
+    async fn flush_batched_writes(
+        provider: &ConnectedNatsProvider,
+        writes: Vec<BatchedWrite>,
+    ) -> Result<()> {
+        let provider_definition_fingerprint = provider.get_fingerprint().ok_or_else(|| {
+            connected_nats_provider::Error::ProviderOfflineOrInvalid(
+                provider.get_provider_id().to_owned(),
+            )
+        })?;
+
+        let num_writes = writes.len();
+        let changed_vars: Vec<VariableT> = writes
+            .into_iter()
+            .filter(|write| write.fingerprint == provider_definition_fingerprint)
+            .map(|write| write.var)
+            .collect();
+
+        if changed_vars.len() != num_writes {
+            warn!(
+                "Dropped {} batched writes to provider {} because its definition changed",
+                num_writes - changed_vars.len(),
+                provider.get_provider_id()
+            );
+        }
+
+        if changed_vars.is_empty() {
+            return Ok(());
+        }
+
+        let write_command = WriteVariablesCommandT {
+            variables: Box::new(VariableListT {
+                provider_definition_fingerprint,
+                //TODO: Only to be set by the provider. For now, add default filler. May revise flatbuffer api later
+                base_timestamp: TimestampT::default(),
+                items: Some(changed_vars),
+            }),
+        };
+
+        provider.write_variables(&write_command).await?;
 
         Ok(())
     }
 
+    /// Maps a low level read response to user friendly data types.
+    fn map_read_response(
+        low_level_data: ReadVariablesQueryResponseT,
+        ignore_unknown_values: bool,
+    ) -> Vec<(VariableID, VariableState)> {
+        let base_timestamp = low_level_data.variables.base_timestamp.into();
+
+        low_level_data
+            .variables
+            .items
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|ll_var| -> Option<(u32, VariableState)> {
+                //Flatbuffer deserializes unknown union values to NONE
+                if ignore_unknown_values && ll_var.value == VariableValueT::NONE {
+                    return None;
+                }
+
+                let map_entry = (ll_var.id, VariableState::new(ll_var, base_timestamp).ok()?);
+
+                Some(map_entry)
+            })
+            .collect()
+    }
+
     /// Builds a hashset of filter IDs for efficient filtering in stream.
     ///
     /// If a key doesnt exist, its id will be skipped and not be inserted into the filter set.
-    fn build_filter_set<'a>(
-        filter_list: &[impl VariableKeyLike<'a>],
+    /// Patterns are matched against the keys of the current provider definition.
+    fn build_filter_set(
+        filter: &VariableFilter,
         low_level_state: &ConnectedNatsProviderState,
     ) -> HashSet<VariableID> {
-        let mut filter_set = HashSet::with_capacity(filter_list.len());
+        match filter {
+            VariableFilter::Keys(key_hashes) => {
+                let mut filter_set = HashSet::with_capacity(key_hashes.len());
+
+                for key_hash in key_hashes {
+                    let id = low_level_state.var_mapping.get(key_hash);
 
-        for var in filter_list {
-            let key: VariableKey = (*var).into();
-            let id = low_level_state.var_mapping.get(&key.key_hash);
+                    if let Some(id) = id {
+                        filter_set.insert(*id);
+                    }
+                }
 
-            if let Some(id) = id {
-                filter_set.insert(*id);
+                filter_set
             }
+            VariableFilter::Patterns(patterns) => low_level_state
+                .cur_variable_defs
+                .values()
+                .filter(|var_def| {
+                    patterns
+                        .iter()
+                        .any(|pattern| matches_key_pattern(pattern, &var_def.key))
+                })
+                .map(|var_def| var_def.id)
+                .collect(),
         }
-
-        filter_set
     }
 
     /// Applies the specified filter set to the event and returns a hashmap of variable IDs to variable states.
+    ///
+    /// `filter_mode` defines whether the filter set contains the variables to keep or to drop.
     fn process_var_changed_evt(
         filter_set: Option<&HashSet<u32>>,
+        filter_mode: FilterMode,
         var_changed_evt: connected_nats_provider::Result<VariablesChangedEventT>,
         ignore_unknown_values: bool,
     ) -> Option<Vec<(VariableID, VariableState)>> {
@@ -552,7 +2334,7 @@ impl DataHubProviderConnection {
             .into_iter()
             .filter_map(|ll_var| -> Option<(VariableID, VariableState)> {
                 if let Some(filter_set) = &filter_set {
-                    if !filter_set.contains(&ll_var.id) {
+                    if !filter_mode.keeps(filter_set.contains(&ll_var.id)) {
                         return None;
                     }
                 }
@@ -571,3 +2353,47 @@ impl DataHubProviderConnection {
         Some(received_filtered_vars)
     }
 }
+
+/// Ties the lifetime of `owner` to the lifetime of `stream`.
+///
+/// Used to keep the low level provider connection (and therefore its event loop and NATS client) alive
+/// for as long as a stream is in use, independent of the order in which the user drops its handles.
+/// The returned stream is fused, so it can be polled again after it ended.
+fn keep_alive<S, T>(stream: S, owner: T) -> impl FusedStream<Item = S::Item> + Unpin
+where
+    S: Stream + Unpin,
+{
+    Box::pin(futures::stream::unfold(
+        (stream, owner),
+        |(mut stream, owner)| async move {
+            let item = stream.next().await?;
+            Some((item, (stream, owner)))
+        },
+    ))
+    .fuse()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_map_write_error() {
+        let error = DataHubProviderConnection::map_write_error(
+            connected_nats_provider::Error::ProviderFingerprintMismatch {
+                expected: 1,
+                actual: 2,
+            },
+        );
+        assert!(matches!(error, Error::DefinitionChangedDuringWrite));
+
+        //Other errors are propagated, even if the definition changed in the meantime
+        let error = DataHubProviderConnection::map_write_error(
+            connected_nats_provider::Error::WritingToReadonly("my_var".to_owned()),
+        );
+        assert!(matches!(
+            error,
+            Error::LowLevelApi(connected_nats_provider::Error::WritingToReadonly(_))
+        ));
+    }
+}
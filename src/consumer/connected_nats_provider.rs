@@ -4,28 +4,35 @@
 
 //! This module provides a low-level API for interacting with a variable hub provider via NATS.
 
-use std::sync::{Arc, RwLock};
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc, RwLock,
+};
 
+use async_nats::{HeaderMap, RequestErrorKind};
 use bytes::Bytes;
 use flatbuffers::FlatBufferBuilder;
 use futures::{Stream, StreamExt};
 use rustc_hash::FxHashMap;
 use thiserror::Error;
 use tokio::task::JoinHandle;
-use tracing::{error, warn};
+use tracing::{debug, warn};
 
 use crate::{
     authenticated_nats_con::NatsPermission,
-    dh_types::VariableID,
+    dh_types::{ProviderStatus, TimestampValue, VariableID},
     generated::weidmueller::ucontrol::hub::{
         ProviderDefinitionChangedEvent, ProviderDefinitionChangedEventT, ProviderDefinitionState,
         ProviderDefinitionT, ReadProviderDefinitionQueryRequestT,
         ReadProviderDefinitionQueryResponse, ReadProviderDefinitionQueryResponseT,
         ReadVariablesQueryRequestT, ReadVariablesQueryResponse, ReadVariablesQueryResponseT,
-        VariableAccessType, VariableDataType, VariableDefinitionT, VariableValueT,
-        VariablesChangedEvent, VariablesChangedEventT, WriteVariablesCommandT,
+        Variable, VariableAccessType, VariableDataType, VariableDefinitionT, VariableT,
+        VariableValueT, VariablesChangedEventT, WriteVariablesCommandT,
     },
     nats_subjects,
+    payload_codec::{PayloadCodec, PayloadDecodeError},
+    payload_compression::{self, PayloadDecompressError},
+    provider::variable_definition_validator::MAX_VARIABLE_KEY_LENGTH,
 };
 
 use super::{
@@ -33,6 +40,28 @@ use super::{
     variable_key::{VariableKey, VariableKeyHash},
 };
 
+/// Name of the NATS header that contains the start of the range of a history request, see [`ConnectedNatsProvider::read_history`].
+pub const HISTORY_FROM_HEADER: &str = "History-From";
+
+/// Name of the NATS header that contains the end of the range of a history request, see [`ConnectedNatsProvider::read_history`].
+pub const HISTORY_TO_HEADER: &str = "History-To";
+
+/// Name of the NATS header that asks the provider to omit timestamps and qualities in a read response,
+/// see [`ConnectedNatsProvider::read_variables_values_only_unchecked`].
+pub const READ_VALUES_ONLY_HEADER: &str = "Read-Values-Only";
+
+/// Name of the NATS header of a write acknowledgement that contains the comma separated IDs of the accepted variable writes,
+/// see [`ConnectedNatsProvider::write_variables_with_ack`].
+pub const WRITE_ACCEPTED_IDS_HEADER: &str = "Write-Accepted-Ids";
+
+/// Name of the NATS header of a write acknowledgement that contains the comma separated IDs of the rejected variable writes,
+/// see [`ConnectedNatsProvider::write_variables_with_ack`].
+pub const WRITE_REJECTED_IDS_HEADER: &str = "Write-Rejected-Ids";
+
+/// Name of the NATS header of a write acknowledgement that is set if the provider could not decode the write command,
+/// see [`ConnectedNatsProvider::write_variables_with_ack`].
+pub const WRITE_INVALID_COMMAND_HEADER: &str = "Write-Invalid-Command";
+
 /// Error type for the connected nats provider
 #[derive(Error, Debug)]
 #[allow(missing_docs)]
@@ -47,6 +76,12 @@ pub enum Error {
     NatsSub(#[from] async_nats::SubscribeError),
     #[error("Invalid payload/deserialization failure: {0}")]
     InvalidPayload(#[from] flatbuffers::InvalidFlatbuffer),
+    #[error("Invalid payload/deserialization failure: {0}")]
+    InvalidCodecPayload(Box<dyn std::error::Error + Send + Sync>),
+    #[error("Invalid payload/deserialization failure: {0}")]
+    CompressedPayload(#[from] PayloadDecompressError),
+    #[error("Invalid write acknowledgement headers")]
+    InvalidWriteAck,
     #[error("Provider fingerprint mismatch. Expected: {expected}, Actual: {actual}")]
     ProviderFingerprintMismatch { expected: u64, actual: u64 },
     #[error("Variable ID {0} is unknown")]
@@ -59,8 +94,21 @@ pub enum Error {
     InvalidValueType,
     #[error("The provider '{0}' is currently offline or has invalid state")]
     ProviderOfflineOrInvalid(String),
+    #[error("The provider '{0}' does not support reading historical values")]
+    HistoryUnsupported(String),
     #[error("Action not permitted: {0}")]
     InsufficientPermissions(String),
+    #[error("Multiple providers seem to be registered with the id '{0}', the provider definition is inconsistent")]
+    DuplicateProviderDetected(String),
+}
+
+impl From<PayloadDecodeError> for Error {
+    fn from(value: PayloadDecodeError) -> Self {
+        match value {
+            PayloadDecodeError::InvalidFlatbuffer(e) => Self::InvalidPayload(e),
+            PayloadDecodeError::Custom(e) => Self::InvalidCodecPayload(e),
+        }
+    }
 }
 
 /// Shared state for the connected provider.
@@ -89,16 +137,102 @@ pub(super) struct ConnectedNatsProviderState {
     pub(super) var_mapping: FxHashMap<VariableKeyHash, VariableID>,
 }
 
+/// Read-only snapshot of the internal state of a [`ConnectedNatsProvider`].
+///
+/// Intended for diagnostics only, e.g. to log what the consumer believes about the provider when a lookup unexpectedly fails.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DebugState {
+    /// The cached fingerprint of the provider definition, `None` if the provider is offline or invalid.
+    pub fingerprint: Option<u64>,
+    /// Maps from variable ID to variable key.
+    pub id_to_key: FxHashMap<VariableID, String>,
+    /// Number of entries in the key to ID lookup table.
+    ///
+    /// Should always match the length of [`Self::id_to_key`].
+    pub var_mapping_len: usize,
+}
+
+/// Result of a write command, returned by [`ConnectedNatsProvider::write_variables_with_ack`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WriteAck {
+    /// IDs of the variables whose write was accepted and forwarded to the write handlers of the provider.
+    pub accepted_ids: Vec<VariableID>,
+    /// IDs of the variables whose write was rejected by the provider,
+    /// e.g. because they are read only, unknown, the value is out of bounds, the provider has no write handler for them
+    /// or could not decode the command.
+    pub rejected_ids: Vec<VariableID>,
+}
+
+impl WriteAck {
+    /// Returns true if the provider accepted the writes of all variables.
+    #[must_use]
+    pub fn all_accepted(&self) -> bool {
+        self.rejected_ids.is_empty()
+    }
+}
+
+impl WriteAck {
+    /// Encodes the acknowledgement as headers of an empty reply, empty ID lists are omitted.
+    pub(crate) fn to_headers(&self) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, ids) in [
+            (WRITE_ACCEPTED_IDS_HEADER, &self.accepted_ids),
+            (WRITE_REJECTED_IDS_HEADER, &self.rejected_ids),
+        ] {
+            if !ids.is_empty() {
+                let value = ids
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+                    .join(",");
+                headers.insert(name, value.as_str());
+            }
+        }
+        headers
+    }
+
+    /// Decodes the acknowledgement from the headers of a reply, missing headers are treated as empty ID lists.
+    ///
+    /// Returns `None` if a header contains an invalid ID.
+    pub(crate) fn from_headers(headers: Option<&HeaderMap>) -> Option<Self> {
+        let parse_ids = |name: &str| -> Option<Vec<VariableID>> {
+            match headers.and_then(|headers| headers.get(name)) {
+                Some(value) => value
+                    .as_str()
+                    .split(',')
+                    .map(|id| id.trim().parse().ok())
+                    .collect(),
+                None => Some(Vec::new()),
+            }
+        };
+
+        Some(Self {
+            accepted_ids: parse_ids(WRITE_ACCEPTED_IDS_HEADER)?,
+            rejected_ids: parse_ids(WRITE_REJECTED_IDS_HEADER)?,
+        })
+    }
+}
+
 /// Represents a connection to a data hub provider.
 /// This is used to read and write variables from/to the provider.
 ///
 /// Internally uses an event loop to constantly synch with the provider definition.
 /// This allows proper error handling when interating with the provider.
+///
+/// Received provider definitions are sanitized: variables with keys longer than [`MAX_VARIABLE_KEY_LENGTH`] violate the schema
+/// and are dropped with a warning, so a buggy or malicious provider can't inject pathological keys into the consumer state.
+/// The registry should never forward such definitions, so this only guards against misbehaving peers.
+///
+/// The connection holds a reference to its [`NatsConsumer`], so the NATS client stays alive as long as the connection exists.
+/// The event loop is aborted once the connection is dropped. Streams returned by this connection only hold their NATS subscription,
+/// so they keep receiving events, but the cached provider definition will no longer be updated after the connection is dropped.
 pub struct ConnectedNatsProvider {
     provider_id: String,
     consumer: Arc<NatsConsumer>,
     event_loop_task: JoinHandle<()>,
     state: SharedState,
+    /// Number of received payloads that could not be deserialized.
+    deserialization_errors: Arc<AtomicU64>,
 }
 
 impl ConnectedNatsProvider {
@@ -109,28 +243,49 @@ impl ConnectedNatsProvider {
     ///
     /// This method may fail if there is an issue with the nats connection, the hub registry is offline
     /// or something goes wrong while deserializing flatbuffer payloads.
+    ///
+    /// As a light consistency check, the provider definition is read more than once.
+    /// If the fingerprint changes and then changes back to the first one, multiple providers are probably registered
+    /// with the same ID and [`Error::DuplicateProviderDetected`] is returned. Consecutive updates are accepted.
+    /// This check is best-effort, it can not detect duplicates that do not alternate during the reads.
     pub async fn new(consumer: Arc<NatsConsumer>, provider_id: impl Into<String>) -> Result<Self> {
         let provider_id: String = provider_id.into();
 
-        //Initialize state
-        let nats_client = consumer.get_nats_con().get_client();
+        let provider_def =
+            Self::read_consistent_provider_definition(&consumer, &provider_id).await?;
 
-        //This will automatically fail if the provider ID doesnt exist, so we dont need additional error handling
-        let provider_def_read_resp =
-            Self::read_provider_definition_internal(nats_client, &provider_id)
-                .await
-                .map_err(|_| Error::ProviderOfflineOrInvalid(provider_id.clone()))?;
+        Self::from_provider_definition(consumer, provider_id, *provider_def).await
+    }
 
-        let provider_def = provider_def_read_resp
-            .provider_definition
-            .ok_or_else(|| Error::ProviderOfflineOrInvalid(provider_id.clone()))?;
+    /// Same as [`Self::new`], but uses `known_def` instead of reading the provider definition from the registry.
+    ///
+    /// This allows to keep working with a provider whose definition is already known,
+    /// e.g. from a previous connection, while the hub registry is offline. Reads and writes are sent directly to the provider.
+    /// Once the registry is online again, the cached definition is updated by the internal event loop as usual.
+    ///
+    /// The definition is not validated. If `known_def` is outdated, the provider ignores write commands,
+    /// as the fingerprints no longer match. Read responses contain the fingerprint of the provider,
+    /// which can be compared to the fingerprint of `known_def` to detect this.
+    /// The state of `known_def` is ignored, the provider is always assumed to be online initially.
+    pub async fn new_with_definition(
+        consumer: Arc<NatsConsumer>,
+        provider_id: impl Into<String>,
+        known_def: ProviderDefinitionT,
+    ) -> Result<Self> {
+        Self::from_provider_definition(consumer, provider_id.into(), known_def).await
+    }
 
-        if provider_def.state != ProviderDefinitionState::OK {
-            return Err(Error::ProviderOfflineOrInvalid(provider_id.clone()));
-        }
+    /// Initializes the state from `provider_def` and starts the internal event loop.
+    async fn from_provider_definition(
+        consumer: Arc<NatsConsumer>,
+        provider_id: String,
+        provider_def: ProviderDefinitionT,
+    ) -> Result<Self> {
+        //Initialize state
+        let nats_client = consumer.get_nats_con().get_client();
 
         let cur_fingerprint = provider_def.fingerprint;
-        let cur_variable_defs = Self::variable_defs_from_provider_def(*provider_def);
+        let cur_variable_defs = Self::variable_defs_from_provider_def(provider_def);
         let var_mapping = Self::key_mapping_from_variable_defs(&cur_variable_defs);
 
         let state = Arc::new(RwLock::new(ConnectedNatsProviderState {
@@ -143,7 +298,12 @@ impl ConnectedNatsProvider {
         let event_stream =
             Self::subscribe_provider_definition_internal(nats_client, &provider_id).await?;
 
-        let event_loop_task = tokio::spawn(Self::internal_event_loop(event_stream, state.clone()));
+        let deserialization_errors = Arc::new(AtomicU64::new(0));
+        let event_loop_task = tokio::spawn(Self::internal_event_loop(
+            event_stream,
+            state.clone(),
+            deserialization_errors.clone(),
+        ));
 
         //Create new instance
         let instance = Self {
@@ -151,6 +311,7 @@ impl ConnectedNatsProvider {
             consumer,
             event_loop_task,
             state,
+            deserialization_errors,
         };
 
         Ok(instance)
@@ -162,6 +323,12 @@ impl ConnectedNatsProvider {
         &self.consumer
     }
 
+    /// Returns the codec that is used for variable payloads, see [`NatsConsumer::with_payload_codec`].
+    #[must_use]
+    pub fn get_payload_codec(&self) -> &Arc<dyn PayloadCodec> {
+        self.consumer.get_payload_codec()
+    }
+
     /// Returns the provider ID.
     #[must_use]
     pub fn get_provider_id(&self) -> &str {
@@ -180,6 +347,18 @@ impl ConnectedNatsProvider {
         self.state.read().unwrap().cur_fingerprint
     }
 
+    /// Returns the number of payloads received for this provider that could not be deserialized.
+    ///
+    /// This makes problems like version mismatches or corrupted payloads observable, which are otherwise
+    /// skipped silently by many streams of the high level API. Counted are provider definition events,
+    /// which are received once by the internal event loop, read responses, and variable change events.
+    /// As each stream returned by [`Self::subscribe_variables`] receives all change events,
+    /// an invalid change event is counted once per active subscription.
+    #[must_use]
+    pub fn deserialization_error_count(&self) -> u64 {
+        self.deserialization_errors.load(Ordering::Relaxed)
+    }
+
     /// Returns if the provider is online and has a valid definition.
     #[must_use]
     #[allow(clippy::missing_panics_doc)] // See comment below
@@ -232,6 +411,27 @@ impl ConnectedNatsProvider {
         self.state.read().unwrap().cur_variable_defs.clone()
     }
 
+    /// Returns a snapshot of the internal state for diagnostic purposes.
+    ///
+    /// The snapshot is taken atomically, but will not be updated if the provider definition changes afterwards.
+    #[must_use]
+    #[allow(clippy::missing_panics_doc)] // See comment below
+    pub fn debug_state(&self) -> DebugState {
+        //Safety: Unwrap is ok here as we know that the mutex cant be poisoned during writing
+        #[allow(clippy::unwrap_used)]
+        let state = self.state.read().unwrap();
+
+        DebugState {
+            fingerprint: state.cur_fingerprint,
+            id_to_key: state
+                .cur_variable_defs
+                .iter()
+                .map(|(id, var_def)| (*id, var_def.key.clone()))
+                .collect(),
+            var_mapping_len: state.var_mapping.len(),
+        }
+    }
+
     /// Converts a variable key to a variable ID.
     ///
     /// Please note that variable IDs and their mapping to keys may change at any time if the provider definition changes.
@@ -283,13 +483,28 @@ impl ConnectedNatsProvider {
             .contains_key(&id)
     }
 
+    /// Checks if the specified variable key exists on the provider
+    #[must_use]
+    #[allow(clippy::missing_panics_doc)] // See comment below
+    pub fn is_variable_key_valid<'a>(&self, key: impl Into<VariableKey<'a>>) -> bool {
+        let key: VariableKey = key.into();
+
+        //Safety: Unwrap is ok here as we know that the mutex cant be poisoned during writing
+        #[allow(clippy::unwrap_used)]
+        self.state
+            .read()
+            .unwrap()
+            .var_mapping
+            .contains_key(&key.key_hash)
+    }
+
     /// Reads the provider definition from the registry.
     ///
     /// This method may fail if there is an issue with the nats connection, the hub registry is offline
     /// or something goes wrong while deserializing flatbuffer payloads.
     pub async fn read_provider_definition(&self) -> Result<ReadProviderDefinitionQueryResponseT> {
         self.check_online()?;
-        Self::read_provider_definition_internal(self.get_nats_client(), &self.provider_id).await
+        Self::read_provider_definition_internal(&self.consumer, &self.provider_id).await
     }
 
     /// Subscribes to changes to the provider definition on the hub registry and returns a stream of change events.
@@ -308,6 +523,39 @@ impl ConnectedNatsProvider {
             .await
     }
 
+    /// Reads the current status from the provider, see [`ProviderStatus`].
+    ///
+    /// This method may fail if there is an issue with the nats connection or the provider is unavailable.
+    /// Providers that use a client version without status support do not answer, so the request fails as well.
+    pub async fn read_provider_status(&self) -> Result<ProviderStatus> {
+        self.check_online()?;
+
+        let response = self
+            .consumer
+            .request(
+                nats_subjects::provider_status_read_query(&self.provider_id),
+                Bytes::new(),
+            )
+            .await?;
+
+        Ok(String::from_utf8_lossy(&response.payload).as_ref().into())
+    }
+
+    /// Subscribes to status changes of the provider and returns a stream of the new status values.
+    ///
+    /// This method will succeed even if the provider is currently offline, but may return an error if there is an issue
+    /// with the NATS connection.
+    pub async fn subscribe_provider_status(&self) -> Result<impl Stream<Item = ProviderStatus>> {
+        let subscription = self
+            .get_nats_client()
+            .subscribe(nats_subjects::provider_status_changed_event(
+                &self.provider_id,
+            ))
+            .await?;
+
+        Ok(subscription.map(|message| String::from_utf8_lossy(&message.payload).as_ref().into()))
+    }
+
     /// Reads the current state of the provided variable IDs from the provider.
     ///
     /// Will check if all supplied variable IDs are still valid before sending the read request.
@@ -341,27 +589,171 @@ impl ConnectedNatsProvider {
         &self,
         variable_ids: &ReadVariablesQueryRequestT,
     ) -> Result<ReadVariablesQueryResponseT> {
-        //Create flatbuffer read request payload
-        let mut builder = FlatBufferBuilder::new();
-        let offset = variable_ids.pack(&mut builder);
-        builder.finish(offset, None);
+        self.read_variables_on_subject(
+            &nats_subjects::read_variables_query(&self.provider_id),
+            variable_ids,
+        )
+        .await
+    }
 
-        //use collapse to avoid copying vector
-        let (all_bytes, data_start_offset) = builder.collapse();
-        let request_bytes = Bytes::from(all_bytes).slice(data_start_offset..);
+    /// Same as `read_variables_unchecked`, but sends the request to the given NATS subject
+    /// instead of the read subject of the provider.
+    ///
+    /// This is an escape hatch for special setups, e.g. a mock provider in tests or a proxy that remaps subjects.
+    /// The response must still match the definition of this provider, as the variable IDs are interpreted accordingly.
+    pub async fn read_variables_on_subject(
+        &self,
+        subject: &str,
+        variable_ids: &ReadVariablesQueryRequestT,
+    ) -> Result<ReadVariablesQueryResponseT> {
+        let reply_payload = self
+            .request_read_variables(subject.to_owned(), variable_ids, false)
+            .await?;
+
+        self.decode_read_variables_response(&reply_payload)
+    }
+
+    /// Same as `read_variables_unchecked`, but asks the provider to only send the variable values
+    /// by setting the [`READ_VALUES_ONLY_HEADER`].
+    ///
+    /// This reduces the payload size for constrained links, as the provider omits timestamps and qualities.
+    /// Providers that do not support the header still send full responses.
+    pub async fn read_variables_values_only_unchecked(
+        &self,
+        variable_ids: &ReadVariablesQueryRequestT,
+    ) -> Result<ReadVariablesQueryResponseT> {
+        let reply_payload = self
+            .request_read_variables(
+                nats_subjects::read_variables_query(&self.provider_id),
+                variable_ids,
+                true,
+            )
+            .await?;
+
+        self.decode_read_variables_response(&reply_payload)
+    }
+
+    /// Deserializes a read response and counts failures as deserialization errors.
+    fn decode_read_variables_response(
+        &self,
+        reply_payload: &[u8],
+    ) -> Result<ReadVariablesQueryResponseT> {
+        let response = self
+            .get_payload_codec()
+            .decode_read_variables_query_response(reply_payload);
+        if response.is_err() {
+            self.deserialization_errors.fetch_add(1, Ordering::Relaxed);
+        }
+
+        Ok(response?)
+    }
+
+    /// Same as `read_variables_unchecked`, but returns the raw flatbuffer payload of the response
+    /// instead of unpacking it into owned types.
+    ///
+    /// For large responses, this avoids materializing all variables at once.
+    /// Use [`for_each_variable`] to iterate over the variables of the returned payload.
+    ///
+    /// The payload is encoded by the [`PayloadCodec`] of the consumer, so it is only a flatbuffer
+    /// if the default [`FlatbufferCodec`](crate::payload_codec::FlatbufferCodec) is used.
+    pub async fn read_variables_raw(
+        &self,
+        variable_ids: &ReadVariablesQueryRequestT,
+    ) -> Result<Bytes> {
+        self.request_read_variables(
+            nats_subjects::read_variables_query(&self.provider_id),
+            variable_ids,
+            false,
+        )
+        .await
+    }
+
+    /// Sends a read request to the given subject and returns the raw payload of the response.
+    ///
+    /// If `values_only` is set, the [`READ_VALUES_ONLY_HEADER`] is added to the request.
+    async fn request_read_variables(
+        &self,
+        subject: String,
+        variable_ids: &ReadVariablesQueryRequestT,
+        values_only: bool,
+    ) -> Result<Bytes> {
+        let request_bytes = self
+            .get_payload_codec()
+            .encode_read_variables_query_request(variable_ids);
 
         //Send read request
+        let reply = if values_only {
+            let mut headers = HeaderMap::new();
+            headers.insert(READ_VALUES_ONLY_HEADER, "true");
+            self.consumer
+                .request_with_headers(subject, headers, request_bytes)
+                .await?
+        } else {
+            self.consumer.request(subject, request_bytes).await?
+        };
+
+        Ok(reply.payload)
+    }
+
+    /// Reads the historical states of a variable between `from` and `to` (both inclusive) from the provider.
+    ///
+    /// Only providers that store a history answer these requests, all others fail with [`Error::HistoryUnsupported`].
+    /// The request is sent to [`nats_subjects::read_history_query`] and contains a read request for the variable,
+    /// while the range is sent as nanoseconds since the unix epoch in the [`HISTORY_FROM_HEADER`] and [`HISTORY_TO_HEADER`] headers.
+    /// The response is a regular read response that may contain multiple states of the variable.
+    ///
+    /// Will check if the variable ID is valid before sending the request.
+    pub async fn read_history(
+        &self,
+        id: VariableID,
+        from: TimestampValue,
+        to: TimestampValue,
+    ) -> Result<ReadVariablesQueryResponseT> {
+        self.check_online()?;
+
+        if !self.is_variable_id_valid(id) {
+            return Err(Error::InvalidVariableId(id));
+        }
+
+        let request_bytes = self
+            .get_payload_codec()
+            .encode_read_variables_query_request(&ReadVariablesQueryRequestT {
+                ids: Some(vec![id]),
+            });
+
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            HISTORY_FROM_HEADER,
+            from.unix_timestamp_nanos().to_string().as_str(),
+        );
+        headers.insert(
+            HISTORY_TO_HEADER,
+            to.unix_timestamp_nanos().to_string().as_str(),
+        );
+
         let reply = self
-            .get_nats_client()
-            .request(
-                nats_subjects::read_variables_query(&self.provider_id),
+            .consumer
+            .request_with_headers(
+                nats_subjects::read_history_query(&self.provider_id),
+                headers,
                 request_bytes,
             )
-            .await?;
+            .await
+            .map_err(|e| match e.kind() {
+                RequestErrorKind::NoResponders => {
+                    Error::HistoryUnsupported(self.provider_id.clone())
+                }
+                _ => e.into(),
+            })?;
+
+        let response = self
+            .get_payload_codec()
+            .decode_read_variables_query_response(&reply.payload);
+        if response.is_err() {
+            self.deserialization_errors.fetch_add(1, Ordering::Relaxed);
+        }
 
-        //Deserialize reply
-        let reply_payload = flatbuffers::root::<ReadVariablesQueryResponse>(&reply.payload)?;
-        Ok(reply_payload.unpack())
+        Ok(response?)
     }
 
     /// Subscribes to changes of the variable states of the provider and returns a stream of change events.
@@ -372,8 +764,8 @@ impl ConnectedNatsProvider {
     /// This method will succeed even if the hub registry is currently offline, but may return an error if there is an issue
     /// with the NATS connection or the provider is no longer available.
     ///
-    /// While subscribed, this method will try to deserialize the flatbuffer payloads that are received via NATS.
-    /// If the flatbuffer deserialization fails, the stream will yield an error value but the subscription will not be cancelled.
+    /// While subscribed, this method will try to deserialize the payloads that are received via NATS.
+    /// If the deserialization fails, the stream will yield an error value but the subscription will not be cancelled.
     pub async fn subscribe_variables(
         &self,
     ) -> Result<impl Stream<Item = Result<VariablesChangedEventT>>> {
@@ -382,9 +774,22 @@ impl ConnectedNatsProvider {
             .subscribe(nats_subjects::vars_changed_event(&self.provider_id))
             .await?;
 
-        let result_stream = subscription.map(|message| -> Result<VariablesChangedEventT> {
-            let payload = flatbuffers::root::<VariablesChangedEvent>(&message.payload)?;
-            Ok(payload.unpack())
+        let payload_codec = self.get_payload_codec().clone();
+        let max_decompressed_size = self.consumer.get_max_decompressed_size();
+        let deserialization_errors = self.deserialization_errors.clone();
+        let result_stream = subscription.map(move |message| -> Result<VariablesChangedEventT> {
+            let event = payload_compression::decompress(
+                message.headers.as_ref(),
+                message.payload,
+                max_decompressed_size,
+            )
+            .map_err(Error::from)
+            .and_then(|payload| Ok(payload_codec.decode_variables_changed_event(&payload)?));
+            if event.is_err() {
+                deserialization_errors.fetch_add(1, Ordering::Relaxed);
+            }
+
+            event
         });
 
         Ok(result_stream)
@@ -404,8 +809,42 @@ impl ConnectedNatsProvider {
         &self,
         write_command: &WriteVariablesCommandT,
     ) -> Result<()> {
-        //perform cheap checks only
+        self.write_variables_on_subject(
+            &nats_subjects::write_variables_command(&self.provider_id),
+            write_command,
+        )
+        .await
+    }
 
+    /// Same as `write_variables_unchecked`, but publishes the command to the given NATS subject
+    /// instead of the write subject of the provider.
+    ///
+    /// This is an escape hatch for special setups, e.g. a mock provider in tests or a proxy that remaps subjects.
+    /// The same cheap checks as in `write_variables_unchecked` are performed against the definition of this provider.
+    pub async fn write_variables_on_subject(
+        &self,
+        subject: &str,
+        write_command: &WriteVariablesCommandT,
+    ) -> Result<()> {
+        self.check_write_preconditions(write_command)?;
+
+        let request_bytes = self
+            .get_payload_codec()
+            .encode_write_variables_command(write_command);
+
+        //Send write command
+        self.get_nats_client()
+            .publish(subject.to_owned(), request_bytes)
+            .await?;
+
+        //Flush to ensure command is sent
+        self.get_nats_client().flush().await?;
+
+        Ok(())
+    }
+
+    /// Performs the cheap checks of `write_variables_unchecked`.
+    fn check_write_preconditions(&self, write_command: &WriteVariablesCommandT) -> Result<()> {
         //Check nats permissions if the client knows them
         //Note that the server will always check permissions, so this is just an additional convenience check
         let nats_perms = self.get_consumer().get_nats_con().get_permissions();
@@ -428,31 +867,12 @@ impl ConnectedNatsProvider {
             });
         }
 
-        //Create flatbuffer read request payload
-        let mut builder = FlatBufferBuilder::new();
-        let offset = write_command.pack(&mut builder);
-        builder.finish(offset, None);
-
-        //use collapse to avoid copying vector
-        let (all_bytes, data_start_offset) = builder.collapse();
-        let request_bytes = Bytes::from(all_bytes).slice(data_start_offset..);
-
-        //Send write command
-        self.get_nats_client()
-            .publish(
-                nats_subjects::write_variables_command(&self.provider_id),
-                request_bytes,
-            )
-            .await?;
-
-        //Flush to ensure command is sent
-        self.get_nats_client().flush().await?;
-
         Ok(())
     }
 
     /// Sends a variable write command to the provider.
     /// Note that the provider decides if the write is accepted or not, however, the provider will not reply to the write command.
+    /// Use [`Self::write_variables_with_ack`] if you need to know which writes were accepted.
     ///
     /// This triggers a nats publish and flush command.
     ///
@@ -467,8 +887,109 @@ impl ConnectedNatsProvider {
         self.write_variables_unchecked(write_command).await
     }
 
+    /// Same as [`Self::write_variables`], but sends the command as request and waits until the provider
+    /// reports which variable writes it accepted and which it rejected.
+    ///
+    /// The provider replies with an empty payload and lists the variable IDs
+    /// in the [`WRITE_ACCEPTED_IDS_HEADER`] and [`WRITE_REJECTED_IDS_HEADER`] headers.
+    /// If the provider could not decode the command, e.g. because it uses a different [`PayloadCodec`],
+    /// it sets the [`WRITE_INVALID_COMMAND_HEADER`] instead and all writes of the command are reported as rejected.
+    ///
+    /// An accepted write was forwarded to the write handlers of the provider,
+    /// it does not guarantee that the provider applied the value.
+    ///
+    /// Providers built with an older version of this library do not reply to write commands,
+    /// so the request fails with a timeout, see [`NatsConsumer::with_request_timeout`].
+    pub async fn write_variables_with_ack(
+        &self,
+        write_command: &WriteVariablesCommandT,
+    ) -> Result<WriteAck> {
+        self.check_online()?;
+
+        self.check_write_command(write_command)?;
+        self.check_write_preconditions(write_command)?;
+
+        let request_bytes = self
+            .get_payload_codec()
+            .encode_write_variables_command(write_command);
+        let reply = self
+            .consumer
+            .request(
+                nats_subjects::write_variables_command(&self.provider_id),
+                request_bytes,
+            )
+            .await?;
+
+        let invalid_command = reply
+            .headers
+            .as_ref()
+            .is_some_and(|headers| headers.get(WRITE_INVALID_COMMAND_HEADER).is_some());
+        if invalid_command {
+            return Ok(WriteAck {
+                accepted_ids: Vec::new(),
+                rejected_ids: write_command
+                    .variables
+                    .items
+                    .iter()
+                    .flatten()
+                    .map(|item| item.id)
+                    .collect(),
+            });
+        }
+
+        let Some(ack) = WriteAck::from_headers(reply.headers.as_ref()) else {
+            self.deserialization_errors.fetch_add(1, Ordering::Relaxed);
+            return Err(Error::InvalidWriteAck);
+        };
+
+        Ok(ack)
+    }
+
+    /// Reads a valid provider definition and checks that the fingerprint is stable.
+    ///
+    /// Changes between the reads are accepted, as the provider may legitimately update its definition.
+    /// Only if the fingerprint changes back to the one of the first read, we assume multiple providers alternate on the same ID.
+    async fn read_consistent_provider_definition(
+        consumer: &NatsConsumer,
+        provider_id: &str,
+    ) -> Result<Box<ProviderDefinitionT>> {
+        let first = Self::read_valid_provider_definition(consumer, provider_id).await?;
+        let second = Self::read_valid_provider_definition(consumer, provider_id).await?;
+        if second.fingerprint == first.fingerprint {
+            return Ok(second);
+        }
+
+        let third = Self::read_valid_provider_definition(consumer, provider_id).await?;
+        if third.fingerprint == first.fingerprint {
+            return Err(Error::DuplicateProviderDetected(provider_id.to_owned()));
+        }
+
+        Ok(third)
+    }
+
+    /// Reads the provider definition and fails if the provider is offline or its definition is invalid.
+    pub(super) async fn read_valid_provider_definition(
+        consumer: &NatsConsumer,
+        provider_id: &str,
+    ) -> Result<Box<ProviderDefinitionT>> {
+        //This will automatically fail if the provider ID doesnt exist, so we dont need additional error handling
+        let provider_def_read_resp = Self::read_provider_definition_internal(consumer, provider_id)
+            .await
+            .map_err(|_| Error::ProviderOfflineOrInvalid(provider_id.to_owned()))?;
+
+        let provider_def = provider_def_read_resp
+            .provider_definition
+            .ok_or_else(|| Error::ProviderOfflineOrInvalid(provider_id.to_owned()))?;
+
+        if provider_def.state != ProviderDefinitionState::OK {
+            return Err(Error::ProviderOfflineOrInvalid(provider_id.to_owned()));
+        }
+
+        Ok(provider_def)
+    }
+
     pub(super) async fn read_provider_definition_internal(
-        nats_client: &async_nats::Client,
+        consumer: &NatsConsumer,
         provider_id: &str,
     ) -> Result<ReadProviderDefinitionQueryResponseT> {
         //Create flatbuffer read request payload
@@ -481,7 +1002,7 @@ impl ConnectedNatsProvider {
         let (all_bytes, data_start_offset) = builder.collapse();
         let request_bytes = Bytes::from(all_bytes).slice(data_start_offset..);
 
-        let reply = nats_client
+        let reply = consumer
             .request(
                 nats_subjects::registry_provider_definition_read_query(provider_id),
                 request_bytes,
@@ -489,7 +1010,12 @@ impl ConnectedNatsProvider {
             .await?;
 
         let payload = flatbuffers::root::<ReadProviderDefinitionQueryResponse>(&reply.payload)?;
-        Ok(payload.unpack())
+        let mut response = payload.unpack();
+        if let Some(provider_def) = &mut response.provider_definition {
+            Self::drop_oversized_keys(provider_def, provider_id);
+        }
+
+        Ok(response)
     }
 
     pub(super) async fn subscribe_provider_definition_internal(
@@ -502,16 +1028,42 @@ impl ConnectedNatsProvider {
             ))
             .await?;
 
+        let provider_id = provider_id.to_owned();
         let result_stream =
-            subscription.map(|message| -> Result<ProviderDefinitionChangedEventT> {
+            subscription.map(move |message| -> Result<ProviderDefinitionChangedEventT> {
                 let payload =
                     flatbuffers::root::<ProviderDefinitionChangedEvent>(&message.payload)?;
-                Ok(payload.unpack())
+                let mut event = payload.unpack();
+                if let Some(provider_def) = &mut event.provider_definition {
+                    Self::drop_oversized_keys(provider_def, &provider_id);
+                }
+
+                Ok(event)
             });
 
         Ok(result_stream)
     }
 
+    /// Removes all variables with keys longer than [`MAX_VARIABLE_KEY_LENGTH`] from a received provider definition.
+    ///
+    /// Valid keys only consist of ASCII characters, so the byte length is checked to avoid iterating huge keys.
+    fn drop_oversized_keys(provider_def: &mut ProviderDefinitionT, provider_id: &str) {
+        let Some(var_defs) = &mut provider_def.variable_definitions else {
+            return;
+        };
+
+        var_defs.retain(|var_def| {
+            let valid = var_def.key.len() <= MAX_VARIABLE_KEY_LENGTH;
+            if !valid {
+                warn!(
+                    "Dropping variable {} of provider {provider_id}, its key exceeds {MAX_VARIABLE_KEY_LENGTH} characters",
+                    var_def.id
+                );
+            }
+            valid
+        });
+    }
+
     fn get_nats_client(&self) -> &async_nats::Client {
         self.consumer.get_nats_con().get_client()
     }
@@ -543,6 +1095,7 @@ impl ConnectedNatsProvider {
     async fn internal_event_loop(
         event_stream: impl Stream<Item = Result<ProviderDefinitionChangedEventT>>,
         state: SharedState,
+        deserialization_errors: Arc<AtomicU64>,
     ) {
         tokio::pin!(event_stream);
 
@@ -586,13 +1139,15 @@ impl ConnectedNatsProvider {
                     }
                 }
                 Err(e) => {
+                    deserialization_errors.fetch_add(1, Ordering::Relaxed);
                     warn!("Error while processing provider definition changed event: {e:?}");
                 }
             }
         }
 
-        //This should never happen?
-        error!("Provider definition events ended");
+        //Only happens if the NATS client was closed, e.g. by draining it.
+        //All other subscriptions of the client end as well, so the high level streams terminate.
+        debug!("Provider definition events ended, the NATS connection was closed");
     }
 
     /// Returns an error if the value type of the variable does not match the variable definition.
@@ -624,26 +1179,42 @@ impl ConnectedNatsProvider {
         let written_vars = &write_command.variables.items;
         if let Some(written_vars) = written_vars {
             for var in written_vars {
-                //lookup variable def by id
-                let var_def = var_defs
-                    .get(&var.id)
-                    .ok_or(Error::InvalidVariableId(var.id))?;
-
-                //Check if var has write permission
-                //Unknown access type enum values will be treated as writable for maximum compatibility
-                //Provider may still reject the write command
-                if var_def.access_type == VariableAccessType::READ_ONLY {
-                    return Err(Error::WritingToReadonly(var_def.key.clone()));
-                }
-
-                //check if value type matches var def
-                Self::check_variable_value_type(&var.value, var_def.data_type)?;
+                Self::check_variable_write_internal(var_defs, var)?;
             }
         }
 
         Ok(())
     }
 
+    /// Same as `check_write_command`, but only checks a single variable.
+    pub(super) fn check_variable_write(&self, var: &VariableT) -> Result<()> {
+        //Safety: Unwrap is ok here as we know that the mutex cant be poisoned during writing
+        #[allow(clippy::unwrap_used)]
+        let var_defs = &self.state.read().unwrap().cur_variable_defs;
+
+        Self::check_variable_write_internal(var_defs, var)
+    }
+
+    fn check_variable_write_internal(
+        var_defs: &FxHashMap<VariableID, VariableDefinitionT>,
+        var: &VariableT,
+    ) -> Result<()> {
+        //lookup variable def by id
+        let var_def = var_defs
+            .get(&var.id)
+            .ok_or(Error::InvalidVariableId(var.id))?;
+
+        //Check if var has write permission
+        //Unknown access type enum values will be treated as writable for maximum compatibility
+        //Provider may still reject the write command
+        if var_def.access_type == VariableAccessType::READ_ONLY {
+            return Err(Error::WritingToReadonly(var_def.key.clone()));
+        }
+
+        //check if value type matches var def
+        Self::check_variable_value_type(&var.value, var_def.data_type)
+    }
+
     pub(super) fn check_online(&self) -> Result<()> {
         if !self.is_online() {
             return Err(Error::ProviderOfflineOrInvalid(self.provider_id.clone()));
@@ -656,9 +1227,97 @@ impl ConnectedNatsProvider {
     }
 }
 
+/// Iterates over the variables of a serialized `ReadVariablesQueryResponse`
+/// without unpacking the whole response into owned types.
+///
+/// The callback receives borrowed flatbuffer views into `response_bytes`.
+/// Fails without calling the callback if the payload is not a valid flatbuffer,
+/// e.g. because the consumer uses a custom [`PayloadCodec`].
+#[cfg_attr(not(feature = "export-low-level-api"), allow(dead_code))]
+pub fn for_each_variable<F>(response_bytes: &[u8], mut f: F) -> Result<()>
+where
+    F: FnMut(Variable<'_>),
+{
+    let response = flatbuffers::root::<ReadVariablesQueryResponse>(response_bytes)?;
+    if let Some(items) = response.variables().items() {
+        for var in items {
+            f(var);
+        }
+    }
+
+    Ok(())
+}
+
 /// Used to stop the internal status update task
 impl Drop for ConnectedNatsProvider {
     fn drop(&mut self) {
         self.event_loop_task.abort();
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn var_def(id: VariableID, key: String) -> VariableDefinitionT {
+        VariableDefinitionT {
+            id,
+            key,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_drop_oversized_keys() {
+        let mut provider_def = ProviderDefinitionT {
+            variable_definitions: Some(vec![
+                var_def(1, "a".repeat(MAX_VARIABLE_KEY_LENGTH)),
+                var_def(2, "a".repeat(MAX_VARIABLE_KEY_LENGTH + 1)),
+                var_def(3, "my_folder.my_var".to_owned()),
+            ]),
+            ..Default::default()
+        };
+
+        ConnectedNatsProvider::drop_oversized_keys(&mut provider_def, "test_provider");
+
+        let ids: Vec<_> = provider_def
+            .variable_definitions
+            .unwrap()
+            .iter()
+            .map(|var_def| var_def.id)
+            .collect();
+        assert_eq!(ids, vec![1, 3]);
+    }
+
+    #[test]
+    fn test_write_ack_headers() {
+        let ack = WriteAck {
+            accepted_ids: vec![0, 7],
+            rejected_ids: vec![],
+        };
+
+        let headers = ack.to_headers();
+
+        assert_eq!(
+            headers.get(WRITE_ACCEPTED_IDS_HEADER).unwrap().as_str(),
+            "0,7"
+        );
+        assert!(headers.get(WRITE_REJECTED_IDS_HEADER).is_none());
+        assert_eq!(WriteAck::from_headers(Some(&headers)), Some(ack));
+    }
+
+    #[test]
+    fn test_write_ack_invalid_headers() {
+        let mut headers = HeaderMap::new();
+        headers.insert(WRITE_REJECTED_IDS_HEADER, "1,abc");
+
+        assert_eq!(WriteAck::from_headers(Some(&headers)), None);
+        assert_eq!(
+            WriteAck::from_headers(None),
+            Some(WriteAck {
+                accepted_ids: vec![],
+                rejected_ids: vec![],
+            })
+        );
+    }
+}
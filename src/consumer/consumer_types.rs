@@ -6,7 +6,10 @@
 //! These types abstract the low level flatbuffer types and provide a more user-friendly interface.
 
 use crate::{
-    dh_types::{self, TimestampValue, VariableQuality, VariableValue},
+    dh_types::{
+        self, DurationValue, HumanDuration, TimestampValue, VariableQuality, VariableValue,
+        SUMMARY_VALUE_WIDTH,
+    },
     generated::weidmueller::ucontrol::hub::VariableT,
 };
 
@@ -14,11 +17,19 @@ use crate::{
 pub type Error = dh_types::Error;
 
 /// The state of a variable
+///
+/// With the `serde` feature, the state is represented as `{"timestamp": <RFC 3339 string>, "value": <value>, "quality": <quality>}`,
+/// see [`VariableValue`] for the representation of the value and the quality.
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct VariableState {
     /// The modification timestamp of the variable.
     ///
     /// Variables can have their own timestamp or inherit the variable list timestamp.
+    #[cfg_attr(
+        feature = "serde",
+        serde(with = "crate::dh_types_serde_extension::rfc3339_timestamp")
+    )]
     pub timestamp: TimestampValue,
     /// Current value of the variable
     pub value: VariableValue,
@@ -49,4 +60,222 @@ impl VariableState {
             quality: ll_var.quality.into(),
         })
     }
+
+    /// Returns a compact, aligned description of the state for table-like output, e.g. in CLI tools.
+    ///
+    /// The line consists of the value, the quality and the age of the timestamp relative to now.
+    /// Values longer than [`SUMMARY_VALUE_WIDTH`] are truncated.
+    /// Append it to [`VariableDefinition::summary_line`](crate::dh_types::VariableDefinition::summary_line)
+    /// to print one row per variable.
+    #[must_use]
+    pub fn summary_line(&self) -> String {
+        self.summary_line_at(TimestampValue::now())
+    }
+
+    /// Same as [`Self::summary_line()`], but calculates the age relative to `now`.
+    ///
+    /// The age is rounded down to whole seconds, or to whole milliseconds if it is less than a second.
+    /// Timestamps in the future result in a negative age.
+    #[must_use]
+    pub fn summary_line_at(&self, now: TimestampValue) -> String {
+        let mut value = self.value.to_string();
+        if value.chars().count() > SUMMARY_VALUE_WIDTH {
+            value = value
+                .chars()
+                .take(SUMMARY_VALUE_WIDTH - 1)
+                .chain(['…'])
+                .collect();
+        }
+
+        let age = now - self.timestamp;
+        let age = if age.whole_seconds() == 0 {
+            DurationValue::milliseconds(age.subsec_milliseconds().into())
+        } else {
+            DurationValue::seconds(age.whole_seconds())
+        };
+
+        format!(
+            "{value:<value_width$} {:<21} {}",
+            self.quality,
+            age.to_human_string(),
+            value_width = SUMMARY_VALUE_WIDTH
+        )
+    }
+
+    /// Returns which fields differ between this state and a `previous` state of the same variable.
+    ///
+    /// This allows to react to specific changes, e.g. only toggle a quality indicator if the value did not change.
+    #[must_use]
+    pub fn changed_fields(&self, previous: &VariableState) -> ChangedFields {
+        ChangedFields {
+            value: self.value != previous.value,
+            quality: self.quality != previous.quality,
+            timestamp: self.timestamp != previous.timestamp,
+        }
+    }
+}
+
+/// Describes which fields of a [`VariableState`] changed.
+///
+/// Returned by [`VariableState::changed_fields`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[allow(clippy::struct_excessive_bools)] // Each flag is independent
+pub struct ChangedFields {
+    /// The value changed
+    pub value: bool,
+    /// The quality changed
+    pub quality: bool,
+    /// The timestamp changed
+    pub timestamp: bool,
+}
+
+impl ChangedFields {
+    /// Returns true if any field changed.
+    #[must_use]
+    pub fn any(&self) -> bool {
+        self.value || self.quality || self.timestamp
+    }
+
+    /// Returns true if only the timestamp changed.
+    ///
+    /// This indicates a liveness refresh, i.e. the provider confirmed that the value is still current,
+    /// in contrast to a genuinely new value. See
+    /// [`UnchangedUpdates::PublishRefreshes`](crate::provider::provider_builder::UnchangedUpdates::PublishRefreshes).
+    #[must_use]
+    pub fn is_refresh(&self) -> bool {
+        self.timestamp && !self.value && !self.quality
+    }
+}
+
+/// Defines how the key list of [`DataHubProviderConnection::subscribe_variables_with_filter_mode`] is applied.
+///
+/// [`DataHubProviderConnection::subscribe_variables_with_filter_mode`]: super::connected_dh_provider::DataHubProviderConnection::subscribe_variables_with_filter_mode
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FilterMode {
+    /// Only changes of the listed variables are returned.
+    #[default]
+    Include,
+    /// Changes of all variables except the listed ones are returned.
+    Exclude,
+}
+
+impl FilterMode {
+    /// Returns true if a variable should be kept, depending on whether it is part of the filter list.
+    pub(crate) fn keeps(self, is_listed: bool) -> bool {
+        match self {
+            FilterMode::Include => is_listed,
+            FilterMode::Exclude => !is_listed,
+        }
+    }
+}
+
+/// Defines what happens if the receiver of a [`SubscriptionBuffer`] can not keep up with the received changes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OverflowStrategy {
+    /// The oldest buffered changes are dropped to make room for new ones.
+    ///
+    /// The subscription never stalls, but changes may get lost. Dropped changes are counted.
+    #[default]
+    DropOldest,
+    /// Receiving further changes waits until there is room in the buffer.
+    ///
+    /// No changes are dropped by the buffer itself, but the NATS client buffers changes in the meantime
+    /// and drops them if its own buffer overflows. In this case, the NATS connection reports a slow consumer event.
+    Wait,
+}
+
+/// Configures the buffer of a subscription, see
+/// [`DataHubProviderConnection::subscribe_variables_buffered`](super::connected_dh_provider::DataHubProviderConnection::subscribe_variables_buffered).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SubscriptionBuffer {
+    /// Maximum number of change events that are buffered. A capacity of 0 is treated as 1.
+    pub capacity: usize,
+    /// What happens if the buffer is full.
+    pub overflow_strategy: OverflowStrategy,
+}
+
+impl Default for SubscriptionBuffer {
+    /// Buffers up to 1024 change events and drops the oldest ones on overflow.
+    fn default() -> Self {
+        Self {
+            capacity: 1024,
+            overflow_strategy: OverflowStrategy::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case::int(VariableValue::Int(-5), VariableQuality::Good, 1500, "-5                       good                  1s")]
+    #[case::sub_second(
+        VariableValue::Boolean(true),
+        VariableQuality::Unknown(7),
+        250,
+        "true                     unknown(7)            250ms"
+    )]
+    #[case::string(VariableValue::String("a\tb".to_string()), VariableQuality::UncertainLastUsableValue, 3_725_000, "\"a\\tb\"                   uncertain_last_usable 1h 2m 5s")]
+    #[case::truncated(VariableValue::String("x".repeat(30)), VariableQuality::BadOrUndefined, 0, "\"xxxxxxxxxxxxxxxxxxxxxx… bad                   0s")]
+    #[case::future(VariableValue::Duration(DurationValue::milliseconds(1500)), VariableQuality::UncertainInitialValue, -2000, "1s 500ms                 uncertain_initial     -2s")]
+    fn test_summary_line(
+        #[case] value: VariableValue,
+        #[case] quality: VariableQuality,
+        #[case] age_millis: i64,
+        #[case] expected: &str,
+    ) {
+        let state = VariableState {
+            timestamp: TimestampValue::UNIX_EPOCH,
+            value,
+            quality,
+        };
+        let now = TimestampValue::UNIX_EPOCH + DurationValue::milliseconds(age_millis);
+
+        assert_eq!(state.summary_line_at(now), expected);
+    }
+
+    #[rstest]
+    #[case::nothing(
+        VariableValue::Int(1),
+        VariableQuality::Good,
+        0,
+        ChangedFields::default()
+    )]
+    #[case::value(VariableValue::Int(2), VariableQuality::Good, 0, ChangedFields { value: true, ..Default::default() })]
+    #[case::value_type(VariableValue::Float64(1.0), VariableQuality::Good, 0, ChangedFields { value: true, ..Default::default() })]
+    #[case::quality(VariableValue::Int(1), VariableQuality::BadOrUndefined, 0, ChangedFields { quality: true, ..Default::default() })]
+    #[case::timestamp(VariableValue::Int(1), VariableQuality::Good, 1, ChangedFields { timestamp: true, ..Default::default() })]
+    #[case::all(VariableValue::Int(2), VariableQuality::BadOrUndefined, 1, ChangedFields { value: true, quality: true, timestamp: true })]
+    fn test_changed_fields(
+        #[case] value: VariableValue,
+        #[case] quality: VariableQuality,
+        #[case] timestamp_offset_secs: i64,
+        #[case] expected: ChangedFields,
+    ) {
+        let previous = VariableState {
+            timestamp: TimestampValue::UNIX_EPOCH,
+            value: VariableValue::Int(1),
+            quality: VariableQuality::Good,
+        };
+        let current = VariableState {
+            timestamp: TimestampValue::UNIX_EPOCH
+                + dh_types::DurationValue::seconds(timestamp_offset_secs),
+            value,
+            quality,
+        };
+
+        let changed = current.changed_fields(&previous);
+        assert_eq!(changed, expected);
+        assert_eq!(changed.any(), expected != ChangedFields::default());
+        assert_eq!(
+            changed.is_refresh(),
+            expected
+                == ChangedFields {
+                    timestamp: true,
+                    ..Default::default()
+                }
+        );
+    }
 }
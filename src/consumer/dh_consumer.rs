@@ -5,14 +5,30 @@
 //! This module provides a high-level API for interacting with the variable hub registry
 //! by abstacting the low-level API details via easy to use rust types.
 
-use std::sync::Arc;
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+    time::Duration,
+};
 
-use futures::{Stream, StreamExt};
+use futures::{stream::FusedStream, StreamExt};
 use thiserror::Error;
+use tracing::warn;
 
-use crate::authenticated_nats_con::{AuthenticatedNatsConnection, AuthenticationSettings};
+use crate::{
+    authenticated_nats_con::{AuthenticatedNatsConnection, AuthenticationSettings},
+    dh_types::{VariableDefinition, VariableID},
+    generated::weidmueller::ucontrol::hub::ReadVariablesQueryRequestT,
+    payload_codec::{FlatbufferCodec, PayloadCodec},
+};
 
-use super::nats_consumer::{self, NatsConsumer};
+use super::{
+    connected_dh_provider::{self, DataHubProviderConnection},
+    connected_nats_provider::{self, ConnectedNatsProvider},
+    consumer_types::{self, VariableState},
+    nats_consumer::{self, NatsConsumer, DEFAULT_REQUEST_TIMEOUT},
+    variable_key::VariableKey,
+};
 
 /// Error type for the data hub consumer
 #[derive(Error, Debug)]
@@ -22,39 +38,307 @@ pub enum Error {
     NatsConnection(#[from] async_nats::Error),
     #[error("{0}")]
     LowLevelApi(#[from] nats_consumer::Error),
+    #[error("{0}")]
+    LowLevelProviderApi(#[from] connected_nats_provider::Error),
+    #[error("{0}")]
+    TypeConversion(#[from] consumer_types::Error),
 }
 
 /// Result type for the data hub consumer
 pub type Result<T> = std::result::Result<T, Error>;
 
+/// Result of [`DataHubConsumer::compare_providers`].
+///
+/// Variables are matched by key, variable IDs are ignored.
+/// All lists are sorted by variable key.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProviderComparison {
+    /// Variables that only exist on the first provider.
+    pub only_in_a: Vec<VariableDefinition>,
+    /// Variables that only exist on the second provider.
+    pub only_in_b: Vec<VariableDefinition>,
+    /// Variables that exist on both providers, but with a different data type or access type.
+    pub mismatches: Vec<VariableMismatch>,
+}
+
+/// A variable that exists on both compared providers, but with a different definition.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VariableMismatch {
+    /// The variable key
+    pub key: String,
+    /// The definition on the first provider
+    pub a: VariableDefinition,
+    /// The definition on the second provider
+    pub b: VariableDefinition,
+}
+
+impl VariableMismatch {
+    /// Returns true if the data types of both definitions differ.
+    #[must_use]
+    pub fn type_differs(&self) -> bool {
+        self.a.data_type != self.b.data_type
+    }
+
+    /// Returns true if the access types of both definitions differ.
+    #[must_use]
+    pub fn access_differs(&self) -> bool {
+        self.a.access_type != self.b.access_type
+    }
+}
+
+impl ProviderComparison {
+    fn new(a: Vec<VariableDefinition>, b: Vec<VariableDefinition>) -> Self {
+        let mut b_by_key: HashMap<String, VariableDefinition> =
+            b.into_iter().map(|def| (def.key.clone(), def)).collect();
+
+        let mut comparison = Self::default();
+
+        for a_def in a {
+            match b_by_key.remove(&a_def.key) {
+                None => comparison.only_in_a.push(a_def),
+                Some(b_def)
+                    if a_def.data_type != b_def.data_type
+                        || a_def.access_type != b_def.access_type =>
+                {
+                    comparison.mismatches.push(VariableMismatch {
+                        key: a_def.key.clone(),
+                        a: a_def,
+                        b: b_def,
+                    });
+                }
+                Some(_) => {}
+            }
+        }
+
+        comparison.only_in_b = b_by_key.into_values().collect();
+
+        comparison.only_in_a.sort_by(|a, b| a.key.cmp(&b.key));
+        comparison.only_in_b.sort_by(|a, b| a.key.cmp(&b.key));
+        comparison.mismatches.sort_by(|a, b| a.key.cmp(&b.key));
+
+        comparison
+    }
+
+    /// Returns true if both providers expose the same keys with the same data and access types.
+    #[must_use]
+    pub fn is_equivalent(&self) -> bool {
+        self.only_in_a.is_empty() && self.only_in_b.is_empty() && self.mismatches.is_empty()
+    }
+}
+
+/// Event of [`DataHubConsumer::watch_providers`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProviderWatchEvent {
+    /// A provider with a matching ID was added to the registry.
+    Appeared(String),
+    /// A provider with a matching ID was removed from the registry.
+    Gone(String),
+}
+
+impl ProviderWatchEvent {
+    /// Returns the ID of the provider this event refers to.
+    #[must_use]
+    pub fn provider_id(&self) -> &str {
+        match self {
+            Self::Appeared(provider_id) | Self::Gone(provider_id) => provider_id,
+        }
+    }
+}
+
+/// Event of [`DataHubConsumer::watch_and_connect_providers`].
+pub enum ProviderConnectionEvent {
+    /// A provider with a matching ID was added to the registry and a connection to it was established.
+    Connected(Box<DataHubProviderConnection>),
+    /// A provider with a matching ID was added to the registry, but connecting to it failed,
+    /// e.g. because it does not have a valid definition yet or was removed again in the meantime.
+    ConnectFailed {
+        /// The ID of the provider
+        provider_id: String,
+        /// The reason why the connection failed
+        error: connected_dh_provider::Error,
+    },
+    /// A provider with a matching ID was removed from the registry.
+    ///
+    /// A connection that was handed out via [`Self::Connected`] is not closed automatically,
+    /// so dropping it is up to the caller.
+    Gone(String),
+}
+
+/// Compares the provider IDs of a registry update with the currently `known` IDs that start with `prefix`.
+///
+/// Updates `known` and returns the events that lead to the new state.
+/// Removed providers are reported before added ones.
+fn diff_provider_ids(
+    known: &mut HashSet<String>,
+    provider_ids: Vec<String>,
+    prefix: &str,
+) -> Vec<ProviderWatchEvent> {
+    let current: Vec<String> = provider_ids
+        .into_iter()
+        .filter(|id| id.starts_with(prefix))
+        .collect();
+    let current_set: HashSet<&String> = current.iter().collect();
+
+    let mut gone: Vec<String> = known
+        .iter()
+        .filter(|id| !current_set.contains(id))
+        .cloned()
+        .collect();
+    gone.sort();
+
+    let mut events = Vec::new();
+    for provider_id in gone {
+        known.remove(&provider_id);
+        events.push(ProviderWatchEvent::Gone(provider_id));
+    }
+
+    for provider_id in current {
+        if known.insert(provider_id.clone()) {
+            events.push(ProviderWatchEvent::Appeared(provider_id));
+        }
+    }
+
+    events
+}
+
 /// A high-level API for interacting with the variable hub registry
 /// by abstacting the low-level API details via easy to use rust types.
 pub struct DataHubConsumer {
     nats_consumer: Arc<NatsConsumer>,
 }
 
-impl DataHubConsumer {
+/// Used to create a [`DataHubConsumer`] with advanced options.
+///
+/// Use [`DataHubConsumer::connect`] instead if the default options are sufficient.
+#[derive(Debug, Clone)]
+pub struct DataHubConsumerBuilder {
+    payload_codec: Arc<dyn PayloadCodec>,
+    request_timeout: Duration,
+    max_decompressed_size: Option<usize>,
+}
+
+impl Default for DataHubConsumerBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DataHubConsumerBuilder {
+    /// Creates a new instance with the default options.
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            payload_codec: Arc::new(FlatbufferCodec),
+            request_timeout: DEFAULT_REQUEST_TIMEOUT,
+            max_decompressed_size: None,
+        }
+    }
+
+    /// Replaces the codec that is used for the payloads of variable reads, writes and change events.
+    ///
+    /// By default, the [`FlatbufferCodec`] is used. All providers connected via this consumer must use the same codec,
+    /// see [`crate::payload_codec`] for details.
+    #[must_use]
+    pub fn with_payload_codec(mut self, payload_codec: Arc<dyn PayloadCodec>) -> Self {
+        self.payload_codec = payload_codec;
+        self
+    }
+
+    /// Sets the timeout of NATS requests, e.g. for reading provider IDs, provider definitions and variable states.
+    ///
+    /// Defaults to 10 seconds, which is the default of the async-nats client.
+    /// Subscriptions and writes are not affected, as they do not wait for a reply.
+    /// Methods that wait for an event, e.g. [`DataHubConsumer::wait_for_provider`], have no timeout.
+    /// If you wrap a call in a `tokio::time::timeout`, the shorter timeout applies.
+    #[must_use]
+    pub fn with_request_timeout(mut self, request_timeout: Duration) -> Self {
+        self.request_timeout = request_timeout;
+        self
+    }
+
+    /// Sets the maximum size in bytes of decompressed variable change events.
+    ///
+    /// Defaults to the maximum payload size of the NATS server. Compressed events that would exceed the limit
+    /// are rejected with [`PayloadDecompressError::TooLarge`](crate::payload_compression::PayloadDecompressError::TooLarge)
+    /// instead of being decompressed, see [`crate::payload_compression`].
+    #[must_use]
+    pub fn with_max_decompressed_size(mut self, max_decompressed_size: usize) -> Self {
+        self.max_decompressed_size = Some(max_decompressed_size);
+        self
+    }
+
     /// Tries to connect and authenticate to the provided NATS address and initializes the consumer.
     ///
     /// See documentation of [`AuthenticatedNatsConnection`] for more details on the connection process.
     pub async fn connect(
+        self,
         nats_server_addr: impl Into<String>,
         auth_settings: &AuthenticationSettings,
-    ) -> Result<Self> {
+    ) -> Result<DataHubConsumer> {
         let auth_nats_con =
             Arc::new(AuthenticatedNatsConnection::new(nats_server_addr, auth_settings).await?);
 
-        Self::from_existing_connection(auth_nats_con).await
+        self.connect_with_existing_connection(auth_nats_con).await
+    }
+
+    /// Same as [`Self::connect()`], but uses an existing connection.
+    ///
+    /// This is useful if you want to use the same connection for multiple clients.
+    pub async fn connect_with_existing_connection(
+        self,
+        nats_con: Arc<AuthenticatedNatsConnection>,
+    ) -> Result<DataHubConsumer> {
+        let mut nats_consumer = NatsConsumer::new(nats_con)
+            .await?
+            .with_payload_codec(self.payload_codec)
+            .with_request_timeout(self.request_timeout);
+        if let Some(max_decompressed_size) = self.max_decompressed_size {
+            nats_consumer = nats_consumer.with_max_decompressed_size(max_decompressed_size);
+        }
+
+        Ok(DataHubConsumer::from_nats_consumer(Arc::new(nats_consumer)))
+    }
+}
+
+impl DataHubConsumer {
+    /// Creates a [`DataHubConsumerBuilder`] to configure advanced options before connecting.
+    #[must_use]
+    pub fn builder() -> DataHubConsumerBuilder {
+        DataHubConsumerBuilder::new()
+    }
+
+    /// Tries to connect and authenticate to the provided NATS address and initializes the consumer.
+    ///
+    /// Same as [`DataHubConsumerBuilder::connect`] with the default options.
+    /// See documentation of [`AuthenticatedNatsConnection`] for more details on the connection process.
+    pub async fn connect(
+        nats_server_addr: impl Into<String>,
+        auth_settings: &AuthenticationSettings,
+    ) -> Result<Self> {
+        Self::builder()
+            .connect(nats_server_addr, auth_settings)
+            .await
     }
 
     /// Creates a new data hub consumer from an existing nats connection.
     ///
     /// This is useful if you want to use the same connection for multiple clients.
+    /// Same as [`DataHubConsumerBuilder::connect_with_existing_connection`] with the default options.
     pub async fn from_existing_connection(
         nats_con: Arc<AuthenticatedNatsConnection>,
     ) -> Result<Self> {
-        let nats_consumer = Arc::new(NatsConsumer::new(nats_con).await?);
-        Ok(Self { nats_consumer })
+        Self::builder()
+            .connect_with_existing_connection(nats_con)
+            .await
+    }
+
+    /// Creates a new data hub consumer from an existing low level consumer.
+    ///
+    /// This is useful if the low level consumer needs a special configuration that is not offered
+    /// by the [`DataHubConsumerBuilder`].
+    #[must_use]
+    pub fn from_nats_consumer(nats_consumer: Arc<NatsConsumer>) -> Self {
+        Self { nats_consumer }
     }
 
     /// Allows access to low level api
@@ -90,7 +374,9 @@ impl DataHubConsumer {
     /// Internally uses the low level api to receive the values.
     /// Each received value from the low level api will be converted to an easy to use rust type.
     /// If the low level api stream returned an error value, this value will be silenly ignored, but the subscription will not be cancelled.
-    pub async fn subscribe_provider_ids(&self) -> Result<impl Stream<Item = Vec<String>>> {
+    ///
+    /// The stream only ends once the NATS connection is closed. It is fused, so it keeps returning `None` afterwards.
+    pub async fn subscribe_provider_ids(&self) -> Result<impl FusedStream<Item = Vec<String>>> {
         let low_level_data = self.nats_consumer.subscribe_provider_ids().await?;
 
         let mapped_result = low_level_data.filter_map(move |prov_changed_evt| async move {
@@ -105,7 +391,79 @@ impl DataHubConsumer {
             })
         });
 
-        Ok(Box::pin(mapped_result))
+        Ok(Box::pin(mapped_result.fuse()))
+    }
+
+    /// Watches the registry for providers whose ID starts with `prefix`, e.g. `"plc-"`.
+    ///
+    /// The stream first yields [`ProviderWatchEvent::Appeared`] for all matching providers that are already registered,
+    /// afterwards it yields an event each time a matching provider is added to or removed from the registry.
+    /// Use an empty prefix to watch all providers.
+    ///
+    /// This method will succeed even if the hub registry is currently offline, but may return an error if there is an issue
+    /// with the NATS connection. If the registry is offline, the already registered providers are reported
+    /// with the first provider list change instead.
+    ///
+    /// The stream only ends once the NATS connection is closed. It is fused, so it keeps returning `None` afterwards.
+    pub async fn watch_providers(
+        &self,
+        prefix: impl Into<String>,
+    ) -> Result<impl FusedStream<Item = ProviderWatchEvent>> {
+        let prefix = prefix.into();
+
+        //Must sub before reading, otherwise we could miss a registration
+        let provider_id_changes = self.subscribe_provider_ids().await?;
+        let initial_ids = self.read_provider_ids().await.unwrap_or_default();
+
+        let mut known = HashSet::new();
+        let events = futures::stream::once(async move { initial_ids })
+            .chain(provider_id_changes)
+            .flat_map(move |provider_ids| {
+                futures::stream::iter(diff_provider_ids(&mut known, provider_ids, &prefix))
+            });
+
+        Ok(Box::pin(events.fuse()))
+    }
+
+    /// Same as [`Self::watch_providers()`], but automatically connects to each matching provider that appears.
+    ///
+    /// The connections are established one after another with the default options,
+    /// see [`DataHubProviderConnection::new()`], without waiting for the provider to become valid.
+    /// A provider that can not be connected is reported via [`ProviderConnectionEvent::ConnectFailed`]
+    /// and is not retried until it is removed from and added to the registry again.
+    ///
+    /// The stream only ends once the NATS connection is closed. It is fused, so it keeps returning `None` afterwards.
+    pub async fn watch_and_connect_providers(
+        self: &Arc<Self>,
+        prefix: impl Into<String>,
+    ) -> Result<impl FusedStream<Item = ProviderConnectionEvent>> {
+        let consumer = self.clone();
+
+        let events = self.watch_providers(prefix).await?.then(move |event| {
+            let consumer = consumer.clone();
+            async move {
+                match event {
+                    ProviderWatchEvent::Appeared(provider_id) => {
+                        match DataHubProviderConnection::new(consumer, provider_id.clone(), false)
+                            .await
+                        {
+                            Ok(connection) => {
+                                ProviderConnectionEvent::Connected(Box::new(connection))
+                            }
+                            Err(error) => {
+                                warn!("Failed to connect to provider {provider_id}: {error}");
+                                ProviderConnectionEvent::ConnectFailed { provider_id, error }
+                            }
+                        }
+                    }
+                    ProviderWatchEvent::Gone(provider_id) => {
+                        ProviderConnectionEvent::Gone(provider_id)
+                    }
+                }
+            }
+        });
+
+        Ok(Box::pin(events.fuse()))
     }
 
     /// Waits until the specified provider ID is available on the registry and contains a valid definition.
@@ -120,4 +478,354 @@ impl DataHubConsumer {
             .wait_for_provider(provider_id)
             .await?)
     }
+
+    /// Reads the variable definitions of a provider once from the registry.
+    ///
+    /// In contrast to a [`DataHubProviderConnection`],
+    /// no background task is started and the definitions are not kept up to date.
+    /// This is useful for tools that only need a single snapshot of a provider.
+    ///
+    /// This method will fail if the provider is offline or has an invalid definition,
+    /// there is an issue with the nats connection or something goes wrong while deserializing flatbuffer payloads.
+    pub async fn read_provider_definition_once(
+        &self,
+        provider_id: &str,
+    ) -> Result<Vec<VariableDefinition>> {
+        let provider_def =
+            ConnectedNatsProvider::read_valid_provider_definition(&self.nats_consumer, provider_id)
+                .await?;
+
+        let result = provider_def
+            .variable_definitions
+            .unwrap_or_default()
+            .into_iter()
+            .map(Into::into)
+            .collect();
+
+        Ok(result)
+    }
+
+    /// Compares the variable sets of two providers, e.g. to verify that a migrated provider is equivalent to the old one.
+    ///
+    /// Both definitions are read once from the registry, see [`Self::read_provider_definition_once()`].
+    /// Variables are matched by key, so differing variable IDs are not reported.
+    /// Besides keys that only exist on one side, keys with a different data type or access type are reported.
+    ///
+    /// This method will fail if one of the providers is offline or has an invalid definition,
+    /// there is an issue with the nats connection or something goes wrong while deserializing flatbuffer payloads.
+    pub async fn compare_providers(
+        &self,
+        provider_a: &str,
+        provider_b: &str,
+    ) -> Result<ProviderComparison> {
+        let (a, b) = futures::future::try_join(
+            self.read_provider_definition_once(provider_a),
+            self.read_provider_definition_once(provider_b),
+        )
+        .await?;
+
+        Ok(ProviderComparison::new(a, b))
+    }
+
+    /// Reads the current state of variables that are spread across multiple providers.
+    ///
+    /// Each entry of `qualified_keys` consists of a provider ID and a variable key of that provider.
+    /// The keys are grouped by provider and one read request per provider is issued concurrently.
+    /// The returned states are in the same order as the requested keys.
+    ///
+    /// Internally, a temporary connection to each provider is created, so if you read the same provider
+    /// frequently, a [`DataHubProviderConnection`] is more efficient.
+    ///
+    /// This method will fail if any of the providers is offline, any key is unknown,
+    /// there is an issue with the nats connection or something goes wrong while deserializing flatbuffer payloads.
+    pub async fn read_qualified(
+        &self,
+        qualified_keys: &[(&str, &str)],
+    ) -> Result<Vec<VariableState>> {
+        //group keys by provider, keeping the order of first occurrence
+        let mut keys_by_provider: Vec<(&str, Vec<&str>)> = Vec::new();
+        for (provider_id, key) in qualified_keys {
+            match keys_by_provider
+                .iter_mut()
+                .find(|(id, _)| id == provider_id)
+            {
+                Some((_, keys)) => keys.push(key),
+                None => keys_by_provider.push((provider_id, vec![key])),
+            }
+        }
+
+        let provider_reads = keys_by_provider
+            .into_iter()
+            .map(|(provider_id, keys)| async move {
+                let variable_keys: Vec<VariableKey> =
+                    keys.iter().map(|key| VariableKey::from(*key)).collect();
+                let states = self.read_provider_keys(provider_id, &variable_keys).await?;
+                Ok::<_, Error>(
+                    keys.into_iter()
+                        .zip(states)
+                        .map(move |(key, (_, state))| ((provider_id, key), state)),
+                )
+            });
+
+        let states_by_key: HashMap<(&str, &str), VariableState> =
+            futures::future::try_join_all(provider_reads)
+                .await?
+                .into_iter()
+                .flatten()
+                .collect();
+
+        //merge results in request order
+        let mut result = Vec::with_capacity(qualified_keys.len());
+        for qualified_key in qualified_keys {
+            let state = states_by_key.get(qualified_key).cloned().ok_or_else(|| {
+                connected_nats_provider::Error::InvalidVariableKey(qualified_key.1.to_string())
+            })?;
+            result.push(state);
+        }
+
+        Ok(result)
+    }
+
+    /// Reads the current state of variables of multiple providers concurrently.
+    ///
+    /// Each request consists of a provider ID and the keys to read from that provider.
+    /// One read request per provider is issued, all of them concurrently.
+    /// The result contains an entry per requested provider with the variable IDs and states in the same order as the keys.
+    ///
+    /// In contrast to [`Self::read_qualified()`], an error only affects the entry of the provider it occurred on,
+    /// e.g. if the provider is offline or a key is unknown, so a dashboard can still show the values of all other providers.
+    /// If the same provider is requested multiple times, its keys are merged into a single read
+    /// and its entry contains the states of all of these keys in the order of the requests.
+    ///
+    /// Internally, a temporary connection to each provider is created, so if you read the same provider
+    /// frequently, a [`DataHubProviderConnection`] is more efficient.
+    pub async fn read_variables_multi(
+        &self,
+        requests: &[(&str, Vec<VariableKey<'_>>)],
+    ) -> HashMap<String, Result<Vec<(VariableID, VariableState)>>> {
+        let mut keys_by_provider: Vec<(&str, Vec<VariableKey<'_>>)> = Vec::new();
+        for (provider_id, keys) in requests {
+            match keys_by_provider
+                .iter_mut()
+                .find(|(id, _)| id == provider_id)
+            {
+                Some((_, merged_keys)) => merged_keys.extend_from_slice(keys),
+                None => keys_by_provider.push((provider_id, keys.clone())),
+            }
+        }
+
+        let provider_reads = keys_by_provider
+            .into_iter()
+            .map(|(provider_id, keys)| async move {
+                (
+                    provider_id.to_string(),
+                    self.read_provider_keys(provider_id, &keys).await,
+                )
+            });
+
+        futures::future::join_all(provider_reads)
+            .await
+            .into_iter()
+            .collect()
+    }
+
+    /// Reads the IDs and states of the specified keys from a single provider in the order of the keys.
+    async fn read_provider_keys(
+        &self,
+        provider_id: &str,
+        keys: &[VariableKey<'_>],
+    ) -> Result<Vec<(VariableID, VariableState)>> {
+        let provider = ConnectedNatsProvider::new(self.nats_consumer.clone(), provider_id).await?;
+
+        let ids = keys
+            .iter()
+            .map(|key| provider.variable_id_from_key(*key))
+            .collect::<connected_nats_provider::Result<Vec<_>>>()?;
+
+        //Safety: We can use the unchecked method here because we already know that all variable IDs are valid
+        let response = provider
+            .read_variables_unchecked(&ReadVariablesQueryRequestT {
+                ids: Some(ids.clone()),
+            })
+            .await?;
+
+        let base_timestamp = response.variables.base_timestamp.into();
+
+        let mut states_by_id = HashMap::new();
+        for ll_var in response.variables.items.unwrap_or_default() {
+            states_by_id.insert(ll_var.id, VariableState::new(ll_var, base_timestamp)?);
+        }
+
+        ids.into_iter()
+            .map(|id| {
+                states_by_id
+                    .get(&id)
+                    .cloned()
+                    .map(|state| (id, state))
+                    .ok_or_else(|| connected_nats_provider::Error::InvalidVariableId(id).into())
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::dh_types::{VariableAccessType, VariableType};
+
+    fn def(
+        id: u32,
+        key: &str,
+        data_type: VariableType,
+        access_type: VariableAccessType,
+    ) -> VariableDefinition {
+        VariableDefinition {
+            id,
+            key: key.to_string(),
+            data_type,
+            access_type,
+            experimental: false,
+        }
+    }
+
+    #[test]
+    fn test_diff_provider_ids() {
+        let ids = |ids: &[&str]| ids.iter().map(ToString::to_string).collect::<Vec<_>>();
+        let mut known = HashSet::new();
+
+        //initial state, non-matching ids are ignored
+        let events = diff_provider_ids(&mut known, ids(&["plc-1", "hmi", "plc-2"]), "plc-");
+        assert_eq!(
+            events,
+            vec![
+                ProviderWatchEvent::Appeared("plc-1".to_string()),
+                ProviderWatchEvent::Appeared("plc-2".to_string()),
+            ]
+        );
+
+        //unchanged list
+        let events = diff_provider_ids(&mut known, ids(&["plc-2", "plc-1"]), "plc-");
+        assert!(events.is_empty());
+
+        //one removed, one added
+        let events = diff_provider_ids(&mut known, ids(&["plc-2", "plc-3", "hmi-2"]), "plc-");
+        assert_eq!(
+            events,
+            vec![
+                ProviderWatchEvent::Gone("plc-1".to_string()),
+                ProviderWatchEvent::Appeared("plc-3".to_string()),
+            ]
+        );
+
+        //all removed
+        let events = diff_provider_ids(&mut known, Vec::new(), "plc-");
+        assert_eq!(
+            events,
+            vec![
+                ProviderWatchEvent::Gone("plc-2".to_string()),
+                ProviderWatchEvent::Gone("plc-3".to_string()),
+            ]
+        );
+        assert!(known.is_empty());
+    }
+
+    #[test]
+    fn test_compare_equivalent_ignores_ids() {
+        let a = vec![
+            def(1, "x", VariableType::Int64, VariableAccessType::ReadOnly),
+            def(2, "y", VariableType::String, VariableAccessType::ReadWrite),
+        ];
+        let b = vec![
+            def(20, "y", VariableType::String, VariableAccessType::ReadWrite),
+            def(10, "x", VariableType::Int64, VariableAccessType::ReadOnly),
+        ];
+
+        let comparison = ProviderComparison::new(a, b);
+        assert!(comparison.is_equivalent());
+    }
+
+    #[test]
+    fn test_compare_differences() {
+        let a = vec![
+            def(
+                1,
+                "only_a",
+                VariableType::Int64,
+                VariableAccessType::ReadOnly,
+            ),
+            def(2, "type", VariableType::Int64, VariableAccessType::ReadOnly),
+            def(
+                3,
+                "access",
+                VariableType::Int64,
+                VariableAccessType::ReadOnly,
+            ),
+            def(
+                4,
+                "same",
+                VariableType::Float64,
+                VariableAccessType::ReadWrite,
+            ),
+        ];
+        let b = vec![
+            def(
+                1,
+                "same",
+                VariableType::Float64,
+                VariableAccessType::ReadWrite,
+            ),
+            def(
+                2,
+                "access",
+                VariableType::Int64,
+                VariableAccessType::ReadWrite,
+            ),
+            def(
+                3,
+                "type",
+                VariableType::String,
+                VariableAccessType::ReadOnly,
+            ),
+            def(
+                4,
+                "only_b2",
+                VariableType::Boolean,
+                VariableAccessType::ReadOnly,
+            ),
+            def(
+                5,
+                "only_b1",
+                VariableType::Boolean,
+                VariableAccessType::ReadOnly,
+            ),
+        ];
+
+        let comparison = ProviderComparison::new(a, b);
+        assert!(!comparison.is_equivalent());
+
+        let only_in_a: Vec<&str> = comparison
+            .only_in_a
+            .iter()
+            .map(|d| d.key.as_str())
+            .collect();
+        assert_eq!(only_in_a, vec!["only_a"]);
+
+        let only_in_b: Vec<&str> = comparison
+            .only_in_b
+            .iter()
+            .map(|d| d.key.as_str())
+            .collect();
+        assert_eq!(only_in_b, vec!["only_b1", "only_b2"]);
+
+        assert_eq!(comparison.mismatches.len(), 2);
+        let access = comparison.mismatches.first().expect("access mismatch");
+        assert_eq!(access.key, "access");
+        assert!(access.access_differs());
+        assert!(!access.type_differs());
+
+        let type_mismatch = comparison.mismatches.get(1).expect("type mismatch");
+        assert_eq!(type_mismatch.key, "type");
+        assert!(type_mismatch.type_differs());
+        assert!(!type_mismatch.access_differs());
+    }
 }
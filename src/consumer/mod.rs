@@ -83,12 +83,13 @@
 pub mod connected_dh_provider;
 pub mod consumer_types;
 pub mod dh_consumer;
+pub mod stats;
 pub mod variable_key;
 
 #[cfg(feature = "export-low-level-api")]
 pub mod connected_nats_provider;
 #[cfg(not(feature = "export-low-level-api"))]
-mod connected_nats_provider;
+pub(crate) mod connected_nats_provider;
 #[cfg(feature = "export-low-level-api")]
 pub mod nats_consumer;
 #[cfg(not(feature = "export-low-level-api"))]
@@ -4,8 +4,9 @@
 
 //! This module provides a low-level API for interacting with the variable hub registry via NATS.
 
-use std::sync::Arc;
+use std::{sync::Arc, time::Duration};
 
+use async_nats::HeaderMap;
 use bytes::Bytes;
 use flatbuffers::FlatBufferBuilder;
 use futures::{Stream, StreamExt};
@@ -19,6 +20,7 @@ use crate::{
         ReadProvidersQueryResponseT, StateChangedEvent, StateChangedEventT,
     },
     nats_subjects,
+    payload_codec::{FlatbufferCodec, PayloadCodec},
 };
 
 use super::connected_nats_provider::{self, ConnectedNatsProvider};
@@ -42,10 +44,18 @@ pub enum Error {
 /// Result type for the NATS consumer
 pub type Result<T> = std::result::Result<T, Error>;
 
+/// Default timeout of NATS requests, see [`NatsConsumer::with_request_timeout`].
+///
+/// This is the same as the default request timeout of the async-nats client.
+pub const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
 /// Low Level API for nats and data hub registry abstraction.
 /// Primarily handles flatbuffer serialization and abstracts nats paths.
 pub struct NatsConsumer {
     nats_con: Arc<AuthenticatedNatsConnection>,
+    payload_codec: Arc<dyn PayloadCodec>,
+    request_timeout: Duration,
+    max_decompressed_size: Option<usize>,
 }
 
 impl NatsConsumer {
@@ -69,7 +79,103 @@ impl NatsConsumer {
             }
         }
 
-        Ok(Self { nats_con })
+        Ok(Self {
+            nats_con,
+            payload_codec: Arc::new(FlatbufferCodec),
+            request_timeout: DEFAULT_REQUEST_TIMEOUT,
+            max_decompressed_size: None,
+        })
+    }
+
+    /// Replaces the codec that is used for the payloads of variable reads, writes and change events.
+    ///
+    /// By default, the [`FlatbufferCodec`] is used. All providers connected via this consumer must use the same codec,
+    /// see [`crate::payload_codec`] for details.
+    #[must_use]
+    pub fn with_payload_codec(mut self, payload_codec: Arc<dyn PayloadCodec>) -> Self {
+        self.payload_codec = payload_codec;
+        self
+    }
+
+    /// Returns the codec that is used for variable payloads.
+    #[must_use]
+    pub fn get_payload_codec(&self) -> &Arc<dyn PayloadCodec> {
+        &self.payload_codec
+    }
+
+    /// Sets the timeout of all NATS requests sent via this consumer, e.g. reading provider IDs,
+    /// provider definitions or variable states.
+    ///
+    /// Defaults to [`DEFAULT_REQUEST_TIMEOUT`]. Subscriptions and writes are not affected, as they do not wait for a reply.
+    /// If a request times out, it fails with a [`async_nats::RequestError`] of kind
+    /// [`TimedOut`](async_nats::RequestErrorKind::TimedOut).
+    ///
+    /// Methods that wait for an event instead of a reply, e.g. [`Self::wait_for_provider`], have no timeout.
+    /// You can still wrap any call in a `tokio::time::timeout`, in which case the shorter timeout applies.
+    /// Use this setting to bound single round trips and the outer timeout to bound the whole operation.
+    #[must_use]
+    pub fn with_request_timeout(mut self, request_timeout: Duration) -> Self {
+        self.request_timeout = request_timeout;
+        self
+    }
+
+    /// Returns the timeout of NATS requests, see [`Self::with_request_timeout`].
+    #[must_use]
+    pub fn get_request_timeout(&self) -> Duration {
+        self.request_timeout
+    }
+
+    /// Sets the maximum size in bytes of decompressed variable change events, see [`crate::payload_compression`].
+    ///
+    /// Defaults to the maximum payload size of the NATS server. Compressed events that would exceed the limit
+    /// are rejected with [`PayloadDecompressError::TooLarge`](crate::payload_compression::PayloadDecompressError::TooLarge)
+    /// instead of being decompressed.
+    #[must_use]
+    pub fn with_max_decompressed_size(mut self, max_decompressed_size: usize) -> Self {
+        self.max_decompressed_size = Some(max_decompressed_size);
+        self
+    }
+
+    /// Returns the maximum size of decompressed variable change events, see [`Self::with_max_decompressed_size`].
+    #[must_use]
+    pub fn get_max_decompressed_size(&self) -> usize {
+        self.max_decompressed_size
+            .unwrap_or_else(|| self.nats_con.get_client().server_info().max_payload)
+    }
+
+    /// Sends a NATS request with the configured request timeout.
+    pub(crate) async fn request(
+        &self,
+        subject: String,
+        payload: Bytes,
+    ) -> std::result::Result<async_nats::Message, async_nats::RequestError> {
+        self.send_request(subject, async_nats::Request::new().payload(payload))
+            .await
+    }
+
+    /// Same as [`Self::request`], but sends the given headers along with the payload.
+    pub(crate) async fn request_with_headers(
+        &self,
+        subject: String,
+        headers: HeaderMap,
+        payload: Bytes,
+    ) -> std::result::Result<async_nats::Message, async_nats::RequestError> {
+        self.send_request(
+            subject,
+            async_nats::Request::new().headers(headers).payload(payload),
+        )
+        .await
+    }
+
+    async fn send_request(
+        &self,
+        subject: String,
+        request: async_nats::Request,
+    ) -> std::result::Result<async_nats::Message, async_nats::RequestError> {
+        self.nats_con
+            .get_client()
+            .send_request(subject, request.timeout(Some(self.request_timeout)))
+            .await
     }
 
     /// Gets the nats connection object.
@@ -118,10 +224,8 @@ impl NatsConsumer {
         let request_bytes = Bytes::from(all_bytes).slice(data_start_offset..);
 
         let reply = self
-            .nats_con
-            .get_client()
             .request(
-                nats_subjects::registry_providers_read_query(),
+                nats_subjects::registry_providers_read_query().to_owned(),
                 request_bytes,
             )
             .await?;
@@ -171,8 +275,7 @@ impl NatsConsumer {
 
         //Get current provider definition
         let provider_def_resp =
-            ConnectedNatsProvider::read_provider_definition_internal(nats_client, provider_id)
-                .await;
+            ConnectedNatsProvider::read_provider_definition_internal(self, provider_id).await;
 
         //Check if provider is already valid
         if let Ok(provider_def_resp) = &provider_def_resp {
@@ -0,0 +1,216 @@
+// SPDX-FileCopyrightText: 2025 Weidmueller Interface GmbH & Co. KG <oss@weidmueller.com>
+//
+// SPDX-License-Identifier: MIT
+
+//! Contains [`RunningStats`], which accumulates statistics over the values of a numeric variable,
+//! e.g. for monitoring dashboards.
+
+use std::sync::{Arc, Mutex};
+
+use futures::{Stream, StreamExt};
+
+use crate::dh_types::{VariableQuality, VariableValue};
+
+use super::consumer_types::VariableState;
+
+/// Snapshot of the statistics accumulated by [`RunningStats`].
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct StatsSnapshot {
+    /// Number of samples that are included in the statistics.
+    pub count: u64,
+    /// Number of samples that were excluded, because of their quality or because they are not numeric.
+    pub excluded: u64,
+    /// Smallest included value, `None` if no sample was included yet.
+    pub min: Option<f64>,
+    /// Largest included value, `None` if no sample was included yet.
+    pub max: Option<f64>,
+    /// Arithmetic mean of all included values, `None` if no sample was included yet.
+    pub mean: Option<f64>,
+    /// Most recently included value, `None` if no sample was included yet.
+    pub last: Option<f64>,
+}
+
+/// Running count/min/max/mean/last statistics over the values of a numeric variable.
+///
+/// Samples are added via [`Self::push`] or by driving a stream of states with [`Self::consume`],
+/// e.g. the stream of [`DataHubProviderConnection::subscribe_single_variable`].
+/// Only [`VariableValue::Int`] and [`VariableValue::Float64`] values are included, integers are converted to `f64`.
+/// By default, samples with [`VariableQuality::BadOrUndefined`] are excluded, see [`Self::include_bad_quality`].
+///
+/// The statistics are shared between clones, so a clone can consume the stream while another one takes snapshots.
+///
+/// [`DataHubProviderConnection::subscribe_single_variable`]: super::connected_dh_provider::DataHubProviderConnection::subscribe_single_variable
+#[derive(Debug, Clone, Default)]
+pub struct RunningStats {
+    snapshot: Arc<Mutex<StatsSnapshot>>,
+    include_bad_quality: bool,
+}
+
+impl RunningStats {
+    /// Creates an empty accumulator that excludes samples with bad quality.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets whether samples with [`VariableQuality::BadOrUndefined`] are included in the statistics.
+    ///
+    /// Samples with any other quality, including the uncertain ones, are always included.
+    #[must_use]
+    pub fn include_bad_quality(mut self, include: bool) -> Self {
+        self.include_bad_quality = include;
+        self
+    }
+
+    /// Adds a single sample to the statistics.
+    #[allow(clippy::missing_panics_doc)] // See comment below
+    pub fn push(&self, state: &VariableState) {
+        //Safety: Unwrap is ok here as we know that the mutex cant be poisoned during writing
+        #[allow(clippy::unwrap_used)]
+        let mut snapshot = self.snapshot.lock().unwrap();
+
+        let value = match state.value {
+            #[allow(clippy::cast_precision_loss)] // Precision loss is acceptable for statistics
+            VariableValue::Int(value) => Some(value as f64),
+            VariableValue::Float64(value) => Some(value),
+            _ => None,
+        };
+
+        let value = match value {
+            Some(value)
+                if self.include_bad_quality || state.quality != VariableQuality::BadOrUndefined =>
+            {
+                value
+            }
+            _ => {
+                snapshot.excluded += 1;
+                return;
+            }
+        };
+
+        snapshot.count += 1;
+        snapshot.min = Some(snapshot.min.map_or(value, |min| min.min(value)));
+        snapshot.max = Some(snapshot.max.map_or(value, |max| max.max(value)));
+        // Incremental mean, avoids overflowing a sum of all values
+        #[allow(clippy::cast_precision_loss)] // Precision loss is acceptable for statistics
+        let count = snapshot.count as f64;
+        snapshot.mean = Some(
+            snapshot
+                .mean
+                .map_or(value, |mean| mean + (value - mean) / count),
+        );
+        snapshot.last = Some(value);
+    }
+
+    /// Adds all states of `states` to the statistics until the stream ends.
+    ///
+    /// Use [`Self::snapshot`] on a clone of this instance to read the statistics in the meantime.
+    pub async fn consume(&self, states: impl Stream<Item = VariableState>) {
+        let mut states = Box::pin(states);
+
+        while let Some(state) = states.next().await {
+            self.push(&state);
+        }
+    }
+
+    /// Returns the current statistics.
+    #[allow(clippy::missing_panics_doc)] // See comment below
+    #[must_use]
+    pub fn snapshot(&self) -> StatsSnapshot {
+        //Safety: Unwrap is ok here as we know that the mutex cant be poisoned during writing
+        #[allow(clippy::unwrap_used)]
+        *self.snapshot.lock().unwrap()
+    }
+
+    /// Discards all accumulated statistics, e.g. to start a new measurement interval.
+    #[allow(clippy::missing_panics_doc)] // See comment below
+    pub fn reset(&self) {
+        //Safety: Unwrap is ok here as we know that the mutex cant be poisoned during writing
+        #[allow(clippy::unwrap_used)]
+        let mut snapshot = self.snapshot.lock().unwrap();
+        *snapshot = StatsSnapshot::default();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::dh_types::TimestampValue;
+    use rstest::rstest;
+
+    fn state(value: impl Into<VariableValue>, quality: VariableQuality) -> VariableState {
+        VariableState {
+            timestamp: TimestampValue::UNIX_EPOCH,
+            value: value.into(),
+            quality,
+        }
+    }
+
+    #[test]
+    fn test_empty() {
+        assert_eq!(RunningStats::new().snapshot(), StatsSnapshot::default());
+    }
+
+    #[test]
+    fn test_numeric_values() {
+        let stats = RunningStats::new();
+        stats.push(&state(4, VariableQuality::Good));
+        stats.push(&state(-2.0, VariableQuality::Uncertain));
+        stats.push(&state(1, VariableQuality::UncertainLastUsableValue));
+
+        assert_eq!(
+            stats.snapshot(),
+            StatsSnapshot {
+                count: 3,
+                excluded: 0,
+                min: Some(-2.0),
+                max: Some(4.0),
+                mean: Some(1.0),
+                last: Some(1.0),
+            }
+        );
+
+        stats.reset();
+        assert_eq!(stats.snapshot(), StatsSnapshot::default());
+    }
+
+    #[rstest]
+    #[case::excluded_by_default(false, 1, 1, Some(2.0))]
+    #[case::included(true, 2, 0, Some(4.0))]
+    fn test_bad_quality(
+        #[case] include_bad_quality: bool,
+        #[case] expected_count: u64,
+        #[case] expected_excluded: u64,
+        #[case] expected_mean: Option<f64>,
+    ) {
+        let stats = RunningStats::new().include_bad_quality(include_bad_quality);
+        stats.push(&state(2, VariableQuality::Good));
+        stats.push(&state(6, VariableQuality::BadOrUndefined));
+
+        let snapshot = stats.snapshot();
+        assert_eq!(snapshot.count, expected_count);
+        assert_eq!(snapshot.excluded, expected_excluded);
+        assert_eq!(snapshot.mean, expected_mean);
+    }
+
+    #[test]
+    fn test_non_numeric_values_are_excluded() {
+        let stats = RunningStats::new();
+        stats.push(&state("text", VariableQuality::Good));
+        stats.push(&state(true, VariableQuality::Good));
+        stats.push(&state(VariableValue::Unknown, VariableQuality::Good));
+
+        let snapshot = stats.snapshot();
+        assert_eq!(snapshot.count, 0);
+        assert_eq!(snapshot.excluded, 3);
+        assert_eq!(snapshot.last, None);
+    }
+
+    #[test]
+    fn test_clones_share_statistics() {
+        let stats = RunningStats::new();
+        stats.clone().push(&state(1, VariableQuality::Good));
+
+        assert_eq!(stats.snapshot().count, 1);
+    }
+}
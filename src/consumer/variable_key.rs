@@ -49,3 +49,69 @@ impl Display for VariableKey<'_> {
         write!(f, "{}", self.key_str)
     }
 }
+
+/// Checks whether `key` matches the wildcard `pattern`.
+///
+/// `*` matches any sequence of characters, including the empty sequence and `.` separators,
+/// `?` matches exactly one character. All other characters must match exactly.
+pub(super) fn matches_key_pattern(pattern: &str, key: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let key: Vec<char> = key.chars().collect();
+
+    let (mut p, mut k) = (0, 0);
+    //Position of the last `*` in the pattern and the key position it currently matches up to
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while k < key.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, k));
+                p += 1;
+            }
+            Some(&c) if c == '?' || key.get(k) == Some(&c) => {
+                p += 1;
+                k += 1;
+            }
+            _ => match backtrack {
+                //Let the last `*` consume one more character and retry
+                Some((star_p, star_k)) => {
+                    backtrack = Some((star_p, star_k + 1));
+                    p = star_p + 1;
+                    k = star_k + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern
+        .get(p..)
+        .is_some_and(|rest| rest.iter().all(|c| *c == '*'))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case::exact("folder.var", "folder.var", true)]
+    #[case::exact_mismatch("folder.var", "folder.other", false)]
+    #[case::star_suffix("folder.*", "folder.var", true)]
+    #[case::star_crosses_separator("folder.*", "folder.sub.var", true)]
+    #[case::star_empty("folder.var*", "folder.var", true)]
+    #[case::star_prefix("*.var", "folder.sub.var", true)]
+    #[case::star_middle("folder.*_int", "folder.rw_int", true)]
+    #[case::star_middle_mismatch("folder.*_int", "folder.rw_string", false)]
+    #[case::only_star("*", "any.key", true)]
+    #[case::question_mark("var_?", "var_1", true)]
+    #[case::question_mark_needs_char("var_?", "var_", false)]
+    #[case::question_mark_single_char("var_?", "var_12", false)]
+    #[case::backtracking("*a*b", "xaxxab", true)]
+    #[case::key_longer("folder", "folder.var", false)]
+    #[case::pattern_longer("folder.var", "folder", false)]
+    #[case::empty_pattern("", "folder", false)]
+    fn test_matches_key_pattern(#[case] pattern: &str, #[case] key: &str, #[case] expected: bool) {
+        assert_eq!(matches_key_pattern(pattern, key), expected);
+    }
+}
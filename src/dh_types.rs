@@ -5,6 +5,8 @@
 //! Collection of types that are used in the library.
 //! These types abstract the low level flatbuffer types and provide a more user-friendly interface.
 
+use std::fmt;
+
 use thiserror::Error;
 
 use crate::generated::weidmueller::ucontrol::hub::{
@@ -26,6 +28,11 @@ pub type VariableID = u32;
 pub enum Error {
     #[error("Failed to convert low level flatbuffer value")]
     FlatbufferDataTypeConversionFailure,
+    #[error("Variable value type mismatch. Expected: {expected:?}, Actual: {actual:?}")]
+    ValueTypeMismatch {
+        expected: VariableType,
+        actual: Option<VariableType>,
+    },
 }
 
 /// The quality of a variable.
@@ -33,6 +40,7 @@ pub enum Error {
 /// This is set by the provider and indicates the quality of a variable value.
 /// Check the documentation of the individual values for more information.
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum VariableQuality {
     /// Indicates that the value is not usable
     BadOrUndefined,
@@ -81,8 +89,68 @@ impl From<VariableQuality> for FbVariableQuality {
     }
 }
 
+impl fmt::Display for VariableQuality {
+    /// Writes a short lowercase name, e.g. `good` or `uncertain_initial`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            VariableQuality::BadOrUndefined => "bad",
+            VariableQuality::Good => "good",
+            VariableQuality::Uncertain => "uncertain",
+            VariableQuality::UncertainLastUsableValue => "uncertain_last_usable",
+            VariableQuality::UncertainInitialValue => "uncertain_initial",
+            VariableQuality::Unknown(raw) => return f.pad(&format!("unknown({raw})")),
+        };
+        f.pad(name)
+    }
+}
+
+/// The overall status of a provider, reported in addition to the quality of its variables.
+///
+/// This is coarser than [`VariableQuality`] and meant for conditions that affect the whole provider,
+/// e.g. to show a maintenance banner.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum ProviderStatus {
+    /// The provider works normally
+    #[default]
+    Ok,
+    /// The provider is running with limited functionality, e.g. because one of its data sources is unavailable
+    Degraded,
+    /// The provider is in maintenance and its values should not be trusted
+    Maintenance,
+    /// The status is unknown in this API version.
+    /// Stores the raw status string.
+    ///
+    /// This can happen if the provider uses a newer client version.
+    Unknown(String),
+}
+
+impl ProviderStatus {
+    /// Returns the string that is used to transfer the status via NATS.
+    #[must_use]
+    pub fn as_str(&self) -> &str {
+        match self {
+            ProviderStatus::Ok => "ok",
+            ProviderStatus::Degraded => "degraded",
+            ProviderStatus::Maintenance => "maintenance",
+            ProviderStatus::Unknown(status) => status,
+        }
+    }
+}
+
+impl From<&str> for ProviderStatus {
+    fn from(value: &str) -> Self {
+        match value {
+            "ok" => ProviderStatus::Ok,
+            "degraded" => ProviderStatus::Degraded,
+            "maintenance" => ProviderStatus::Maintenance,
+            other => ProviderStatus::Unknown(other.to_owned()),
+        }
+    }
+}
+
 /// The type of the variable
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[allow(missing_docs)]
 pub enum VariableType {
     Float64,
@@ -128,6 +196,22 @@ impl From<VariableType> for VariableDataType {
     }
 }
 
+impl fmt::Display for VariableType {
+    /// Writes a short lowercase name, e.g. `float64` or `timestamp`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            VariableType::Float64 => "float64",
+            VariableType::Int64 => "int64",
+            VariableType::String => "string",
+            VariableType::Timestamp => "timestamp",
+            VariableType::Duration => "duration",
+            VariableType::Boolean => "boolean",
+            VariableType::Unknown(raw) => return f.pad(&format!("unknown({raw})")),
+        };
+        f.pad(name)
+    }
+}
+
 /// The access type of the variable
 ///
 /// This determines which actions are allowed on the variable.
@@ -165,6 +249,17 @@ impl From<VariableAccessType> for FbVariableAccessType {
     }
 }
 
+impl fmt::Display for VariableAccessType {
+    /// Writes a short lowercase name, i.e. `read_write` or `read_only`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VariableAccessType::ReadWrite => f.pad("read_write"),
+            VariableAccessType::ReadOnly => f.pad("read_only"),
+            VariableAccessType::Unknown(raw) => f.pad(&format!("unknown({raw})")),
+        }
+    }
+}
+
 /// The definition of a variable
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub struct VariableDefinition {
@@ -192,6 +287,31 @@ impl From<VariableDefinitionT> for VariableDefinition {
     }
 }
 
+/// Width of the key column of [`VariableDefinition::summary_line`]. Longer keys are not truncated.
+pub const SUMMARY_KEY_WIDTH: usize = 40;
+/// Width of the value column of [`VariableState::summary_line`](crate::consumer::consumer_types::VariableState::summary_line).
+/// Longer values are truncated.
+pub const SUMMARY_VALUE_WIDTH: usize = 24;
+
+impl VariableDefinition {
+    /// Returns a compact, aligned description of the variable for table-like output, e.g. in CLI tools.
+    ///
+    /// The line consists of the key, the data type and the access type, each padded to a fixed width.
+    /// It can be combined with
+    /// [`VariableState::summary_line`](crate::consumer::consumer_types::VariableState::summary_line)
+    /// to print one row per variable.
+    #[must_use]
+    pub fn summary_line(&self) -> String {
+        format!(
+            "{:<key_width$} {:<9} {:<10}",
+            self.key,
+            self.data_type,
+            self.access_type,
+            key_width = SUMMARY_KEY_WIDTH
+        )
+    }
+}
+
 /// User friendly duration type that abstracts the low level flatbuffer value
 pub type DurationValue = time::Duration;
 
@@ -210,6 +330,128 @@ impl From<DurationValue> for DurationT {
     }
 }
 
+/// Units of human-readable duration strings, from largest to smallest, with their length in nanoseconds.
+const HUMAN_DURATION_UNITS: [(&str, u128); 7] = [
+    ("d", 86_400_000_000_000),
+    ("h", 3_600_000_000_000),
+    ("m", 60_000_000_000),
+    ("s", 1_000_000_000),
+    ("ms", 1_000_000),
+    ("us", 1_000),
+    ("ns", 1),
+];
+
+/// Error returned by [`HumanDuration::from_human_string`].
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[allow(missing_docs)]
+pub enum ParseDurationError {
+    #[error("Duration string is empty")]
+    Empty,
+    #[error("Invalid duration component `{0}`, expected a number followed by one of the units d, h, m, s, ms, us, ns")]
+    InvalidComponent(String),
+    #[error("Duration is out of range")]
+    OutOfRange,
+}
+
+/// Conversion between [`DurationValue`] and human-readable strings like `1h 30m 5s`.
+///
+/// A string consists of an optional leading `-` and one or more components, each made up of a whole number
+/// and one of the units `d`, `h`, `m`, `s`, `ms`, `us` and `ns`. Whitespace between components is optional.
+pub trait HumanDuration: Sized {
+    /// Formats the duration, e.g. `1h 30m 5s`, `1s 500ms` or `-250ms`.
+    ///
+    /// Only non-zero components are written, from largest to smallest unit. A zero duration is written as `0s`.
+    fn to_human_string(&self) -> String;
+
+    /// Parses a duration from a string as written by [`Self::to_human_string`].
+    ///
+    /// Components may be in any order and units may repeat, all components are summed up.
+    fn from_human_string(s: &str) -> Result<Self, ParseDurationError>;
+}
+
+impl HumanDuration for DurationValue {
+    fn to_human_string(&self) -> String {
+        let mut remaining_nanos = self.whole_nanoseconds().unsigned_abs();
+        let mut components = Vec::new();
+
+        for (unit, unit_nanos) in HUMAN_DURATION_UNITS {
+            let count = remaining_nanos / unit_nanos;
+            if count > 0 {
+                components.push(format!("{count}{unit}"));
+                remaining_nanos %= unit_nanos;
+            }
+        }
+
+        if components.is_empty() {
+            return "0s".to_owned();
+        }
+
+        let sign = if self.is_negative() { "-" } else { "" };
+        format!("{sign}{}", components.join(" "))
+    }
+
+    fn from_human_string(s: &str) -> Result<Self, ParseDurationError> {
+        let s = s.trim();
+        let (is_negative, mut remaining) = match s.strip_prefix('-') {
+            Some(unsigned) => (true, unsigned.trim_start()),
+            None => (false, s),
+        };
+
+        if remaining.is_empty() {
+            return Err(ParseDurationError::Empty);
+        }
+
+        let mut total_nanos: u128 = 0;
+
+        while !remaining.is_empty() {
+            let number_len = remaining
+                .find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(remaining.len());
+            let (number, rest) = remaining.split_at(number_len);
+            let unit_len = rest
+                .find(|c: char| !c.is_ascii_alphabetic())
+                .unwrap_or(rest.len());
+            let (unit, rest) = rest.split_at(unit_len);
+
+            let unit_nanos = HUMAN_DURATION_UNITS
+                .iter()
+                .find(|(name, _)| *name == unit)
+                .map(|(_, unit_nanos)| *unit_nanos);
+
+            let (Some(unit_nanos), false) = (unit_nanos, number.is_empty()) else {
+                let component_len = remaining
+                    .find(char::is_whitespace)
+                    .unwrap_or(remaining.len());
+                let (component, _) = remaining.split_at(component_len);
+                return Err(ParseDurationError::InvalidComponent(component.to_owned()));
+            };
+
+            let count: u128 = number.parse().map_err(|_| ParseDurationError::OutOfRange)?;
+            total_nanos = count
+                .checked_mul(unit_nanos)
+                .and_then(|nanos| total_nanos.checked_add(nanos))
+                .ok_or(ParseDurationError::OutOfRange)?;
+
+            remaining = rest.trim_start();
+        }
+
+        let total_nanos =
+            i128::try_from(total_nanos).map_err(|_| ParseDurationError::OutOfRange)?;
+        let total_nanos = if is_negative {
+            -total_nanos
+        } else {
+            total_nanos
+        };
+
+        let seconds = i64::try_from(total_nanos / 1_000_000_000)
+            .map_err(|_| ParseDurationError::OutOfRange)?;
+        let nanos = i32::try_from(total_nanos % 1_000_000_000)
+            .map_err(|_| ParseDurationError::OutOfRange)?;
+
+        Ok(DurationValue::new(seconds, nanos))
+    }
+}
+
 /// User friendly timestamp type that abstracts the low level flatbuffer value
 pub type TimestampValue = time::UtcDateTime;
 
@@ -241,22 +483,151 @@ impl From<TimestampValue> for TimestampT {
 }
 
 /// The value of a variable.
+///
+/// Values are equal if they have the same variant and the same content. Note that all [`VariableValue::Unknown`]
+/// values are equal to each other, see its documentation for details.
+///
+/// # Serialization
+///
+/// With the `serde` feature, values are represented by their variant name in `type` and the content in `value`,
+/// e.g. `{"type": "Int", "value": 42}` or `{"type": "String", "value": "text"}`.
+/// Timestamps are RFC 3339 strings, e.g. `{"type": "Timestamp", "value": "2025-01-31T12:00:00.5Z"}`,
+/// durations consist of seconds and nanos with the same sign, e.g. `{"type": "Duration", "value": {"seconds": 1, "nanos": 500000000}}`.
+/// [`VariableValue::Unknown`] is represented as `{"type": "Unknown"}`.
+///
+/// [`VariableQuality`] and [`VariableType`] are represented by their variant name, e.g. `"Good"` or `"Int64"`,
+/// unknown values as `{"Unknown": <raw value>}`. This representation is stable.
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(tag = "type", content = "value")
+)]
 #[allow(missing_docs)]
 pub enum VariableValue {
     Int(i64),
     Boolean(bool),
     String(String),
     Float64(f64),
-    Duration(DurationValue),
-    Timestamp(TimestampValue),
+    Duration(
+        #[cfg_attr(
+            feature = "serde",
+            serde(with = "crate::dh_types_serde_extension::seconds_nanos_duration")
+        )]
+        DurationValue,
+    ),
+    Timestamp(
+        #[cfg_attr(
+            feature = "serde",
+            serde(with = "crate::dh_types_serde_extension::rfc3339_timestamp")
+        )]
+        TimestampValue,
+    ),
     /// The variable value type is unknown in this API version.
     ///
     /// This can happen if the payload flatbuffer spec is newer than the client API version.
     /// You can not read or write this variable value.
+    ///
+    /// The unknown type itself is not preserved, as the flatbuffer object API maps every unsupported value type to the same empty value.
+    /// Therefore, all unknown values compare equal, even if they have different types or contents on the provider side.
+    /// An unknown value that stays equal does not mean that the value on the provider did not change.
+    /// Check for this variant, e.g. via [`VariableValue::value_type`], before comparing values to detect changes.
     Unknown,
 }
 
+impl VariableValue {
+    /// Returns the [`VariableType`] that matches this value.
+    ///
+    /// Returns `None` for [`VariableValue::Unknown`], as the type can not be determined in this case.
+    /// This can be used to check a value against [`VariableDefinition::data_type`] before writing it.
+    #[must_use]
+    pub fn value_type(&self) -> Option<VariableType> {
+        match self {
+            VariableValue::Unknown => None,
+            VariableValue::Int(_) => Some(VariableType::Int64),
+            VariableValue::Boolean(_) => Some(VariableType::Boolean),
+            VariableValue::String(_) => Some(VariableType::String),
+            VariableValue::Float64(_) => Some(VariableType::Float64),
+            VariableValue::Duration(_) => Some(VariableType::Duration),
+            VariableValue::Timestamp(_) => Some(VariableType::Timestamp),
+        }
+    }
+
+    /// Returns the value if it is a [`VariableValue::Int`], otherwise `None`.
+    #[must_use]
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            VariableValue::Int(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    /// Returns the value if it is a [`VariableValue::Float64`], otherwise `None`.
+    ///
+    /// Integers are not converted, use [`VariableValue::as_i64`] for them.
+    #[must_use]
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            VariableValue::Float64(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    /// Returns the value if it is a [`VariableValue::Boolean`], otherwise `None`.
+    #[must_use]
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            VariableValue::Boolean(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    /// Returns the value if it is a [`VariableValue::String`], otherwise `None`.
+    #[must_use]
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            VariableValue::String(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Returns the value if it is a [`VariableValue::Timestamp`], otherwise `None`.
+    #[must_use]
+    pub fn as_timestamp(&self) -> Option<TimestampValue> {
+        match self {
+            VariableValue::Timestamp(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    /// Returns the value if it is a [`VariableValue::Duration`], otherwise `None`.
+    #[must_use]
+    pub fn as_duration(&self) -> Option<DurationValue> {
+        match self {
+            VariableValue::Duration(value) => Some(*value),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for VariableValue {
+    /// Writes the value in a human friendly format.
+    ///
+    /// Strings are quoted and escaped, so that empty strings and control characters are visible.
+    /// Durations are written via [`HumanDuration::to_human_string`].
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VariableValue::Int(value) => write!(f, "{value}"),
+            VariableValue::Boolean(value) => write!(f, "{value}"),
+            VariableValue::String(value) => write!(f, "{value:?}"),
+            VariableValue::Float64(value) => write!(f, "{value}"),
+            VariableValue::Duration(value) => f.write_str(&value.to_human_string()),
+            VariableValue::Timestamp(value) => write!(f, "{value}"),
+            VariableValue::Unknown => f.write_str("unknown"),
+        }
+    }
+}
+
 impl From<i64> for VariableValue {
     fn from(value: i64) -> Self {
         VariableValue::Int(value)
@@ -299,6 +670,143 @@ impl From<TimestampValue> for VariableValue {
     }
 }
 
+/// Conversion from a [`VariableValue`] into a concrete rust type.
+///
+/// This is implemented for all rust types that have a matching [`VariableValue`] variant
+/// and allows strongly typed reads, e.g. via
+/// [`DataHubProviderConnection::read_single_variable_typed`](crate::consumer::connected_dh_provider::DataHubProviderConnection::read_single_variable_typed).
+/// The same conversions are available via [`TryFrom`], e.g. `i64::try_from(value)`.
+pub trait FromVariableValue: Sized {
+    /// The variable type that matches this rust type.
+    const VARIABLE_TYPE: VariableType;
+
+    /// Converts the value into this type.
+    ///
+    /// Fails with [`Error::ValueTypeMismatch`] if the value has a different type.
+    fn from_variable_value(value: VariableValue) -> Result<Self, Error>;
+}
+
+fn value_type_mismatch<T: FromVariableValue>(value: &VariableValue) -> Error {
+    Error::ValueTypeMismatch {
+        expected: T::VARIABLE_TYPE,
+        actual: value.value_type(),
+    }
+}
+
+impl FromVariableValue for i64 {
+    const VARIABLE_TYPE: VariableType = VariableType::Int64;
+
+    fn from_variable_value(value: VariableValue) -> Result<Self, Error> {
+        match value {
+            VariableValue::Int(value) => Ok(value),
+            other => Err(value_type_mismatch::<Self>(&other)),
+        }
+    }
+}
+
+impl FromVariableValue for f64 {
+    const VARIABLE_TYPE: VariableType = VariableType::Float64;
+
+    fn from_variable_value(value: VariableValue) -> Result<Self, Error> {
+        match value {
+            VariableValue::Float64(value) => Ok(value),
+            other => Err(value_type_mismatch::<Self>(&other)),
+        }
+    }
+}
+
+impl FromVariableValue for String {
+    const VARIABLE_TYPE: VariableType = VariableType::String;
+
+    fn from_variable_value(value: VariableValue) -> Result<Self, Error> {
+        match value {
+            VariableValue::String(value) => Ok(value),
+            other => Err(value_type_mismatch::<Self>(&other)),
+        }
+    }
+}
+
+impl FromVariableValue for bool {
+    const VARIABLE_TYPE: VariableType = VariableType::Boolean;
+
+    fn from_variable_value(value: VariableValue) -> Result<Self, Error> {
+        match value {
+            VariableValue::Boolean(value) => Ok(value),
+            other => Err(value_type_mismatch::<Self>(&other)),
+        }
+    }
+}
+
+impl FromVariableValue for DurationValue {
+    const VARIABLE_TYPE: VariableType = VariableType::Duration;
+
+    fn from_variable_value(value: VariableValue) -> Result<Self, Error> {
+        match value {
+            VariableValue::Duration(value) => Ok(value),
+            other => Err(value_type_mismatch::<Self>(&other)),
+        }
+    }
+}
+
+impl FromVariableValue for TimestampValue {
+    const VARIABLE_TYPE: VariableType = VariableType::Timestamp;
+
+    fn from_variable_value(value: VariableValue) -> Result<Self, Error> {
+        match value {
+            VariableValue::Timestamp(value) => Ok(value),
+            other => Err(value_type_mismatch::<Self>(&other)),
+        }
+    }
+}
+
+impl TryFrom<VariableValue> for i64 {
+    type Error = Error;
+
+    fn try_from(value: VariableValue) -> Result<Self, Error> {
+        Self::from_variable_value(value)
+    }
+}
+
+impl TryFrom<VariableValue> for f64 {
+    type Error = Error;
+
+    fn try_from(value: VariableValue) -> Result<Self, Error> {
+        Self::from_variable_value(value)
+    }
+}
+
+impl TryFrom<VariableValue> for String {
+    type Error = Error;
+
+    fn try_from(value: VariableValue) -> Result<Self, Error> {
+        Self::from_variable_value(value)
+    }
+}
+
+impl TryFrom<VariableValue> for bool {
+    type Error = Error;
+
+    fn try_from(value: VariableValue) -> Result<Self, Error> {
+        Self::from_variable_value(value)
+    }
+}
+
+impl TryFrom<VariableValue> for DurationValue {
+    type Error = Error;
+
+    fn try_from(value: VariableValue) -> Result<Self, Error> {
+        Self::from_variable_value(value)
+    }
+}
+
+impl TryFrom<VariableValue> for TimestampValue {
+    type Error = Error;
+
+    fn try_from(value: VariableValue) -> Result<Self, Error> {
+        Self::from_variable_value(value)
+    }
+}
+
 impl From<VariableValueT> for Option<VariableValue> {
     fn from(value: VariableValueT) -> Self {
         Some(match value {
@@ -384,4 +892,186 @@ mod test {
         let dh_timestamp_converted: TimestampValue = flatbuffer_timestamp.into();
         assert_eq!(dh_timestamp_converted, dh_timestamp);
     }
+
+    #[test]
+    fn test_typed_accessors() {
+        let timestamp = TimestampValue::UNIX_EPOCH + DurationValue::seconds(10);
+
+        assert_eq!(VariableValue::Int(42).as_i64(), Some(42));
+        assert_eq!(VariableValue::Float64(1.5).as_f64(), Some(1.5));
+        assert_eq!(VariableValue::Boolean(true).as_bool(), Some(true));
+        assert_eq!(VariableValue::from("text").as_str(), Some("text"));
+        assert_eq!(
+            VariableValue::Timestamp(timestamp).as_timestamp(),
+            Some(timestamp)
+        );
+        assert_eq!(
+            VariableValue::Duration(DurationValue::seconds(5)).as_duration(),
+            Some(DurationValue::seconds(5))
+        );
+
+        //no implicit conversions between types
+        assert_eq!(VariableValue::Int(42).as_f64(), None);
+        assert_eq!(VariableValue::Float64(1.0).as_i64(), None);
+        assert_eq!(VariableValue::from("true").as_bool(), None);
+        assert_eq!(VariableValue::Unknown.as_str(), None);
+    }
+
+    #[test]
+    fn test_try_from_value() {
+        assert_eq!(i64::try_from(VariableValue::Int(42)).unwrap(), 42);
+        assert_eq!(
+            String::try_from(VariableValue::from("text")).unwrap(),
+            "text"
+        );
+
+        let result: Result<bool, _> = VariableValue::Int(1).try_into();
+        assert!(matches!(
+            result,
+            Err(Error::ValueTypeMismatch {
+                expected: VariableType::Boolean,
+                actual: Some(VariableType::Int64),
+            })
+        ));
+        assert!(matches!(
+            f64::try_from(VariableValue::Unknown),
+            Err(Error::ValueTypeMismatch { actual: None, .. })
+        ));
+    }
+
+    #[test]
+    fn test_definition_summary_line() {
+        let mut def = VariableDefinition {
+            id: 1,
+            key: "plc.temperature".to_string(),
+            data_type: VariableType::Float64,
+            access_type: VariableAccessType::ReadOnly,
+            experimental: false,
+        };
+        assert_eq!(
+            def.summary_line(),
+            "plc.temperature                          float64   read_only "
+        );
+
+        def.data_type = VariableType::Unknown(42);
+        def.access_type = VariableAccessType::ReadWrite;
+        assert_eq!(
+            def.summary_line(),
+            "plc.temperature                          unknown(42) read_write"
+        );
+    }
+
+    #[rstest]
+    #[case::zero(DurationValue::ZERO, "0s")]
+    #[case::hours_minutes_seconds(DurationValue::new(5405, 0), "1h 30m 5s")]
+    #[case::days(DurationValue::new(2 * 86_400 + 3 * 3600, 0), "2d 3h")]
+    #[case::sub_second(DurationValue::new(1, 500_000_000), "1s 500ms")]
+    #[case::only_sub_second(DurationValue::new(0, 250_001_001), "250ms 1us 1ns")]
+    #[case::negative(DurationValue::new(-90, 0), "-1m 30s")]
+    #[case::negative_sub_second(DurationValue::new(0, -500_000_000), "-500ms")]
+    #[case::min(DurationValue::new(i64::MIN, 0), "-106751991167300d 15h 30m 8s")]
+    fn test_human_duration_round_trip(#[case] duration: DurationValue, #[case] expected: &str) {
+        assert_eq!(duration.to_human_string(), expected);
+        assert_eq!(DurationValue::from_human_string(expected), Ok(duration));
+    }
+
+    #[rstest]
+    #[case::without_whitespace("1h30m5s", Ok(DurationValue::new(5405, 0)))]
+    #[case::surrounding_whitespace("  90s ", Ok(DurationValue::new(90, 0)))]
+    #[case::repeated_units("1s 1s 1000ms", Ok(DurationValue::new(3, 0)))]
+    #[case::negative_with_whitespace("- 1s", Ok(DurationValue::new(-1, 0)))]
+    #[case::empty("", Err(ParseDurationError::Empty))]
+    #[case::only_sign("-", Err(ParseDurationError::Empty))]
+    #[case::missing_unit("1h 5", Err(ParseDurationError::InvalidComponent("5".to_owned())))]
+    #[case::unknown_unit("5x", Err(ParseDurationError::InvalidComponent("5x".to_owned())))]
+    #[case::missing_number("h", Err(ParseDurationError::InvalidComponent("h".to_owned())))]
+    #[case::fraction("1.5s", Err(ParseDurationError::InvalidComponent("1.5s".to_owned())))]
+    #[case::inner_sign("1h -5m", Err(ParseDurationError::InvalidComponent("-5m".to_owned())))]
+    #[case::out_of_range("106751991167301d", Err(ParseDurationError::OutOfRange))]
+    #[case::number_overflow(
+        "999999999999999999999999999999999999999999s",
+        Err(ParseDurationError::OutOfRange)
+    )]
+    fn test_human_duration_parse(
+        #[case] input: &str,
+        #[case] expected: Result<DurationValue, ParseDurationError>,
+    ) {
+        assert_eq!(DurationValue::from_human_string(input), expected);
+    }
+
+    #[rstest]
+    #[case::int(VariableValue::Int(1), Some(VariableType::Int64))]
+    #[case::boolean(VariableValue::Boolean(true), Some(VariableType::Boolean))]
+    #[case::string(VariableValue::from("test"), Some(VariableType::String))]
+    #[case::float(VariableValue::Float64(1.0), Some(VariableType::Float64))]
+    #[case::duration(
+        VariableValue::Duration(DurationValue::ZERO),
+        Some(VariableType::Duration)
+    )]
+    #[case::timestamp(
+        VariableValue::Timestamp(TimestampValue::UNIX_EPOCH),
+        Some(VariableType::Timestamp)
+    )]
+    #[case::unknown(VariableValue::Unknown, None)]
+    fn test_value_type(#[case] value: VariableValue, #[case] expected_type: Option<VariableType>) {
+        assert_eq!(value.value_type(), expected_type);
+    }
+
+    #[test]
+    fn test_from_variable_value() {
+        assert_eq!(
+            i64::from_variable_value(VariableValue::Int(1)).ok(),
+            Some(1)
+        );
+        assert_eq!(
+            f64::from_variable_value(VariableValue::Float64(1.5)).ok(),
+            Some(1.5)
+        );
+        assert_eq!(
+            String::from_variable_value(VariableValue::from("test")).ok(),
+            Some("test".to_string())
+        );
+        assert_eq!(
+            bool::from_variable_value(VariableValue::Boolean(true)).ok(),
+            Some(true)
+        );
+        assert_eq!(
+            DurationValue::from_variable_value(VariableValue::Duration(DurationValue::ZERO)).ok(),
+            Some(DurationValue::ZERO)
+        );
+        assert_eq!(
+            TimestampValue::from_variable_value(VariableValue::Timestamp(
+                TimestampValue::UNIX_EPOCH
+            ))
+            .ok(),
+            Some(TimestampValue::UNIX_EPOCH)
+        );
+    }
+
+    #[test]
+    fn test_unknown_values_are_equal() {
+        let unknown: Option<VariableValue> = VariableValueT::NONE.into();
+
+        assert_eq!(unknown, Some(VariableValue::Unknown));
+        assert_eq!(VariableValue::Unknown, VariableValue::Unknown);
+        assert_ne!(VariableValue::Unknown, VariableValue::Int(0));
+        assert_ne!(VariableValue::Unknown, VariableValue::from(""));
+    }
+
+    #[rstest]
+    #[case::other_type(VariableValue::Int(1), Some(VariableType::Int64))]
+    #[case::unknown(VariableValue::Unknown, None)]
+    fn test_from_variable_value_mismatch(
+        #[case] value: VariableValue,
+        #[case] expected_actual: Option<VariableType>,
+    ) {
+        let result = String::from_variable_value(value);
+        assert!(matches!(
+            result,
+            Err(Error::ValueTypeMismatch {
+                expected: VariableType::String,
+                actual
+            }) if actual == expected_actual
+        ));
+    }
 }
@@ -0,0 +1,157 @@
+// SPDX-FileCopyrightText: 2025 Weidmueller Interface GmbH & Co. KG <oss@weidmueller.com>
+//
+// SPDX-License-Identifier: MIT
+
+//! Helpers for the `serde` representation of the types in [`crate::dh_types`].
+//!
+//! The representation is part of the public API and must stay stable, see the documentation of
+//! [`VariableValue`](crate::dh_types::VariableValue) for details.
+
+/// Serializes a [`TimestampValue`] as RFC 3339 string, e.g. `2025-01-31T12:00:00.5Z`.
+pub(crate) mod rfc3339_timestamp {
+    use serde::{de::Error as _, ser::Error as _, Deserialize, Deserializer, Serializer};
+    use time::format_description::well_known::Rfc3339;
+
+    use crate::dh_types::TimestampValue;
+
+    pub(crate) fn serialize<S>(value: &TimestampValue, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let formatted = value.format(&Rfc3339).map_err(S::Error::custom)?;
+        serializer.serialize_str(&formatted)
+    }
+
+    pub(crate) fn deserialize<'de, D>(deserializer: D) -> Result<TimestampValue, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        //Owned, as borrowing is not possible for all deserializers, e.g. `serde_json::Value`
+        let formatted = String::deserialize(deserializer)?;
+        TimestampValue::parse(&formatted, &Rfc3339).map_err(D::Error::custom)
+    }
+}
+
+/// Serializes a [`DurationValue`] as `{"seconds": <i64>, "nanos": <i32>}`, like the flatbuffer duration.
+///
+/// The nanos have the same sign as the seconds, so `-1.5s` is `{"seconds": -1, "nanos": -500000000}`.
+pub(crate) mod seconds_nanos_duration {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use crate::dh_types::DurationValue;
+
+    #[derive(Serialize, Deserialize)]
+    struct SecondsNanos {
+        seconds: i64,
+        nanos: i32,
+    }
+
+    pub(crate) fn serialize<S>(value: &DurationValue, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        SecondsNanos {
+            seconds: value.whole_seconds(),
+            nanos: value.subsec_nanoseconds(),
+        }
+        .serialize(serializer)
+    }
+
+    pub(crate) fn deserialize<'de, D>(deserializer: D) -> Result<DurationValue, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let SecondsNanos { seconds, nanos } = SecondsNanos::deserialize(deserializer)?;
+        Ok(DurationValue::new(seconds, nanos))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use rstest::rstest;
+    use serde_json::json;
+
+    use crate::{
+        consumer::consumer_types::VariableState,
+        dh_types::{DurationValue, TimestampValue, VariableQuality, VariableType, VariableValue},
+    };
+
+    #[rstest]
+    #[case::int(VariableValue::Int(i64::MAX), json!({"type": "Int", "value": i64::MAX}))]
+    #[case::boolean(VariableValue::Boolean(true), json!({"type": "Boolean", "value": true}))]
+    #[case::string(VariableValue::from("text"), json!({"type": "String", "value": "text"}))]
+    #[case::float(VariableValue::Float64(1.5), json!({"type": "Float64", "value": 1.5}))]
+    #[case::duration(
+        VariableValue::Duration(DurationValue::new(-1, -500_000_000)),
+        json!({"type": "Duration", "value": {"seconds": -1, "nanos": -500_000_000}})
+    )]
+    #[case::timestamp(
+        VariableValue::Timestamp(TimestampValue::UNIX_EPOCH + DurationValue::new(1_738_324_800, 500_000_000)),
+        json!({"type": "Timestamp", "value": "2025-01-31T12:00:00.5Z"})
+    )]
+    #[case::unknown(VariableValue::Unknown, json!({"type": "Unknown"}))]
+    fn test_value_round_trip(#[case] value: VariableValue, #[case] expected: serde_json::Value) {
+        assert_eq!(serde_json::to_value(&value).unwrap(), expected);
+        assert_eq!(
+            serde_json::from_value::<VariableValue>(expected).unwrap(),
+            value
+        );
+    }
+
+    #[rstest]
+    #[case::good(VariableQuality::Good, json!("Good"))]
+    #[case::last_usable(
+        VariableQuality::UncertainLastUsableValue,
+        json!("UncertainLastUsableValue")
+    )]
+    #[case::unknown(VariableQuality::Unknown(42), json!({"Unknown": 42}))]
+    fn test_quality_round_trip(
+        #[case] quality: VariableQuality,
+        #[case] expected: serde_json::Value,
+    ) {
+        assert_eq!(serde_json::to_value(quality).unwrap(), expected);
+        assert_eq!(
+            serde_json::from_value::<VariableQuality>(expected).unwrap(),
+            quality
+        );
+    }
+
+    #[rstest]
+    #[case::int(VariableType::Int64, json!("Int64"))]
+    #[case::unknown(VariableType::Unknown(-1), json!({"Unknown": -1}))]
+    fn test_type_round_trip(#[case] data_type: VariableType, #[case] expected: serde_json::Value) {
+        assert_eq!(serde_json::to_value(data_type).unwrap(), expected);
+        assert_eq!(
+            serde_json::from_value::<VariableType>(expected).unwrap(),
+            data_type
+        );
+    }
+
+    #[test]
+    fn test_state_round_trip() {
+        let state = VariableState {
+            timestamp: TimestampValue::UNIX_EPOCH + DurationValue::seconds(1_738_324_800),
+            value: VariableValue::Int(42),
+            quality: VariableQuality::Good,
+        };
+        let expected = json!({
+            "timestamp": "2025-01-31T12:00:00Z",
+            "value": {"type": "Int", "value": 42},
+            "quality": "Good",
+        });
+
+        assert_eq!(serde_json::to_value(&state).unwrap(), expected);
+        assert_eq!(
+            serde_json::from_value::<VariableState>(expected).unwrap(),
+            state
+        );
+
+        //invalid timestamps are rejected
+        assert!(serde_json::from_value::<VariableState>(json!({
+            "timestamp": "yesterday",
+            "value": {"type": "Int", "value": 42},
+            "quality": "Good",
+        }))
+        .is_err());
+    }
+}
@@ -12,6 +12,10 @@
 //! # Features
 //!
 //! * `export-low-level-api` - Exports low level features as public API. See [consumer] module documentation for details about the low level api. (Default: `false`)
+//! * `test-support` - Exports helpers for integration tests, like a fake registry and a dummy provider. See `prelude::test` for details. (Default: `false`)
+//! * `jetstream` - Enables `AuthenticatedNatsConnection::jetstream` to use NATS `JetStream` on the same connection. (Default: `false`)
+//! * `serde` - Implements `Serialize` and `Deserialize` for [`VariableValue`](dh_types::VariableValue), [`VariableQuality`](dh_types::VariableQuality), [`VariableType`](dh_types::VariableType) and the consumer [`VariableState`](consumer::consumer_types::VariableState). See [`VariableValue`](dh_types::VariableValue) for the representation. (Default: `false`)
+//! * `compression` - Enables `ProviderBuilder::with_payload_compression` and decompression of compressed payloads on the consumer side. See [`payload_compression`] for details. (Default: `false`)
 
 // Contains the generated flatbuffers.
 // The module comment/description is located here to not change the generated files.
@@ -32,12 +36,22 @@ pub mod authenticated_nats_con;
 pub mod consumer;
 #[warn(missing_docs)]
 pub mod dh_types;
+#[cfg(feature = "serde")]
+mod dh_types_serde_extension;
 pub mod env_file_parser;
 pub mod nats_subjects;
 pub mod oauth2;
 pub mod payload_builders;
+pub mod payload_codec;
+#[warn(missing_docs)]
+pub mod payload_compression;
+#[warn(missing_docs)]
+pub mod prelude;
 #[warn(missing_docs)]
 pub mod provider;
+#[cfg(feature = "test-support")]
+#[warn(missing_docs)]
+pub mod test_support;
 #[warn(missing_docs)]
 pub mod variable;
 pub mod variable_definition_serde_extension;
@@ -43,6 +43,18 @@ pub fn write_variables_command(provider_id: &str) -> String {
     format!("{VERSION_PREFIX}.{LOCATION_PREFIX}.{provider_id}.vars.cmd.write")
 }
 
+/// Get the subject to read historical values of a variable from a provider.
+///
+/// Only providers that store a history answer on this subject.
+/// This is an extension of this client library, see
+/// [`DataHubProviderConnection::read_history`](crate::consumer::connected_dh_provider::DataHubProviderConnection::read_history)
+/// for the request format.
+#[inline(always)]
+#[must_use]
+pub fn read_history_query(provider_id: &str) -> String {
+    format!("{VERSION_PREFIX}.{LOCATION_PREFIX}.{provider_id}.vars.qry.history")
+}
+
 /// Subject for provider definition changed events.
 ///
 /// The provider will use this subject to notify the registry about a changed definition.
@@ -52,6 +64,23 @@ pub fn provider_changed_event(provider_id: &str) -> String {
     format!("{VERSION_PREFIX}.{LOCATION_PREFIX}.{provider_id}.def.evt.changed")
 }
 
+/// Subject for provider status changed events.
+///
+/// The provider publishes its [`ProviderStatus`](crate::dh_types::ProviderStatus) on this subject whenever it changes.
+/// This is an extension of this client library, the registry does not know about it.
+#[inline(always)]
+#[must_use]
+pub fn provider_status_changed_event(provider_id: &str) -> String {
+    format!("{VERSION_PREFIX}.{LOCATION_PREFIX}.{provider_id}.status.evt.changed")
+}
+
+/// Subject to read the current [`ProviderStatus`](crate::dh_types::ProviderStatus) from a provider.
+#[inline(always)]
+#[must_use]
+pub fn provider_status_read_query(provider_id: &str) -> String {
+    format!("{VERSION_PREFIX}.{LOCATION_PREFIX}.{provider_id}.status.qry.read")
+}
+
 /// Subject for reading the provider definition from the registry. Used by hub participants.
 #[inline(always)]
 #[must_use]
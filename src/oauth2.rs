@@ -36,6 +36,18 @@ pub enum OAuth2RequestTokenError {
     FailedToDeserializeResponse(#[from] serde_json::Error),
 }
 
+/// Determines how the client credentials are sent to the `OAuth2` token endpoint.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ClientAuthStyle {
+    /// Credentials are sent via HTTP Basic authentication (`client_secret_basic`).
+    #[default]
+    Basic,
+    /// Credentials are sent in the request body (`client_secret_post`).
+    ///
+    /// Use this for identity providers that reject HTTP Basic authentication at the token endpoint.
+    Post,
+}
+
 /// Contains `OAuth2` client credentials
 #[derive(Clone, Debug)]
 pub struct OAuth2Credentials {
@@ -74,34 +86,35 @@ impl OAuth2Credentials {
     }
 
     /// Executes the `OAuth2` client credentials flow to request an access token
+    ///
+    /// The credentials are sent via HTTP Basic authentication, see [`Self::request_token_with_auth_style`].
     pub async fn request_token<T: AsRef<str>, T2: AsRef<str>>(
         &self,
         token_endpoint: T,
         scope: T2,
+    ) -> Result<TokenResponse, OAuth2RequestTokenError> {
+        self.request_token_with_auth_style(token_endpoint, scope, ClientAuthStyle::Basic)
+            .await
+    }
+
+    /// Same as [`Self::request_token`], but sends the credentials as specified by `auth_style`.
+    pub async fn request_token_with_auth_style<T: AsRef<str>, T2: AsRef<str>>(
+        &self,
+        token_endpoint: T,
+        scope: T2,
+        auth_style: ClientAuthStyle,
     ) -> Result<TokenResponse, OAuth2RequestTokenError> {
         let reqwest_client = reqwest::ClientBuilder::new()
             .danger_accept_invalid_certs(true)
             .build()?;
 
-        let mut params = BTreeMap::new();
-        params.insert("grant_type", "client_credentials");
-        params.insert("scope", scope.as_ref());
-
-        let credentials = format!(
-            "{}:{}",
-            urlencoding::encode(&self.client_id),
-            urlencoding::encode(&self.client_secret)
-        );
-
-        let http_response = reqwest_client
-            .post(token_endpoint.as_ref())
-            .header("Content-Type", "application/x-www-form-urlencoded")
-            .header("Accept", "application/json")
-            .header(
-                AUTHORIZATION,
-                format!("Basic {}", BASE64_STANDARD.encode(credentials)),
+        let http_response = self
+            .build_token_request(
+                &reqwest_client,
+                token_endpoint.as_ref(),
+                scope.as_ref(),
+                auth_style,
             )
-            .form(&params)
             .send()
             .await?;
 
@@ -125,6 +138,50 @@ impl OAuth2Credentials {
     }
 }
 
+impl OAuth2Credentials {
+    /// Builds the token request of the client credentials flow.
+    fn build_token_request(
+        &self,
+        reqwest_client: &reqwest::Client,
+        token_endpoint: &str,
+        scope: &str,
+        auth_style: ClientAuthStyle,
+    ) -> reqwest::RequestBuilder {
+        let mut params = BTreeMap::new();
+        params.insert("grant_type", "client_credentials");
+        params.insert("scope", scope);
+
+        let request = reqwest_client
+            .post(token_endpoint)
+            .header("Content-Type", "application/x-www-form-urlencoded")
+            .header("Accept", "application/json");
+
+        match auth_style {
+            ClientAuthStyle::Basic => {
+                let credentials = format!(
+                    "{}:{}",
+                    urlencoding::encode(&self.client_id),
+                    urlencoding::encode(&self.client_secret)
+                );
+
+                request
+                    .header(
+                        AUTHORIZATION,
+                        format!("Basic {}", BASE64_STANDARD.encode(credentials)),
+                    )
+                    .form(&params)
+            }
+            ClientAuthStyle::Post => {
+                //The form encoding takes care of escaping the credentials
+                params.insert("client_id", &self.client_id);
+                params.insert("client_secret", &self.client_secret);
+
+                request.form(&params)
+            }
+        }
+    }
+}
+
 /// The response of the client credentials flow
 #[allow(dead_code)]
 #[derive(Deserialize)]
@@ -142,3 +199,55 @@ pub struct Oauth2ErrorResponse {
     pub error_description: Option<String>,
     pub error_hint: Option<String>,
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn build_request(auth_style: ClientAuthStyle) -> reqwest::Request {
+        let creds = OAuth2Credentials {
+            client_name: "test_client".to_string(),
+            client_id: "my id".to_string(),
+            client_secret: "s3cr3t&".to_string(),
+        };
+
+        creds
+            .build_token_request(
+                &reqwest::Client::new(),
+                "https://127.0.0.1/oauth2/token",
+                "hub.variables.readonly",
+                auth_style,
+            )
+            .build()
+            .unwrap()
+    }
+
+    fn body(request: &reqwest::Request) -> &str {
+        std::str::from_utf8(request.body().unwrap().as_bytes().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_basic_auth_style() {
+        let request = build_request(ClientAuthStyle::Basic);
+
+        assert_eq!(
+            request.headers()[AUTHORIZATION],
+            format!("Basic {}", BASE64_STANDARD.encode("my%20id:s3cr3t%26"))
+        );
+        assert_eq!(
+            body(&request),
+            "grant_type=client_credentials&scope=hub.variables.readonly"
+        );
+    }
+
+    #[test]
+    fn test_post_auth_style() {
+        let request = build_request(ClientAuthStyle::Post);
+
+        assert!(!request.headers().contains_key(AUTHORIZATION));
+        assert_eq!(
+            body(&request),
+            "client_id=my+id&client_secret=s3cr3t%26&grant_type=client_credentials&scope=hub.variables.readonly"
+        );
+    }
+}
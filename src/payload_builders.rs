@@ -186,6 +186,24 @@ pub fn build_read_variables_query_response(
     variables: &BTreeMap<u32, Variable>,
     provider_definition_fingerprint: u64,
 ) -> Bytes {
+    let response =
+        create_read_variables_query_response(msg, variables, provider_definition_fingerprint);
+
+    let mut builder = FlatBufferBuilder::new();
+    let packed_response = response.pack(&mut builder);
+
+    builder.finish(packed_response, None);
+    let (all_bytes, data_start_offset) = builder.collapse();
+    Bytes::from(all_bytes).slice(data_start_offset..)
+}
+
+/// Creates the read variables query response, see [`build_read_variables_query_response`].
+#[must_use]
+pub fn create_read_variables_query_response(
+    msg: ReadVariablesQueryRequestT,
+    variables: &BTreeMap<u32, Variable>,
+    provider_definition_fingerprint: u64,
+) -> ReadVariablesQueryResponseT {
     let mut response = ReadVariablesQueryResponseT::default();
 
     let items: Vec<&Variable> = match msg.ids {
@@ -208,12 +226,18 @@ pub fn build_read_variables_query_response(
 
     response.variables = Box::new(var_list_flat);
 
-    let mut builder = FlatBufferBuilder::new();
-    let packed_response = response.pack(&mut builder);
+    response
+}
 
-    builder.finish(packed_response, None);
-    let (all_bytes, data_start_offset) = builder.collapse();
-    Bytes::from(all_bytes).slice(data_start_offset..)
+/// Removes the timestamps and qualities from all variables of a read response, so only the values are serialized.
+///
+/// Used to answer read requests with the [`READ_VALUES_ONLY_HEADER`](crate::consumer::connected_nats_provider::READ_VALUES_ONLY_HEADER).
+pub(crate) fn omit_timestamps_and_qualities(response: &mut ReadVariablesQueryResponseT) {
+    for ll_var in response.variables.items.iter_mut().flatten() {
+        //Both are flatbuffer defaults and will therefore not be serialized
+        ll_var.timestamp = None;
+        ll_var.quality = VariableQuality::BAD;
+    }
 }
 
 /// Builds the payload of the variables changed event
@@ -224,6 +248,22 @@ pub fn build_variables_changed_event(
     variables: &BTreeMap<u32, Variable>,
     provider_definition_fingerprint: u64,
 ) -> Bytes {
+    let response = create_variables_changed_event(variables, provider_definition_fingerprint);
+
+    let mut builder = FlatBufferBuilder::new();
+    let packed_response = response.pack(&mut builder);
+
+    builder.finish(packed_response, None);
+    let (all_bytes, data_start_offset) = builder.collapse();
+    Bytes::from(all_bytes).slice(data_start_offset..)
+}
+
+/// Creates the variables changed event, see [`build_variables_changed_event`].
+#[must_use]
+pub fn create_variables_changed_event(
+    variables: &BTreeMap<u32, Variable>,
+    provider_definition_fingerprint: u64,
+) -> VariablesChangedEventT {
     let mut response = VariablesChangedEventT::default();
 
     let to_publish = variables.values().map(Into::into).collect();
@@ -237,12 +277,7 @@ pub fn build_variables_changed_event(
 
     response.changed_variables = Box::new(var_list_flat);
 
-    let mut builder = FlatBufferBuilder::new();
-    let packed_response = response.pack(&mut builder);
-
-    builder.finish(packed_response, None);
-    let (all_bytes, data_start_offset) = builder.collapse();
-    Bytes::from(all_bytes).slice(data_start_offset..)
+    response
 }
 
 #[cfg(test)]
@@ -342,6 +377,39 @@ mod tests {
         }
     }
 
+    #[rstest]
+    #[case::full(false)]
+    #[case::values_only(true)]
+    fn test_create_read_variables_query_response(#[case] values_only: bool) {
+        // arrange
+        let var = crate::provider::VariableBuilder::new(1, "var_int")
+            .initial_value(42)
+            .build()
+            .unwrap();
+        let variables = BTreeMap::from([(1, var)]);
+        let request = ReadVariablesQueryRequestT { ids: None };
+
+        // act
+        let mut response = create_read_variables_query_response(request, &variables, 1);
+        if values_only {
+            omit_timestamps_and_qualities(&mut response);
+        }
+
+        // assert
+        let result_var = response.variables.items.unwrap().pop().unwrap();
+        assert_eq!(
+            result_var.value,
+            (&crate::dh_types::VariableValue::Int(42)).into()
+        );
+        assert_eq!(result_var.timestamp.is_none(), values_only);
+        let expected_quality = if values_only {
+            VariableQuality::BAD
+        } else {
+            VariableQuality::GOOD
+        };
+        assert_eq!(result_var.quality, expected_quality);
+    }
+
     #[rstest]
     #[case::running(State::RUNNING)]
     #[case::stopping(State::STOPPING)]
@@ -0,0 +1,221 @@
+// SPDX-FileCopyrightText: 2025 Weidmueller Interface GmbH & Co. KG <oss@weidmueller.com>
+//
+// SPDX-License-Identifier: MIT
+
+//! Contains the [`PayloadCodec`] trait, which abstracts the encoding of the messages that are exchanged
+//! directly between providers and consumers, and the default [`FlatbufferCodec`].
+//!
+//! Only variable messages (reads, writes and change events) go through the codec.
+//! Messages of the hub registry, like provider definitions, are always flatbuffers, as the registry is part of the Data Hub.
+//!
+//! A custom codec can be used e.g. to inspect payloads on the wire with generic tooling during debugging.
+//! Note that providers and consumers only understand each other if they use the same codec,
+//! so custom codecs are not compatible with other Data Hub clients.
+//! Implementing a custom codec requires the `export-low-level-api` feature to access the low level message types.
+
+use std::fmt::Debug;
+
+use bytes::Bytes;
+use flatbuffers::{FlatBufferBuilder, WIPOffset};
+use thiserror::Error;
+
+use crate::generated::weidmueller::ucontrol::hub::{
+    ReadVariablesQueryRequest, ReadVariablesQueryRequestT, ReadVariablesQueryResponse,
+    ReadVariablesQueryResponseT, VariablesChangedEvent, VariablesChangedEventT,
+    WriteVariablesCommand, WriteVariablesCommandT,
+};
+
+/// Error that can occur when decoding a payload
+#[derive(Error, Debug)]
+#[allow(missing_docs)]
+pub enum PayloadDecodeError {
+    #[error("{0}")]
+    InvalidFlatbuffer(#[from] flatbuffers::InvalidFlatbuffer),
+    #[error("{0}")]
+    Custom(Box<dyn std::error::Error + Send + Sync>),
+}
+
+/// Encodes and decodes the messages that are exchanged between providers and consumers.
+///
+/// See the [module documentation](self) for details.
+pub trait PayloadCodec: Debug + Send + Sync {
+    /// Encodes a read request of a consumer.
+    fn encode_read_variables_query_request(&self, request: &ReadVariablesQueryRequestT) -> Bytes;
+
+    /// Decodes a read request of a consumer.
+    fn decode_read_variables_query_request(
+        &self,
+        payload: &[u8],
+    ) -> Result<ReadVariablesQueryRequestT, PayloadDecodeError>;
+
+    /// Encodes the response of a provider to a read request.
+    fn encode_read_variables_query_response(&self, response: &ReadVariablesQueryResponseT)
+        -> Bytes;
+
+    /// Decodes the response of a provider to a read request.
+    fn decode_read_variables_query_response(
+        &self,
+        payload: &[u8],
+    ) -> Result<ReadVariablesQueryResponseT, PayloadDecodeError>;
+
+    /// Encodes a variables changed event of a provider.
+    fn encode_variables_changed_event(&self, event: &VariablesChangedEventT) -> Bytes;
+
+    /// Decodes a variables changed event of a provider.
+    fn decode_variables_changed_event(
+        &self,
+        payload: &[u8],
+    ) -> Result<VariablesChangedEventT, PayloadDecodeError>;
+
+    /// Encodes a write command of a consumer.
+    fn encode_write_variables_command(&self, command: &WriteVariablesCommandT) -> Bytes;
+
+    /// Decodes a write command of a consumer.
+    fn decode_write_variables_command(
+        &self,
+        payload: &[u8],
+    ) -> Result<WriteVariablesCommandT, PayloadDecodeError>;
+}
+
+/// The default codec, which encodes all messages as flatbuffers as specified by the Data Hub.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FlatbufferCodec;
+
+/// Finishes the flatbuffer and returns the payload without copying it.
+fn finish<T>(mut builder: FlatBufferBuilder, root: WIPOffset<T>) -> Bytes {
+    builder.finish(root, None);
+
+    //use collapse to avoid copying vector
+    let (all_bytes, data_start_offset) = builder.collapse();
+    Bytes::from(all_bytes).slice(data_start_offset..)
+}
+
+impl PayloadCodec for FlatbufferCodec {
+    fn encode_read_variables_query_request(&self, request: &ReadVariablesQueryRequestT) -> Bytes {
+        let mut builder = FlatBufferBuilder::new();
+        let root = request.pack(&mut builder);
+        finish(builder, root)
+    }
+
+    fn decode_read_variables_query_request(
+        &self,
+        payload: &[u8],
+    ) -> Result<ReadVariablesQueryRequestT, PayloadDecodeError> {
+        Ok(flatbuffers::root::<ReadVariablesQueryRequest>(payload)?.unpack())
+    }
+
+    fn encode_read_variables_query_response(
+        &self,
+        response: &ReadVariablesQueryResponseT,
+    ) -> Bytes {
+        let mut builder = FlatBufferBuilder::new();
+        let root = response.pack(&mut builder);
+        finish(builder, root)
+    }
+
+    fn decode_read_variables_query_response(
+        &self,
+        payload: &[u8],
+    ) -> Result<ReadVariablesQueryResponseT, PayloadDecodeError> {
+        Ok(flatbuffers::root::<ReadVariablesQueryResponse>(payload)?.unpack())
+    }
+
+    fn encode_variables_changed_event(&self, event: &VariablesChangedEventT) -> Bytes {
+        let mut builder = FlatBufferBuilder::new();
+        let root = event.pack(&mut builder);
+        finish(builder, root)
+    }
+
+    fn decode_variables_changed_event(
+        &self,
+        payload: &[u8],
+    ) -> Result<VariablesChangedEventT, PayloadDecodeError> {
+        Ok(flatbuffers::root::<VariablesChangedEvent>(payload)?.unpack())
+    }
+
+    fn encode_write_variables_command(&self, command: &WriteVariablesCommandT) -> Bytes {
+        let mut builder = FlatBufferBuilder::new();
+        let root = command.pack(&mut builder);
+        finish(builder, root)
+    }
+
+    fn decode_write_variables_command(
+        &self,
+        payload: &[u8],
+    ) -> Result<WriteVariablesCommandT, PayloadDecodeError> {
+        Ok(flatbuffers::root::<WriteVariablesCommand>(payload)?.unpack())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::generated::weidmueller::ucontrol::hub::{
+        TimestampT, VariableListT, VariableQuality, VariableT, VariableValueInt64T, VariableValueT,
+    };
+
+    fn variable_list() -> VariableListT {
+        VariableListT {
+            provider_definition_fingerprint: 42,
+            base_timestamp: TimestampT::default(),
+            items: Some(vec![VariableT {
+                id: 1,
+                quality: VariableQuality::GOOD,
+                value: VariableValueT::Int64(Box::new(VariableValueInt64T { value: 7 })),
+                timestamp: None,
+            }]),
+        }
+    }
+
+    #[test]
+    fn test_flatbuffer_round_trip() {
+        let codec = FlatbufferCodec;
+
+        let request = ReadVariablesQueryRequestT {
+            ids: Some(vec![1, 2]),
+        };
+        let payload = codec.encode_read_variables_query_request(&request);
+        assert_eq!(
+            codec.decode_read_variables_query_request(&payload).unwrap(),
+            request
+        );
+
+        let response = ReadVariablesQueryResponseT {
+            variables: Box::new(variable_list()),
+        };
+        let payload = codec.encode_read_variables_query_response(&response);
+        assert_eq!(
+            codec
+                .decode_read_variables_query_response(&payload)
+                .unwrap(),
+            response
+        );
+
+        let event = VariablesChangedEventT {
+            changed_variables: Box::new(variable_list()),
+        };
+        let payload = codec.encode_variables_changed_event(&event);
+        assert_eq!(
+            codec.decode_variables_changed_event(&payload).unwrap(),
+            event
+        );
+
+        let command = WriteVariablesCommandT {
+            variables: Box::new(variable_list()),
+        };
+        let payload = codec.encode_write_variables_command(&command);
+        assert_eq!(
+            codec.decode_write_variables_command(&payload).unwrap(),
+            command
+        );
+    }
+
+    #[test]
+    fn test_flatbuffer_invalid_payload() {
+        let result = FlatbufferCodec.decode_variables_changed_event(&[1, 2, 3]);
+        assert!(matches!(
+            result,
+            Err(PayloadDecodeError::InvalidFlatbuffer(_))
+        ));
+    }
+}
@@ -0,0 +1,211 @@
+// SPDX-FileCopyrightText: 2025 Weidmueller Interface GmbH & Co. KG <oss@weidmueller.com>
+//
+// SPDX-License-Identifier: MIT
+
+//! Contains the optional compression of variable change events.
+//!
+//! Providers with large variable tables can compress their change events with zstd,
+//! see `ProviderBuilder::with_payload_compression`.
+//! Compressed payloads are marked with the [`CONTENT_ENCODING_HEADER`] NATS header, uncompressed payloads carry no header.
+//! Consumers decompress marked payloads transparently if the `compression` feature is enabled.
+//! Otherwise, they detect the header and fail with a clear error instead of interpreting the compressed bytes as a flatbuffer.
+//! Note that clients that predate this header do not check it, so all consumers of a compressing provider should be updated first.
+//!
+//! Compression trades CPU time for bandwidth: every change event is compressed once by the provider
+//! and decompressed by every consumer. This pays off on constrained links with large events,
+//! but only adds latency for small events or fast links. Therefore, only events that reach
+//! [`PayloadCompression::min_size`] are compressed.
+//!
+//! The decompressed size is limited, so that a small malicious payload can not exhaust the memory of consumers,
+//! see [`DataHubConsumerBuilder::with_max_decompressed_size`](crate::consumer::dh_consumer::DataHubConsumerBuilder::with_max_decompressed_size).
+
+use async_nats::HeaderMap;
+use bytes::Bytes;
+use thiserror::Error;
+
+/// Name of the NATS header that marks a compressed payload.
+pub const CONTENT_ENCODING_HEADER: &str = "Content-Encoding";
+
+/// Value of the [`CONTENT_ENCODING_HEADER`] for zstd compressed payloads.
+pub const ZSTD_ENCODING: &str = "zstd";
+
+/// Settings for compressing variable change events.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PayloadCompression {
+    /// Events with an encoded size below this number of bytes are sent uncompressed.
+    pub min_size: usize,
+    /// The zstd compression level. Higher levels compress better, but need more CPU time.
+    pub level: i32,
+}
+
+impl Default for PayloadCompression {
+    /// Compresses events of at least 1 KiB with the default zstd level.
+    fn default() -> Self {
+        Self {
+            min_size: 1024,
+            level: 3,
+        }
+    }
+}
+
+/// Error that can occur when decompressing a payload
+#[derive(Error, Debug)]
+#[allow(missing_docs)]
+pub enum PayloadDecompressError {
+    #[error("Unsupported payload encoding '{0}', the `compression` feature may be disabled")]
+    UnsupportedEncoding(String),
+    #[error("Failed to decompress payload: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Decompressed payload exceeds the limit of {0} bytes")]
+    TooLarge(usize),
+}
+
+/// Compresses the payload if compression is enabled and the payload is large enough.
+///
+/// Returns the headers that mark the payload as compressed, or `None` if the payload is unchanged.
+#[cfg_attr(not(feature = "compression"), allow(clippy::needless_pass_by_value))]
+pub(crate) fn compress(
+    compression: Option<PayloadCompression>,
+    payload: Bytes,
+) -> (Option<HeaderMap>, Bytes) {
+    #[cfg(feature = "compression")]
+    if let Some(compression) = compression.filter(|c| payload.len() >= c.min_size) {
+        match zstd::bulk::compress(&payload, compression.level) {
+            Ok(compressed) => {
+                let mut headers = HeaderMap::new();
+                headers.insert(CONTENT_ENCODING_HEADER, ZSTD_ENCODING);
+                return (Some(headers), Bytes::from(compressed));
+            }
+            Err(e) => {
+                tracing::warn!("Failed to compress payload, sending it uncompressed: {e}");
+            }
+        }
+    }
+
+    #[cfg(not(feature = "compression"))]
+    let _ = compression;
+
+    (None, payload)
+}
+
+/// Decompresses the payload according to the [`CONTENT_ENCODING_HEADER`] of the message.
+///
+/// Payloads without the header are returned unchanged.
+/// Fails with [`PayloadDecompressError::TooLarge`] if the decompressed payload would exceed `max_size` bytes.
+pub(crate) fn decompress(
+    headers: Option<&HeaderMap>,
+    payload: Bytes,
+    max_size: usize,
+) -> Result<Bytes, PayloadDecompressError> {
+    let Some(encoding) = headers.and_then(|headers| headers.get(CONTENT_ENCODING_HEADER)) else {
+        return Ok(payload);
+    };
+
+    #[cfg(not(feature = "compression"))]
+    let _ = max_size;
+
+    match encoding.as_str() {
+        #[cfg(feature = "compression")]
+        ZSTD_ENCODING => {
+            use std::io::Read;
+
+            //Read at most one byte more than allowed, so a small payload can not expand to an arbitrary size
+            let limit = u64::try_from(max_size)
+                .unwrap_or(u64::MAX)
+                .saturating_add(1);
+            let mut decompressed = Vec::new();
+            zstd::stream::read::Decoder::new(payload.as_ref())?
+                .take(limit)
+                .read_to_end(&mut decompressed)?;
+
+            if decompressed.len() > max_size {
+                return Err(PayloadDecompressError::TooLarge(max_size));
+            }
+            Ok(Bytes::from(decompressed))
+        }
+        other => Err(PayloadDecompressError::UnsupportedEncoding(
+            other.to_owned(),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_uncompressed_payload() {
+        let payload = Bytes::from_static(&[1, 2, 3]);
+
+        let (headers, compressed) = compress(None, payload.clone());
+        assert!(headers.is_none());
+        assert_eq!(compressed, payload);
+
+        let decompressed = decompress(headers.as_ref(), compressed, 0).unwrap();
+        assert_eq!(decompressed, payload);
+    }
+
+    #[test]
+    fn test_unknown_encoding() {
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_ENCODING_HEADER, "unknown");
+
+        let result = decompress(Some(&headers), Bytes::from_static(&[1, 2, 3]), 1024);
+        assert!(matches!(
+            result,
+            Err(PayloadDecompressError::UnsupportedEncoding(encoding)) if encoding == "unknown"
+        ));
+    }
+
+    #[cfg(feature = "compression")]
+    #[test]
+    fn test_zstd_round_trip() {
+        let payload = Bytes::from(vec![42; 4096]);
+        let compression = PayloadCompression::default();
+
+        let (headers, compressed) = compress(Some(compression), payload.clone());
+        assert!(headers.is_some());
+        assert!(compressed.len() < payload.len());
+
+        let decompressed = decompress(headers.as_ref(), compressed, payload.len()).unwrap();
+        assert_eq!(decompressed, payload);
+    }
+
+    #[cfg(feature = "compression")]
+    #[test]
+    fn test_decompressed_size_is_limited() {
+        let payload = Bytes::from(vec![0; 1024 * 1024]);
+
+        let (headers, compressed) = compress(Some(PayloadCompression::default()), payload.clone());
+        assert!(compressed.len() < 1024);
+
+        let result = decompress(headers.as_ref(), compressed, payload.len() - 1);
+        assert!(matches!(
+            result,
+            Err(PayloadDecompressError::TooLarge(limit)) if limit == payload.len() - 1
+        ));
+    }
+
+    #[cfg(feature = "compression")]
+    #[test]
+    fn test_small_payload_is_not_compressed() {
+        let payload = Bytes::from_static(&[1, 2, 3]);
+
+        let (headers, compressed) = compress(Some(PayloadCompression::default()), payload.clone());
+        assert!(headers.is_none());
+        assert_eq!(compressed, payload);
+    }
+
+    #[cfg(not(feature = "compression"))]
+    #[test]
+    fn test_zstd_without_feature() {
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_ENCODING_HEADER, ZSTD_ENCODING);
+
+        let result = decompress(Some(&headers), Bytes::from_static(&[1, 2, 3]), 1024);
+        assert!(matches!(
+            result,
+            Err(PayloadDecompressError::UnsupportedEncoding(_))
+        ));
+    }
+}
@@ -0,0 +1,36 @@
+// SPDX-FileCopyrightText: 2025 Weidmueller Interface GmbH & Co. KG <oss@weidmueller.com>
+//
+// SPDX-License-Identifier: MIT
+
+//! Convenience re-exports of commonly used items.
+
+/// Helpers for writing integration tests for code built on this crate.
+///
+/// Requires the `test-support` feature and a NATS server running at [`NATS_HOSTNAME`](test::NATS_HOSTNAME).
+///
+/// ```no_run
+/// use u_os_hub_client::prelude::test::{run_with_timeout, DummyProvider, FakeRegistry};
+///
+/// # async fn example() {
+/// run_with_timeout(async {
+///     let _fake_registry = FakeRegistry::new().await;
+///     let _dummy_provider = DummyProvider::new().await.unwrap();
+///
+///     // Connect a consumer to `DUMMY_PROVIDER_ID` and test your code here
+/// })
+/// .await;
+/// # }
+/// ```
+#[cfg(feature = "test-support")]
+pub mod test {
+    pub use crate::test_support::{
+        create_auth_con, create_auth_con_with_perm,
+        dummy_provider::{
+            DummyProvider, PROVIDER_ID as DUMMY_PROVIDER_ID,
+            VARIABLE_UPDATE_RATE as DUMMY_VARIABLE_UPDATE_RATE,
+        },
+        fake_registry::FakeRegistry,
+        mock_provider::{MockProvider, MockProviderBuilder},
+        run_with_timeout, DEFAULT_TIMEOUT, NATS_HOSTNAME,
+    };
+}
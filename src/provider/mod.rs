@@ -130,6 +130,7 @@
 pub mod provider_builder;
 pub mod provider_definition_validator;
 pub mod provider_types;
+pub mod proxy_variable;
 
 pub mod test_data;
 
@@ -138,19 +139,26 @@ pub mod variable_definition_validator;
 mod worker;
 
 pub use provider_builder::ProviderBuilder;
-use provider_builder::UpdateProviderDefinitionError;
+use provider_builder::{UnchangedUpdates, UpdateProviderDefinitionError};
 use provider_definition_validator::InvalidProviderDefinitionError;
-use provider_types::{VariableState, VariableWriteCommand};
+use provider_types::{VariableCapacity, VariableState, VariableWriteCommand};
+use proxy_variable::ProxyVariable;
 use thiserror::Error;
 pub use variable_builder::{VariableBuildError, VariableBuilder};
 
+use std::{sync::Arc, time::Duration};
+
 use async_nats::Message;
 use tokio::sync::{
     mpsc::{self, Receiver, Sender},
-    oneshot,
+    oneshot, watch,
 };
 
-use crate::variable::Variable;
+use crate::{
+    consumer::connected_dh_provider::{self, DataHubProviderConnection},
+    dh_types::{ProviderStatus, VariableDefinition, VariableID},
+    variable::Variable,
+};
 
 /// Commands that are sent to the worker task
 #[derive(Debug)]
@@ -163,22 +171,47 @@ pub(crate) enum ProviderCommand {
         Vec<Variable>,
         oneshot::Sender<Result<(), RemoveVariablesError>>,
     ),
+    /// If set, the policy overrides the one of the provider builder for this update.
     UpdateStates(
         Vec<VariableState>,
+        Option<UnchangedUpdates>,
         oneshot::Sender<Result<(), UpdateVariableValuesError>>,
     ),
     HandleWrite(Message),
     Query(Message),
+    StatusQuery(Message),
     Register,
     Unregister,
+    GetDefinition(oneshot::Sender<Vec<VariableDefinition>>),
+    GetCapacity(oneshot::Sender<VariableCapacity>),
+    GetStates(
+        Vec<VariableID>,
+        oneshot::Sender<Result<Vec<VariableState>, GetVariableStatesError>>,
+    ),
+    PausePublishing(oneshot::Sender<()>),
+    ResumePublishing(oneshot::Sender<Result<(), PublishingControlError>>),
     Subscribe(
         Vec<Variable>,
         oneshot::Sender<
             Result<mpsc::Receiver<Vec<VariableWriteCommand>>, SubscribeToWriteCommandError>,
         >,
     ),
+    SubscribeAll(
+        oneshot::Sender<
+            Result<mpsc::Receiver<Vec<VariableWriteCommand>>, SubscribeToWriteCommandError>,
+        >,
+    ),
+    StopWrites(oneshot::Sender<Vec<mpsc::Sender<Vec<VariableWriteCommand>>>>),
+    SetStatus(ProviderStatus, oneshot::Sender<Result<(), SetStatusError>>),
+    AddAndSubscribe(
+        Vec<Variable>,
+        oneshot::Sender<Result<mpsc::Receiver<Vec<VariableWriteCommand>>, AddAndSubscribeError>>,
+    ),
 }
 
+/// Interval in which [`Provider::drain_writes`] checks whether the write channels are empty.
+const DRAIN_WRITES_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
 /// Error that can occur when adding a variable
 #[derive(Error, Debug)]
 #[allow(missing_docs)]
@@ -219,6 +252,16 @@ pub enum UpdateVariableValuesError {
     VariableNotFound(String),
 }
 
+/// Error that can occur when setting the provider status
+#[derive(Error, Debug)]
+#[allow(missing_docs)]
+pub enum SetStatusError {
+    #[error("The background thread crashed. You need to recreate the provider.")]
+    ProviderThreadCrashed,
+    #[error("Nats error: `{0}`")]
+    NatsError(async_nats::Error),
+}
+
 /// Error that can occur when subscribing to a write command
 #[derive(Error, Debug)]
 #[allow(missing_docs)]
@@ -231,6 +274,103 @@ pub enum SubscribeToWriteCommandError {
     AlreadySubscribed(String),
     #[error("Can't subscribt to write command of read only variabe `{0}`")]
     ReadOnlyVariable(String),
+    #[error("There is already an active write subscription of all variables")]
+    AllVariablesSubscribed,
+}
+
+/// Error that can occur when reading the current provider definition
+#[derive(Error, Debug)]
+#[allow(missing_docs)]
+pub enum CurrentDefinitionError {
+    #[error("The background thread crashed. You need to recreate the provider.")]
+    ProviderThreadCrashed,
+}
+
+/// Error that can occur when reading the number of variables
+#[derive(Error, Debug)]
+#[allow(missing_docs)]
+pub enum VariableCapacityError {
+    #[error("The background thread crashed. You need to recreate the provider.")]
+    ProviderThreadCrashed,
+}
+
+/// Error that can occur when reading the cached variable states
+#[derive(Error, Debug)]
+#[allow(missing_docs)]
+pub enum GetVariableStatesError {
+    #[error("The background thread crashed. You need to recreate the provider.")]
+    ProviderThreadCrashed,
+    #[error("Can't find variable `{0}`")]
+    VariableNotFound(String),
+}
+
+/// Error that can occur when pausing or resuming publishing
+#[derive(Error, Debug)]
+#[allow(missing_docs)]
+pub enum PublishingControlError {
+    #[error("The background thread crashed. You need to recreate the provider.")]
+    ProviderThreadCrashed,
+    #[error("Nats error: `{0}`")]
+    NatsError(async_nats::Error),
+}
+
+/// Error that can occur when adding variables and subscribing to their write commands at once
+#[derive(Error, Debug)]
+#[allow(missing_docs)]
+pub enum AddAndSubscribeError {
+    #[error("The background thread crashed. You need to recreate the provider.")]
+    ProviderThreadCrashed,
+    #[error("{0}")]
+    AddVariables(#[from] AddVariablesError),
+    #[error("{0}")]
+    SubscribeToWriteCommand(#[from] SubscribeToWriteCommandError),
+}
+
+/// Error that can occur when draining the write commands
+#[derive(Error, Debug)]
+#[allow(missing_docs)]
+pub enum DrainWritesError {
+    #[error("The background thread crashed. You need to recreate the provider.")]
+    ProviderThreadCrashed,
+    #[error("Timeout while waiting for the pending write commands to be received")]
+    Timeout,
+}
+
+/// Error that stopped the provider, returned by [`Provider::wait_for_fatal_error`]
+///
+/// Afterwards, all methods of the provider fail with `ProviderThreadCrashed`, so the provider needs to be recreated.
+#[derive(Error, Debug, Clone)]
+pub enum FatalProviderError {
+    /// The background thread stopped without reporting an error, e.g. because it panicked.
+    #[error("The background thread crashed. You need to recreate the provider.")]
+    ProviderThreadCrashed,
+    /// The provider gave up registering according to its
+    /// [`RegistrationFailurePolicy`](provider_builder::RegistrationFailurePolicy).
+    ///
+    /// Contains the error of the last attempt, which allows to distinguish a definition that was rejected by the registry
+    /// ([`UpdateProviderDefinitionError::InvalidProviderDefinition`]) from an unreachable registry
+    /// ([`UpdateProviderDefinitionError::Nats`]).
+    #[error("Registering the provider failed: `{0}`")]
+    RegistrationFailed(Arc<UpdateProviderDefinitionError>),
+    /// The NATS connection was closed, e.g. because it was drained, and is not going to reconnect.
+    #[error("The NATS connection was closed")]
+    ConnectionClosed,
+}
+
+/// Error that can occur when adding a proxy variable
+#[derive(Error, Debug)]
+#[allow(missing_docs)]
+pub enum AddProxyVariableError {
+    #[error("Upstream provider error: `{0}`")]
+    Upstream(#[from] connected_dh_provider::Error),
+    #[error("Can't build the local variable: `{0}`")]
+    VariableBuild(#[from] VariableBuildError),
+    #[error("{0}")]
+    AddVariables(#[from] AddVariablesError),
+    #[error("{0}")]
+    SubscribeToWriteCommand(#[from] SubscribeToWriteCommandError),
+    #[error("The forwarding task crashed.")]
+    ForwardingTaskCrashed,
 }
 
 /// Represents a provider that is registered on the Data Hub registry.
@@ -240,12 +380,34 @@ pub enum SubscribeToWriteCommandError {
 #[derive(Clone)]
 pub struct Provider {
     command_channel: Sender<ProviderCommand>,
+    fatal_error: watch::Receiver<Option<FatalProviderError>>,
 }
 
 impl Provider {
     /// Creates a new Provider
-    pub(crate) fn new(command_channel: Sender<ProviderCommand>) -> Self {
-        Self { command_channel }
+    pub(crate) fn new(
+        command_channel: Sender<ProviderCommand>,
+        fatal_error: watch::Receiver<Option<FatalProviderError>>,
+    ) -> Self {
+        Self {
+            command_channel,
+            fatal_error,
+        }
+    }
+
+    /// Waits until the provider stops because of an error it can't recover from and returns that error.
+    ///
+    /// Temporary problems, e.g. a NATS reconnect, are handled internally and do not resolve this future.
+    /// Once it resolves, all other methods fail with `ProviderThreadCrashed`,
+    /// so this can be used to decide whether and how to rebuild the provider.
+    /// This method is cancel safe and can be called multiple times, e.g. from a supervising task.
+    pub async fn wait_for_fatal_error(&self) -> FatalProviderError {
+        let mut fatal_error = self.fatal_error.clone();
+        let error = match fatal_error.wait_for(Option::is_some).await {
+            Ok(error) => error.clone(),
+            Err(_) => None,
+        };
+        error.unwrap_or(FatalProviderError::ProviderThreadCrashed)
     }
 
     /// Adds variables to the provider.
@@ -294,6 +456,7 @@ impl Provider {
     ///
     /// This will fail if a variable does not currently exist on the provider or the value type does not match the variable type.
     /// Will trigger a publish on the NATS layer which notifies all subscribed consumers.
+    /// Updates that do not really change a variable may be suppressed, see [`ProviderBuilder::unchanged_updates`].
     ///
     /// Note that you can not change a variable definition after adding it to the provider. To do so, remove and re-add the variable.
     pub async fn update_variable_states(
@@ -302,7 +465,7 @@ impl Provider {
     ) -> Result<(), UpdateVariableValuesError> {
         let (tx, rx) = oneshot::channel();
         self.command_channel
-            .send(ProviderCommand::UpdateStates(states, tx))
+            .send(ProviderCommand::UpdateStates(states, None, tx))
             .await
             .map_err(|_| UpdateVariableValuesError::ProviderThreadCrashed)?;
 
@@ -312,10 +475,168 @@ impl Provider {
         }
     }
 
+    /// Same as [`Self::update_variable_states`], but only publishes the variables whose state really changed.
+    ///
+    /// Each state is compared to the state the provider currently holds. If value and quality are equal,
+    /// the variable is not included in the published change event. Timestamp-only changes are published as
+    /// liveness refreshes, unless `ignore_timestamp_only` is set.
+    /// All states are applied nonetheless, so reads return the latest timestamp.
+    ///
+    /// This is useful for polling loops that update all variables periodically, independent of the policy
+    /// configured via [`ProviderBuilder::unchanged_updates`], which applies to all other updates.
+    pub async fn update_variable_states_if_changed(
+        &self,
+        states: Vec<VariableState>,
+        ignore_timestamp_only: bool,
+    ) -> Result<(), UpdateVariableValuesError> {
+        let unchanged_updates = if ignore_timestamp_only {
+            UnchangedUpdates::Suppress
+        } else {
+            UnchangedUpdates::PublishRefreshes
+        };
+
+        let (tx, rx) = oneshot::channel();
+        self.command_channel
+            .send(ProviderCommand::UpdateStates(
+                states,
+                Some(unchanged_updates),
+                tx,
+            ))
+            .await
+            .map_err(|_| UpdateVariableValuesError::ProviderThreadCrashed)?;
+
+        match rx.await {
+            Ok(result) => result,
+            Err(_) => Err(UpdateVariableValuesError::ProviderThreadCrashed),
+        }
+    }
+
+    /// Returns the current states of the variables with the given `ids` in the same order, as stored by the provider.
+    ///
+    /// If `ids` is empty, the states of all variables are returned, sorted by variable ID.
+    /// This avoids keeping a copy of each [`Variable`] just to know its current value, quality and timestamp.
+    /// The states reflect all changes applied via [`Self::update_variable_states`] and [`Self::add_variables`].
+    /// Write commands of consumers are only reflected once the provider applied them.
+    ///
+    /// Fails with [`GetVariableStatesError::VariableNotFound`] if a variable does not currently exist on the provider.
+    pub async fn get_variable_states(
+        &self,
+        ids: Vec<VariableID>,
+    ) -> Result<Vec<VariableState>, GetVariableStatesError> {
+        let (tx, rx) = oneshot::channel();
+        self.command_channel
+            .send(ProviderCommand::GetStates(ids, tx))
+            .await
+            .map_err(|_| GetVariableStatesError::ProviderThreadCrashed)?;
+
+        match rx.await {
+            Ok(result) => result,
+            Err(_) => Err(GetVariableStatesError::ProviderThreadCrashed),
+        }
+    }
+
+    /// Pauses publishing of variable changes until [`Self::resume_publishing`] is called.
+    ///
+    /// While paused, [`Self::update_variable_states`] and [`Self::add_variables`] update the variable states,
+    /// but the changes are not published to subscribed consumers. Read requests are still answered with the current states.
+    /// This is useful for bulk updates that should reach the consumers as one combined change event.
+    ///
+    /// Pausing an already paused provider has no effect.
+    pub async fn pause_publishing(&self) -> Result<(), PublishingControlError> {
+        let (tx, rx) = oneshot::channel();
+        self.command_channel
+            .send(ProviderCommand::PausePublishing(tx))
+            .await
+            .map_err(|_| PublishingControlError::ProviderThreadCrashed)?;
+
+        rx.await
+            .map_err(|_| PublishingControlError::ProviderThreadCrashed)
+    }
+
+    /// Resumes publishing after [`Self::pause_publishing`].
+    ///
+    /// Publishes the latest states of all variables that changed while paused in a single change event.
+    /// Does nothing if publishing is not paused or no variable changed.
+    pub async fn resume_publishing(&self) -> Result<(), PublishingControlError> {
+        let (tx, rx) = oneshot::channel();
+        self.command_channel
+            .send(ProviderCommand::ResumePublishing(tx))
+            .await
+            .map_err(|_| PublishingControlError::ProviderThreadCrashed)?;
+
+        match rx.await {
+            Ok(result) => result,
+            Err(_) => Err(PublishingControlError::ProviderThreadCrashed),
+        }
+    }
+
+    /// Sets the overall status of the provider, e.g. [`ProviderStatus::Maintenance`] during maintenance work.
+    ///
+    /// The status is independent of the quality of the variables and defaults to [`ProviderStatus::Ok`].
+    /// Changes are published to consumers, which receive them as
+    /// [`ProviderEvent::StatusChanged`](crate::consumer::connected_dh_provider::ProviderEvent::StatusChanged)
+    /// and can read the current status via
+    /// [`DataHubProviderConnection::read_provider_status`].
+    ///
+    /// The status is not part of the provider definition, so consumers using other hub clients do not see it.
+    pub async fn set_status(&self, status: ProviderStatus) -> Result<(), SetStatusError> {
+        let (tx, rx) = oneshot::channel();
+        self.command_channel
+            .send(ProviderCommand::SetStatus(status, tx))
+            .await
+            .map_err(|_| SetStatusError::ProviderThreadCrashed)?;
+
+        match rx.await {
+            Ok(result) => result,
+            Err(_) => Err(SetStatusError::ProviderThreadCrashed),
+        }
+    }
+
+    /// Returns the definitions of all variables the provider currently exposes, sorted by variable ID.
+    ///
+    /// This reflects all variables added or removed after registering.
+    pub async fn current_definition(
+        &self,
+    ) -> Result<Vec<VariableDefinition>, CurrentDefinitionError> {
+        let (tx, rx) = oneshot::channel();
+        self.command_channel
+            .send(ProviderCommand::GetDefinition(tx))
+            .await
+            .map_err(|_| CurrentDefinitionError::ProviderThreadCrashed)?;
+
+        rx.await
+            .map_err(|_| CurrentDefinitionError::ProviderThreadCrashed)
+    }
+
+    /// Returns the number of variables the provider currently hosts.
+    ///
+    /// This reflects all variables added or removed after registering.
+    pub async fn variable_count(&self) -> Result<usize, VariableCapacityError> {
+        Ok(self.variable_capacity().await?.count)
+    }
+
+    /// Returns the number of variables the provider currently hosts together with the configured limit,
+    /// see [`ProviderBuilder::max_variables`].
+    ///
+    /// This allows a provider to monitor itself, e.g. to log a warning or refuse adding more variables
+    /// before [`Self::add_variables`] fails with [`InvalidProviderDefinitionError::TooManyVariables`].
+    pub async fn variable_capacity(&self) -> Result<VariableCapacity, VariableCapacityError> {
+        let (tx, rx) = oneshot::channel();
+        self.command_channel
+            .send(ProviderCommand::GetCapacity(tx))
+            .await
+            .map_err(|_| VariableCapacityError::ProviderThreadCrashed)?;
+
+        rx.await
+            .map_err(|_| VariableCapacityError::ProviderThreadCrashed)
+    }
+
     /// Subscribes to the write command of multiple variables.
     ///
     /// Readonly variables will be ignored.
     /// You can only open one subscriber per variable to avoid conflicts.
+    /// Fails with [`SubscribeToWriteCommandError::AllVariablesSubscribed`] while a subscription
+    /// created by [`Self::subscribe_to_all_write_commands`] is active.
     pub async fn subscribe_to_write_command(
         &self,
         variables: Vec<Variable>,
@@ -331,4 +652,114 @@ impl Provider {
             Err(_) => Err(SubscribeToWriteCommandError::ProviderThreadCrashed),
         }
     }
+
+    /// Adds variables to the provider and subscribes to their write commands in a single operation.
+    ///
+    /// In contrast to calling [`Self::add_variables`] and [`Self::subscribe_to_write_command`] one after another,
+    /// there is no window in which a write command for the new variables could arrive before the subscription is ready.
+    /// The subscription is checked for conflicts before adding, so the variables are not added
+    /// if one of them already has an active write subscription.
+    ///
+    /// Like [`Self::subscribe_to_write_command`], write commands for readonly variables are ignored.
+    pub async fn add_and_subscribe(
+        &self,
+        variables: Vec<Variable>,
+    ) -> Result<Receiver<Vec<VariableWriteCommand>>, AddAndSubscribeError> {
+        let (tx, rx) = oneshot::channel();
+        self.command_channel
+            .send(ProviderCommand::AddAndSubscribe(variables, tx))
+            .await
+            .map_err(|_| AddAndSubscribeError::ProviderThreadCrashed)?;
+
+        match rx.await {
+            Ok(result) => result,
+            Err(_) => Err(AddAndSubscribeError::ProviderThreadCrashed),
+        }
+    }
+
+    /// Stops accepting write commands and waits until all pending write commands are received by the application.
+    ///
+    /// Call this on graceful shutdown to process the last write commands before exiting.
+    /// Afterwards, write commands from consumers are ignored, while the provider keeps publishing and answering read requests.
+    /// Write commands that are already queued in the write command receivers are still delivered,
+    /// so keep consuming the receivers from [`Self::subscribe_to_write_command`] while waiting.
+    ///
+    /// Returns [`DrainWritesError::Timeout`] if the receivers still contain write commands after `timeout`.
+    /// Receivers that have been dropped are not waited for.
+    pub async fn drain_writes(&self, timeout: Duration) -> Result<(), DrainWritesError> {
+        let (tx, rx) = oneshot::channel();
+        self.command_channel
+            .send(ProviderCommand::StopWrites(tx))
+            .await
+            .map_err(|_| DrainWritesError::ProviderThreadCrashed)?;
+
+        let senders = rx
+            .await
+            .map_err(|_| DrainWritesError::ProviderThreadCrashed)?;
+
+        tokio::time::timeout(timeout, async {
+            while senders
+                .iter()
+                .any(|sender| !sender.is_closed() && sender.capacity() < sender.max_capacity())
+            {
+                tokio::time::sleep(DRAIN_WRITES_POLL_INTERVAL).await;
+            }
+        })
+        .await
+        .map_err(|_| DrainWritesError::Timeout)
+    }
+
+    /// Subscribes to the write commands of all read write variables of the provider.
+    ///
+    /// The receiver is not bound to the variables that exist now, so read write variables
+    /// that are added later on are covered automatically.
+    ///
+    /// As it covers all variables, this subscription can not be combined with other write subscriptions.
+    /// It fails with [`SubscribeToWriteCommandError::AlreadySubscribed`] if another receiver is subscribed to one of the variables
+    /// and with [`SubscribeToWriteCommandError::AllVariablesSubscribed`] if it is already active.
+    /// While it is active, [`Self::subscribe_to_write_command`] fails with [`SubscribeToWriteCommandError::AllVariablesSubscribed`].
+    /// Drop the receiver to end the subscription.
+    pub async fn subscribe_to_all_write_commands(
+        &self,
+    ) -> Result<Receiver<Vec<VariableWriteCommand>>, SubscribeToWriteCommandError> {
+        let (tx, rx) = oneshot::channel();
+        self.command_channel
+            .send(ProviderCommand::SubscribeAll(tx))
+            .await
+            .map_err(|_| SubscribeToWriteCommandError::ProviderThreadCrashed)?;
+
+        match rx.await {
+            Ok(result) => result,
+            Err(_) => Err(SubscribeToWriteCommandError::ProviderThreadCrashed),
+        }
+    }
+
+    /// Adds a local variable that mirrors the variable `upstream_key` of another provider.
+    ///
+    /// This is useful for gateways that aggregate variables of multiple providers.
+    /// The local variable gets the access type and the current state of the upstream variable.
+    /// Afterwards, upstream value changes are published on the local variable and write commands
+    /// to the local variable are forwarded to the upstream provider.
+    /// The local provider does not update the local value on writes, it waits for the upstream provider to publish the change.
+    ///
+    /// Forwarding writes requires `upstream_conn` to have [`NatsPermission::VariableHubReadWrite`](crate::authenticated_nats_con::NatsPermission::VariableHubReadWrite).
+    /// Failed forwards are logged, as the provider API has no way to report them back to the writing consumer.
+    ///
+    /// Forwarding runs in a background task until the returned [`ProxyVariable`] is dropped.
+    pub async fn add_proxy_variable(
+        &self,
+        local_id: VariableID,
+        local_key: impl Into<String>,
+        upstream_conn: Arc<DataHubProviderConnection>,
+        upstream_key: impl Into<String>,
+    ) -> Result<ProxyVariable, AddProxyVariableError> {
+        proxy_variable::spawn(
+            self.clone(),
+            local_id,
+            local_key.into(),
+            upstream_conn,
+            upstream_key.into(),
+        )
+        .await
+    }
 }
@@ -3,28 +3,220 @@
 // SPDX-License-Identifier: MIT
 
 //! Contains the provider builder which is need to create a provider.
-use std::{collections::BTreeMap, sync::Arc};
+use std::{
+    collections::{BTreeMap, HashSet},
+    sync::Arc,
+    time::Duration,
+};
 
 use thiserror::Error;
-use tracing::{debug, error};
+use tracing::{debug, error, warn};
 
 use crate::{
-    authenticated_nats_con::{AuthenticatedNatsConnection, AuthenticationSettings},
-    dh_types::VariableID,
+    authenticated_nats_con::{
+        AuthenticatedNatsConnection, AuthenticationSettings, InvalidServerAddressError,
+    },
+    dh_types::{
+        TimestampValue, VariableAccessType, VariableDefinition, VariableID, VariableQuality,
+        VariableType, VariableValue,
+    },
     generated::weidmueller::ucontrol::hub::ProviderDefinitionT,
+    payload_codec::{FlatbufferCodec, PayloadCodec},
+    payload_compression::PayloadCompression,
     provider::worker::ProviderWorker,
     variable::Variable,
 };
 
-use super::{provider_definition_validator::InvalidProviderDefinitionError, Provider};
+use super::{
+    provider_definition_validator::{InvalidProviderDefinitionError, ProviderDefinitionLimits},
+    provider_types::VariableState,
+    Provider,
+};
 
 #[cfg(test)]
 mod provider_builder_test;
 
+/// Default of [`ProviderBuilder::with_registration_timeout`].
+pub const DEFAULT_REGISTRATION_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Smallest interval of [`ProviderBuilder::with_heartbeat`], shorter intervals are raised to it.
+pub const MIN_HEARTBEAT_INTERVAL: Duration = Duration::from_millis(1);
+
+/// Defines how the provider reacts if registering its definition on the registry fails.
+///
+/// This applies to the initial registration as well as to the registration after a NATS reconnect.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RegistrationFailurePolicy {
+    /// Retries after waiting for `backoff`, but gives up after `max_attempts` failed attempts in a row.
+    Retry {
+        /// Time to wait before the next attempt
+        backoff: Duration,
+        /// Number of failed attempts in a row after which the provider gives up
+        max_attempts: u32,
+    },
+    /// Gives up after the first failed attempt.
+    Abort,
+}
+
+impl Default for RegistrationFailurePolicy {
+    /// Retries every second, at most 10 times.
+    fn default() -> Self {
+        Self::Retry {
+            backoff: Duration::from_secs(1),
+            max_attempts: 10,
+        }
+    }
+}
+
+/// Defines whether updates are published if the state of a variable did not really change,
+/// see [`ProviderBuilder::unchanged_updates`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum UnchangedUpdates {
+    /// Every update is published, even if value, quality and timestamp are unchanged.
+    #[default]
+    Publish,
+    /// Updates that only change the timestamp are published as liveness refreshes,
+    /// updates that change nothing at all are suppressed.
+    ///
+    /// Consumers can detect refreshes via [`ChangedFields::is_refresh`](crate::consumer::consumer_types::ChangedFields::is_refresh).
+    PublishRefreshes,
+    /// Updates are only published if the value or the quality changed, timestamp-only changes are suppressed as well.
+    Suppress,
+}
+
+impl UnchangedUpdates {
+    /// Returns true if updating a variable from `previous` to `current` should be published.
+    pub(super) fn should_publish(self, previous: &VariableState, current: &VariableState) -> bool {
+        let changed = previous.value != current.value || previous.quality != current.quality;
+        match self {
+            Self::Publish => true,
+            Self::PublishRefreshes => changed || previous.timestamp != current.timestamp,
+            Self::Suppress => changed,
+        }
+    }
+}
+
+/// Defines how [`ProviderBuilder::register_with_backoff`] retries the initial NATS connection.
+///
+/// The delay between two attempts starts at `initial_delay` and doubles after each failed attempt, up to `max_delay`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConnectBackoff {
+    /// Time to wait after the first failed attempt
+    pub initial_delay: Duration,
+    /// Upper bound for the time to wait between two attempts
+    pub max_delay: Duration,
+    /// Number of failed attempts after which the provider gives up, `None` to retry forever
+    pub max_attempts: Option<u32>,
+}
+
+impl Default for ConnectBackoff {
+    /// Retries forever, starting with 500 ms and waiting at most 30 seconds between two attempts.
+    fn default() -> Self {
+        Self {
+            initial_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(30),
+            max_attempts: None,
+        }
+    }
+}
+
+impl ConnectBackoff {
+    /// Returns the time to wait after the given number of failed attempts in a row.
+    pub(super) fn delay(&self, failed_attempts: u32) -> Duration {
+        let factor = 2_u32.saturating_pow(failed_attempts.saturating_sub(1));
+        self.initial_delay
+            .saturating_mul(factor)
+            .min(self.max_delay)
+    }
+}
+
+/// Configuration of the heartbeat variable, see [`ProviderBuilder::with_heartbeat`].
+#[derive(Debug, Clone, Copy)]
+pub(super) struct HeartbeatConfig {
+    pub(super) id: VariableID,
+    pub(super) interval: Duration,
+}
+
+/// Defines how the members of an aggregate variable are combined, see [`ProviderBuilder::add_aggregate`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AggregateOp {
+    /// Sum of all member values
+    Sum,
+    /// Arithmetic mean of all member values
+    Avg,
+    /// Smallest member value
+    Min,
+    /// Largest member value
+    Max,
+}
+
+/// Configuration of an aggregate variable, see [`ProviderBuilder::add_aggregate`].
+#[derive(Debug, Clone)]
+pub(super) struct AggregateConfig {
+    pub(super) id: VariableID,
+    pub(super) prefix: String,
+    pub(super) op: AggregateOp,
+}
+
+impl AggregateConfig {
+    /// Returns true if the variable key is located below the prefix of the aggregate.
+    pub(super) fn contains_key(&self, key: &str) -> bool {
+        key.strip_prefix(self.prefix.as_str())
+            .is_some_and(|rest| rest.starts_with('.'))
+    }
+
+    /// Calculates the aggregate of all numeric variables below the prefix.
+    ///
+    /// Variables in `excluded` and variables with bad quality are ignored.
+    /// Returns `None` if there is no member to aggregate.
+    #[allow(clippy::cast_precision_loss)] // aggregates are always floats
+    pub(super) fn calculate(
+        &self,
+        variables: &BTreeMap<VariableID, Variable>,
+        excluded: &HashSet<VariableID>,
+    ) -> Option<f64> {
+        let mut values = variables
+            .values()
+            .filter(|var| !excluded.contains(&var.definition.id))
+            .filter(|var| var.state.quality != VariableQuality::BadOrUndefined)
+            .filter(|var| self.contains_key(&var.definition.key))
+            .filter_map(|var| match var.state.value {
+                VariableValue::Int(value) => Some(value as f64),
+                VariableValue::Float64(value) => Some(value),
+                _ => None,
+            })
+            .peekable();
+
+        values.peek()?;
+
+        let result = match self.op {
+            AggregateOp::Sum => values.sum(),
+            AggregateOp::Avg => {
+                let (sum, count) =
+                    values.fold((0.0, 0_u32), |(sum, count), value| (sum + value, count + 1));
+                sum / f64::from(count)
+            }
+            AggregateOp::Min => values.fold(f64::INFINITY, f64::min),
+            AggregateOp::Max => values.fold(f64::NEG_INFINITY, f64::max),
+        };
+
+        Some(result)
+    }
+}
+
 /// Used to create a [`Provider`] instance.
 #[derive(Debug, Clone)]
 pub struct ProviderBuilder {
     variables: BTreeMap<u32, Variable>,
+    limits: ProviderDefinitionLimits,
+    provider_id: Option<String>,
+    registration_failure_policy: RegistrationFailurePolicy,
+    payload_codec: Arc<dyn PayloadCodec>,
+    payload_compression: Option<PayloadCompression>,
+    heartbeat: Option<HeartbeatConfig>,
+    aggregates: Vec<AggregateConfig>,
+    unchanged_updates: UnchangedUpdates,
+    registration_timeout: Duration,
 }
 
 impl Default for ProviderBuilder {
@@ -39,15 +231,107 @@ impl ProviderBuilder {
     pub fn new() -> Self {
         Self {
             variables: BTreeMap::new(),
+            limits: ProviderDefinitionLimits::default(),
+            provider_id: None,
+            registration_failure_policy: RegistrationFailurePolicy::default(),
+            payload_codec: Arc::new(FlatbufferCodec),
+            payload_compression: None,
+            heartbeat: None,
+            aggregates: Vec::new(),
+            unchanged_updates: UnchangedUpdates::default(),
+            registration_timeout: DEFAULT_REGISTRATION_TIMEOUT,
         }
     }
 
+    /// Sets the provider id explicitly.
+    ///
+    /// By default, the client name of the [`AuthenticatedNatsConnection`] is used as provider id.
+    /// Setting the id explicitly allows a single connection to host multiple providers under different ids.
+    ///
+    /// Please note that the NATS permissions of a connection are granted for its client name.
+    /// If the provider id differs from the client name, the connection must be granted permissions
+    /// for the subjects of the provider id as well, otherwise registering the provider and
+    /// answering read and write requests will fail.
+    #[must_use]
+    pub fn with_provider_id(mut self, provider_id: impl Into<String>) -> Self {
+        self.provider_id = Some(provider_id.into());
+        self
+    }
+
+    /// Sets how the provider reacts if registering on the registry fails.
+    ///
+    /// Once the provider gives up, it stops working and all methods of the [`Provider`] handle will fail.
+    /// If this happens during [`Self::register`], [`ConnectError::RegistrationFailed`] is returned.
+    /// By default, [`RegistrationFailurePolicy::default`] is used.
+    #[must_use]
+    pub fn registration_failure_policy(mut self, policy: RegistrationFailurePolicy) -> Self {
+        self.registration_failure_policy = policy;
+        self
+    }
+
+    /// Sets how long registering waits for the registry to accept the provider definition.
+    ///
+    /// If the timeout is hit, [`Self::register`] and its variants return [`ConnectError::Timeout`].
+    /// Lower this for deployments where a provider must fail fast if the registry is unreachable.
+    /// [`Duration::ZERO`] waits forever. Defaults to [`DEFAULT_REGISTRATION_TIMEOUT`], i.e. 5 minutes.
+    ///
+    /// The timeout does not include establishing the NATS connection itself.
+    #[must_use]
+    pub fn with_registration_timeout(mut self, registration_timeout: Duration) -> Self {
+        self.registration_timeout = registration_timeout;
+        self
+    }
+
+    /// Sets whether [`Provider::update_variable_states`] publishes updates that do not really change a variable.
+    ///
+    /// Suppressed updates are still applied, so reads return the latest timestamp.
+    /// The heartbeat variable always changes, so it is not affected.
+    /// By default, every update is published, see [`UnchangedUpdates::Publish`].
+    #[must_use]
+    pub fn unchanged_updates(mut self, unchanged_updates: UnchangedUpdates) -> Self {
+        self.unchanged_updates = unchanged_updates;
+        self
+    }
+
+    /// Replaces the codec that is used for the payloads of variable reads, writes and change events.
+    ///
+    /// By default, the [`FlatbufferCodec`] is used. Consumers must use the same codec to communicate with the provider,
+    /// see [`crate::payload_codec`] for details. The provider definition is always sent as flatbuffer.
+    #[must_use]
+    pub fn with_payload_codec(mut self, payload_codec: Arc<dyn PayloadCodec>) -> Self {
+        self.payload_codec = payload_codec;
+        self
+    }
+
+    /// Compresses variable change events that reach the size threshold of `compression`.
+    ///
+    /// This reduces the bandwidth for large variable tables at the cost of CPU time on the provider and all consumers.
+    /// Consumers must enable the `compression` feature to read compressed events,
+    /// see [`crate::payload_compression`] for details. By default, events are not compressed.
+    #[cfg(feature = "compression")]
+    #[must_use]
+    pub fn with_payload_compression(mut self, compression: PayloadCompression) -> Self {
+        self.payload_compression = Some(compression);
+        self
+    }
+
+    /// Limits the number of variables the provider may contain.
+    ///
+    /// This acts as a guardrail against accidentally registering an enormous number of variables.
+    /// The limit is checked by [`Self::add_variables`] and by [`Provider::add_variables`] after registration,
+    /// so it should be set before adding variables. By default, the number of variables is not limited.
+    #[must_use]
+    pub fn max_variables(mut self, max_variables: usize) -> Self {
+        self.limits.max_variables = max_variables;
+        self
+    }
+
     /// Adds multiple variables to the provider builder.
     ///
     /// These variables will be available immediately after the provider is registered.
     /// You can change the variables later by using the [`Provider`] handle after registration.
     pub fn add_variables(mut self, vars: Vec<Variable>) -> Result<Self, AddVariablesError> {
-        validate_var_list(&self.variables, &vars)?;
+        validate_var_list(&self.variables, &vars, self.limits)?;
 
         let new_variables: BTreeMap<VariableID, Variable> = vars
             .into_iter()
@@ -59,11 +343,114 @@ impl ProviderBuilder {
         Ok(self)
     }
 
+    /// Adds a read only heartbeat variable of type [`VariableType::Timestamp`], which the provider
+    /// sets to the current time every `interval`.
+    ///
+    /// Consumers can use it as a liveness signal and detect a dead provider by a stale heartbeat,
+    /// even if the registry did not mark the provider as offline yet.
+    /// The heartbeat stops while the provider is not connected to NATS and continues once it is registered again.
+    /// Like all other changes, heartbeat updates are deferred while publishing is paused, see [`Provider::pause_publishing`].
+    ///
+    /// The variable is validated like the variables of [`Self::add_variables`].
+    /// A provider has at most one heartbeat, calling this again replaces the previous heartbeat variable.
+    /// An `interval` shorter than [`MIN_HEARTBEAT_INTERVAL`], e.g. [`Duration::ZERO`], is raised to it.
+    pub fn with_heartbeat(
+        mut self,
+        id: VariableID,
+        key: impl Into<String>,
+        interval: Duration,
+    ) -> Result<Self, AddVariablesError> {
+        let now = TimestampValue::now();
+        let heartbeat = Variable {
+            definition: VariableDefinition {
+                id,
+                key: key.into(),
+                data_type: VariableType::Timestamp,
+                access_type: VariableAccessType::ReadOnly,
+                experimental: false,
+            },
+            state: VariableState {
+                id,
+                timestamp: Some(now),
+                value: now.into(),
+                quality: VariableQuality::Good,
+            },
+            write_bounds: None,
+        };
+
+        if let Some(previous) = self.heartbeat.take() {
+            self.variables.remove(&previous.id);
+        }
+
+        self = self.add_variables(vec![heartbeat])?;
+        self.heartbeat = Some(HeartbeatConfig {
+            id,
+            //tokio panics on an interval of zero
+            interval: interval.max(MIN_HEARTBEAT_INTERVAL),
+        });
+
+        Ok(self)
+    }
+
+    /// Adds a read only variable of type [`VariableType::Float64`], which the provider keeps set to the
+    /// sum, average, minimum or maximum of all numeric variables below `prefix`.
+    ///
+    /// Members are all variables of type [`VariableType::Int64`] or [`VariableType::Float64`] whose key starts with
+    /// `prefix` followed by a folder separator, so `"machine"` matches `"machine.motor.power"`, but not `"machine2.power"`.
+    /// The membership is evaluated on every recomputation, so variables that are added later are included automatically.
+    /// Members with [`VariableQuality::BadOrUndefined`] and other aggregate variables are ignored.
+    ///
+    /// The aggregate is recomputed and published whenever a member changes or variables are added or removed.
+    /// If there is no member, the value is kept and the quality is set to [`VariableQuality::BadOrUndefined`].
+    /// Values set for the aggregate via [`Provider::update_variable_states`] are overwritten by the next recomputation.
+    ///
+    /// The variable is validated like the variables of [`Self::add_variables`].
+    pub fn add_aggregate(
+        mut self,
+        id: VariableID,
+        key: impl Into<String>,
+        prefix: impl Into<String>,
+        op: AggregateOp,
+    ) -> Result<Self, AddVariablesError> {
+        let aggregate = Variable {
+            definition: VariableDefinition {
+                id,
+                key: key.into(),
+                data_type: VariableType::Float64,
+                access_type: VariableAccessType::ReadOnly,
+                experimental: false,
+            },
+            state: VariableState {
+                id,
+                timestamp: Some(TimestampValue::now()),
+                value: VariableValue::Float64(0.0),
+                quality: VariableQuality::BadOrUndefined,
+            },
+            write_bounds: None,
+        };
+
+        self = self.add_variables(vec![aggregate])?;
+        self.aggregates.push(AggregateConfig {
+            id,
+            prefix: prefix.into(),
+            op,
+        });
+
+        Ok(self)
+    }
+
     /// Registers the provider on the registry, using the provided nats server address and authentication settings.
     ///
     /// Returns a [`Provider`] handle which can be used to add/remove and modify variables even after the provider has been registered.
     ///
     /// This will create a new [`AuthenticatedNatsConnection`] internally and use it to register the provider.
+    ///
+    /// Registering overwrites a definition that is already stored under the provider id, so a service that restarts
+    /// without unregistering before, e.g. after a crash, simply replaces its stale definition.
+    /// However, the provider id is not locked. If another live process holds the same id, its definition is replaced as well,
+    /// but it is not notified about this. It keeps answering read and write requests for the id
+    /// and re-publishes its own definition if the registry restarts, so the registered definition is whichever was published last.
+    /// Provider ids must therefore be unique among running processes.
     pub async fn register(
         self,
         nats_server_address: impl Into<String>,
@@ -75,6 +462,53 @@ impl ProviderBuilder {
         self.register_with_existing_connection(auth_nats_con).await
     }
 
+    /// Same as [`Self::register()`], but retries the NATS connection according to `backoff` until it succeeds.
+    ///
+    /// This allows a provider to start before the Data Hub is ready, e.g. during system boot.
+    /// Fails immediately if the server address is invalid, as retrying can't fix that.
+    /// If [`ConnectBackoff::max_attempts`] is reached, the error of the last attempt is returned.
+    ///
+    /// Only the connection is retried. Registering on the registry afterwards follows the [`RegistrationFailurePolicy`].
+    pub async fn register_with_backoff(
+        self,
+        nats_server_address: impl Into<String>,
+        auth_settings: &AuthenticationSettings,
+        backoff: ConnectBackoff,
+    ) -> Result<Provider, ConnectError> {
+        let nats_server_address = nats_server_address.into();
+        let mut failed_attempts = 0_u32;
+
+        let auth_nats_con = loop {
+            match AuthenticatedNatsConnection::new(nats_server_address.as_str(), auth_settings)
+                .await
+            {
+                Ok(auth_nats_con) => break auth_nats_con,
+                Err(e) if e.is::<InvalidServerAddressError>() => return Err(e.into()),
+                Err(e) => {
+                    failed_attempts = failed_attempts.saturating_add(1);
+                    if backoff
+                        .max_attempts
+                        .is_some_and(|max_attempts| failed_attempts >= max_attempts)
+                    {
+                        error!(
+                            "Giving up connecting to NATS server `{nats_server_address}` after {failed_attempts} attempts: {e}"
+                        );
+                        return Err(e.into());
+                    }
+
+                    let delay = backoff.delay(failed_attempts);
+                    warn!(
+                        "Failed to connect to NATS server `{nats_server_address}` (attempt {failed_attempts}), retrying in {delay:?}: {e}"
+                    );
+                    tokio::time::sleep(delay).await;
+                }
+            }
+        };
+
+        self.register_with_existing_connection(Arc::new(auth_nats_con))
+            .await
+    }
+
     /// Same as [`Self::register()`], but uses an existing connection.
     ///
     /// This is useful if you want to use the same [`AuthenticatedNatsConnection`] for multiple clients.
@@ -86,18 +520,36 @@ impl ProviderBuilder {
             "Register `{}` variables at creation time",
             self.variables.len()
         );
-        let control_tx = ProviderWorker::new(nats_con, self.variables, true).await?;
+        let provider_id = self
+            .provider_id
+            .unwrap_or_else(|| nats_con.get_client_name().to_owned());
+        let (control_tx, fatal_error) = ProviderWorker::new(
+            nats_con,
+            provider_id,
+            self.variables,
+            self.limits,
+            self.registration_failure_policy,
+            self.payload_codec,
+            self.payload_compression,
+            self.heartbeat,
+            self.aggregates,
+            self.unchanged_updates,
+            true,
+            self.registration_timeout,
+        )
+        .await?;
 
-        Ok(Provider::new(control_tx))
+        Ok(Provider::new(control_tx, fatal_error))
     }
 }
 
 /// Checks if a potential merge of [`existing_variables`] and [`new_variables`] would still be a valid provider definition.
 ///
-/// Uses the original registry validator to validate the merged variable list.
+/// Uses the original registry validator to validate the merged variable list against the specified `limits`.
 pub(crate) fn validate_var_list(
     existing_variables: &BTreeMap<u32, Variable>,
     new_variables: &[Variable],
+    limits: ProviderDefinitionLimits,
 ) -> Result<(), InvalidProviderDefinitionError> {
     let merged_var_list = [
         new_variables
@@ -118,7 +570,7 @@ pub(crate) fn validate_var_list(
         ..Default::default() //Other fields are irrelevant for validation
     };
 
-    tmp_provider_def.validate()
+    tmp_provider_def.validate_with_limits(limits)
 }
 
 /// Error that can occur when adding a variable.
@@ -141,9 +593,12 @@ pub enum ConnectError {
     /// Indicates an error with the registration at the registry
     #[error("Error while sending the provider definition: `{0}`")]
     UpdateProviderDefinition(UpdateProviderDefinitionError),
-    /// Indicates that a connection to the data hub timed out after 5 minutes
-    #[error("Connection to the data hub timed out after 5 minutes")]
+    /// Indicates that the registration timed out, see [`ProviderBuilder::with_registration_timeout`]
+    #[error("Connection to the data hub timed out")]
     Timeout,
+    /// Indicates that the provider gave up registering according to its [`RegistrationFailurePolicy`]
+    #[error("Registering the provider failed, see log for details")]
+    RegistrationFailed,
 }
 
 /// Error that can occur when updating a provider definition to the registry.
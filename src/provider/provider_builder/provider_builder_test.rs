@@ -2,12 +2,26 @@
 //
 // SPDX-License-Identifier: MIT
 
-use crate::provider::{
-    provider_builder::AddVariablesError,
-    provider_definition_validator::InvalidProviderDefinitionError, VariableBuilder,
+use std::{collections::HashSet, time::Duration};
+
+use rstest::rstest;
+
+use crate::{
+    dh_types::{
+        DurationValue, TimestampValue, VariableAccessType, VariableQuality, VariableType,
+        VariableValue,
+    },
+    provider::{
+        provider_builder::AddVariablesError,
+        provider_definition_validator::InvalidProviderDefinitionError,
+        provider_types::VariableState, VariableBuilder,
+    },
 };
 
-use super::ProviderBuilder;
+use super::{
+    AggregateConfig, AggregateOp, ConnectBackoff, ProviderBuilder, UnchangedUpdates,
+    MIN_HEARTBEAT_INTERVAL,
+};
 
 #[test]
 fn test_add_variables() {
@@ -150,3 +164,235 @@ fn test_duplicated_variable_names_2() {
         )
     );
 }
+
+#[test]
+fn test_max_variables() {
+    // Prepare
+    let provider = ProviderBuilder::new().max_variables(1);
+
+    let var1 = VariableBuilder::new(0, "test_var_1")
+        .initial_value(true)
+        .build()
+        .expect("the variable should build");
+
+    let var2 = VariableBuilder::new(1, "test_var_2")
+        .initial_value(true)
+        .build()
+        .expect("the variable should build");
+
+    // Act
+    let error = provider
+        .add_variables(vec![var1])
+        .expect("this should work")
+        .add_variables(vec![var2])
+        .unwrap_err();
+
+    // Assert
+    assert_eq!(
+        error,
+        AddVariablesError::InvalidMergedVariableList(
+            InvalidProviderDefinitionError::TooManyVariables(1)
+        )
+    );
+}
+
+#[test]
+fn test_with_heartbeat() {
+    // Prepare
+    let provider = ProviderBuilder::new();
+
+    // Act
+    let provider = provider
+        .with_heartbeat(0, "heartbeat_1", Duration::from_secs(1))
+        .expect("the heartbeat should be added")
+        .with_heartbeat(1, "heartbeat_2", Duration::from_secs(1))
+        .expect("the heartbeat should be replaced");
+
+    // Assert
+    assert_eq!(provider.variables.len(), 1);
+    let heartbeat = provider
+        .variables
+        .get(&1)
+        .expect("the heartbeat should exist");
+    assert_eq!(heartbeat.definition.data_type, VariableType::Timestamp);
+    assert_eq!(
+        heartbeat.definition.access_type,
+        VariableAccessType::ReadOnly
+    );
+}
+
+#[test]
+fn test_with_heartbeat_zero_interval() {
+    // Act
+    let provider = ProviderBuilder::new()
+        .with_heartbeat(0, "heartbeat", Duration::ZERO)
+        .expect("the heartbeat should be added");
+
+    // Assert
+    let heartbeat = provider.heartbeat.expect("the heartbeat should exist");
+    assert_eq!(heartbeat.interval, MIN_HEARTBEAT_INTERVAL);
+}
+
+#[test]
+fn test_with_heartbeat_invalid_key() {
+    // Act
+    let error = ProviderBuilder::new()
+        .with_heartbeat(0, "invalid key", Duration::from_secs(1))
+        .unwrap_err();
+
+    // Assert
+    assert!(matches!(
+        error,
+        AddVariablesError::InvalidMergedVariableList(
+            InvalidProviderDefinitionError::InvalidVariableDefinition(_)
+        )
+    ));
+}
+
+#[test]
+fn test_add_aggregate() {
+    // Act
+    let provider = ProviderBuilder::new()
+        .add_aggregate(0, "machine.total", "machine", AggregateOp::Sum)
+        .expect("the aggregate should be added");
+
+    // Assert
+    assert_eq!(provider.aggregates.len(), 1);
+    let aggregate = provider
+        .variables
+        .get(&0)
+        .expect("the aggregate should exist");
+    assert_eq!(aggregate.definition.data_type, VariableType::Float64);
+    assert_eq!(
+        aggregate.definition.access_type,
+        VariableAccessType::ReadOnly
+    );
+    assert_eq!(aggregate.state.quality, VariableQuality::BadOrUndefined);
+}
+
+#[rstest]
+#[case(AggregateOp::Sum, Some(6.5))]
+#[case(AggregateOp::Avg, Some(6.5 / 3.0))]
+#[case(AggregateOp::Min, Some(-1.0))]
+#[case(AggregateOp::Max, Some(5.5))]
+fn test_aggregate_calculate(#[case] op: AggregateOp, #[case] expected: Option<f64>) {
+    // Prepare
+    let provider = ProviderBuilder::new()
+        .add_variables(vec![
+            VariableBuilder::new(1, "machine.a")
+                .initial_value(2)
+                .build()
+                .expect("the variable should build"),
+            VariableBuilder::new(2, "machine.sub.b")
+                .initial_value(5.5)
+                .build()
+                .expect("the variable should build"),
+            VariableBuilder::new(3, "machine.c")
+                .initial_value(-1.0)
+                .build()
+                .expect("the variable should build"),
+            // not numeric
+            VariableBuilder::new(4, "machine.name")
+                .initial_value("name")
+                .build()
+                .expect("the variable should build"),
+            // bad quality
+            VariableBuilder::new(5, "machine.broken")
+                .initial_value(100)
+                .initial_quality(VariableQuality::BadOrUndefined)
+                .build()
+                .expect("the variable should build"),
+            // not below the prefix
+            VariableBuilder::new(6, "machine2.a")
+                .initial_value(100)
+                .build()
+                .expect("the variable should build"),
+            // excluded
+            VariableBuilder::new(7, "machine.total")
+                .initial_value(100.0)
+                .build()
+                .expect("the variable should build"),
+        ])
+        .expect("the variables should be added");
+
+    let aggregate = AggregateConfig {
+        id: 7,
+        prefix: "machine".to_string(),
+        op,
+    };
+
+    // Act
+    let result = aggregate.calculate(&provider.variables, &HashSet::from([7]));
+
+    // Assert
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn test_aggregate_without_members() {
+    // Prepare
+    let provider = ProviderBuilder::new()
+        .add_variables(vec![VariableBuilder::new(1, "other.a")
+            .initial_value(VariableValue::Int(1))
+            .build()
+            .expect("the variable should build")])
+        .expect("the variables should be added");
+
+    let aggregate = AggregateConfig {
+        id: 0,
+        prefix: "machine".to_string(),
+        op: AggregateOp::Sum,
+    };
+
+    // Act
+    let result = aggregate.calculate(&provider.variables, &HashSet::new());
+
+    // Assert
+    assert_eq!(result, None);
+}
+
+#[test]
+fn test_connect_backoff_delay() {
+    let backoff = ConnectBackoff {
+        initial_delay: Duration::from_millis(100),
+        max_delay: Duration::from_secs(1),
+        max_attempts: None,
+    };
+
+    assert_eq!(backoff.delay(1), Duration::from_millis(100));
+    assert_eq!(backoff.delay(2), Duration::from_millis(200));
+    assert_eq!(backoff.delay(4), Duration::from_millis(800));
+    assert_eq!(backoff.delay(5), Duration::from_secs(1));
+    assert_eq!(backoff.delay(u32::MAX), Duration::from_secs(1));
+}
+
+#[rstest]
+#[case::publish_no_op(UnchangedUpdates::Publish, 1, 0, true)]
+#[case::refreshes_no_op(UnchangedUpdates::PublishRefreshes, 1, 0, false)]
+#[case::refreshes_timestamp(UnchangedUpdates::PublishRefreshes, 1, 1, true)]
+#[case::refreshes_value(UnchangedUpdates::PublishRefreshes, 2, 0, true)]
+#[case::suppress_timestamp(UnchangedUpdates::Suppress, 1, 1, false)]
+#[case::suppress_value(UnchangedUpdates::Suppress, 2, 1, true)]
+fn test_unchanged_updates(
+    #[case] unchanged_updates: UnchangedUpdates,
+    #[case] value: i64,
+    #[case] timestamp_offset_secs: i64,
+    #[case] expected: bool,
+) {
+    let previous = VariableState {
+        id: 0,
+        timestamp: Some(TimestampValue::UNIX_EPOCH),
+        value: VariableValue::Int(1),
+        quality: VariableQuality::Good,
+    };
+    let current = VariableState {
+        timestamp: Some(TimestampValue::UNIX_EPOCH + DurationValue::seconds(timestamp_offset_secs)),
+        value: VariableValue::Int(value),
+        ..previous.clone()
+    };
+
+    assert_eq!(
+        unchanged_updates.should_publish(&previous, &current),
+        expected
+    );
+}
@@ -30,6 +30,28 @@ pub enum InvalidProviderDefinitionError {
     /// Found a duplicate id in the provider definition.
     #[error("The id `{0}` is duplicated")]
     DuplicateId(u32),
+    /// The provider definition contains more variables than allowed by the limits.
+    /// Contains the configured maximum number of variables.
+    #[error("The provider definition exceeds the maximum of `{0}` variables")]
+    TooManyVariables(usize),
+}
+
+/// Configurable limits that are checked when validating a provider definition.
+///
+/// Providers set them via [`ProviderBuilder::max_variables`](crate::provider::ProviderBuilder::max_variables).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProviderDefinitionLimits {
+    /// The maximum number of variables a provider definition may contain.
+    pub max_variables: usize,
+}
+
+impl Default for ProviderDefinitionLimits {
+    /// By default, no limits are enforced.
+    fn default() -> Self {
+        Self {
+            max_variables: usize::MAX,
+        }
+    }
 }
 
 /// Block extends the generated flatbuffers structure '`ProviderDefinitionT`' with a validate function.
@@ -42,7 +64,21 @@ impl ProviderDefinitionT {
     /// - all variable names are unique (in the provider definition)
     /// - all variables are added to a folder and not to another variable
     pub fn validate(&self) -> Result<(), InvalidProviderDefinitionError> {
+        self.validate_with_limits(ProviderDefinitionLimits::default())
+    }
+
+    /// Same as [`Self::validate`], but additionally checks the provider definition against the specified limits.
+    pub fn validate_with_limits(
+        &self,
+        limits: ProviderDefinitionLimits,
+    ) -> Result<(), InvalidProviderDefinitionError> {
         if let Some(variable_definitions) = &self.variable_definitions {
+            if variable_definitions.len() > limits.max_variables {
+                return Err(InvalidProviderDefinitionError::TooManyVariables(
+                    limits.max_variables,
+                ));
+            }
+
             let mut preverified_paths = HashSet::with_capacity(variable_definitions.len());
             let mut preverified_ids = HashSet::with_capacity(variable_definitions.len());
 
@@ -68,3 +68,20 @@ fn test_performance() {
     //Currently only takes about 100ms on my local hardware.
     assert!(duration.as_secs() < 2, "Validation took too long");
 }
+
+#[rstest]
+#[case::below_limit(8, Ok(()))]
+#[case::at_limit(7, Ok(()))]
+#[case::above_limit(6, Err(InvalidProviderDefinitionError::TooManyVariables(6)))]
+fn test_validate_with_limits(
+    #[case] max_variables: usize,
+    #[case] expected_result: Result<(), InvalidProviderDefinitionError>,
+) {
+    let provider_definition = test_data::valid_provider_definition_with_variables();
+    let limits = ProviderDefinitionLimits { max_variables };
+
+    assert_eq!(
+        provider_definition.validate_with_limits(limits),
+        expected_result
+    );
+}
@@ -5,7 +5,12 @@
 //! Collection of types that are used in the provider side of the library.
 //! These types abstract the low level flatbuffer types and provide a more user-friendly interface.
 
-use crate::dh_types::{self, TimestampValue, VariableID, VariableQuality, VariableValue};
+use std::cmp::Ordering;
+
+use crate::{
+    dh_types::{self, TimestampValue, VariableID, VariableQuality, VariableValue},
+    generated::weidmueller::ucontrol::hub::VariableT,
+};
 
 /// Errors for data hub type conversions
 //TODO: how to handle type conversion errors? should that really cause an error, or do we want to have placeholder values?
@@ -99,6 +104,26 @@ impl VariableState {
     pub fn get_id(&self) -> VariableID {
         self.id
     }
+
+    /// Converts the state into the low level flatbuffer type.
+    ///
+    /// This is useful for advanced users that build change events or write commands manually.
+    /// Naming the returned type requires the `export-low-level-api` feature.
+    #[must_use]
+    pub fn to_variable_t(&self) -> VariableT {
+        self.into()
+    }
+}
+
+impl From<&VariableState> for VariableT {
+    fn from(state: &VariableState) -> Self {
+        VariableT {
+            quality: state.quality.into(),
+            id: state.id,
+            value: (&state.value).into(),
+            timestamp: state.timestamp.map(Into::into),
+        }
+    }
 }
 
 /// Represents a variable write command from a consumer.
@@ -112,3 +137,97 @@ pub struct VariableWriteCommand {
     /// The requested new value of the variable.
     pub value: VariableValue,
 }
+
+/// Number of variables a provider currently hosts, compared to its configured limit.
+///
+/// Returned by [`Provider::variable_capacity`](crate::provider::Provider::variable_capacity).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VariableCapacity {
+    /// The number of variables the provider currently hosts.
+    pub count: usize,
+    /// The configured maximum number of variables, see [`ProviderBuilder::max_variables`](crate::provider::ProviderBuilder::max_variables).
+    ///
+    /// Is `usize::MAX` if the number of variables is not limited.
+    pub max_variables: usize,
+}
+
+impl VariableCapacity {
+    /// Returns how many variables can still be added before the limit is reached.
+    #[must_use]
+    pub fn remaining(&self) -> usize {
+        self.max_variables.saturating_sub(self.count)
+    }
+
+    /// Returns the share of the limit that is in use, between `0.0` and `1.0`.
+    ///
+    /// Useful to log a warning before the limit is reached, e.g. above `0.9`.
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)] // Precision is irrelevant for a utilization ratio
+    pub fn utilization(&self) -> f64 {
+        if self.max_variables == 0 {
+            return 1.0;
+        }
+        (self.count as f64 / self.max_variables as f64).min(1.0)
+    }
+}
+
+/// Determines how a provider handles write commands with values outside the [`WriteBounds`] of a variable.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OutOfBoundsWrite {
+    /// The write command is dropped, so the application never receives it.
+    #[default]
+    Reject,
+    /// The value is clamped to the nearest bound before the write command is forwarded to the application.
+    Clamp,
+}
+
+/// Inclusive value range of a numeric variable, which the provider enforces on write commands.
+///
+/// Set via [`VariableBuilder::min`](crate::provider::VariableBuilder::min) and
+/// [`VariableBuilder::max`](crate::provider::VariableBuilder::max).
+/// The bounds are local to the provider and not part of the variable definition, so consumers can't see them.
+/// Values set by the provider itself are not checked.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WriteBounds {
+    /// The smallest valid value, if any. Has the same type as the variable.
+    pub min: Option<VariableValue>,
+    /// The largest valid value, if any. Has the same type as the variable.
+    pub max: Option<VariableValue>,
+    /// How write commands outside the bounds are handled.
+    pub out_of_bounds: OutOfBoundsWrite,
+}
+
+impl WriteBounds {
+    /// Returns the value that should be forwarded to the application, or `None` if the write must be rejected.
+    ///
+    /// Values that can't be compared with the bounds, e.g. `NaN`, are always rejected.
+    pub(crate) fn apply(&self, value: VariableValue) -> Option<VariableValue> {
+        let mut value = value;
+
+        for (bound, violation) in [(&self.min, Ordering::Less), (&self.max, Ordering::Greater)] {
+            let Some(bound) = bound else {
+                continue;
+            };
+
+            if compare_numeric(&value, bound)? == violation {
+                match self.out_of_bounds {
+                    OutOfBoundsWrite::Reject => return None,
+                    OutOfBoundsWrite::Clamp => value = bound.clone(),
+                }
+            }
+        }
+
+        Some(value)
+    }
+}
+
+/// Compares two numeric values of the same type.
+///
+/// Returns `None` for different or non numeric types and for `NaN`.
+pub(crate) fn compare_numeric(value: &VariableValue, other: &VariableValue) -> Option<Ordering> {
+    match (value, other) {
+        (VariableValue::Int(value), VariableValue::Int(other)) => Some(value.cmp(other)),
+        (VariableValue::Float64(value), VariableValue::Float64(other)) => value.partial_cmp(other),
+        _ => None,
+    }
+}
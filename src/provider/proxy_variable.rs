@@ -0,0 +1,210 @@
+// SPDX-FileCopyrightText: 2025 Weidmueller Interface GmbH & Co. KG <oss@weidmueller.com>
+//
+// SPDX-License-Identifier: MIT
+
+//! Contains the [`ProxyVariable`] handle and the background task that mirrors an upstream variable.
+//! Proxy variables are created via [`Provider::add_proxy_variable`].
+
+use std::sync::Arc;
+
+use futures::StreamExt;
+use tokio::{
+    sync::{mpsc::Receiver, oneshot},
+    task::JoinHandle,
+};
+use tracing::{error, warn};
+
+use crate::{
+    consumer::connected_dh_provider::DataHubProviderConnection,
+    dh_types::{VariableAccessType, VariableID},
+    variable::Variable,
+};
+
+use super::{
+    provider_types::VariableWriteCommand, AddProxyVariableError, Provider, VariableBuilder,
+};
+
+/// Handle of a local variable that mirrors a variable of an upstream provider.
+///
+/// Value changes of the upstream variable are published on the local variable
+/// and write commands to the local variable are forwarded to the upstream provider.
+///
+/// Dropping the handle stops the forwarding, but does not remove the local variable from the provider.
+#[derive(Debug)]
+pub struct ProxyVariable {
+    variable: Variable,
+    forwarding_task: JoinHandle<()>,
+}
+
+impl ProxyVariable {
+    /// Returns the local variable as it was added to the provider.
+    ///
+    /// The state is not updated while forwarding, it only contains the initial upstream state.
+    #[must_use]
+    pub fn get_variable(&self) -> &Variable {
+        &self.variable
+    }
+}
+
+/// Used to stop the forwarding task
+impl Drop for ProxyVariable {
+    fn drop(&mut self) {
+        self.forwarding_task.abort();
+    }
+}
+
+/// Spawns the forwarding task and waits until the local variable has been added to the provider.
+pub(super) async fn spawn(
+    provider: Provider,
+    local_id: VariableID,
+    local_key: String,
+    upstream_conn: Arc<DataHubProviderConnection>,
+    upstream_key: String,
+) -> Result<ProxyVariable, AddProxyVariableError> {
+    let (setup_tx, setup_rx) = oneshot::channel();
+
+    let forwarding_task = tokio::spawn(forwarding_loop(
+        provider,
+        local_id,
+        local_key,
+        upstream_conn,
+        upstream_key,
+        setup_tx,
+    ));
+
+    match setup_rx.await {
+        Ok(Ok(variable)) => Ok(ProxyVariable {
+            variable,
+            forwarding_task,
+        }),
+        Ok(Err(e)) => Err(e),
+        Err(_) => Err(AddProxyVariableError::ForwardingTaskCrashed),
+    }
+}
+
+/// Sets up the local variable and forwards upstream changes and local write commands until the provider is dropped.
+///
+/// The task owns the upstream key, so that the upstream subscription can borrow it.
+async fn forwarding_loop(
+    provider: Provider,
+    local_id: VariableID,
+    local_key: String,
+    upstream_conn: Arc<DataHubProviderConnection>,
+    upstream_key: String,
+    setup_tx: oneshot::Sender<Result<Variable, AddProxyVariableError>>,
+) {
+    //Subscribe before reading the initial state, so no upstream change gets lost
+    let upstream_states = match upstream_conn
+        .subscribe_single_variable(upstream_key.as_str())
+        .await
+    {
+        Ok(upstream_states) => upstream_states,
+        Err(e) => {
+            let _ = setup_tx.send(Err(e.into()));
+            return;
+        }
+    };
+    let mut upstream_states = Box::pin(upstream_states);
+
+    let (mut variable, mut write_commands) = match setup_local_variable(
+        &provider,
+        local_id,
+        local_key,
+        &upstream_conn,
+        &upstream_key,
+    )
+    .await
+    {
+        Ok(setup) => setup,
+        Err(e) => {
+            let _ = setup_tx.send(Err(e));
+            return;
+        }
+    };
+
+    if setup_tx.send(Ok(variable.clone())).is_err() {
+        //Caller is gone, nobody can stop us later
+        return;
+    }
+
+    loop {
+        tokio::select! {
+            upstream_state = upstream_states.next() => {
+                let Some(upstream_state) = upstream_state else {
+                    warn!("Upstream subscription of proxy variable `{upstream_key}` ended");
+                    break;
+                };
+
+                let local_state = variable.get_mut_state();
+                local_state.set_all(
+                    upstream_state.value,
+                    upstream_state.quality,
+                    Some(upstream_state.timestamp),
+                );
+
+                if let Err(e) = provider.update_variable_states(vec![local_state.clone()]).await {
+                    error!("Failed to update proxy variable `{upstream_key}`: {e}");
+                }
+            },
+            commands = next_write_commands(write_commands.as_mut()) => {
+                let Some(commands) = commands else {
+                    //Provider is gone
+                    break;
+                };
+
+                for command in commands {
+                    if let Err(e) = upstream_conn
+                        .write_single_variable(upstream_key.as_str(), command.value)
+                        .await
+                    {
+                        warn!("Failed to forward write command to upstream variable `{upstream_key}`: {e}");
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Builds the local variable from the upstream definition and state, adds it to the provider
+/// and subscribes to its write commands if it is writable.
+async fn setup_local_variable(
+    provider: &Provider,
+    local_id: VariableID,
+    local_key: String,
+    upstream_conn: &DataHubProviderConnection,
+    upstream_key: &str,
+) -> Result<(Variable, Option<Receiver<Vec<VariableWriteCommand>>>), AddProxyVariableError> {
+    let upstream_definition = upstream_conn.get_variable_definition(upstream_key)?;
+    let upstream_state = upstream_conn.read_single_variable(upstream_key).await?;
+
+    let variable = VariableBuilder::new(local_id, local_key)
+        .access_type(upstream_definition.access_type)
+        .initial_value(upstream_state.value)
+        .initial_quality(upstream_state.quality)
+        .initial_timestamp(Some(upstream_state.timestamp))
+        .build()?;
+
+    provider.add_variables(vec![variable.clone()]).await?;
+
+    let write_commands = if upstream_definition.access_type == VariableAccessType::ReadWrite {
+        Some(
+            provider
+                .subscribe_to_write_command(vec![variable.clone()])
+                .await?,
+        )
+    } else {
+        None
+    };
+
+    Ok((variable, write_commands))
+}
+
+/// Waits for the next write commands, or forever if the variable is not writable.
+async fn next_write_commands(
+    write_commands: Option<&mut Receiver<Vec<VariableWriteCommand>>>,
+) -> Option<Vec<VariableWriteCommand>> {
+    match write_commands {
+        Some(write_commands) => write_commands.recv().await,
+        None => std::future::pending().await,
+    }
+}
@@ -4,6 +4,8 @@
 
 //! Contains the variable builder. It's a helper to create variables.
 
+use std::cmp::Ordering;
+
 use thiserror::Error;
 
 use crate::{
@@ -11,11 +13,11 @@ use crate::{
         TimestampValue, VariableAccessType, VariableDefinition, VariableID, VariableQuality,
         VariableType, VariableValue,
     },
-    provider::provider_types::VariableState,
+    provider::provider_types::{compare_numeric, OutOfBoundsWrite, VariableState, WriteBounds},
     variable::Variable,
 };
 
-use super::variable_definition_validator::validate_variable_key;
+use super::variable_definition_validator::{validate_variable_key, InvalidVariableDefinitionError};
 
 #[cfg(test)]
 mod variable_builder_test;
@@ -29,6 +31,10 @@ pub struct VariableBuilder {
     id: u32,
     access_type: VariableAccessType,
     experimental: bool,
+    //write validation
+    min: Option<VariableValue>,
+    max: Option<VariableValue>,
+    out_of_bounds: OutOfBoundsWrite,
     //state
     value: Option<VariableValue>,
     quality: VariableQuality,
@@ -46,12 +52,23 @@ impl VariableBuilder {
             key: key.into(),
             access_type: VariableAccessType::ReadOnly,
             experimental: false,
+            min: None,
+            max: None,
+            out_of_bounds: OutOfBoundsWrite::Reject,
             value: None,
             quality: VariableQuality::Good,
             override_timestamp: None,
         }
     }
 
+    /// Checks if `key` is a valid variable key, without building a variable.
+    ///
+    /// This is useful to validate user input up front, e.g. to show inline errors in a configuration form.
+    /// [`Self::build`] performs the same check. See [`validate_variable_key`] for details.
+    pub fn is_valid_key(key: &str) -> Result<(), InvalidVariableDefinitionError> {
+        validate_variable_key(key)
+    }
+
     /// Changes the [`VariableAccessType`] of the variable.
     ///
     /// This determines how the variable can be accessed by consumers.
@@ -73,6 +90,36 @@ impl VariableBuilder {
         self
     }
 
+    /// Sets the smallest value that consumers may write (optional)
+    ///
+    /// Only allowed for variables of type [`VariableType::Int64`] or [`VariableType::Float64`],
+    /// and the bound must have the same type as the variable.
+    /// The provider checks write commands against the bounds before forwarding them to the application,
+    /// see [`Self::out_of_bounds_writes`] for how violations are handled.
+    #[must_use]
+    pub fn min(mut self, value: impl Into<VariableValue>) -> Self {
+        self.min = Some(value.into());
+        self
+    }
+
+    /// Sets the largest value that consumers may write (optional)
+    ///
+    /// See [`Self::min`] for details.
+    #[must_use]
+    pub fn max(mut self, value: impl Into<VariableValue>) -> Self {
+        self.max = Some(value.into());
+        self
+    }
+
+    /// Sets how write commands outside of [`Self::min`] and [`Self::max`] are handled.
+    ///
+    /// This is optional. By default, such write commands are rejected, see [`OutOfBoundsWrite`].
+    #[must_use]
+    pub fn out_of_bounds_writes(mut self, mode: OutOfBoundsWrite) -> Self {
+        self.out_of_bounds = mode;
+        self
+    }
+
     /// Sets the initial value of the variable.
     ///
     /// You must set a value before calling [`Self::build`].
@@ -127,11 +174,15 @@ impl VariableBuilder {
         }
 
         if let Some(value) = self.value {
+            let data_type = Self::infer_variable_type_from_value(&value)?;
+            let write_bounds =
+                Self::build_write_bounds(data_type, self.min, self.max, self.out_of_bounds)?;
+
             Ok(Variable {
                 definition: VariableDefinition {
                     key: self.key,
                     id: self.id,
-                    data_type: Self::infer_variable_type_from_value(&value)?,
+                    data_type,
                     access_type: self.access_type,
                     experimental: self.experimental,
                 },
@@ -145,24 +196,51 @@ impl VariableBuilder {
                         Some(TimestampValue::now())
                     },
                 },
+                write_bounds,
             })
         } else {
             Err(VariableBuildError::MissingValue)
         }
     }
 
+    fn build_write_bounds(
+        data_type: VariableType,
+        min: Option<VariableValue>,
+        max: Option<VariableValue>,
+        out_of_bounds: OutOfBoundsWrite,
+    ) -> Result<Option<WriteBounds>, VariableBuildError> {
+        if min.is_none() && max.is_none() {
+            return Ok(None);
+        }
+
+        if !matches!(data_type, VariableType::Int64 | VariableType::Float64) {
+            return Err(VariableBuildError::InvalidBounds);
+        }
+
+        //Comparing a bound with itself checks the type and rejects NaN
+        for bound in min.iter().chain(&max) {
+            if compare_numeric(bound, bound).is_none() || bound.value_type() != Some(data_type) {
+                return Err(VariableBuildError::InvalidBounds);
+            }
+        }
+
+        if let (Some(min), Some(max)) = (&min, &max) {
+            if compare_numeric(min, max) == Some(Ordering::Greater) {
+                return Err(VariableBuildError::InvalidBounds);
+            }
+        }
+
+        Ok(Some(WriteBounds {
+            min,
+            max,
+            out_of_bounds,
+        }))
+    }
+
     fn infer_variable_type_from_value(
         value: &VariableValue,
     ) -> Result<VariableType, VariableBuildError> {
-        match value {
-            VariableValue::Unknown => Err(VariableBuildError::InvalidValue),
-            VariableValue::Int(_) => Ok(VariableType::Int64),
-            VariableValue::Float64(_) => Ok(VariableType::Float64),
-            VariableValue::String(_) => Ok(VariableType::String),
-            VariableValue::Boolean(_) => Ok(VariableType::Boolean),
-            VariableValue::Timestamp(_) => Ok(VariableType::Timestamp),
-            VariableValue::Duration(_) => Ok(VariableType::Duration),
-        }
+        value.value_type().ok_or(VariableBuildError::InvalidValue)
     }
 }
 
@@ -179,4 +257,8 @@ pub enum VariableBuildError {
     InvalidValue,
     #[error("Invalid access type")]
     InvalidAccessType,
+    #[error(
+        "Invalid bounds, they must match the numeric variable type and min must not exceed max"
+    )]
+    InvalidBounds,
 }
@@ -4,7 +4,14 @@
 
 use rstest::rstest;
 
-use crate::{dh_types::VariableAccessType, provider::VariableBuildError};
+use crate::{
+    dh_types::{VariableAccessType, VariableQuality, VariableValue},
+    provider::{
+        provider_types::OutOfBoundsWrite,
+        variable_definition_validator::InvalidVariableDefinitionError, VariableBuildError,
+    },
+    variable::Variable,
+};
 
 use super::VariableBuilder;
 
@@ -32,6 +39,74 @@ fn test_invalid_access_type_error() {
     assert_eq!(result, Err(VariableBuildError::InvalidAccessType));
 }
 
+#[test]
+fn test_write_bounds() {
+    // Prepare
+    let reject = VariableBuilder::new(0, "reject")
+        .access_type(VariableAccessType::ReadWrite)
+        .initial_value(5)
+        .min(0)
+        .max(10)
+        .build()
+        .expect("variable should build");
+    let clamp = VariableBuilder::new(1, "clamp")
+        .access_type(VariableAccessType::ReadWrite)
+        .initial_value(0.5)
+        .min(0.0)
+        .out_of_bounds_writes(OutOfBoundsWrite::Clamp)
+        .build()
+        .expect("variable should build");
+    let reject = reject.get_write_bounds().expect("bounds should be set");
+    let clamp = clamp.get_write_bounds().expect("bounds should be set");
+
+    // Act & Assert
+    assert_eq!(
+        reject.apply(VariableValue::Int(10)),
+        Some(VariableValue::Int(10))
+    );
+    assert_eq!(reject.apply(VariableValue::Int(11)), None);
+    assert_eq!(reject.apply(VariableValue::Int(-1)), None);
+    assert_eq!(
+        clamp.apply(VariableValue::Float64(-1.0)),
+        Some(VariableValue::Float64(0.0))
+    );
+    assert_eq!(
+        clamp.apply(VariableValue::Float64(1e9)),
+        Some(VariableValue::Float64(1e9))
+    );
+    assert_eq!(clamp.apply(VariableValue::Float64(f64::NAN)), None);
+}
+
+#[rstest]
+#[case(VariableBuilder::new(0, "my_var").initial_value("text").min(0))]
+#[case(VariableBuilder::new(0, "my_var").initial_value(0).min(0.0))]
+#[case(VariableBuilder::new(0, "my_var").initial_value(0.0).max(f64::NAN))]
+#[case(VariableBuilder::new(0, "my_var").initial_value(0).min(10).max(0))]
+fn test_invalid_write_bounds(#[case] builder: VariableBuilder) {
+    // Act
+    let result = builder.build();
+
+    // Assert
+    assert_eq!(result, Err(VariableBuildError::InvalidBounds));
+}
+
+#[test]
+fn test_variable_builder_entry_and_accessors() {
+    // Prepare
+    let my_var = Variable::builder(7, "my_var")
+        .initial_value(42)
+        .initial_quality(VariableQuality::Uncertain);
+
+    // Act
+    let result = my_var.build().expect("variable should build");
+
+    // Assert
+    assert_eq!(result.id(), 7);
+    assert_eq!(result.key(), "my_var");
+    assert_eq!(result.value(), &VariableValue::Int(42));
+    assert_eq!(result.quality(), VariableQuality::Uncertain);
+}
+
 #[rstest]
 #[case("Test", true)]
 #[case("teSt", true)]
@@ -77,3 +152,17 @@ fn test_key_validation(#[case] key: String, #[case] valid: bool) {
         }
     }
 }
+
+#[rstest]
+#[case::valid("my_folder.my_var", Ok(()))]
+#[case::empty("", Err(InvalidVariableDefinitionError::UnnamedVariable))]
+#[case::trailing_dot(
+    "my_folder.",
+    Err(InvalidVariableDefinitionError::TrailingDot("my_folder.".to_string()))
+)]
+fn test_is_valid_key(
+    #[case] key: &str,
+    #[case] expected: Result<(), InvalidVariableDefinitionError>,
+) {
+    assert_eq!(VariableBuilder::is_valid_key(key), expected);
+}
@@ -35,6 +35,13 @@ pub enum InvalidVariableDefinitionError {
     InvalidLength(String),
 }
 
+/// The maximum length of a variable key in characters.
+///
+/// Checked by the variable builder, the provider and the uOS Data Hub registry.
+/// Consumers drop received variables with longer keys, see
+/// [`DataHubProviderConnection::get_all_variable_definitions`](crate::consumer::connected_dh_provider::DataHubProviderConnection::get_all_variable_definitions).
+pub const MAX_VARIABLE_KEY_LENGTH: usize = 1023;
+
 /// The regex pattern used to validate variable keys.
 /// All variable keys must match this pattern to be valid.
 ///
@@ -68,7 +75,7 @@ pub fn validate_variable_key(key: &str) -> Result<(), InvalidVariableDefinitionE
     }
 
     //Note: this is O(n), but should be acceptable as the key is usually short
-    if key.chars().count() > 1023 {
+    if key.chars().count() > MAX_VARIABLE_KEY_LENGTH {
         return Err(InvalidVariableDefinitionError::InvalidLength(
             key.to_string(),
         ));
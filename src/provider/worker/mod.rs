@@ -11,56 +11,77 @@
 //! For example, the current values and the last change to the values.
 //! It automatically responds to read requests.
 use std::{
-    collections::{BTreeMap, HashSet},
+    collections::{BTreeMap, BTreeSet, HashSet},
     sync::Arc,
+    time::Duration,
 };
 
-use async_nats::{Event, Message, Subscriber};
+use async_nats::{Event, HeaderMap, Message, Subject, Subscriber};
+use bytes::Bytes;
 use futures::StreamExt;
 
 use tokio::{
     select,
     sync::{
         broadcast::{self, error::RecvError},
-        mpsc,
+        mpsc, watch,
     },
-    time::timeout,
+    time::{timeout, Interval, MissedTickBehavior},
 };
-use tracing::{debug, error, info, trace};
+use tracing::{debug, error, info, trace, warn};
 
 use crate::{
     authenticated_nats_con::AuthenticatedNatsConnection,
-    dh_types::{VariableAccessType, VariableID, VariableValue},
+    consumer::connected_nats_provider::{
+        WriteAck, READ_VALUES_ONLY_HEADER, WRITE_INVALID_COMMAND_HEADER,
+    },
+    dh_types::{
+        ProviderStatus, TimestampValue, VariableAccessType, VariableID, VariableQuality,
+        VariableType, VariableValue,
+    },
     generated::weidmueller::ucontrol::hub::{
-        ProviderDefinitionChangedEvent, ProviderDefinitionState, ProviderDefinitionT,
-        ReadVariablesQueryRequest, WriteVariablesCommand,
+        ProviderDefinitionChangedEvent, ProviderDefinitionState, ProviderDefinitionT, VariableT,
     },
     nats_subjects,
     payload_builders::{
-        build_provider_definition_changed_event, build_read_variables_query_response,
-        build_variables_changed_event,
+        build_provider_definition_changed_event, create_read_variables_query_response,
+        create_variables_changed_event, omit_timestamps_and_qualities,
     },
+    payload_codec::PayloadCodec,
+    payload_compression::{self, PayloadCompression},
     variable::{calc_variables_hash, Variable},
 };
 
 use super::{
-    provider_builder::{validate_var_list, ConnectError, UpdateProviderDefinitionError},
-    provider_types::{VariableState, VariableWriteCommand},
-    AddVariablesError, ProviderCommand, RemoveVariablesError, SubscribeToWriteCommandError,
-    UpdateVariableValuesError,
+    provider_builder::{
+        validate_var_list, AggregateConfig, ConnectError, HeartbeatConfig,
+        RegistrationFailurePolicy, UnchangedUpdates, UpdateProviderDefinitionError,
+    },
+    provider_definition_validator::ProviderDefinitionLimits,
+    provider_types::{VariableCapacity, VariableState, VariableWriteCommand},
+    AddAndSubscribeError, AddVariablesError, FatalProviderError, GetVariableStatesError,
+    ProviderCommand, PublishingControlError, RemoveVariablesError, SetStatusError,
+    SubscribeToWriteCommandError, UpdateVariableValuesError,
 };
 
+#[cfg(test)]
+mod worker_test;
+
 /// The state of the provider worker.
 #[derive(Debug, Copy, Clone)]
 pub enum State {
     Connecting,
     Registering,
     Running,
+    /// Registering failed permanently according to the [`RegistrationFailurePolicy`], the worker stops.
+    Failed,
 }
 
 #[derive(Debug)]
 pub(super) struct ProviderWorker {
     nats_con: Arc<AuthenticatedNatsConnection>,
+    /// The id under which the provider is registered, used for all subjects.
+    provider_id: String,
     state: State,
     state_changed_sender: tokio::sync::broadcast::Sender<State>,
     /// The receiver of the commands from the [`crate::provider::Provider`] instances.
@@ -68,13 +89,41 @@ pub(super) struct ProviderWorker {
     /// Stores the variables with fast access.
     /// A `BTreeMap` is used instead of a `HashMap` because it is always deterministic.
     variables: BTreeMap<VariableID, Variable>,
+    /// Limits that are checked when variables are added.
+    limits: ProviderDefinitionLimits,
+    registration_failure_policy: RegistrationFailurePolicy,
+    /// Encodes and decodes the variable payloads.
+    payload_codec: Arc<dyn PayloadCodec>,
+    /// If set, large variable change events are compressed.
+    payload_compression: Option<PayloadCompression>,
+    /// The heartbeat variable that is updated periodically while running.
+    heartbeat: Option<HeartbeatConfig>,
+    /// The aggregate variables that are recomputed whenever one of their members changes.
+    aggregates: Vec<AggregateConfig>,
+    /// Decides whether updates that do not really change a variable are published.
+    unchanged_updates: UnchangedUpdates,
+    /// Number of failed registration attempts in a row.
+    registration_failures: u32,
+    /// Reports the error that stopped the worker to the [`crate::provider::Provider`] instances.
+    fatal_error_sender: watch::Sender<Option<FatalProviderError>>,
     /// The current fingerprint of the provider definition.
     current_fingerprint: u64,
+    /// Set while publishing is paused, contains the IDs of all variables that changed in the meantime.
+    paused_changes: Option<BTreeSet<VariableID>>,
+    /// Cleared by [`ProviderCommand::StopWrites`], afterwards all write commands are ignored.
+    accept_writes: bool,
     /// Stores the senders for the write commands.
     /// Each sender will be notified about write commands for it's subscribed variable ids.
     /// A variable can't be mapped to multiple senders to avoid conflicts.
+    /// The notifiers are kept across reconnects, as the NATS client restores the write subscription by itself.
     write_event_notiers: Vec<(HashSet<VariableID>, mpsc::Sender<Vec<VariableWriteCommand>>)>,
+    /// Receives the write commands of all variables, see [`ProviderCommand::SubscribeAll`].
+    /// Can't be combined with the notifiers of specific variables.
+    all_write_event_notifier: Option<mpsc::Sender<Vec<VariableWriteCommand>>>,
+    /// The status set via [`ProviderCommand::SetStatus`].
+    status: ProviderStatus,
     query_subscription: Subscriber,
+    status_query_subscription: Subscriber,
     write_subscription: Subscriber,
     registry_up: Subscriber,
 }
@@ -82,22 +131,41 @@ pub(super) struct ProviderWorker {
 impl ProviderWorker {
     /// Creates a worker task and registers the provider.
     #[allow(clippy::new_ret_no_self)] // we return the control, because this runs in a separate thread
+    #[allow(clippy::too_many_arguments)] // only called by the provider builder
     pub(super) async fn new(
         nats_con: Arc<AuthenticatedNatsConnection>,
+        provider_id: String,
         variables: BTreeMap<VariableID, Variable>,
+        limits: ProviderDefinitionLimits,
+        registration_failure_policy: RegistrationFailurePolicy,
+        payload_codec: Arc<dyn PayloadCodec>,
+        payload_compression: Option<PayloadCompression>,
+        heartbeat: Option<HeartbeatConfig>,
+        aggregates: Vec<AggregateConfig>,
+        unchanged_updates: UnchangedUpdates,
         wait_for_success: bool,
-    ) -> Result<mpsc::Sender<ProviderCommand>, ConnectError> {
+        registration_timeout: Duration,
+    ) -> Result<
+        (
+            mpsc::Sender<ProviderCommand>,
+            watch::Receiver<Option<FatalProviderError>>,
+        ),
+        ConnectError,
+    > {
         let client = nats_con.get_client();
-        let provider_id = nats_con.get_client_name();
         let nats_events = nats_con.get_events();
 
         let (tx, rx) = mpsc::channel(100);
         let query_subscription = client
-            .subscribe(nats_subjects::read_variables_query(provider_id))
+            .subscribe(nats_subjects::read_variables_query(&provider_id))
             .await
             .map_err(|x| ConnectError::Nats(Box::new(x)))?;
         let write_subscription = client
-            .subscribe(nats_subjects::write_variables_command(provider_id))
+            .subscribe(nats_subjects::write_variables_command(&provider_id))
+            .await
+            .map_err(|x| ConnectError::Nats(Box::new(x)))?;
+        let status_query_subscription = client
+            .subscribe(nats_subjects::provider_status_read_query(&provider_id))
             .await
             .map_err(|x| ConnectError::Nats(Box::new(x)))?;
         let registry_up = client
@@ -106,26 +174,43 @@ impl ProviderWorker {
             .map_err(|x| ConnectError::Nats(Box::new(x)))?;
 
         let (state_sender, mut state_receiver) = tokio::sync::broadcast::channel(1);
+        let (fatal_error_sender, fatal_error_receiver) = watch::channel(None);
 
         //Calculate initial FP. The FP will change each time the variables in the definition change.
         let current_fingerprint = calc_variables_hash(&variables);
 
         let mut created = ProviderWorker {
             nats_con,
+            provider_id,
             state: State::Connecting,
             state_changed_sender: state_sender,
             command_channel: rx,
             variables,
+            limits,
+            registration_failure_policy,
+            payload_codec,
+            payload_compression,
+            heartbeat,
+            aggregates,
+            unchanged_updates,
+            registration_failures: 0,
+            fatal_error_sender,
             current_fingerprint,
+            paused_changes: None,
+            accept_writes: true,
             write_event_notiers: vec![],
+            all_write_event_notifier: None,
+            status: ProviderStatus::Ok,
             query_subscription,
+            status_query_subscription,
             write_subscription,
             registry_up,
         };
 
-        if created.nats_con.get_client().connection_state()
-            == async_nats::connection::State::Connected
-        {
+        // The initial values are part of the registration, so they don't need to be published
+        created.recompute_aggregates(None);
+
+        if created.nats_con.is_connected() {
             created.enter_state(State::Registering);
         } else {
             created.enter_state(State::Connecting);
@@ -134,19 +219,40 @@ impl ProviderWorker {
         tokio::spawn(async move { created.run(nats_events).await });
 
         if wait_for_success {
-            // Wait until the provider is registered or timeout after 5 minutes
-            timeout(tokio::time::Duration::from_secs(300), async {
+            // Wait until the provider is registered
+            let registered = async {
                 loop {
-                    if let Ok(State::Running) = state_receiver.recv().await {
-                        break;
+                    match state_receiver.recv().await {
+                        Ok(State::Running) => return Ok(()),
+                        Ok(State::Failed) | Err(RecvError::Closed) => {
+                            return Err(ConnectError::RegistrationFailed)
+                        }
+                        _ => {}
                     }
                 }
-            })
-            .await
-            .map_err(|_| ConnectError::Timeout)?;
+            };
+
+            // A zero timeout means waiting forever
+            if registration_timeout.is_zero() {
+                registered.await?;
+            } else {
+                timeout(registration_timeout, registered)
+                    .await
+                    .map_err(|_| ConnectError::Timeout)??;
+            }
         }
 
-        Ok(tx)
+        Ok((tx, fatal_error_receiver))
+    }
+
+    /// Reports `error` to the provider handles and stops the worker.
+    fn fail(&mut self, error: FatalProviderError) {
+        error!(
+            "u-OS Data Hub provider `{}` stopped: {error}",
+            self.get_provider_id()
+        );
+        self.fatal_error_sender.send_replace(Some(error));
+        self.enter_state(State::Failed);
     }
 
     /// Changes the state of the provider.
@@ -161,7 +267,7 @@ impl ProviderWorker {
     }
 
     fn get_provider_id(&self) -> &str {
-        self.nats_con.get_client_name()
+        &self.provider_id
     }
 
     /// Sends an empty provider definition to the registry to unregister the provider.
@@ -250,6 +356,13 @@ impl ProviderWorker {
     ///
     /// It handles the different state events and can be interrupted by nats events.
     async fn run(mut self, mut nats_events: broadcast::Receiver<Event>) {
+        let mut heartbeat_timer = self.heartbeat.map(|heartbeat| {
+            let mut timer = tokio::time::interval(heartbeat.interval);
+            // Ticks are only consumed while running, so skip the ones that were missed in the meantime
+            timer.set_missed_tick_behavior(MissedTickBehavior::Skip);
+            timer
+        });
+
         while !self.command_channel.is_closed() {
             match &self.state {
                 State::Connecting => {
@@ -271,8 +384,13 @@ impl ProviderWorker {
                             }
                         },
                         msg = nats_events.recv() => self.handle_nats_event(msg),
+                        () = next_heartbeat(&mut heartbeat_timer) => self.update_heartbeat().await,
                     };
                 }
+                State::Failed => {
+                    // Dropping the command channel lets all provider handles fail
+                    break;
+                }
             }
         }
     }
@@ -301,7 +419,8 @@ impl ProviderWorker {
                 debug!("Client Error: {:?}", error);
             }
             Ok(Event::Closed) => {
-                debug!("NATS connection closed");
+                // A closed connection is never reconnected, so the provider can't recover
+                self.fail(FatalProviderError::ConnectionClosed);
             }
             Ok(Event::Draining) => {
                 debug!("NATS connection draining");
@@ -318,22 +437,33 @@ impl ProviderWorker {
     ///
     /// `run_state_registering()` must be safe to abort on every "await" (by select)
     async fn run_state_registering(&mut self) {
-        let result = self
-            .update_definition(true)
-            .await
-            .map_err(ConnectError::UpdateProviderDefinition);
-
-        if let Err(e) = result {
-            // TODO: refactor the error handling, should this be returned on connect? Is this even possible because of the further checks?
+        if let Err(e) = self.update_definition(true).await {
+            self.registration_failures += 1;
             error!(
-                "u-OS Data Hub provider `{}` failed to register: {:?}",
+                "u-OS Data Hub provider `{}` failed to register (attempt {}): {:?}",
                 self.get_provider_id(),
+                self.registration_failures,
                 e
             );
 
+            match self.registration_failure_policy {
+                RegistrationFailurePolicy::Retry {
+                    backoff,
+                    max_attempts,
+                } if self.registration_failures < max_attempts => {
+                    // Sleeping is safe to abort, the next attempt starts when this state is run again
+                    tokio::time::sleep(backoff).await;
+                }
+                _ => {
+                    self.fail(FatalProviderError::RegistrationFailed(Arc::new(e)));
+                }
+            }
+
             return;
         }
 
+        self.registration_failures = 0;
+
         info!(
             "u-OS Data Hub provider `{}` successfully registered",
             self.get_provider_id()
@@ -360,6 +490,9 @@ impl ProviderWorker {
             Some(msg) = self.write_subscription.next() => {
                 ProviderCommand::HandleWrite(msg)
             }
+            Some(msg) = self.status_query_subscription.next() => {
+                ProviderCommand::StatusQuery(msg)
+            }
             _ = self.registry_up.next() => {
                 ProviderCommand::Register
             }
@@ -382,14 +515,26 @@ impl ProviderWorker {
             ProviderCommand::RemoveVariables(vars, result_tx) => {
                 result_tx.send(self.remove_variables(vars, true).await).ok();
             }
-            ProviderCommand::UpdateStates(states, result_tx) => {
+            ProviderCommand::UpdateStates(states, unchanged_updates, result_tx) => {
                 result_tx
-                    .send(self.update_variable_states(states).await)
+                    .send(
+                        self.update_variable_states(
+                            states,
+                            unchanged_updates.unwrap_or(self.unchanged_updates),
+                        )
+                        .await,
+                    )
                     .ok();
             }
             ProviderCommand::Query(msg) => {
                 self.handle_variable_read_query(msg).await;
             }
+            ProviderCommand::StatusQuery(msg) => {
+                self.handle_status_read_query(msg).await;
+            }
+            ProviderCommand::SetStatus(status, result_tx) => {
+                result_tx.send(self.set_status(status).await).ok();
+            }
             ProviderCommand::Register => {
                 // This will be called on registry UP event.
                 // This can only fail on a nats error (e.g Permissions violation)
@@ -399,26 +544,96 @@ impl ProviderWorker {
             ProviderCommand::Unregister => {
                 self.send_empty_definition().await?;
             }
+            ProviderCommand::GetDefinition(result_tx) => {
+                result_tx
+                    .send(
+                        self.variables
+                            .values()
+                            .map(|var| var.definition.clone())
+                            .collect(),
+                    )
+                    .ok();
+            }
+            ProviderCommand::GetCapacity(result_tx) => {
+                result_tx
+                    .send(VariableCapacity {
+                        count: self.variables.len(),
+                        max_variables: self.limits.max_variables,
+                    })
+                    .ok();
+            }
+            ProviderCommand::GetStates(ids, result_tx) => {
+                result_tx.send(self.get_variable_states(&ids)).ok();
+            }
+            ProviderCommand::PausePublishing(result_tx) => {
+                self.paused_changes.get_or_insert_with(BTreeSet::new);
+                result_tx.send(()).ok();
+            }
+            ProviderCommand::ResumePublishing(result_tx) => {
+                result_tx.send(self.resume_publishing().await).ok();
+            }
             ProviderCommand::Subscribe(vars, result_tx) => {
                 result_tx.send(self.create_write_event_notifier(&vars)).ok();
             }
             ProviderCommand::HandleWrite(msg) => {
                 self.handle_write(msg).await;
             }
+            ProviderCommand::AddAndSubscribe(vars, result_tx) => {
+                result_tx.send(self.add_and_subscribe(vars).await).ok();
+            }
+            ProviderCommand::SubscribeAll(result_tx) => {
+                result_tx.send(self.create_all_write_event_notifier()).ok();
+            }
+            ProviderCommand::StopWrites(result_tx) => {
+                self.accept_writes = false;
+                self.write_event_notiers
+                    .retain(|(_, sender)| !sender.is_closed());
+                result_tx
+                    .send(
+                        self.write_event_notiers
+                            .iter()
+                            .map(|(_, sender)| sender)
+                            .chain(&self.all_write_event_notifier)
+                            .cloned()
+                            .collect(),
+                    )
+                    .ok();
+            }
         }
 
         Ok(())
     }
 
+    /// Returns the states of the variables with the given ids, or of all variables if `ids` is empty.
+    fn get_variable_states(
+        &self,
+        ids: &[VariableID],
+    ) -> Result<Vec<VariableState>, GetVariableStatesError> {
+        if ids.is_empty() {
+            return Ok(self
+                .variables
+                .values()
+                .map(|var| var.state.clone())
+                .collect());
+        }
+
+        ids.iter()
+            .map(|id| {
+                self.variables
+                    .get(id)
+                    .map(|var| var.state.clone())
+                    .ok_or_else(|| {
+                        GetVariableStatesError::VariableNotFound(format!("Variable with ID {id}"))
+                    })
+            })
+            .collect()
+    }
+
     /// Creates a new write event notifier
     fn create_write_event_notifier(
         &mut self,
         variables: &[Variable],
     ) -> Result<mpsc::Receiver<Vec<VariableWriteCommand>>, SubscribeToWriteCommandError> {
-        // First remove closed channels
-        self.write_event_notiers
-            .retain(|(_, sender)| !sender.is_closed());
-
         for variable in variables {
             // Check if all ids exists
             if !self.variables.contains_key(&variable.definition.id) {
@@ -426,9 +641,33 @@ impl ProviderWorker {
                     variable.definition.key.to_string(),
                 ));
             }
+        }
+        self.check_write_event_notifier_conflicts(variables)?;
+
+        // Create the write event notifier
+        let (tx, rx) = mpsc::channel(100);
+
+        let variable_ids = variables.iter().map(|v| v.definition.id).collect();
+        self.write_event_notiers.push((variable_ids, tx));
+        Ok(rx)
+    }
+
+    /// Checks that none of the variables already has a write event notifier.
+    fn check_write_event_notifier_conflicts(
+        &mut self,
+        variables: &[Variable],
+    ) -> Result<(), SubscribeToWriteCommandError> {
+        if self.has_all_write_event_notifier() {
+            return Err(super::SubscribeToWriteCommandError::AllVariablesSubscribed);
+        }
 
-            // TODO: Could we move this to compile time?
-            // Check if a write event notifier for any variable still exists (avoid conflicts)
+        // First remove closed channels
+        self.write_event_notiers
+            .retain(|(_, sender)| !sender.is_closed());
+
+        // TODO: Could we move this to compile time?
+        // Check if a write event notifier for any variable still exists (avoid conflicts)
+        for variable in variables {
             for (ids, _) in &self.write_event_notiers {
                 if ids.contains(&variable.definition.id) {
                     return Err(super::SubscribeToWriteCommandError::AlreadySubscribed(
@@ -437,89 +676,204 @@ impl ProviderWorker {
                 }
             }
         }
-        // Create the write event notifier
-        let (tx, rx) = mpsc::channel(100);
 
-        let variable_ids = variables.iter().map(|v| v.definition.id).collect();
-        self.write_event_notiers.push((variable_ids, tx));
+        Ok(())
+    }
+
+    /// Returns true if the write event notifier for all variables is still open.
+    fn has_all_write_event_notifier(&mut self) -> bool {
+        if self
+            .all_write_event_notifier
+            .as_ref()
+            .is_some_and(mpsc::Sender::is_closed)
+        {
+            self.all_write_event_notifier = None;
+        }
+        self.all_write_event_notifier.is_some()
+    }
+
+    /// Creates the write event notifier for all variables, including variables that are added later on.
+    fn create_all_write_event_notifier(
+        &mut self,
+    ) -> Result<mpsc::Receiver<Vec<VariableWriteCommand>>, SubscribeToWriteCommandError> {
+        if self.has_all_write_event_notifier() {
+            return Err(super::SubscribeToWriteCommandError::AllVariablesSubscribed);
+        }
+
+        // Notifiers of removed variables don't conflict
+        self.write_event_notiers
+            .retain(|(_, sender)| !sender.is_closed());
+        let subscribed_variable = self
+            .write_event_notiers
+            .iter()
+            .flat_map(|(ids, _)| ids)
+            .find_map(|id| self.variables.get(id));
+        if let Some(variable) = subscribed_variable {
+            return Err(super::SubscribeToWriteCommandError::AlreadySubscribed(
+                variable.definition.key.clone(),
+            ));
+        }
+
+        let (tx, rx) = mpsc::channel(100);
+        self.all_write_event_notifier = Some(tx);
         Ok(rx)
     }
 
+    /// Adds the variables and creates a write event notifier for them.
+    ///
+    /// Write commands are handled by this worker as well, so none can be processed in between.
+    async fn add_and_subscribe(
+        &mut self,
+        vars: Vec<Variable>,
+    ) -> Result<mpsc::Receiver<Vec<VariableWriteCommand>>, AddAndSubscribeError> {
+        // Check before adding, so the variables are not added if subscribing fails
+        self.check_write_event_notifier_conflicts(&vars)?;
+
+        self.add_variables(vars.clone(), true).await?;
+
+        Ok(self.create_write_event_notifier(&vars)?)
+    }
+
     /// Handle a write command from nats
+    ///
+    /// If the command was sent as request, the IDs of the accepted and rejected variables are replied.
     async fn handle_write(&mut self, msg: async_nats::Message) {
-        let write_command = match flatbuffers::root::<WriteVariablesCommand>(&msg.payload) {
-            Ok(x) => x,
-            _ => return,
+        let Ok(write_command) = self
+            .payload_codec
+            .decode_write_variables_command(&msg.payload)
+        else {
+            trace!("Ignore undecodable write command");
+            // Requests are still answered, so the caller does not run into a timeout
+            self.reply_invalid_write_command(msg.reply).await;
+            return;
+        };
+        let items = write_command.variables.items.unwrap_or_default();
+
+        if !self.accept_writes {
+            trace!("Ignore write command while draining writes");
+            let rejected_ids = items.iter().map(|item| item.id).collect();
+            self.reply_write_ack(msg.reply, Vec::new(), rejected_ids)
+                .await;
+            return;
         }
-        .unpack();
         if write_command.variables.provider_definition_fingerprint != self.current_fingerprint {
             trace!("Ignore write command with wrong fingerprint");
+            let rejected_ids = items.iter().map(|item| item.id).collect();
+            self.reply_write_ack(msg.reply, Vec::new(), rejected_ids)
+                .await;
             return;
         }
-        let Some(items) = write_command.variables.items else {
-            trace!("Ignore write command without items");
-            return;
-        };
 
         // Add definition to items, and filter out readonly and non existing variables.
+        let mut rejected_ids = Vec::new();
         let items = items
             .into_iter()
             .filter_map(|to_conv| {
-                if let Some(current_variable) = self.variables.get(&to_conv.id) {
-                    // Filter out read only variables
-                    if current_variable.definition.access_type == VariableAccessType::ReadOnly {
-                        trace!(
-                            "Ignore write command on readonly variable with id `{}`",
-                            to_conv.id
-                        );
-                        return None;
-                    }
-
-                    let write_command = VariableWriteCommand {
-                        id: current_variable.definition.id,
-                        value: Option::<VariableValue>::from(to_conv.value)?,
-                    };
-
-                    Some(write_command)
-                } else {
-                    trace!("Ignore non existing id `{}` from write command", to_conv.id);
-                    None
+                let id = to_conv.id;
+                let write_command = self.to_write_command(to_conv);
+                if write_command.is_none() {
+                    rejected_ids.push(id);
                 }
+                write_command
             })
             .collect::<Vec<_>>();
+        let ids: Vec<VariableID> = items.iter().map(|write_cmd| write_cmd.id).collect();
+
+        let delivered_ids = dispatch_write_commands(
+            &mut self.write_event_notiers,
+            &mut self.all_write_event_notifier,
+            items,
+        )
+        .await;
+
+        // Writes without a handler are dropped, so they must not be acknowledged
+        let (accepted_ids, undelivered_ids): (Vec<_>, Vec<_>) =
+            ids.into_iter().partition(|id| delivered_ids.contains(id));
+        if !undelivered_ids.is_empty() {
+            trace!("Ignore write command on variables without write handler: {undelivered_ids:?}");
+        }
+        rejected_ids.extend(undelivered_ids);
 
-        let mut dead_conns = vec![];
-        for (index, (ids, tx)) in self.write_event_notiers.iter_mut().enumerate() {
-            // Search out the variables for each sender
-            // TODO: Could we do this without cloning?
-            let items_for_sender: Vec<VariableWriteCommand> = items
-                .iter()
-                .filter(|write_cmd| ids.contains(&write_cmd.id))
-                .cloned()
-                .collect();
+        self.reply_write_ack(msg.reply, accepted_ids, rejected_ids)
+            .await;
+    }
 
-            // Forward the write command
-            if !items_for_sender.is_empty() && tx.send(items_for_sender).await.is_err() {
-                // Marks the sender as dead so it can removed
-                dead_conns.push(index);
-            }
+    /// Converts a single variable of a write command, returns `None` if the write is not allowed.
+    fn to_write_command(&self, to_conv: VariableT) -> Option<VariableWriteCommand> {
+        let Some(current_variable) = self.variables.get(&to_conv.id) else {
+            trace!("Ignore non existing id `{}` from write command", to_conv.id);
+            return None;
+        };
+
+        // Filter out read only variables
+        if current_variable.definition.access_type == VariableAccessType::ReadOnly {
+            trace!(
+                "Ignore write command on readonly variable with id `{}`",
+                to_conv.id
+            );
+            return None;
         }
 
-        // Remove dead connections from write_event_notiers
-        self.write_event_notiers = self
-            .write_event_notiers
-            .clone()
-            .into_iter()
-            .enumerate()
-            .filter(|(index, _)| !dead_conns.contains(index))
-            .map(|(_, x)| x)
-            .collect();
+        let mut value = Option::<VariableValue>::from(to_conv.value)?;
+        if let Some(bounds) = &current_variable.write_bounds {
+            let Some(bounded_value) = bounds.apply(value) else {
+                trace!(
+                    "Ignore out of bounds write command on variable with id `{}`",
+                    to_conv.id
+                );
+                return None;
+            };
+            value = bounded_value;
+        }
+
+        Some(VariableWriteCommand {
+            id: current_variable.definition.id,
+            value,
+        })
+    }
+
+    /// Replies the result of a write command, if it was sent as request.
+    async fn reply_write_ack(
+        &self,
+        reply_subject: Option<Subject>,
+        accepted_ids: Vec<VariableID>,
+        rejected_ids: Vec<VariableID>,
+    ) {
+        let Some(reply_subject) = reply_subject else {
+            return;
+        };
+
+        let ack = WriteAck {
+            accepted_ids,
+            rejected_ids,
+        };
+        self.get_nats_client()
+            .publish_with_headers(reply_subject.into_string(), ack.to_headers(), Bytes::new())
+            .await
+            .ok();
+    }
+
+    /// Replies that a write command could not be decoded, if it was sent as request.
+    async fn reply_invalid_write_command(&self, reply_subject: Option<Subject>) {
+        let Some(reply_subject) = reply_subject else {
+            return;
+        };
+
+        let mut headers = HeaderMap::new();
+        headers.insert(WRITE_INVALID_COMMAND_HEADER, "true");
+        self.get_nats_client()
+            .publish_with_headers(reply_subject.into_string(), headers, Bytes::new())
+            .await
+            .ok();
     }
 
     /// Update the value of a variable. You are not allowed to change the variable value data type.
+    ///
+    /// Only the updates that pass `unchanged_updates` are published.
     async fn update_variable_states(
         &mut self,
         states: Vec<VariableState>,
+        unchanged_updates: UnchangedUpdates,
     ) -> Result<(), UpdateVariableValuesError> {
         // Check if the variable exists and if the value type is the same.
         for updated_state in &states {
@@ -542,21 +896,125 @@ impl ProviderWorker {
             }
         }
 
-        let updated_ids: Vec<u32> = states.iter().map(|x| x.id).collect();
+        let mut updated_ids: Vec<u32> = Vec::with_capacity(states.len());
 
-        // Update the states
+        // Update the states, but only publish the updates that pass the unchanged updates policy
         for updated_state in states {
             if let Some(updated_variable) = self.variables.get_mut(&updated_state.id) {
+                if unchanged_updates.should_publish(&updated_variable.state, &updated_state) {
+                    updated_ids.push(updated_state.id);
+                }
                 updated_variable.state = updated_state;
             }
         }
 
+        if updated_ids.is_empty() {
+            return Ok(());
+        }
+
+        let updated_aggregates = self.recompute_aggregates(Some(&updated_ids));
+        updated_ids.extend(updated_aggregates);
+
         // Publish the changes to nats
-        self.publish_updates(Some(updated_ids))
+        self.publish_or_defer_updates(updated_ids)
             .await
             .map_err(|e| UpdateVariableValuesError::NatsError(Box::new(e)))
     }
 
+    /// Sets the heartbeat variable to the current time and publishes the change.
+    async fn update_heartbeat(&mut self) {
+        let Some(heartbeat) = self.heartbeat else {
+            return;
+        };
+
+        // The heartbeat stops if its variable was removed or replaced via the provider handle
+        let Some(variable) = self
+            .variables
+            .get_mut(&heartbeat.id)
+            .filter(|variable| variable.definition.data_type == VariableType::Timestamp)
+        else {
+            return;
+        };
+        variable.state.set_value(TimestampValue::now());
+
+        if let Err(e) = self.publish_or_defer_updates(vec![heartbeat.id]).await {
+            warn!(
+                "Failed to publish the heartbeat of provider `{}`: {e}",
+                self.get_provider_id()
+            );
+        }
+    }
+
+    /// Recomputes the aggregate variables and returns the IDs of the aggregates whose state changed.
+    ///
+    /// If `changed` is set, only aggregates with a member in `changed` are recomputed.
+    fn recompute_aggregates(&mut self, changed: Option<&[VariableID]>) -> Vec<VariableID> {
+        let aggregate_ids: HashSet<VariableID> = self
+            .aggregates
+            .iter()
+            .map(|aggregate| aggregate.id)
+            .collect();
+        let mut updated = Vec::new();
+
+        for aggregate in &self.aggregates {
+            if let Some(changed) = changed {
+                let affected = changed.iter().any(|id| {
+                    !aggregate_ids.contains(id)
+                        && self
+                            .variables
+                            .get(id)
+                            .is_some_and(|var| aggregate.contains_key(&var.definition.key))
+                });
+                if !affected {
+                    continue;
+                }
+            }
+
+            let result = aggregate.calculate(&self.variables, &aggregate_ids);
+
+            // The aggregate stops if its variable was removed or replaced via the provider handle
+            let Some(variable) = self
+                .variables
+                .get_mut(&aggregate.id)
+                .filter(|variable| variable.definition.data_type == VariableType::Float64)
+            else {
+                continue;
+            };
+
+            let (value, quality) = match result {
+                Some(value) => (VariableValue::Float64(value), VariableQuality::Good),
+                None => (
+                    variable.state.value.clone(),
+                    VariableQuality::BadOrUndefined,
+                ),
+            };
+
+            if variable.state.value != value || variable.state.quality != quality {
+                variable
+                    .state
+                    .set_all(value, quality, Some(TimestampValue::now()));
+                updated.push(aggregate.id);
+            }
+        }
+
+        updated
+    }
+
+    /// Publishes all changes that were deferred while publishing was paused.
+    async fn resume_publishing(&mut self) -> Result<(), PublishingControlError> {
+        let Some(paused_changes) = self.paused_changes.take() else {
+            return Ok(());
+        };
+
+        if paused_changes.is_empty() {
+            return Ok(());
+        }
+
+        self.publish_updates(Some(paused_changes.into_iter().collect()))
+            .await
+            .map_err(|e| PublishingControlError::NatsError(Box::new(e)))
+    }
+
     /// Remove a variable from the provider
     async fn remove_variables(
         &mut self,
@@ -567,9 +1025,19 @@ impl ProviderWorker {
             self.variables.remove(&x.definition.id);
         }
 
+        let updated_aggregates = self.recompute_aggregates(None);
+
         self.update_definition(wait_for_success)
             .await
-            .map_err(RemoveVariablesError::UpdateProviderDefinition)
+            .map_err(RemoveVariablesError::UpdateProviderDefinition)?;
+
+        if !updated_aggregates.is_empty() {
+            self.publish_or_defer_updates(updated_aggregates)
+                .await
+                .map_err(|e| RemoveVariablesError::NatsError(Box::new(e)))?;
+        }
+
+        Ok(())
     }
 
     /// Adds a variable to the provider
@@ -578,20 +1046,23 @@ impl ProviderWorker {
         vars: Vec<Variable>,
         wait_for_success: bool,
     ) -> Result<(), AddVariablesError> {
-        validate_var_list(&self.variables, &vars)?;
+        validate_var_list(&self.variables, &vars, self.limits)?;
 
-        let var_ids: Vec<u32> = vars.iter().map(|x| x.definition.id).collect();
+        let mut var_ids: Vec<u32> = vars.iter().map(|x| x.definition.id).collect();
 
         self.variables.extend(
             vars.into_iter()
                 .map(|variable| (variable.definition.id, variable)),
         );
 
+        let updated_aggregates = self.recompute_aggregates(None);
+        var_ids.extend(updated_aggregates);
+
         self.update_definition(wait_for_success)
             .await
             .map_err(AddVariablesError::UpdateProviderDefinition)?;
 
-        self.publish_updates(Some(var_ids))
+        self.publish_or_defer_updates(var_ids)
             .await
             .map_err(|e| AddVariablesError::NatsError(Box::new(e)))?;
 
@@ -600,7 +1071,10 @@ impl ProviderWorker {
 
     /// Handles the variable read query. It answers with the cached values.
     async fn handle_variable_read_query(&self, msg: Message) {
-        let Ok(read_request) = flatbuffers::root::<ReadVariablesQueryRequest>(&msg.payload) else {
+        let Ok(read_request) = self
+            .payload_codec
+            .decode_read_variables_query_request(&msg.payload)
+        else {
             return;
         };
 
@@ -608,17 +1082,75 @@ impl ProviderWorker {
             return;
         };
 
-        let response = build_read_variables_query_response(
-            read_request.unpack(),
+        let mut response = create_read_variables_query_response(
+            read_request,
             &self.variables,
             self.current_fingerprint,
         );
+        let values_only = msg
+            .headers
+            .as_ref()
+            .and_then(|headers| headers.get(READ_VALUES_ONLY_HEADER))
+            .is_some_and(|value| value.as_str() == "true");
+        if values_only {
+            omit_timestamps_and_qualities(&mut response);
+        }
+
+        let response = self
+            .payload_codec
+            .encode_read_variables_query_response(&response);
         self.get_nats_client()
             .publish(reply_subject.into_string(), response)
             .await
             .ok();
     }
 
+    /// Answers a read query for the provider status.
+    async fn handle_status_read_query(&self, msg: Message) {
+        let Some(reply_subject) = msg.reply else {
+            return;
+        };
+
+        self.get_nats_client()
+            .publish(
+                reply_subject.into_string(),
+                Bytes::copy_from_slice(self.status.as_str().as_bytes()),
+            )
+            .await
+            .ok();
+    }
+
+    /// Changes the provider status and publishes it, if it differs from the current status.
+    async fn set_status(&mut self, status: ProviderStatus) -> Result<(), SetStatusError> {
+        if self.status == status {
+            return Ok(());
+        }
+
+        self.get_nats_client()
+            .publish(
+                nats_subjects::provider_status_changed_event(self.get_provider_id()),
+                Bytes::copy_from_slice(status.as_str().as_bytes()),
+            )
+            .await
+            .map_err(|e| SetStatusError::NatsError(Box::new(e)))?;
+
+        self.status = status;
+        Ok(())
+    }
+
+    /// Publishes value updates, or remembers them for [`Self::resume_publishing`] if publishing is paused.
+    async fn publish_or_defer_updates(
+        &mut self,
+        changed: Vec<VariableID>,
+    ) -> Result<(), async_nats::error::Error<async_nats::client::PublishErrorKind>> {
+        if let Some(paused_changes) = &mut self.paused_changes {
+            paused_changes.extend(changed);
+            return Ok(());
+        }
+
+        self.publish_updates(Some(changed)).await
+    }
+
     /// Publish value updates
     async fn publish_updates(
         &mut self,
@@ -638,13 +1170,90 @@ impl ProviderWorker {
             None => &self.variables,
         };
 
-        let payload = build_variables_changed_event(to_publish, self.current_fingerprint);
+        let payload =
+            self.payload_codec
+                .encode_variables_changed_event(&create_variables_changed_event(
+                    to_publish,
+                    self.current_fingerprint,
+                ));
 
-        self.get_nats_client()
-            .publish(
-                nats_subjects::vars_changed_event(self.get_provider_id()),
-                payload,
-            )
-            .await
+        let subject = nats_subjects::vars_changed_event(self.get_provider_id());
+        match payload_compression::compress(self.payload_compression, payload) {
+            (Some(headers), payload) => {
+                self.get_nats_client()
+                    .publish_with_headers(subject, headers, payload)
+                    .await
+            }
+            (None, payload) => self.get_nats_client().publish(subject, payload).await,
+        }
+    }
+}
+
+/// Forwards the write commands to the notifiers and returns the IDs of the commands that were delivered.
+///
+/// Notifiers whose receiver was dropped are removed.
+async fn dispatch_write_commands(
+    write_event_notiers: &mut Vec<(HashSet<VariableID>, mpsc::Sender<Vec<VariableWriteCommand>>)>,
+    all_write_event_notifier: &mut Option<mpsc::Sender<Vec<VariableWriteCommand>>>,
+    items: Vec<VariableWriteCommand>,
+) -> HashSet<VariableID> {
+    let mut delivered_ids = HashSet::new();
+
+    let mut dead_conns = vec![];
+    for (index, (ids, tx)) in write_event_notiers.iter_mut().enumerate() {
+        // Search out the variables for each sender
+        // TODO: Could we do this without cloning?
+        let items_for_sender: Vec<VariableWriteCommand> = items
+            .iter()
+            .filter(|write_cmd| ids.contains(&write_cmd.id))
+            .cloned()
+            .collect();
+        if items_for_sender.is_empty() {
+            continue;
+        }
+
+        // Forward the write command
+        let ids_for_sender: Vec<VariableID> = items_for_sender
+            .iter()
+            .map(|write_cmd| write_cmd.id)
+            .collect();
+        if tx.send(items_for_sender).await.is_ok() {
+            delivered_ids.extend(ids_for_sender);
+        } else {
+            // Marks the sender as dead so it can removed
+            dead_conns.push(index);
+        }
+    }
+
+    // Forward all write commands to the notifier for all variables
+    if let Some(tx) = all_write_event_notifier {
+        let ids: Vec<VariableID> = items.iter().map(|write_cmd| write_cmd.id).collect();
+        if !items.is_empty() {
+            if tx.send(items).await.is_ok() {
+                delivered_ids.extend(ids);
+            } else {
+                *all_write_event_notifier = None;
+            }
+        }
+    }
+
+    // Remove dead connections from write_event_notiers
+    *write_event_notiers = std::mem::take(write_event_notiers)
+        .into_iter()
+        .enumerate()
+        .filter(|(index, _)| !dead_conns.contains(index))
+        .map(|(_, x)| x)
+        .collect();
+
+    delivered_ids
+}
+
+/// Waits for the next tick of the heartbeat timer, or forever if the provider has no heartbeat.
+async fn next_heartbeat(timer: &mut Option<Interval>) {
+    match timer {
+        Some(timer) => {
+            timer.tick().await;
+        }
+        None => std::future::pending().await,
     }
 }
@@ -0,0 +1,85 @@
+// SPDX-FileCopyrightText: 2025 Weidmueller Interface GmbH & Co. KG <oss@weidmueller.com>
+//
+// SPDX-License-Identifier: MIT
+
+use std::collections::HashSet;
+
+use tokio::sync::mpsc;
+
+use crate::{dh_types::VariableValue, provider::provider_types::VariableWriteCommand};
+
+use super::dispatch_write_commands;
+
+fn write_commands(ids: &[u32]) -> Vec<VariableWriteCommand> {
+    ids.iter()
+        .map(|id| VariableWriteCommand {
+            id: *id,
+            value: VariableValue::Int(1),
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn test_dispatch_without_write_subscriber() {
+    // Prepare
+    let (tx, mut rx) = mpsc::channel(1);
+    let mut write_event_notiers = vec![(HashSet::from([1]), tx)];
+    let mut all_write_event_notifier = None;
+
+    // Act
+    let delivered_ids = dispatch_write_commands(
+        &mut write_event_notiers,
+        &mut all_write_event_notifier,
+        write_commands(&[1, 2]),
+    )
+    .await;
+
+    // Assert
+    assert_eq!(delivered_ids, HashSet::from([1]));
+    let received: Vec<u32> = rx.recv().await.unwrap().iter().map(|cmd| cmd.id).collect();
+    assert_eq!(received, vec![1]);
+}
+
+#[tokio::test]
+async fn test_dispatch_to_dropped_subscriber() {
+    // Prepare
+    let (tx, rx) = mpsc::channel(1);
+    let (all_tx, all_rx) = mpsc::channel(1);
+    drop(rx);
+    drop(all_rx);
+    let mut write_event_notiers = vec![(HashSet::from([1]), tx)];
+    let mut all_write_event_notifier = Some(all_tx);
+
+    // Act
+    let delivered_ids = dispatch_write_commands(
+        &mut write_event_notiers,
+        &mut all_write_event_notifier,
+        write_commands(&[1]),
+    )
+    .await;
+
+    // Assert
+    assert!(delivered_ids.is_empty());
+    assert!(write_event_notiers.is_empty());
+    assert!(all_write_event_notifier.is_none());
+}
+
+#[tokio::test]
+async fn test_dispatch_to_all_subscriber() {
+    // Prepare
+    let (all_tx, mut all_rx) = mpsc::channel(1);
+    let mut write_event_notiers = vec![];
+    let mut all_write_event_notifier = Some(all_tx);
+
+    // Act
+    let delivered_ids = dispatch_write_commands(
+        &mut write_event_notiers,
+        &mut all_write_event_notifier,
+        write_commands(&[1, 2]),
+    )
+    .await;
+
+    // Assert
+    assert_eq!(delivered_ids, HashSet::from([1, 2]));
+    assert_eq!(all_rx.recv().await.unwrap().len(), 2);
+}
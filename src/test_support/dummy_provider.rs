@@ -2,19 +2,29 @@
 //
 // SPDX-License-Identifier: MIT
 
+//! Contains a dummy provider with a fixed set of variables for consumer tests.
+
 use std::{sync::Arc, time::Duration};
 
-use tokio::sync::Notify;
-use u_os_hub_client::{
+use crate::{
     dh_types::{VariableAccessType, VariableValue},
     provider::{ProviderBuilder, VariableBuilder},
 };
+use tokio::sync::Notify;
 
-use crate::utils::create_auth_con;
+use super::create_auth_con;
 
+/// The provider ID of the [`DummyProvider`].
 pub const PROVIDER_ID: &str = "dummy_provider";
+/// Interval in which the [`DummyProvider`] updates its read only variables.
 pub const VARIABLE_UPDATE_RATE: Duration = Duration::from_millis(200);
 
+/// Provider with two read only and two read write variables.
+///
+/// - `my_folder.ro_float` (ID 100) and `my_folder.ro_int` (ID 400) are updated every [`VARIABLE_UPDATE_RATE`].
+/// - `my_folder.rw_string` (ID 200) and `my_folder.rw_int` (ID 300) accept all write commands.
+///
+/// The provider is stopped when it is dropped.
 pub struct DummyProvider {
     change_vars_notify: Arc<Notify>,
     worker_task: tokio::task::JoinHandle<()>,
@@ -27,10 +37,12 @@ impl Drop for DummyProvider {
 }
 
 impl DummyProvider {
+    /// Starts the dummy provider, which registers itself in the background.
     pub async fn new() -> Result<Self, Box<dyn std::error::Error>> {
         Self::new_with_delay(Duration::ZERO).await
     }
 
+    /// Same as [`Self::new()`], but delays the registration by `registration_delay`.
     #[allow(clippy::too_many_lines)] // We accept this for the dummy provider as its only used for testing
     pub async fn new_with_delay(
         registration_delay: Duration,
@@ -175,6 +187,10 @@ impl DummyProvider {
         })
     }
 
+    /// Replaces all variables with a new set of variables with different IDs.
+    ///
+    /// Afterwards, the provider has the variables `my_folder.ro_float` (ID 10), `my_folder.rw_string` (ID 20),
+    /// `my_folder.rw_int2` (ID 30), `my_folder.ro_int2` (ID 40) and `my_folder.rw_int3` (ID 50).
     pub fn change_variables(&self) {
         self.change_vars_notify.notify_one();
     }
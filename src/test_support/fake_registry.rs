@@ -2,15 +2,14 @@
 //
 // SPDX-License-Identifier: MIT
 
+//! Contains a fake registry that replaces the u-OS Data Hub registry in tests.
+
 use std::{
     collections::HashMap,
     sync::{Arc, Mutex},
 };
 
-use async_nats::client;
-use futures::StreamExt;
-use tokio::task;
-use u_os_hub_client::{
+use crate::{
     generated::weidmueller::ucontrol::hub::{
         ProviderDefinitionChangedEvent, ProviderDefinitionState, ProviderDefinitionT,
     },
@@ -20,9 +19,13 @@ use u_os_hub_client::{
         build_read_provider_definition_response, build_read_providers_response,
     },
 };
+use async_nats::client;
+use futures::StreamExt;
+use tokio::task;
 
 use super::create_auth_con;
 
+/// State of the [`FakeRegistry`].
 #[derive(Clone, Debug)]
 pub struct FakeRegistryState {
     registered_providers: HashMap<String, ProviderDefinitionT>,
@@ -30,6 +33,10 @@ pub struct FakeRegistryState {
 
 type SharedFakeRegistryState = Arc<Mutex<FakeRegistryState>>;
 
+/// Minimal replacement for the u-OS Data Hub registry.
+///
+/// Accepts all provider definitions, marks them as OK and answers registry queries.
+/// The registry is stopped when it is dropped.
 pub struct FakeRegistry {
     worker_task: task::JoinHandle<()>,
     state: SharedFakeRegistryState,
@@ -90,6 +97,8 @@ impl FakeRegistry {
         Self { worker_task, state }
     }
 
+    /// Returns a snapshot of the current registry state.
+    #[must_use]
     pub fn get_state(&self) -> FakeRegistryState {
         self.state.lock().unwrap().clone()
     }
@@ -0,0 +1,284 @@
+// SPDX-FileCopyrightText: 2025 Weidmueller Interface GmbH & Co. KG <oss@weidmueller.com>
+//
+// SPDX-License-Identifier: MIT
+
+//! Contains a configurable mock provider for consumer tests.
+
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex},
+};
+
+use crate::{
+    dh_types::{VariableAccessType, VariableID, VariableValue},
+    provider::{Provider, ProviderBuilder, VariableBuilder},
+    variable::Variable,
+};
+
+use super::create_auth_con;
+
+/// Used to declare the variables of a [`MockProvider`] before starting it.
+pub struct MockProviderBuilder {
+    provider_id: String,
+    variables: Vec<Variable>,
+    apply_writes: bool,
+}
+
+impl MockProviderBuilder {
+    /// Creates a builder for a mock provider with the given provider ID and no variables.
+    #[must_use]
+    pub fn new(provider_id: impl Into<String>) -> Self {
+        Self {
+            provider_id: provider_id.into(),
+            variables: Vec::new(),
+            apply_writes: true,
+        }
+    }
+
+    /// Adds a read only variable with an initial value.
+    ///
+    /// Panics if the variable is invalid, e.g. because of an invalid key.
+    #[must_use]
+    pub fn variable(self, id: VariableID, key: &str, value: impl Into<VariableValue>) -> Self {
+        self.add_variable(id, key, value, VariableAccessType::ReadOnly)
+    }
+
+    /// Adds a read write variable with an initial value.
+    ///
+    /// Panics if the variable is invalid, e.g. because of an invalid key.
+    #[must_use]
+    pub fn writable_variable(
+        self,
+        id: VariableID,
+        key: &str,
+        value: impl Into<VariableValue>,
+    ) -> Self {
+        self.add_variable(id, key, value, VariableAccessType::ReadWrite)
+    }
+
+    /// If set to false, write commands are only recorded, but the written values are not applied.
+    ///
+    /// This allows to test consumers against providers that reject writes. By default, all writes are applied.
+    #[must_use]
+    pub fn apply_writes(mut self, apply_writes: bool) -> Self {
+        self.apply_writes = apply_writes;
+        self
+    }
+
+    fn add_variable(
+        mut self,
+        id: VariableID,
+        key: &str,
+        value: impl Into<VariableValue>,
+        access_type: VariableAccessType,
+    ) -> Self {
+        let variable = VariableBuilder::new(id, key)
+            .access_type(access_type)
+            .initial_value(value)
+            .build()
+            .unwrap();
+
+        self.variables.push(variable);
+        self
+    }
+
+    /// Registers the mock provider on the registry and starts handling write commands.
+    ///
+    /// Requires a running registry, e.g. a [`FakeRegistry`](super::fake_registry::FakeRegistry).
+    /// Like any provider, the mock provider may not be registered yet when this returns.
+    /// Consumers should wait for it, e.g. via [`DataHubConsumer::wait_for_provider`](crate::consumer::dh_consumer::DataHubConsumer::wait_for_provider).
+    pub async fn start(self) -> MockProvider {
+        let auth_nats_con = create_auth_con(&self.provider_id).await;
+
+        let writable_variables: Vec<Variable> = self
+            .variables
+            .iter()
+            .filter(|var| var.get_definition().access_type == VariableAccessType::ReadWrite)
+            .cloned()
+            .collect();
+
+        let provider = ProviderBuilder::new()
+            .add_variables(self.variables.clone())
+            .unwrap()
+            .register_with_existing_connection(auth_nats_con)
+            .await
+            .unwrap();
+
+        let variables: SharedVariables = Arc::new(Mutex::new(
+            self.variables
+                .into_iter()
+                .map(|var| (var.get_definition().id, var))
+                .collect(),
+        ));
+        let received_writes = Arc::new(Mutex::new(Vec::new()));
+
+        let mut write_commands = provider
+            .subscribe_to_write_command(writable_variables)
+            .await
+            .unwrap();
+
+        let write_task = tokio::spawn({
+            let provider = provider.clone();
+            let variables = variables.clone();
+            let received_writes = received_writes.clone();
+            let apply_writes = self.apply_writes;
+
+            async move {
+                while let Some(write_commands) = write_commands.recv().await {
+                    let mut updated_states = Vec::with_capacity(write_commands.len());
+
+                    {
+                        let mut variables = variables.lock().unwrap();
+                        let mut received_writes = received_writes.lock().unwrap();
+
+                        for write_cmd in write_commands {
+                            let Some(variable) = variables.get_mut(&write_cmd.id) else {
+                                continue;
+                            };
+
+                            received_writes.push((
+                                variable.get_definition().key.clone(),
+                                write_cmd.value.clone(),
+                            ));
+
+                            if apply_writes {
+                                let state = variable.get_mut_state();
+                                state.set_value(write_cmd.value);
+                                updated_states.push(state.clone());
+                            }
+                        }
+                    }
+
+                    if !updated_states.is_empty() {
+                        provider
+                            .update_variable_states(updated_states)
+                            .await
+                            .unwrap();
+                    }
+                }
+            }
+        });
+
+        MockProvider {
+            provider_id: self.provider_id,
+            provider,
+            variables,
+            received_writes,
+            write_task,
+        }
+    }
+}
+
+type SharedVariables = Arc<Mutex<BTreeMap<VariableID, Variable>>>;
+
+/// Provider with variables declared via a [`MockProviderBuilder`], for testing consumer logic.
+///
+/// The mock provider is a regular [`Provider`], so it answers read requests and publishes changes like a real provider.
+/// In addition, tests can change values on demand and inspect the write commands that were received.
+///
+/// ```no_run
+/// use u_os_hub_client::prelude::test::{run_with_timeout, FakeRegistry, MockProvider};
+///
+/// # async fn example() {
+/// run_with_timeout(async {
+///     let _fake_registry = FakeRegistry::new().await;
+///     let mock_provider = MockProvider::builder("mock_provider")
+///         .variable(1, "sensors.temperature", 21.5)
+///         .writable_variable(2, "control.setpoint", 20.0)
+///         .start()
+///         .await;
+///
+///     // Connect a consumer to "mock_provider" and test your code here
+///     mock_provider.set_value("sensors.temperature", 22.0).await;
+///     assert!(mock_provider.received_writes().is_empty());
+/// })
+/// .await;
+/// # }
+/// ```
+///
+/// The provider is stopped when it is dropped.
+pub struct MockProvider {
+    provider_id: String,
+    provider: Provider,
+    variables: SharedVariables,
+    received_writes: Arc<Mutex<Vec<(String, VariableValue)>>>,
+    write_task: tokio::task::JoinHandle<()>,
+}
+
+impl Drop for MockProvider {
+    fn drop(&mut self) {
+        self.write_task.abort();
+    }
+}
+
+impl MockProvider {
+    /// Creates a [`MockProviderBuilder`] to declare the variables of the mock provider.
+    #[must_use]
+    pub fn builder(provider_id: impl Into<String>) -> MockProviderBuilder {
+        MockProviderBuilder::new(provider_id)
+    }
+
+    /// Returns the provider ID.
+    #[must_use]
+    pub fn provider_id(&self) -> &str {
+        &self.provider_id
+    }
+
+    /// Returns the underlying provider, e.g. to add or remove variables.
+    #[must_use]
+    pub fn provider(&self) -> &Provider {
+        &self.provider
+    }
+
+    /// Returns the current value of the variable with the given key.
+    #[must_use]
+    pub fn value(&self, key: &str) -> Option<VariableValue> {
+        self.variables
+            .lock()
+            .unwrap()
+            .values()
+            .find(|var| var.get_definition().key == key)
+            .map(|var| var.get_state().get_value().clone())
+    }
+
+    /// Sets the value of the variable with the given key and publishes the change to subscribed consumers.
+    ///
+    /// Panics if the variable does not exist or the value type does not match.
+    pub async fn set_value(&self, key: &str, value: impl Into<VariableValue>) {
+        self.set_values(vec![(key, value.into())]).await;
+    }
+
+    /// Same as [`Self::set_value`], but publishes all changes in a single change event.
+    pub async fn set_values(&self, values: Vec<(&str, VariableValue)>) {
+        let updated_states = {
+            let mut variables = self.variables.lock().unwrap();
+
+            values
+                .into_iter()
+                .map(|(key, value)| {
+                    let variable = variables
+                        .values_mut()
+                        .find(|var| var.get_definition().key == key)
+                        .unwrap_or_else(|| panic!("Mock provider has no variable `{key}`"));
+
+                    let state = variable.get_mut_state();
+                    state.set_value(value);
+                    state.clone()
+                })
+                .collect()
+        };
+
+        self.provider
+            .update_variable_states(updated_states)
+            .await
+            .unwrap();
+    }
+
+    /// Returns the key and value of all write commands received so far, in the order they were received.
+    ///
+    /// Writes are recorded even if [`MockProviderBuilder::apply_writes`] is disabled.
+    #[must_use]
+    pub fn received_writes(&self) -> Vec<(String, VariableValue)> {
+        self.received_writes.lock().unwrap().clone()
+    }
+}
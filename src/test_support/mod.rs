@@ -0,0 +1,80 @@
+// SPDX-FileCopyrightText: 2025 Weidmueller Interface GmbH & Co. KG <oss@weidmueller.com>
+//
+// SPDX-License-Identifier: MIT
+
+//! Helpers for writing integration tests against a local NATS server.
+//!
+//! Contains a [`FakeRegistry`](fake_registry::FakeRegistry) that replaces the u-OS Data Hub registry
+//! and a [`DummyProvider`](dummy_provider::DummyProvider) with a fixed set of variables.
+//! For custom variables, use a [`MockProvider`](mock_provider::MockProvider) instead.
+//! Usually, these are used via [`crate::prelude::test`].
+//!
+//! Like the tests they are meant for, these helpers panic if something goes wrong.
+//! They must not be used in production code.
+
+// Test support code is allowed to panic, just like the tests that use it.
+#![allow(
+    clippy::unwrap_used,
+    clippy::expect_used,
+    clippy::panic,
+    clippy::missing_panics_doc
+)]
+
+pub mod dummy_provider;
+pub mod fake_registry;
+pub mod mock_provider;
+
+use std::{future::IntoFuture, sync::Arc, time::Duration};
+
+use tokio::time::timeout;
+
+use crate::{
+    authenticated_nats_con::{
+        AuthenticatedNatsConnection, AuthenticationSettingsBuilder, NatsPermission,
+    },
+    oauth2::OAuth2Credentials,
+};
+
+/// Address of the local NATS server used for tests.
+pub const NATS_HOSTNAME: &str = "nats://localhost:4222";
+
+/// As some nats operations are async and may wait for a long time, we define a default timeout to avoid blocking tests forever.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Creates a connection to [`NATS_HOSTNAME`] with provider permissions, using `client_name` as client name.
+pub async fn create_auth_con(client_name: &str) -> Arc<AuthenticatedNatsConnection> {
+    create_auth_con_with_perm(client_name, NatsPermission::VariableHubProvide).await
+}
+
+/// Same as [`create_auth_con`], but with custom permissions.
+pub async fn create_auth_con_with_perm(
+    client_name: &str,
+    perm: NatsPermission,
+) -> Arc<AuthenticatedNatsConnection> {
+    let auth_settings = AuthenticationSettingsBuilder::new(perm)
+        .with_credentials(OAuth2Credentials {
+            client_name: client_name.to_owned(),
+            client_id: String::new(),
+            client_secret: String::new(),
+        })
+        .build();
+
+    tracing::info!("Creating AuthenticatedNatsConnection");
+    let con = timeout(
+        Duration::from_secs(10),
+        AuthenticatedNatsConnection::new(NATS_HOSTNAME, &auth_settings),
+    )
+    .await
+    .unwrap()
+    .unwrap();
+
+    Arc::new(con)
+}
+
+/// Runs `future` and panics if it does not complete within [`DEFAULT_TIMEOUT`].
+pub async fn run_with_timeout<F>(future: F) -> <F as IntoFuture>::Output
+where
+    F: IntoFuture,
+{
+    timeout(DEFAULT_TIMEOUT, future).await.expect("Timeout")
+}
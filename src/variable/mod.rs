@@ -9,22 +9,62 @@ use std::{
 };
 
 use crate::{
-    dh_types::VariableDefinition,
+    dh_types::{VariableDefinition, VariableID, VariableQuality, VariableValue},
     generated::weidmueller::ucontrol::hub::{VariableDefinitionT, VariableT},
-    provider::provider_types::VariableState,
+    provider::{
+        provider_types::{VariableState, WriteBounds},
+        VariableBuilder,
+    },
 };
 
 /// Holds information about a variable (definition and value).
 ///
 /// Warning: If you initialise this struct directly, there is no validation.
-/// Instead you should use the [`VariableBuilder`](crate::provider::VariableBuilder) struct.
+/// Instead you should use [`Variable::builder`], which returns a [`VariableBuilder`].
 #[derive(Clone, Debug, PartialEq)]
 pub struct Variable {
     pub(crate) state: VariableState,
     pub(crate) definition: VariableDefinition,
+    pub(crate) write_bounds: Option<WriteBounds>,
 }
 
 impl Variable {
+    /// Creates a [`VariableBuilder`], which is the recommended way to create a variable.
+    ///
+    /// Same as [`VariableBuilder::new`].
+    #[must_use]
+    pub fn builder(id: VariableID, key: impl Into<String>) -> VariableBuilder {
+        VariableBuilder::new(id, key)
+    }
+
+    /// Returns the ID of the variable.
+    #[inline(always)]
+    #[must_use]
+    pub fn id(&self) -> VariableID {
+        self.definition.id
+    }
+
+    /// Returns the key of the variable.
+    #[inline(always)]
+    #[must_use]
+    pub fn key(&self) -> &str {
+        &self.definition.key
+    }
+
+    /// Returns the current value of the variable.
+    #[inline(always)]
+    #[must_use]
+    pub fn value(&self) -> &VariableValue {
+        self.state.get_value()
+    }
+
+    /// Returns the current quality of the variable.
+    #[inline(always)]
+    #[must_use]
+    pub fn quality(&self) -> VariableQuality {
+        *self.state.get_quality()
+    }
+
     /// Returns the immutable state of the variable.
     #[inline(always)]
     #[must_use]
@@ -47,15 +87,22 @@ impl Variable {
     pub fn get_definition(&self) -> &VariableDefinition {
         &self.definition
     }
+
+    /// Returns the bounds that the provider enforces on write commands, if any.
+    ///
+    /// See [`VariableBuilder::min`] for details.
+    #[inline(always)]
+    #[must_use]
+    pub fn get_write_bounds(&self) -> Option<&WriteBounds> {
+        self.write_bounds.as_ref()
+    }
 }
 
 impl From<&Variable> for VariableT {
     fn from(var: &Variable) -> Self {
         VariableT {
-            quality: (*var.state.get_quality()).into(),
             id: var.definition.id,
-            value: var.state.get_value().into(),
-            timestamp: var.state.get_timestamp().map(Into::into),
+            ..var.state.to_variable_t()
         }
     }
 }
@@ -9,6 +9,10 @@ use serde::{
 
 use crate::generated::weidmueller::ucontrol::hub::VariableValueT;
 
+/// Serializes the value as a plain JSON value.
+///
+/// Integers are always serialized as integers and never as floats,
+/// so values outside the range that `f64` can represent exactly, like counters near [`i64::MAX`], round-trip without loss.
 impl Serialize for VariableValueT {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
@@ -73,3 +77,28 @@ impl Serialize for VariableValueT {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use rstest::rstest;
+
+    use crate::dh_types::VariableValue;
+
+    use super::*;
+
+    #[rstest]
+    #[case(0)]
+    #[case(-1)]
+    #[case(9_007_199_254_740_993)] // 2^53 + 1, the first integer that f64 can not represent
+    #[case(-9_007_199_254_740_993)]
+    #[case(i64::MAX)]
+    #[case(i64::MIN)]
+    fn test_serialize_int_is_lossless(#[case] value: i64) {
+        let json =
+            serde_json::to_string(&VariableValueT::from(&VariableValue::Int(value))).unwrap();
+
+        // Must be a JSON integer, not a float
+        assert_eq!(json, value.to_string());
+        assert_eq!(serde_json::from_str::<i64>(&json).unwrap(), value);
+    }
+}
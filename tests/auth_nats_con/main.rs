@@ -10,13 +10,22 @@
     clippy::indexing_slicing
 )]
 
-use std::{collections::HashSet, time::Duration};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
 
+use futures::StreamExt;
 use serial_test::serial;
 use tokio::time::timeout;
 use u_os_hub_client::{
     authenticated_nats_con::{
-        AuthenticatedNatsConnection, AuthenticationSettingsBuilder, NatsPermission,
+        AuthSettingsError, AuthenticatedNatsConnection, AuthenticationSettingsBuilder,
+        ConnectionError, InvalidServerAddressError, NatsPermission,
     },
     oauth2::OAuth2Credentials,
 };
@@ -99,6 +108,76 @@ async fn test_custom_name_and_multi_perms() {
     );
 }
 
+#[tokio::test]
+#[serial]
+async fn test_client_description_and_version() {
+    let creds = OAuth2Credentials {
+        client_name: "test_client".to_string(),
+        client_secret: String::new(),
+        client_id: String::new(),
+    };
+
+    let auth_settings = AuthenticationSettingsBuilder::new(NatsPermission::VariableHubRead)
+        .with_credentials(creds.clone())
+        .with_client_description("Test App")
+        .with_client_version("1.2.3")
+        .build();
+
+    let con = run_with_timeout(AuthenticatedNatsConnection::new(
+        NATS_HOSTNAME,
+        &auth_settings,
+    ))
+    .await
+    .unwrap();
+
+    //the client name is not affected by the tags
+    assert_eq!(con.get_client_name(), "test_client");
+    assert_eq!(con.get_connection_name(), "test_client (Test App 1.2.3)");
+
+    let auth_settings = AuthenticationSettingsBuilder::new(NatsPermission::VariableHubRead)
+        .with_credentials(creds)
+        .with_client_version("1.2.3")
+        .build();
+
+    let con = run_with_timeout(AuthenticatedNatsConnection::new(
+        NATS_HOSTNAME,
+        &auth_settings,
+    ))
+    .await
+    .unwrap();
+
+    assert_eq!(con.get_connection_name(), "test_client (1.2.3)");
+}
+
+#[tokio::test]
+#[serial]
+async fn test_connection_state() {
+    let auth_settings =
+        AuthenticationSettingsBuilder::new(NatsPermission::VariableHubProvide).build();
+
+    let con = run_with_timeout(AuthenticatedNatsConnection::new(
+        NATS_HOSTNAME,
+        &auth_settings,
+    ))
+    .await
+    .unwrap();
+
+    assert!(con.is_connected());
+    assert_eq!(
+        con.connection_state(),
+        async_nats::connection::State::Connected
+    );
+
+    //the connection is still pending, as there is no server on this port
+    let (offline_con, _errors) = run_with_timeout(
+        AuthenticatedNatsConnection::new_with_error_stream("nats://localhost:4223", &auth_settings),
+    )
+    .await
+    .unwrap();
+
+    assert!(!offline_con.is_connected());
+}
+
 #[tokio::test]
 #[serial]
 async fn test_server_offline() {
@@ -114,6 +193,34 @@ async fn test_server_offline() {
     assert!(con_result.is_err());
 }
 
+#[tokio::test]
+async fn test_invalid_server_address() {
+    let auth_settings =
+        AuthenticationSettingsBuilder::new(NatsPermission::VariableHubProvide).build();
+
+    for address in [
+        "",
+        "localhost:4222",
+        "http://localhost:4222",
+        "nats://",
+        "nats://localhost:4222, localhost:4223",
+    ] {
+        //Must fail immediately, without trying to connect
+        let con_result = timeout(
+            Duration::from_millis(100),
+            AuthenticatedNatsConnection::new(address, &auth_settings),
+        )
+        .await
+        .expect("validation should fail before connecting");
+
+        let err = con_result.expect_err("address should be rejected");
+        let err = err
+            .downcast_ref::<InvalidServerAddressError>()
+            .expect("error should be InvalidServerAddressError");
+        assert_eq!(err.address, address);
+    }
+}
+
 #[tokio::test]
 #[serial]
 async fn test_creds_with_invalid_auth_server() {
@@ -137,6 +244,39 @@ async fn test_creds_with_invalid_auth_server() {
     assert!(timeout_result.is_err());
 }
 
+#[tokio::test]
+#[serial]
+async fn test_error_stream_reports_auth_failure() {
+    let auth_settings = AuthenticationSettingsBuilder::new(NatsPermission::VariableHubProvide)
+        .with_credentials(OAuth2Credentials {
+            client_name: "test_client".to_string(),
+            client_secret: "somepass".to_string(),
+            client_id: "someid".to_string(),
+        })
+        //doesnt exist in dev container
+        .with_custom_oauth2_endpoint("https://127.0.0.1/oauth2/token")
+        .build();
+
+    //In contrast to new(), this must not wait for the connection, which never succeeds
+    let (_con, mut errors) = run_with_timeout(AuthenticatedNatsConnection::new_with_error_stream(
+        NATS_HOSTNAME,
+        &auth_settings,
+    ))
+    .await
+    .unwrap();
+
+    //the failed token request is reported with its reason
+    let error = run_with_timeout(async {
+        loop {
+            if let ConnectionError::Authentication(reason) = errors.next().await.unwrap() {
+                break reason;
+            }
+        }
+    })
+    .await;
+    assert!(error.contains("Error requesting token"));
+}
+
 #[tokio::test]
 #[serial]
 async fn test_invalid_auth_server_but_no_creds() {
@@ -154,3 +294,142 @@ async fn test_invalid_auth_server_but_no_creds() {
 
     assert!(con_result.is_ok());
 }
+
+#[tokio::test]
+#[serial]
+async fn test_credentials_provider() {
+    let call_count = Arc::new(AtomicUsize::new(0));
+
+    let call_count_clone = call_count.clone();
+    let auth_settings = AuthenticationSettingsBuilder::new(NatsPermission::VariableHubProvide)
+        .with_credentials_provider(move || {
+            call_count_clone.fetch_add(1, Ordering::SeqCst);
+            async {
+                OAuth2Credentials {
+                    client_name: "rotating_client".to_string(),
+                    client_secret: String::new(),
+                    client_id: String::new(),
+                }
+            }
+        })
+        .build();
+
+    let con = run_with_timeout(AuthenticatedNatsConnection::new(
+        NATS_HOSTNAME,
+        &auth_settings,
+    ))
+    .await
+    .unwrap();
+
+    assert_eq!(con.get_client_name(), "rotating_client");
+    //Called once for the client name and once by the auth callback
+    assert!(call_count.load(Ordering::SeqCst) >= 2);
+}
+
+#[test]
+fn test_parse_nats_permission() {
+    for perm in [
+        NatsPermission::VariableHubRead,
+        NatsPermission::VariableHubReadWrite,
+        NatsPermission::VariableHubProvide,
+    ] {
+        assert_eq!(NatsPermission::try_from(perm.as_str()).unwrap(), perm);
+        assert_eq!(perm.as_str().parse::<NatsPermission>().unwrap(), perm);
+    }
+
+    assert!(NatsPermission::try_from("hub.variables.unknown").is_err());
+    assert!("".parse::<NatsPermission>().is_err());
+}
+
+#[test]
+fn test_build_validated() {
+    let creds = OAuth2Credentials {
+        client_name: "test_client".to_owned(),
+        client_id: "id".to_owned(),
+        client_secret: "secret".to_owned(),
+    };
+
+    assert!(
+        AuthenticationSettingsBuilder::new(NatsPermission::VariableHubRead)
+            .build_validated()
+            .is_ok()
+    );
+    assert!(
+        AuthenticationSettingsBuilder::new(NatsPermission::VariableHubRead)
+            .with_credentials(creds.clone())
+            .build_validated()
+            .is_ok()
+    );
+
+    let result = AuthenticationSettingsBuilder::new(NatsPermission::VariableHubRead)
+        .with_credentials(OAuth2Credentials {
+            client_id: String::new(),
+            ..creds.clone()
+        })
+        .with_custom_oauth2_endpoint("https://192.168.0.1/oauth2/token")
+        .build_validated();
+    assert_eq!(
+        result.unwrap_err(),
+        AuthSettingsError::EmptyClientId("test_client".to_owned())
+    );
+
+    let result = AuthenticationSettingsBuilder::new(NatsPermission::VariableHubRead)
+        .with_credentials(creds)
+        .with_custom_oauth2_endpoint("not a url")
+        .build_validated();
+    assert!(matches!(
+        result,
+        Err(AuthSettingsError::InvalidOAuth2Endpoint { .. })
+    ));
+}
+
+#[test]
+fn test_permission_presets() {
+    let auth_settings = AuthenticationSettingsBuilder::provider_and_consumer().build();
+    assert_eq!(
+        auth_settings.permissions,
+        [
+            NatsPermission::VariableHubProvide.as_str().to_owned(),
+            NatsPermission::VariableHubRead.as_str().to_owned(),
+        ]
+        .into_iter()
+        .collect::<HashSet<_>>()
+    );
+
+    let auth_settings = AuthenticationSettingsBuilder::provider_and_read_write_consumer().build();
+    assert_eq!(
+        auth_settings.permissions,
+        [
+            NatsPermission::VariableHubProvide.as_str().to_owned(),
+            NatsPermission::VariableHubReadWrite.as_str().to_owned(),
+        ]
+        .into_iter()
+        .collect::<HashSet<_>>()
+    );
+}
+
+#[test]
+fn test_scope_mapping() {
+    let auth_settings = AuthenticationSettingsBuilder::provider_and_consumer()
+        .with_scope_mapping(HashMap::from([(
+            NatsPermission::VariableHubRead,
+            "custom.variables.read".to_owned(),
+        )]))
+        .build();
+
+    assert_eq!(
+        auth_settings
+            .oauth2_scopes()
+            .into_iter()
+            .collect::<HashSet<_>>(),
+        HashSet::from([
+            NatsPermission::VariableHubProvide.as_str(),
+            "custom.variables.read"
+        ])
+    );
+
+    //the permissions keep their built-in names
+    assert!(auth_settings
+        .permissions
+        .contains(NatsPermission::VariableHubRead.as_str()));
+}
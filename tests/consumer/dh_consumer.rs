@@ -11,13 +11,21 @@ use u_os_hub_client::{
         AuthenticatedNatsConnection, AuthenticationSettings, AuthenticationSettingsBuilder,
         NatsPermission,
     },
-    consumer::dh_consumer::DataHubConsumer,
+    consumer::{
+        dh_consumer::{self, DataHubConsumer, ProviderConnectionEvent, ProviderWatchEvent},
+        nats_consumer,
+        variable_key::VariableKey,
+    },
+    dh_types::{VariableAccessType, VariableType, VariableValue},
+    nats_subjects,
     oauth2::OAuth2Credentials,
+    payload_codec::FlatbufferCodec,
+    provider::{ProviderBuilder, VariableBuilder},
 };
 
 use crate::{
     dummy_provider::{DummyProvider, PROVIDER_ID},
-    utils::{fake_registry::FakeRegistry, run_with_timeout, NATS_HOSTNAME},
+    utils::{create_auth_con, fake_registry::FakeRegistry, run_with_timeout, NATS_HOSTNAME},
 };
 
 const CONSUMER_ID: &str = "test_consumer";
@@ -62,6 +70,69 @@ async fn test_reuse_con() {
     .await;
 }
 
+#[tokio::test]
+#[serial]
+async fn test_connect_with_builder() {
+    run_with_timeout(async move {
+        let _fake_reg = FakeRegistry::new().await;
+
+        let auth_settings = consumer_auth_settings(NatsPermission::VariableHubRead);
+        let consumer = DataHubConsumer::builder()
+            .with_payload_codec(Arc::new(FlatbufferCodec))
+            .connect(NATS_HOSTNAME, &auth_settings)
+            .await
+            .unwrap();
+
+        let _dummy_provider = DummyProvider::new().await.unwrap();
+        consumer.wait_for_provider(PROVIDER_ID).await.unwrap();
+
+        let states = consumer
+            .read_qualified(&[(PROVIDER_ID, "my_folder.rw_int")])
+            .await
+            .unwrap();
+        assert_eq!(states[0].value, VariableValue::Int(1000));
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn test_request_timeout() {
+    run_with_timeout(async move {
+        //a registry that receives the request, but never replies
+        let registry_con = create_auth_con("silent-registry").await;
+        let _read_sub = registry_con
+            .get_client()
+            .subscribe(nats_subjects::registry_providers_read_query())
+            .await
+            .unwrap();
+        registry_con.get_client().flush().await.unwrap();
+
+        let auth_settings = consumer_auth_settings(NatsPermission::VariableHubRead);
+        let consumer = DataHubConsumer::builder()
+            .with_request_timeout(Duration::from_millis(200))
+            .connect(NATS_HOSTNAME, &auth_settings)
+            .await
+            .unwrap();
+        assert_eq!(
+            consumer.get_nats_consumer().get_request_timeout(),
+            Duration::from_millis(200)
+        );
+
+        let start = tokio::time::Instant::now();
+        let result = consumer.read_provider_ids().await;
+
+        //the default timeout of 10 seconds would exceed the test timeout
+        assert!(start.elapsed() < Duration::from_secs(2));
+        assert!(matches!(
+            result,
+            Err(dh_consumer::Error::LowLevelApi(nats_consumer::Error::NatsRequest(e)))
+                if e.kind() == async_nats::RequestErrorKind::TimedOut
+        ));
+    })
+    .await;
+}
+
 #[tokio::test]
 #[serial]
 async fn test_invalid_scope() {
@@ -151,3 +222,274 @@ async fn monitor_providers() {
     })
     .await;
 }
+
+#[tokio::test]
+#[serial]
+async fn watch_providers() {
+    run_with_timeout(async move {
+        let _fake_reg = FakeRegistry::new().await;
+
+        let auth_settings = consumer_auth_settings(NatsPermission::VariableHubRead);
+        let consumer = Arc::new(
+            DataHubConsumer::connect(NATS_HOSTNAME, &auth_settings)
+                .await
+                .unwrap(),
+        );
+
+        let mut matching = consumer.watch_providers("dummy").await.unwrap();
+        let mut not_matching = consumer.watch_providers("plc-").await.unwrap();
+        let mut connected = consumer.watch_and_connect_providers("").await.unwrap();
+
+        let dummy_provider = DummyProvider::new().await.unwrap();
+
+        assert_eq!(
+            matching.next().await.unwrap(),
+            ProviderWatchEvent::Appeared(PROVIDER_ID.to_string())
+        );
+        match connected.next().await.unwrap() {
+            ProviderConnectionEvent::Connected(connection) => {
+                assert!(!connection
+                    .get_all_variable_definitions()
+                    .unwrap()
+                    .is_empty());
+            }
+            _ => panic!("expected a connection to the dummy provider"),
+        }
+
+        drop(dummy_provider);
+
+        assert_eq!(
+            matching.next().await.unwrap(),
+            ProviderWatchEvent::Gone(PROVIDER_ID.to_string())
+        );
+        assert!(matches!(
+            connected.next().await.unwrap(),
+            ProviderConnectionEvent::Gone(id) if id == PROVIDER_ID
+        ));
+
+        //providers that do not match the prefix are not reported
+        let no_event = tokio::time::timeout(Duration::from_millis(100), not_matching.next()).await;
+        assert!(no_event.is_err());
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn read_qualified() {
+    run_with_timeout(async move {
+        let _fake_reg = FakeRegistry::new().await;
+
+        let auth_settings = consumer_auth_settings(NatsPermission::VariableHubRead);
+        let consumer = DataHubConsumer::connect(NATS_HOSTNAME, &auth_settings)
+            .await
+            .unwrap();
+
+        let _dummy_provider = DummyProvider::new().await.unwrap();
+        consumer.wait_for_provider(PROVIDER_ID).await.unwrap();
+
+        //results must be in request order
+        let states = consumer
+            .read_qualified(&[
+                (PROVIDER_ID, "my_folder.rw_string"),
+                (PROVIDER_ID, "my_folder.rw_int"),
+                (PROVIDER_ID, "my_folder.rw_string"),
+            ])
+            .await
+            .unwrap();
+        assert_eq!(states.len(), 3);
+        assert_eq!(states[0].value, VariableValue::from("write me!"));
+        assert_eq!(states[1].value, VariableValue::Int(1000));
+        assert_eq!(states[2].value, VariableValue::from("write me!"));
+
+        //unknown keys and providers should fail
+        assert!(consumer
+            .read_qualified(&[(PROVIDER_ID, "doesntexist")])
+            .await
+            .is_err());
+        assert!(consumer
+            .read_qualified(&[("unknown_provider", "my_folder.rw_int")])
+            .await
+            .is_err());
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn read_variables_multi() {
+    run_with_timeout(async move {
+        let _fake_reg = FakeRegistry::new().await;
+
+        let auth_settings = consumer_auth_settings(NatsPermission::VariableHubRead);
+        let consumer = DataHubConsumer::connect(NATS_HOSTNAME, &auth_settings)
+            .await
+            .unwrap();
+
+        let _dummy_provider = DummyProvider::new().await.unwrap();
+        consumer.wait_for_provider(PROVIDER_ID).await.unwrap();
+
+        let results = consumer
+            .read_variables_multi(&[
+                (
+                    PROVIDER_ID,
+                    vec![
+                        VariableKey::from("my_folder.rw_string"),
+                        VariableKey::from("my_folder.rw_int"),
+                    ],
+                ),
+                (
+                    "unknown_provider",
+                    vec![VariableKey::from("my_folder.rw_int")],
+                ),
+                (PROVIDER_ID, vec![VariableKey::from("my_folder.rw_int")]),
+            ])
+            .await;
+        assert_eq!(results.len(), 2);
+
+        //results must be in key order and contain the keys of all requests of a provider
+        let states = results[PROVIDER_ID].as_ref().unwrap();
+        assert_eq!(states.len(), 3);
+        assert_eq!(states[0].1.value, VariableValue::from("write me!"));
+        assert_eq!(states[1].1.value, VariableValue::Int(1000));
+        assert_eq!(states[2].1.value, VariableValue::Int(1000));
+        assert_ne!(states[0].0, states[1].0);
+        assert_eq!(states[1].0, states[2].0);
+
+        //the unknown provider does not affect the other results
+        assert!(results["unknown_provider"].is_err());
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn read_provider_definition_once() {
+    run_with_timeout(async move {
+        let _fake_reg = FakeRegistry::new().await;
+
+        let auth_settings = consumer_auth_settings(NatsPermission::VariableHubRead);
+        let consumer = DataHubConsumer::connect(NATS_HOSTNAME, &auth_settings)
+            .await
+            .unwrap();
+
+        let _dummy_provider = DummyProvider::new().await.unwrap();
+        consumer.wait_for_provider(PROVIDER_ID).await.unwrap();
+
+        let mut definitions = consumer
+            .read_provider_definition_once(PROVIDER_ID)
+            .await
+            .unwrap();
+        definitions.sort_by_key(|def| def.id);
+
+        let keys: Vec<_> = definitions.iter().map(|def| def.key.as_str()).collect();
+        assert_eq!(
+            keys,
+            [
+                "my_folder.ro_float",
+                "my_folder.rw_string",
+                "my_folder.rw_int",
+                "my_folder.ro_int"
+            ]
+        );
+        assert_eq!(definitions[2].data_type, VariableType::Int64);
+
+        //unknown providers should fail
+        assert!(consumer
+            .read_provider_definition_once("unknown_provider")
+            .await
+            .is_err());
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn compare_providers() {
+    run_with_timeout(async move {
+        const MIGRATED_PROVIDER_ID: &str = "migrated_provider";
+
+        let _fake_reg = FakeRegistry::new().await;
+
+        let auth_settings = consumer_auth_settings(NatsPermission::VariableHubRead);
+        let consumer = DataHubConsumer::connect(NATS_HOSTNAME, &auth_settings)
+            .await
+            .unwrap();
+
+        let _dummy_provider = DummyProvider::new().await.unwrap();
+        consumer.wait_for_provider(PROVIDER_ID).await.unwrap();
+
+        //same keys as the dummy provider, but with different ids and some differences
+        let variables = vec![
+            VariableBuilder::new(1, "my_folder.ro_float")
+                .initial_value(0.0)
+                .build()
+                .unwrap(),
+            VariableBuilder::new(2, "my_folder.rw_string")
+                .initial_value("read only now")
+                .build()
+                .unwrap(),
+            VariableBuilder::new(3, "my_folder.rw_int")
+                .access_type(VariableAccessType::ReadWrite)
+                .initial_value("not an int")
+                .build()
+                .unwrap(),
+            VariableBuilder::new(4, "my_folder.new_var")
+                .initial_value(0)
+                .build()
+                .unwrap(),
+        ];
+        let _migrated_provider = ProviderBuilder::new()
+            .add_variables(variables)
+            .unwrap()
+            .register_with_existing_connection(create_auth_con(MIGRATED_PROVIDER_ID).await)
+            .await
+            .unwrap();
+        consumer
+            .wait_for_provider(MIGRATED_PROVIDER_ID)
+            .await
+            .unwrap();
+
+        //a provider is always equivalent to itself
+        let comparison = consumer
+            .compare_providers(PROVIDER_ID, PROVIDER_ID)
+            .await
+            .unwrap();
+        assert!(comparison.is_equivalent());
+
+        let comparison = consumer
+            .compare_providers(PROVIDER_ID, MIGRATED_PROVIDER_ID)
+            .await
+            .unwrap();
+        assert!(!comparison.is_equivalent());
+
+        let only_in_a: Vec<_> = comparison
+            .only_in_a
+            .iter()
+            .map(|def| def.key.as_str())
+            .collect();
+        assert_eq!(only_in_a, ["my_folder.ro_int"]);
+
+        let only_in_b: Vec<_> = comparison
+            .only_in_b
+            .iter()
+            .map(|def| def.key.as_str())
+            .collect();
+        assert_eq!(only_in_b, ["my_folder.new_var"]);
+
+        assert_eq!(comparison.mismatches.len(), 2);
+        assert_eq!(comparison.mismatches[0].key, "my_folder.rw_int");
+        assert!(comparison.mismatches[0].type_differs());
+        assert!(!comparison.mismatches[0].access_differs());
+        assert_eq!(comparison.mismatches[1].key, "my_folder.rw_string");
+        assert!(!comparison.mismatches[1].type_differs());
+        assert!(comparison.mismatches[1].access_differs());
+
+        //unknown providers should fail
+        assert!(consumer
+            .compare_providers(PROVIDER_ID, "unknown_provider")
+            .await
+            .is_err());
+    })
+    .await;
+}
@@ -2,9 +2,13 @@
 //
 // SPDX-License-Identifier: MIT
 
-use std::{collections::HashMap, sync::Arc, time::Duration};
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Arc,
+    time::Duration,
+};
 
-use futures::StreamExt;
+use futures::{stream::FusedStream, StreamExt};
 use serial_test::serial;
 use tokio::time::timeout;
 use u_os_hub_client::{
@@ -12,23 +16,40 @@ use u_os_hub_client::{
         AuthenticationSettings, AuthenticationSettingsBuilder, NatsPermission,
     },
     consumer::{
-        connected_dh_provider::{self, DataHubProviderConnection, ProviderEvent},
-        connected_nats_provider,
-        consumer_types::VariableState,
+        connected_dh_provider::{
+            self, DataHubProviderConnection, ProviderEvent, SchemaMismatch, SchemaTypeMismatch,
+        },
+        connected_nats_provider::{
+            self, ConnectedNatsProvider, WriteAck, HISTORY_FROM_HEADER, HISTORY_TO_HEADER,
+        },
+        consumer_types::{FilterMode, OverflowStrategy, SubscriptionBuffer, VariableState},
         dh_consumer::DataHubConsumer,
+        stats::{RunningStats, StatsSnapshot},
         variable_key::VariableKey,
     },
     dh_types::{
-        VariableAccessType, VariableDefinition, VariableID, VariableQuality, VariableType,
-        VariableValue,
+        self, ProviderStatus, TimestampValue, VariableAccessType, VariableDefinition, VariableID,
+        VariableQuality, VariableType, VariableValue,
+    },
+    generated::weidmueller::ucontrol::hub::{
+        ProviderDefinitionState, ProviderDefinitionT, ReadVariablesQueryRequestT,
+        ReadVariablesQueryResponseT, WriteVariablesCommandT,
     },
+    nats_subjects,
     oauth2::OAuth2Credentials,
+    payload_builders::{
+        build_read_provider_definition_response, build_read_variables_query_response,
+        VariableUpdate,
+    },
+    payload_codec::{FlatbufferCodec, PayloadCodec},
+    prelude::test::MockProvider,
+    provider::{ProviderBuilder, VariableBuilder},
 };
 
 use crate::{
     dummy_provider::{self, DummyProvider, PROVIDER_ID},
     incompatible_provider::{self, IncompatibleProvider, VariableIDs, INCOMPATIBLE_ENUM_VALUE},
-    utils::{fake_registry::FakeRegistry, run_with_timeout, NATS_HOSTNAME},
+    utils::{create_auth_con, fake_registry::FakeRegistry, run_with_timeout, NATS_HOSTNAME},
 };
 
 /// Things that can go wrong while we are connected to a provider:
@@ -87,8 +108,357 @@ async fn registry_offline() {
 
 #[tokio::test]
 #[serial]
-async fn provider_offline() {
+async fn connect_with_known_definition() {
+    run_with_timeout(Box::pin(async move {
+        let fake_reg = FakeRegistry::new().await;
+
+        let auth_settings = consumer_auth_settings(NatsPermission::VariableHubRead);
+        let consumer = Arc::new(
+            DataHubConsumer::connect(NATS_HOSTNAME, &auth_settings)
+                .await
+                .unwrap(),
+        );
+
+        let _dummy_provider = DummyProvider::new().await.unwrap();
+        let dh_provider_con = DataHubProviderConnection::new(consumer.clone(), PROVIDER_ID, true)
+            .await
+            .unwrap();
+
+        //remember the definition of the provider, e.g. from a previous session
+        let ll_provider = dh_provider_con.get_connected_nats_provider();
+        let mut known_def = ProviderDefinitionT::default();
+        known_def.fingerprint = ll_provider.get_fingerprint().unwrap();
+        known_def.variable_definitions = Some(
+            ll_provider
+                .get_all_variable_definitions()
+                .into_values()
+                .collect(),
+        );
+        known_def.state = ProviderDefinitionState::OK;
+
+        drop(fake_reg);
+
+        //The definition can not be read while the registry is offline
+        let con_result =
+            ConnectedNatsProvider::new(consumer.get_nats_consumer().clone(), PROVIDER_ID).await;
+        assert!(con_result.is_err());
+
+        let ll_provider = ConnectedNatsProvider::new_with_definition(
+            consumer.get_nats_consumer().clone(),
+            PROVIDER_ID,
+            known_def.clone(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(ll_provider.get_fingerprint(), Some(known_def.fingerprint));
+
+        //Variables are read directly from the provider
+        let mut read_request = ReadVariablesQueryRequestT::default();
+        read_request.ids = Some(vec![300]);
+        let response = ll_provider.read_variables(&read_request).await.unwrap();
+        assert_eq!(
+            response.variables.provider_definition_fingerprint,
+            known_def.fingerprint
+        );
+        let items = response.variables.items.unwrap();
+        assert_eq!(items.len(), 1);
+        assert_eq!(
+            Option::<VariableValue>::from(items[0].value.clone()),
+            Some(VariableValue::Int(1000))
+        );
+    }))
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn deserialization_error_count() {
+    run_with_timeout(Box::pin(async move {
+        let _fake_reg = FakeRegistry::new().await;
+
+        let auth_settings = consumer_auth_settings(NatsPermission::VariableHubRead);
+        let consumer = Arc::new(
+            DataHubConsumer::connect(NATS_HOSTNAME, &auth_settings)
+                .await
+                .unwrap(),
+        );
+
+        let _dummy_provider = DummyProvider::new().await.unwrap();
+        let dh_provider_con = DataHubProviderConnection::new(consumer.clone(), PROVIDER_ID, true)
+            .await
+            .unwrap();
+        let ll_provider = dh_provider_con.get_connected_nats_provider();
+        assert_eq!(ll_provider.deserialization_error_count(), 0);
+
+        let mut var_changes = ll_provider.subscribe_variables().await.unwrap();
+
+        //publish an invalid change event as the provider
+        let provider_con = create_auth_con(PROVIDER_ID).await;
+        provider_con
+            .get_client()
+            .publish(
+                nats_subjects::vars_changed_event(PROVIDER_ID),
+                vec![1, 2, 3].into(),
+            )
+            .await
+            .unwrap();
+
+        while var_changes.next().await.unwrap().is_ok() {}
+        assert_eq!(ll_provider.deserialization_error_count(), 1);
+
+        //publish an invalid definition event as the registry, which is counted by the internal event loop
+        let registry_con = create_auth_con("fake-registry").await;
+        registry_con
+            .get_client()
+            .publish(
+                nats_subjects::registry_provider_definition_changed_event(PROVIDER_ID),
+                vec![1, 2, 3].into(),
+            )
+            .await
+            .unwrap();
+
+        while ll_provider.deserialization_error_count() < 2 {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(ll_provider.deserialization_error_count(), 2);
+    }))
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn get_variable_index() {
+    run_with_timeout(Box::pin(async move {
+        let _fake_reg = FakeRegistry::new().await;
+
+        let auth_settings = consumer_auth_settings(NatsPermission::VariableHubRead);
+        let consumer = Arc::new(
+            DataHubConsumer::connect(NATS_HOSTNAME, &auth_settings)
+                .await
+                .unwrap(),
+        );
+
+        let _dummy_provider = DummyProvider::new().await.unwrap();
+        let dh_provider_con = DataHubProviderConnection::new(consumer.clone(), PROVIDER_ID, true)
+            .await
+            .unwrap();
+
+        let index: Vec<_> = dh_provider_con.get_variable_index().into_iter().collect();
+        assert_eq!(
+            index,
+            [
+                ("my_folder.ro_float".to_string(), 100),
+                ("my_folder.ro_int".to_string(), 400),
+                ("my_folder.rw_int".to_string(), 300),
+                ("my_folder.rw_string".to_string(), 200),
+            ]
+        );
+    }))
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn read_and_write_on_custom_subject() {
+    run_with_timeout(Box::pin(async move {
+        const MOCK_PROVIDER_ID: &str = "mock_provider";
+
+        let _fake_reg = FakeRegistry::new().await;
+
+        let auth_settings = consumer_auth_settings(NatsPermission::VariableHubReadWrite);
+        let consumer = Arc::new(
+            DataHubConsumer::connect(NATS_HOSTNAME, &auth_settings)
+                .await
+                .unwrap(),
+        );
+
+        let _dummy_provider = DummyProvider::new().await.unwrap();
+        let dh_provider_con = DataHubProviderConnection::new(consumer.clone(), PROVIDER_ID, true)
+            .await
+            .unwrap();
+        let ll_provider = dh_provider_con.get_connected_nats_provider();
+        let fingerprint = ll_provider.get_fingerprint().unwrap();
+
+        //a mock that answers reads with a different value than the dummy provider
+        let mock_con = create_auth_con(MOCK_PROVIDER_ID).await;
+        let mut read_sub = mock_con
+            .get_client()
+            .subscribe(nats_subjects::read_variables_query(MOCK_PROVIDER_ID))
+            .await
+            .unwrap();
+        let mut write_sub = mock_con
+            .get_client()
+            .subscribe(nats_subjects::write_variables_command(MOCK_PROVIDER_ID))
+            .await
+            .unwrap();
+
+        let mock_client = mock_con.get_client().clone();
+        let mock_task = tokio::spawn(async move {
+            let mock_variables = BTreeMap::from([(
+                300,
+                VariableBuilder::new(300, "my_folder.rw_int")
+                    .initial_value(42)
+                    .build()
+                    .unwrap(),
+            )]);
+
+            while let Some(msg) = read_sub.next().await {
+                let request = FlatbufferCodec
+                    .decode_read_variables_query_request(&msg.payload)
+                    .unwrap();
+                let resp =
+                    build_read_variables_query_response(request, &mock_variables, fingerprint);
+                mock_client.publish(msg.reply.unwrap(), resp).await.unwrap();
+                mock_client.flush().await.unwrap();
+            }
+        });
+
+        let mut read_request = ReadVariablesQueryRequestT::default();
+        read_request.ids = Some(vec![300]);
+        let response = ll_provider
+            .read_variables_on_subject(
+                &nats_subjects::read_variables_query(MOCK_PROVIDER_ID),
+                &read_request,
+            )
+            .await
+            .unwrap();
+        let items = response.variables.items.unwrap();
+        assert_eq!(items.len(), 1);
+        assert_eq!(
+            Option::<VariableValue>::from(items[0].value.clone()),
+            Some(VariableValue::Int(42))
+        );
+
+        //the provider itself is not affected
+        let response = ll_provider.read_variables(&read_request).await.unwrap();
+        let items = response.variables.items.unwrap();
+        assert_eq!(
+            Option::<VariableValue>::from(items[0].value.clone()),
+            Some(VariableValue::Int(1000))
+        );
+
+        let mut write_command = WriteVariablesCommandT::default();
+        write_command.variables.provider_definition_fingerprint = fingerprint;
+        ll_provider
+            .write_variables_on_subject(
+                &nats_subjects::write_variables_command(MOCK_PROVIDER_ID),
+                &write_command,
+            )
+            .await
+            .unwrap();
+
+        let msg = write_sub.next().await.unwrap();
+        let received = FlatbufferCodec
+            .decode_write_variables_command(&msg.payload)
+            .unwrap();
+        assert_eq!(received, write_command);
+
+        mock_task.abort();
+    }))
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn duplicate_provider_detected() {
+    run_with_timeout(async move {
+        //Simulates a registry that answers alternately with the definitions of two providers using the same ID
+        let registry_con = create_auth_con("fake-registry").await;
+        let mut read_provider_def_sub = registry_con
+            .get_client()
+            .subscribe(nats_subjects::registry_provider_definition_read_query(
+                PROVIDER_ID,
+            ))
+            .await
+            .unwrap();
+
+        let registry_task = tokio::spawn(async move {
+            let client = registry_con.get_client();
+            let mut fingerprint = 0;
+
+            while let Some(msg) = read_provider_def_sub.next().await {
+                fingerprint = if fingerprint == 1 { 2 } else { 1 };
+
+                let mut provider_def = ProviderDefinitionT::default();
+                provider_def.fingerprint = fingerprint;
+                provider_def.state = ProviderDefinitionState::OK;
+
+                let resp = build_read_provider_definition_response(Some(provider_def));
+                client.publish(msg.reply.unwrap(), resp).await.unwrap();
+                client.flush().await.unwrap();
+            }
+        });
+
+        let auth_settings = consumer_auth_settings(NatsPermission::VariableHubRead);
+        let consumer = Arc::new(
+            DataHubConsumer::connect(NATS_HOSTNAME, &auth_settings)
+                .await
+                .unwrap(),
+        );
+
+        let con_result = DataHubProviderConnection::new(consumer.clone(), PROVIDER_ID, false).await;
+        assert!(matches!(
+            con_result,
+            Err(connected_dh_provider::Error::LowLevelApi(
+                connected_nats_provider::Error::DuplicateProviderDetected(_)
+            ))
+        ));
+
+        registry_task.abort();
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn consecutive_definition_updates_are_no_duplicate_provider() {
     run_with_timeout(async move {
+        //Simulates a registry whose provider updates its definition between every read
+        let registry_con = create_auth_con("fake-registry").await;
+        let mut read_provider_def_sub = registry_con
+            .get_client()
+            .subscribe(nats_subjects::registry_provider_definition_read_query(
+                PROVIDER_ID,
+            ))
+            .await
+            .unwrap();
+
+        let registry_task = tokio::spawn(async move {
+            let client = registry_con.get_client();
+            let mut fingerprint = 0;
+
+            while let Some(msg) = read_provider_def_sub.next().await {
+                fingerprint += 1;
+
+                let mut provider_def = ProviderDefinitionT::default();
+                provider_def.fingerprint = fingerprint;
+                provider_def.state = ProviderDefinitionState::OK;
+
+                let resp = build_read_provider_definition_response(Some(provider_def));
+                client.publish(msg.reply.unwrap(), resp).await.unwrap();
+                client.flush().await.unwrap();
+            }
+        });
+
+        let auth_settings = consumer_auth_settings(NatsPermission::VariableHubRead);
+        let consumer = Arc::new(
+            DataHubConsumer::connect(NATS_HOSTNAME, &auth_settings)
+                .await
+                .unwrap(),
+        );
+
+        let con_result = DataHubProviderConnection::new(consumer.clone(), PROVIDER_ID, false).await;
+        assert!(con_result.is_ok());
+
+        registry_task.abort();
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn provider_offline() {
+    run_with_timeout(Box::pin(async move {
         let _fake_reg = FakeRegistry::new().await;
 
         let auth_settings = consumer_auth_settings(NatsPermission::VariableHubRead);
@@ -120,7 +490,7 @@ async fn provider_offline() {
             .await
             .unwrap();
         assert_eq!(var_states.len(), 4);
-    })
+    }))
     .await;
 }
 
@@ -176,6 +546,101 @@ async fn wait_for_variable_keys() {
     .await;
 }
 
+#[tokio::test]
+#[serial]
+async fn subscribe_single_variable_with_current() {
+    run_with_timeout(Box::pin(async move {
+        let _fake_reg = FakeRegistry::new().await;
+
+        let auth_settings = consumer_auth_settings(NatsPermission::VariableHubRead);
+        let consumer = Arc::new(
+            DataHubConsumer::connect(NATS_HOSTNAME, &auth_settings)
+                .await
+                .unwrap(),
+        );
+
+        let dummy_provider = DummyProvider::new().await.unwrap();
+        let dh_provider_con = DataHubProviderConnection::new(consumer.clone(), PROVIDER_ID, true)
+            .await
+            .unwrap();
+
+        //rw_int never changes by itself, so the first item must be the current state
+        let mut sub = dh_provider_con
+            .subscribe_single_variable_with_current("my_folder.rw_int")
+            .await
+            .unwrap();
+        let state = timeout(Duration::from_millis(100), sub.next())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(state.value, VariableValue::Int(1000));
+
+        //the stream is established even if the provider is offline
+        drop(dummy_provider);
+        let mut sub = dh_provider_con
+            .subscribe_single_variable_with_current("my_folder.ro_int")
+            .await
+            .unwrap();
+        assert!(timeout(Duration::from_millis(500), sub.next())
+            .await
+            .is_err());
+
+        //and yields once the provider is back
+        let _dummy_provider = DummyProvider::new().await.unwrap();
+        let state = sub.next().await.unwrap();
+        assert!(matches!(state.value, VariableValue::Int(_)));
+    }))
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn wait_for_variable_keys_cancelled() {
+    run_with_timeout(async move {
+        let _fake_reg = FakeRegistry::new().await;
+
+        let auth_settings = consumer_auth_settings(NatsPermission::VariableHubRead);
+        let consumer = Arc::new(
+            DataHubConsumer::connect(NATS_HOSTNAME, &auth_settings)
+                .await
+                .unwrap(),
+        );
+
+        let _dummy_provider = DummyProvider::new().await.unwrap();
+        let dh_provider_con = DataHubProviderConnection::new(consumer.clone(), PROVIDER_ID, true)
+            .await
+            .unwrap();
+
+        //available keys should return instantly, even if cancel never completes
+        assert!(dh_provider_con
+            .wait_until_variable_keys_are_available_or_cancelled(
+                &["my_folder.ro_float"],
+                std::future::pending()
+            )
+            .await
+            .is_ok());
+
+        //invalid keys never arrive, so the wait ends once it is cancelled
+        let (cancel_tx, cancel_rx) = tokio::sync::oneshot::channel::<()>();
+        let cancel_task = tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            cancel_tx.send(()).unwrap();
+        });
+
+        let wait_res = dh_provider_con
+            .wait_until_variable_keys_are_available_or_cancelled(&["doesntexist"], async {
+                let _ = cancel_rx.await;
+            })
+            .await;
+        assert!(matches!(
+            wait_res,
+            Err(connected_dh_provider::Error::Cancelled)
+        ));
+        cancel_task.await.unwrap();
+    })
+    .await;
+}
+
 #[tokio::test]
 #[serial]
 async fn read_var_defs() {
@@ -196,6 +661,9 @@ async fn read_var_defs() {
 
         //invalid ids and keys should not work
         assert!(dh_provider_con.variable_key_from_id(999).is_err());
+        assert!(!dh_provider_con.variable_exists("unknown_key"));
+        assert!(dh_provider_con.variable_exists("my_folder.ro_float"));
+        assert!(dh_provider_con.variable_exists(VariableKey::from("my_folder.rw_int")));
         assert!(dh_provider_con
             .get_variable_definition("unknown_key")
             .is_err());
@@ -292,7 +760,7 @@ async fn read_var_defs() {
 
 #[tokio::test]
 #[serial]
-async fn read_var_state() {
+async fn assert_schema() {
     run_with_timeout(async move {
         let _fake_reg = FakeRegistry::new().await;
 
@@ -308,8 +776,195 @@ async fn read_var_state() {
             .await
             .unwrap();
 
-        //read all at once
-        let var_states = dh_provider_con
+        dh_provider_con
+            .assert_schema(&[
+                ("my_folder.ro_float", VariableType::Float64),
+                ("my_folder.ro_int", VariableType::Int64),
+                ("my_folder.rw_string", VariableType::String),
+                ("my_folder.rw_int", VariableType::Int64),
+            ])
+            .unwrap();
+
+        let mismatch = dh_provider_con
+            .assert_schema(&[
+                ("my_folder.ro_float", VariableType::Float64),
+                ("my_folder.ro_int", VariableType::String),
+                ("my_folder.rw_string", VariableType::String),
+                ("my_folder.unknown", VariableType::Boolean),
+            ])
+            .unwrap_err();
+
+        assert_eq!(
+            mismatch,
+            SchemaMismatch {
+                missing: vec!["my_folder.unknown".to_owned()],
+                unexpected: vec!["my_folder.rw_int".to_owned()],
+                type_mismatches: vec![SchemaTypeMismatch {
+                    key: "my_folder.ro_int".to_owned(),
+                    expected: VariableType::String,
+                    actual: VariableType::Int64,
+                }],
+            }
+        );
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn definition_diff_since() {
+    run_with_timeout(async move {
+        let _fake_reg = FakeRegistry::new().await;
+
+        let auth_settings = consumer_auth_settings(NatsPermission::VariableHubRead);
+        let consumer = Arc::new(
+            DataHubConsumer::connect(NATS_HOSTNAME, &auth_settings)
+                .await
+                .unwrap(),
+        );
+
+        let _dummy_provider = DummyProvider::new().await.unwrap();
+        let dh_provider_con = DataHubProviderConnection::new(consumer.clone(), PROVIDER_ID, true)
+            .await
+            .unwrap();
+
+        //no changes against the current definitions
+        let mut snapshot = dh_provider_con.get_all_variable_definitions().unwrap();
+        assert!(dh_provider_con.definition_diff_since(&snapshot).is_empty());
+
+        //simulate an old snapshot that differs from the current definitions
+        snapshot.retain(|def| def.key != "my_folder.rw_int");
+        let removed_def = VariableDefinition {
+            id: 500,
+            key: "my_folder.removed".to_string(),
+            access_type: VariableAccessType::ReadOnly,
+            experimental: false,
+            data_type: VariableType::Boolean,
+        };
+        snapshot.push(removed_def.clone());
+        let rw_string = snapshot
+            .iter_mut()
+            .find(|def| def.key == "my_folder.rw_string")
+            .unwrap();
+        rw_string.data_type = VariableType::Int64;
+
+        let delta = dh_provider_con.definition_diff_since(&snapshot);
+        assert_eq!(
+            delta.added,
+            vec![dh_provider_con
+                .get_variable_definition("my_folder.rw_int")
+                .unwrap()]
+        );
+        assert_eq!(delta.removed, vec![removed_def]);
+        assert_eq!(
+            delta.type_changed,
+            vec![dh_provider_con
+                .get_variable_definition("my_folder.rw_string")
+                .unwrap()]
+        );
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn debug_state() {
+    run_with_timeout(async move {
+        let _fake_reg = FakeRegistry::new().await;
+
+        let auth_settings = consumer_auth_settings(NatsPermission::VariableHubRead);
+        let consumer = Arc::new(
+            DataHubConsumer::connect(NATS_HOSTNAME, &auth_settings)
+                .await
+                .unwrap(),
+        );
+
+        let _dummy_provider = DummyProvider::new().await.unwrap();
+        let dh_provider_con = DataHubProviderConnection::new(consumer.clone(), PROVIDER_ID, true)
+            .await
+            .unwrap();
+
+        let connected_nats_provider = dh_provider_con.get_connected_nats_provider();
+        let debug_state = connected_nats_provider.debug_state();
+
+        assert_eq!(
+            debug_state.fingerprint,
+            connected_nats_provider.get_fingerprint()
+        );
+        assert!(debug_state.fingerprint.is_some());
+        assert_eq!(debug_state.var_mapping_len, 4);
+        assert_eq!(debug_state.id_to_key.len(), 4);
+        assert_eq!(debug_state.id_to_key.get(&300).unwrap(), "my_folder.rw_int");
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn read_variables_raw() {
+    run_with_timeout(async move {
+        let _fake_reg = FakeRegistry::new().await;
+
+        let auth_settings = consumer_auth_settings(NatsPermission::VariableHubRead);
+        let consumer = Arc::new(
+            DataHubConsumer::connect(NATS_HOSTNAME, &auth_settings)
+                .await
+                .unwrap(),
+        );
+
+        let _dummy_provider = DummyProvider::new().await.unwrap();
+        let dh_provider_con = DataHubProviderConnection::new(consumer.clone(), PROVIDER_ID, true)
+            .await
+            .unwrap();
+
+        let rw_int_id = dh_provider_con
+            .variable_id_from_key("my_folder.rw_int")
+            .unwrap();
+
+        let response_bytes = dh_provider_con
+            .get_connected_nats_provider()
+            .read_variables_raw(&ReadVariablesQueryRequestT::default())
+            .await
+            .unwrap();
+
+        let mut ids = Vec::new();
+        connected_nats_provider::for_each_variable(&response_bytes, |var| ids.push(var.id()))
+            .unwrap();
+        ids.sort_unstable();
+        assert_eq!(ids.len(), 4);
+        assert!(ids.contains(&rw_int_id));
+
+        //invalid payloads are rejected without calling the callback
+        let mut called = false;
+        assert!(matches!(
+            connected_nats_provider::for_each_variable(&[1, 2, 3], |_| called = true),
+            Err(connected_nats_provider::Error::InvalidPayload(_))
+        ));
+        assert!(!called);
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn read_var_state() {
+    run_with_timeout(async move {
+        let _fake_reg = FakeRegistry::new().await;
+
+        let auth_settings = consumer_auth_settings(NatsPermission::VariableHubRead);
+        let consumer = Arc::new(
+            DataHubConsumer::connect(NATS_HOSTNAME, &auth_settings)
+                .await
+                .unwrap(),
+        );
+
+        let _dummy_provider = DummyProvider::new().await.unwrap();
+        let dh_provider_con = DataHubProviderConnection::new(consumer.clone(), PROVIDER_ID, true)
+            .await
+            .unwrap();
+
+        //read all at once
+        let var_states = dh_provider_con
             .read_variables(Option::<&[VariableKey]>::None)
             .await
             .unwrap();
@@ -380,7 +1035,7 @@ async fn read_var_state() {
 
 #[tokio::test]
 #[serial]
-async fn subscribe_variables() {
+async fn read_with_timeout() {
     run_with_timeout(async move {
         let _fake_reg = FakeRegistry::new().await;
 
@@ -391,44 +1046,1299 @@ async fn subscribe_variables() {
                 .unwrap(),
         );
 
-        let dummy_provider = DummyProvider::new().await.unwrap();
+        let _dummy_provider = DummyProvider::new().await.unwrap();
         let dh_provider_con = DataHubProviderConnection::new(consumer.clone(), PROVIDER_ID, true)
             .await
             .unwrap();
 
-        //The subscribe method allows to filter for keys that do not yet exist
-        let mut change_stream = dh_provider_con
-            .subscribe_variables_with_filter(Some(vec!["my_folder.ro_float", "my_folder.ro_int2"]))
+        let state = dh_provider_con
+            .read_single_variable_with_timeout("my_folder.rw_int", Duration::from_secs(5))
+            .await
+            .unwrap();
+        assert_eq!(state.value, VariableValue::Int(1000));
+
+        let var_states = dh_provider_con
+            .read_variables_with_timeout(
+                Some(&["my_folder.rw_int", "my_folder.ro_int"]),
+                Duration::from_secs(5),
+            )
+            .await
+            .unwrap();
+        assert_eq!(var_states.len(), 2);
+
+        //the provider can not answer before the first poll of the read
+        let result = dh_provider_con
+            .read_single_variable_with_timeout("my_folder.rw_int", Duration::ZERO)
+            .await;
+        assert!(matches!(result, Err(connected_dh_provider::Error::Timeout)));
+
+        let result = dh_provider_con
+            .read_variables_with_timeout(Option::<&[VariableKey]>::None, Duration::ZERO)
+            .await;
+        assert!(matches!(result, Err(connected_dh_provider::Error::Timeout)));
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn read_typed_variable() {
+    run_with_timeout(async move {
+        let _fake_reg = FakeRegistry::new().await;
+
+        let auth_settings = consumer_auth_settings(NatsPermission::VariableHubRead);
+        let consumer = Arc::new(
+            DataHubConsumer::connect(NATS_HOSTNAME, &auth_settings)
+                .await
+                .unwrap(),
+        );
+
+        let _dummy_provider = DummyProvider::new().await.unwrap();
+        let dh_provider_con = DataHubProviderConnection::new(consumer.clone(), PROVIDER_ID, true)
+            .await
+            .unwrap();
+
+        let rw_int: i64 = dh_provider_con
+            .read_single_variable_typed("my_folder.rw_int")
+            .await
+            .unwrap();
+        assert_eq!(rw_int, 1000);
+
+        let rw_string = dh_provider_con
+            .read_single_variable_typed::<String>("my_folder.rw_string")
+            .await
+            .unwrap();
+        assert_eq!(rw_string, "write me!");
+
+        //type mismatch
+        let result = dh_provider_con
+            .read_single_variable_typed::<bool>("my_folder.rw_int")
+            .await;
+        assert!(matches!(
+            result,
+            Err(connected_dh_provider::Error::TypeConversion(
+                dh_types::Error::ValueTypeMismatch {
+                    expected: VariableType::Boolean,
+                    actual: Some(VariableType::Int64),
+                }
+            ))
+        ));
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn typed_variable_handle() {
+    run_with_timeout(async move {
+        let _fake_reg = FakeRegistry::new().await;
+
+        let auth_settings = consumer_auth_settings(NatsPermission::VariableHubReadWrite);
+        let consumer = Arc::new(
+            DataHubConsumer::connect(NATS_HOSTNAME, &auth_settings)
+                .await
+                .unwrap(),
+        );
+
+        let _dummy_provider = DummyProvider::new().await.unwrap();
+        let dh_provider_con = DataHubProviderConnection::new(consumer.clone(), PROVIDER_ID, true)
+            .await
+            .unwrap();
+        dh_provider_con
+            .wait_until_variable_keys_are_available(&["my_folder.rw_int"])
+            .await
+            .unwrap();
+
+        let rw_int = dh_provider_con
+            .typed_handle::<i64>("my_folder.rw_int")
+            .unwrap();
+        assert_eq!(rw_int.key(), "my_folder.rw_int");
+        assert_eq!(rw_int.read().await.unwrap(), 1000);
+
+        let mut changes = dh_provider_con
+            .subscribe_single_variable("my_folder.rw_int")
+            .await
+            .unwrap();
+        rw_int.write(1234).await.unwrap();
+        assert_eq!(
+            changes.next().await.unwrap().value,
+            VariableValue::Int(1234)
+        );
+        assert_eq!(rw_int.read().await.unwrap(), 1234);
+
+        //type mismatch is detected when creating the handle
+        let result = dh_provider_con.typed_handle::<f64>("my_folder.rw_int");
+        assert!(matches!(
+            result,
+            Err(connected_dh_provider::Error::TypeConversion(
+                dh_types::Error::ValueTypeMismatch {
+                    expected: VariableType::Float64,
+                    actual: Some(VariableType::Int64),
+                }
+            ))
+        ));
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn read_history() {
+    run_with_timeout(async move {
+        let _fake_reg = FakeRegistry::new().await;
+
+        let auth_settings = consumer_auth_settings(NatsPermission::VariableHubRead);
+        let consumer = Arc::new(
+            DataHubConsumer::connect(NATS_HOSTNAME, &auth_settings)
+                .await
+                .unwrap(),
+        );
+
+        let _dummy_provider = DummyProvider::new().await.unwrap();
+        let dh_provider_con = DataHubProviderConnection::new(consumer.clone(), PROVIDER_ID, true)
+            .await
+            .unwrap();
+        dh_provider_con
+            .wait_until_variable_keys_are_available(&["my_folder.rw_int"])
+            .await
+            .unwrap();
+
+        let to = TimestampValue::now();
+        let from = to - Duration::from_secs(60);
+
+        //the dummy provider has no history
+        let result = dh_provider_con
+            .read_history("my_folder.rw_int", from, to)
+            .await;
+        assert!(matches!(
+            result,
+            Err(connected_dh_provider::Error::HistoryUnsupported)
+        ));
+
+        //a mock that answers history requests on behalf of the dummy provider
+        let rw_int_id = dh_provider_con
+            .variable_id_from_key("my_folder.rw_int")
+            .unwrap();
+        let mock_con = create_auth_con("history_mock").await;
+        let mut history_sub = mock_con
+            .get_client()
+            .subscribe(nats_subjects::read_history_query(PROVIDER_ID))
+            .await
+            .unwrap();
+
+        let mock_client = mock_con.get_client().clone();
+        let mock_task = tokio::spawn(async move {
+            let msg = history_sub.next().await.unwrap();
+            let headers = msg.headers.unwrap();
+            assert_eq!(
+                headers.get(HISTORY_FROM_HEADER).unwrap().as_str(),
+                from.unix_timestamp_nanos().to_string()
+            );
+            assert_eq!(
+                headers.get(HISTORY_TO_HEADER).unwrap().as_str(),
+                to.unix_timestamp_nanos().to_string()
+            );
+
+            //the states are deliberately not sorted
+            let items = [
+                (2, to - Duration::from_secs(10)),
+                (1, to - Duration::from_secs(20)),
+            ]
+            .into_iter()
+            .map(|(value, timestamp)| {
+                let variable = VariableBuilder::new(rw_int_id, "my_folder.rw_int")
+                    .initial_value(value)
+                    .initial_timestamp(Some(timestamp))
+                    .build()
+                    .unwrap();
+                (&variable).into()
+            })
+            .collect();
+
+            let mut response = ReadVariablesQueryResponseT::default();
+            response.variables.items = Some(items);
+            mock_client
+                .publish(
+                    msg.reply.unwrap(),
+                    FlatbufferCodec.encode_read_variables_query_response(&response),
+                )
+                .await
+                .unwrap();
+            mock_client.flush().await.unwrap();
+        });
+
+        let history = dh_provider_con
+            .read_history("my_folder.rw_int", from, to)
+            .await
+            .unwrap();
+        mock_task.await.unwrap();
+
+        assert_eq!(
+            history
+                .iter()
+                .map(|state| state.value.clone())
+                .collect::<Vec<_>>(),
+            vec![VariableValue::Int(1), VariableValue::Int(2)]
+        );
+        assert!(history[0].timestamp < history[1].timestamp);
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn read_by_path() {
+    run_with_timeout(Box::pin(async move {
+        let _fake_reg = FakeRegistry::new().await;
+
+        let auth_settings = consumer_auth_settings(NatsPermission::VariableHubRead);
+        let consumer = Arc::new(
+            DataHubConsumer::connect(NATS_HOSTNAME, &auth_settings)
+                .await
+                .unwrap(),
+        );
+
+        let _dummy_provider = DummyProvider::new().await.unwrap();
+        let dh_provider_con = DataHubProviderConnection::new(consumer.clone(), PROVIDER_ID, true)
+            .await
+            .unwrap();
+
+        let rw_int = dh_provider_con
+            .read_by_path(&["my_folder", "rw_int"])
+            .await
+            .unwrap();
+        assert_eq!(rw_int.value, VariableValue::Int(1000));
+
+        //the path leads to a folder
+        let result = dh_provider_con.read_by_path(&["my_folder"]).await;
+        assert!(matches!(
+            result,
+            Err(connected_dh_provider::Error::FolderKey(key)) if key == "my_folder"
+        ));
+
+        //the path does not exist
+        let result = dh_provider_con
+            .read_by_path(&["my_folder", "unknown"])
+            .await;
+        assert!(matches!(
+            result,
+            Err(connected_dh_provider::Error::LowLevelApi(
+                connected_nats_provider::Error::InvalidVariableKey(_)
+            ))
+        ));
+    }))
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn read_values_only() {
+    run_with_timeout(async move {
+        let _fake_reg = FakeRegistry::new().await;
+
+        let auth_settings = consumer_auth_settings(NatsPermission::VariableHubRead);
+        let consumer = Arc::new(
+            DataHubConsumer::connect(NATS_HOSTNAME, &auth_settings)
+                .await
+                .unwrap(),
+        );
+
+        let _dummy_provider = DummyProvider::new().await.unwrap();
+        let mut dh_provider_con =
+            DataHubProviderConnection::new(consumer.clone(), PROVIDER_ID, true)
+                .await
+                .unwrap();
+        dh_provider_con.set_values_only_reads(true);
+
+        //values are still transmitted, but quality is omitted by the provider
+        let state = dh_provider_con
+            .read_single_variable("my_folder.rw_int")
+            .await
+            .unwrap();
+        assert_eq!(state.value, VariableValue::Int(1000));
+        assert_eq!(state.quality, VariableQuality::BadOrUndefined);
+
+        dh_provider_con.set_values_only_reads(false);
+        let state = dh_provider_con
+            .read_single_variable("my_folder.rw_int")
+            .await
+            .unwrap();
+        assert_eq!(state.value, VariableValue::Int(1000));
+        assert_eq!(state.quality, VariableQuality::Good);
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn read_variables_by_quality() {
+    run_with_timeout(async move {
+        let _fake_reg = FakeRegistry::new().await;
+
+        let auth_settings = consumer_auth_settings(NatsPermission::VariableHubRead);
+        let consumer = Arc::new(
+            DataHubConsumer::connect(NATS_HOSTNAME, &auth_settings)
+                .await
+                .unwrap(),
+        );
+
+        let _dummy_provider = DummyProvider::new().await.unwrap();
+        let mut dh_provider_con =
+            DataHubProviderConnection::new(consumer.clone(), PROVIDER_ID, true)
+                .await
+                .unwrap();
+        //qualities must be read even if values only reads are enabled
+        dh_provider_con.set_values_only_reads(true);
+
+        //all variables of the dummy provider have a good quality
+        let var_states = dh_provider_con
+            .read_variables_by_quality(&[VariableQuality::Good])
+            .await
+            .unwrap();
+        assert_eq!(var_states.len(), 4);
+
+        let var_states = dh_provider_con
+            .read_variables_by_quality(&[
+                VariableQuality::BadOrUndefined,
+                VariableQuality::Uncertain,
+            ])
+            .await
+            .unwrap();
+        assert!(var_states.is_empty());
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn subscribe_variables() {
+    run_with_timeout(async move {
+        let _fake_reg = FakeRegistry::new().await;
+
+        let auth_settings = consumer_auth_settings(NatsPermission::VariableHubRead);
+        let consumer = Arc::new(
+            DataHubConsumer::connect(NATS_HOSTNAME, &auth_settings)
+                .await
+                .unwrap(),
+        );
+
+        let dummy_provider = DummyProvider::new().await.unwrap();
+        let dh_provider_con = DataHubProviderConnection::new(consumer.clone(), PROVIDER_ID, true)
+            .await
+            .unwrap();
+
+        //The subscribe method allows to filter for keys that do not yet exist
+        let mut change_stream = dh_provider_con
+            .subscribe_variables_with_filter(Some(vec!["my_folder.ro_float", "my_folder.ro_int2"]))
+            .await
+            .unwrap();
+
+        //wait for the some events
+        let change_evt = change_stream.next().await.unwrap();
+        assert_eq!(change_evt.len(), 1);
+        let changed_var = &change_evt.first().unwrap().1;
+        assert_eq!(changed_var.value, 0.0.into());
+        assert_eq!(changed_var.quality, VariableQuality::Good);
+
+        let change_evt = change_stream.next().await.unwrap();
+        assert_eq!(change_evt.len(), 1);
+        let changed_var = &change_evt.first().unwrap().1;
+        assert_eq!(changed_var.value, 123.0.into());
+        assert_eq!(changed_var.quality, VariableQuality::Good);
+
+        //this will register a new variable and change IDs of existing vars
+        dummy_provider.change_variables();
+
+        //should now receive events for the old variable and the new variable,
+        //even though the id of the old variable has changed and the new variable didnt exist during the initial subscription call.
+        let change_evt = change_stream.next().await.unwrap();
+        assert_eq!(change_evt.len(), 2);
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn subscribe_variables_excluding() {
+    run_with_timeout(async move {
+        let _fake_reg = FakeRegistry::new().await;
+
+        let auth_settings = consumer_auth_settings(NatsPermission::VariableHubReadWrite);
+        let consumer = Arc::new(
+            DataHubConsumer::connect(NATS_HOSTNAME, &auth_settings)
+                .await
+                .unwrap(),
+        );
+
+        let dummy_provider = DummyProvider::new().await.unwrap();
+        let dh_provider_con = DataHubProviderConnection::new(consumer.clone(), PROVIDER_ID, true)
+            .await
+            .unwrap();
+
+        //The exclude filter may contain keys that do not yet exist
+        let mut change_stream = dh_provider_con
+            .subscribe_variables_with_filter_mode(
+                Some(vec!["my_folder.ro_float", "my_folder.ro_int2"]),
+                FilterMode::Exclude,
+            )
+            .await
+            .unwrap();
+
+        //only ro_int is received, ro_float is excluded
+        let change_evt = change_stream.next().await.unwrap();
+        assert_eq!(change_evt.len(), 1);
+        assert_eq!(change_evt[0].0, 400);
+        assert_eq!(change_evt[0].1.value, VariableValue::Int(1000));
+
+        //this will register ro_int2 and change the ID of ro_float
+        dummy_provider.change_variables();
+
+        //Give the consumer time to receive the new definition
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        dh_provider_con
+            .write_single_variable("my_folder.rw_int3", 42)
+            .await
+            .unwrap();
+
+        //the rebuilt exclude filter drops the changes of ro_float and ro_int2
+        let change_evt = loop {
+            let change_evt = change_stream.next().await.unwrap();
+            if !change_evt.is_empty() {
+                break change_evt;
+            }
+        };
+        assert_eq!(change_evt.len(), 1);
+        assert_eq!(change_evt[0].0, 50);
+        assert_eq!(change_evt[0].1.value, VariableValue::Int(42));
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn subscribe_variables_with_pattern() {
+    run_with_timeout(Box::pin(async move {
+        let _fake_reg = FakeRegistry::new().await;
+
+        let auth_settings = consumer_auth_settings(NatsPermission::VariableHubRead);
+        let consumer = Arc::new(
+            DataHubConsumer::connect(NATS_HOSTNAME, &auth_settings)
+                .await
+                .unwrap(),
+        );
+
+        let dummy_provider = DummyProvider::new().await.unwrap();
+        let dh_provider_con = DataHubProviderConnection::new(consumer.clone(), PROVIDER_ID, true)
+            .await
+            .unwrap();
+
+        let mut change_stream = dh_provider_con
+            .subscribe_variables_with_pattern(&["my_folder.ro_i?t*"])
+            .await
+            .unwrap();
+
+        //only ro_int matches, ro_float is filtered out
+        let change_evt = change_stream.next().await.unwrap();
+        assert_eq!(change_evt.len(), 1);
+        assert_eq!(change_evt[0].0, 400);
+        assert_eq!(change_evt[0].1.value, VariableValue::Int(1000));
+
+        //this will replace ro_int with ro_int2, which matches the pattern as well
+        dummy_provider.change_variables();
+
+        let change_evt = loop {
+            let change_evt = change_stream.next().await.unwrap();
+            assert!(change_evt.iter().all(|(id, _)| *id == 400 || *id == 40));
+            if change_evt.iter().any(|(id, _)| *id == 40) {
+                break change_evt;
+            }
+        };
+        assert_eq!(change_evt.len(), 1);
+    }))
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn on_variable_change() {
+    run_with_timeout(Box::pin(async move {
+        let _fake_reg = FakeRegistry::new().await;
+
+        let auth_settings = consumer_auth_settings(NatsPermission::VariableHubRead);
+        let consumer = Arc::new(
+            DataHubConsumer::connect(NATS_HOSTNAME, &auth_settings)
+                .await
+                .unwrap(),
+        );
+
+        let _dummy_provider = DummyProvider::new().await.unwrap();
+        let dh_provider_con = DataHubProviderConnection::new(consumer.clone(), PROVIDER_ID, true)
+            .await
+            .unwrap();
+
+        let (change_tx, mut change_rx) = tokio::sync::mpsc::unbounded_channel();
+        let subscription = dh_provider_con
+            .on_variable_change(Some(vec!["my_folder.ro_int"]), move |id, state| {
+                change_tx.send((id, state)).unwrap();
+            })
+            .await
+            .unwrap();
+
+        let (id, state) = change_rx.recv().await.unwrap();
+        assert_eq!(id, 400);
+        assert_eq!(state.value, VariableValue::Int(1000));
+
+        let (id, state) = change_rx.recv().await.unwrap();
+        assert_eq!(id, 400);
+        assert_eq!(state.value, VariableValue::Int(1001));
+
+        //dropping the handle cancels the subscription and drops the callback
+        drop(subscription);
+        while change_rx.recv().await.is_some() {}
+    }))
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn subscribe_variables_with_snapshot() {
+    run_with_timeout(Box::pin(async move {
+        let _fake_reg = FakeRegistry::new().await;
+
+        let auth_settings = consumer_auth_settings(NatsPermission::VariableHubRead);
+        let consumer = Arc::new(
+            DataHubConsumer::connect(NATS_HOSTNAME, &auth_settings)
+                .await
+                .unwrap(),
+        );
+
+        let _dummy_provider = DummyProvider::new().await.unwrap();
+        let dh_provider_con = DataHubProviderConnection::new(consumer.clone(), PROVIDER_ID, true)
+            .await
+            .unwrap();
+
+        let mut change_stream = dh_provider_con
+            .subscribe_variables_with_snapshot(Some(vec!["my_folder.rw_int", "my_folder.ro_int"]))
+            .await
+            .unwrap();
+
+        //the snapshot contains all filtered variables, even if they never change
+        let snapshot: HashMap<_, _> = change_stream.next().await.unwrap().into_iter().collect();
+        assert_eq!(snapshot.len(), 2);
+        assert_eq!(snapshot[&300].value, VariableValue::Int(1000));
+        let ro_int_snapshot = &snapshot[&400];
+
+        //followed by changes that are newer than the snapshot
+        let change_evt = change_stream.next().await.unwrap();
+        assert_eq!(change_evt.len(), 1);
+        assert_eq!(change_evt[0].0, 400);
+        assert!(change_evt[0].1.timestamp > ro_int_snapshot.timestamp);
+    }))
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn snapshot_and_subscribe() {
+    run_with_timeout(Box::pin(async move {
+        const MOCK_PROVIDER_ID: &str = "mock_provider";
+
+        let _fake_reg = FakeRegistry::new().await;
+
+        let auth_settings = consumer_auth_settings(NatsPermission::VariableHubRead);
+        let consumer = Arc::new(
+            DataHubConsumer::connect(NATS_HOSTNAME, &auth_settings)
+                .await
+                .unwrap(),
+        );
+
+        let mock_provider = MockProvider::builder(MOCK_PROVIDER_ID)
+            .variable(1, "sensors.temperature", 21.5)
+            .variable(2, "sensors.humidity", 40.0)
+            .start()
+            .await;
+        let dh_provider_con =
+            DataHubProviderConnection::new(consumer.clone(), MOCK_PROVIDER_ID, true)
+                .await
+                .unwrap();
+        dh_provider_con
+            .wait_until_variable_keys_are_available(&["sensors.temperature"])
+            .await
+            .unwrap();
+
+        let (snapshot, mut change_stream) = dh_provider_con
+            .snapshot_and_subscribe(Some(vec!["sensors.temperature"]))
+            .await
+            .unwrap();
+
+        //the snapshot only contains the filtered variable
+        assert_eq!(snapshot.len(), 1);
+        assert_eq!(snapshot[0].0, 1);
+        assert_eq!(snapshot[0].1.value, VariableValue::Float64(21.5));
+
+        //the stream does not repeat the snapshot values
+        assert!(timeout(Duration::from_millis(300), change_stream.next())
+            .await
+            .is_err());
+
+        //but yields all changes after the snapshot
+        mock_provider.set_value("sensors.humidity", 45.0).await;
+        mock_provider.set_value("sensors.temperature", 22.0).await;
+        let change_evt = change_stream.next().await.unwrap();
+        assert_eq!(change_evt.len(), 1);
+        assert_eq!(change_evt[0].0, 1);
+        assert_eq!(change_evt[0].1.value, VariableValue::Float64(22.0));
+    }))
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn running_stats() {
+    run_with_timeout(Box::pin(async move {
+        let _fake_reg = FakeRegistry::new().await;
+
+        let auth_settings = consumer_auth_settings(NatsPermission::VariableHubRead);
+        let consumer = Arc::new(
+            DataHubConsumer::connect(NATS_HOSTNAME, &auth_settings)
+                .await
+                .unwrap(),
+        );
+
+        let _dummy_provider = DummyProvider::new().await.unwrap();
+        let dh_provider_con = DataHubProviderConnection::new(consumer.clone(), PROVIDER_ID, true)
+            .await
+            .unwrap();
+
+        let ro_int_states = dh_provider_con
+            .subscribe_single_variable("my_folder.ro_int")
+            .await
+            .unwrap();
+
+        //ro_int counts up from 1000
+        let stats = RunningStats::new();
+        stats.consume(ro_int_states.take(3)).await;
+
+        assert_eq!(
+            stats.snapshot(),
+            StatsSnapshot {
+                count: 3,
+                excluded: 0,
+                min: Some(1000.0),
+                max: Some(1002.0),
+                mean: Some(1001.0),
+                last: Some(1002.0),
+            }
+        );
+    }))
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn subscribe_variables_buffered() {
+    run_with_timeout(Box::pin(async move {
+        let _fake_reg = FakeRegistry::new().await;
+
+        let auth_settings = consumer_auth_settings(NatsPermission::VariableHubRead);
+        let consumer = Arc::new(
+            DataHubConsumer::connect(NATS_HOSTNAME, &auth_settings)
+                .await
+                .unwrap(),
+        );
+
+        let _dummy_provider = DummyProvider::new().await.unwrap();
+        let dh_provider_con = DataHubProviderConnection::new(consumer.clone(), PROVIDER_ID, true)
+            .await
+            .unwrap();
+
+        for overflow_strategy in [OverflowStrategy::DropOldest, OverflowStrategy::Wait] {
+            let mut change_stream = dh_provider_con
+                .subscribe_variables_buffered(
+                    Some(vec!["my_folder.ro_int"]),
+                    SubscriptionBuffer {
+                        capacity: 1,
+                        overflow_strategy,
+                    },
+                )
+                .await
+                .unwrap();
+
+            //ro_int changes every 200ms, so several changes arrive while the stream is not polled
+            tokio::time::sleep(Duration::from_secs(1)).await;
+
+            let first = change_stream.next().await.unwrap();
+            let second = change_stream.next().await.unwrap();
+            let (VariableValue::Int(first), VariableValue::Int(second)) =
+                (&first[0].1.value, &second[0].1.value)
+            else {
+                panic!("ro_int should be an int");
+            };
+            assert_eq!(*second, first + 1);
+
+            match overflow_strategy {
+                OverflowStrategy::DropOldest => assert!(change_stream.dropped_changes() > 0),
+                OverflowStrategy::Wait => assert_eq!(change_stream.dropped_changes(), 0),
+            }
+        }
+    }))
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn subscribe_adaptive() {
+    run_with_timeout(async move {
+        let _fake_reg = FakeRegistry::new().await;
+
+        let auth_settings = consumer_auth_settings(NatsPermission::VariableHubReadWrite);
+        let consumer = Arc::new(
+            DataHubConsumer::connect(NATS_HOSTNAME, &auth_settings)
+                .await
+                .unwrap(),
+        );
+
+        let _dummy_provider = DummyProvider::new().await.unwrap();
+        let dh_provider_con = DataHubProviderConnection::new(consumer.clone(), PROVIDER_ID, true)
+            .await
+            .unwrap();
+        let rw_int_id = dh_provider_con
+            .variable_id_from_key("my_folder.rw_int")
+            .unwrap();
+
+        let mut change_stream = dh_provider_con
+            .subscribe_adaptive(Some(vec!["my_folder.rw_int"]), Duration::from_millis(100))
+            .await
+            .unwrap();
+
+        //rw_int never changes on its own, so the first poll reconciles the current state
+        let change_evt = change_stream.next().await.unwrap();
+        assert_eq!(change_evt.len(), 1);
+        assert_eq!(change_evt[0].0, rw_int_id);
+        assert_eq!(change_evt[0].1.value, VariableValue::Int(1000));
+
+        //changes are received via event
+        dh_provider_con
+            .write_single_variable("my_folder.rw_int", 42)
+            .await
+            .unwrap();
+        let change_evt = change_stream.next().await.unwrap();
+        assert_eq!(change_evt.len(), 1);
+        assert_eq!(change_evt[0].1.value, VariableValue::Int(42));
+
+        //subsequent polls do not yield already known states
+        assert!(timeout(Duration::from_millis(300), change_stream.next())
+            .await
+            .is_err());
+
+        //a zero poll interval is rejected
+        assert!(matches!(
+            dh_provider_con
+                .subscribe_adaptive(Some(vec!["my_folder.rw_int"]), Duration::ZERO)
+                .await,
+            Err(connected_dh_provider::Error::ZeroPollInterval)
+        ));
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn subscribe_resilient() {
+    run_with_timeout(Box::pin(async move {
+        const MOCK_PROVIDER_ID: &str = "mock_provider";
+
+        let _fake_reg = FakeRegistry::new().await;
+
+        let auth_settings = consumer_auth_settings(NatsPermission::VariableHubRead);
+        let consumer = Arc::new(
+            DataHubConsumer::connect(NATS_HOSTNAME, &auth_settings)
+                .await
+                .unwrap(),
+        );
+
+        let mock_provider = MockProvider::builder(MOCK_PROVIDER_ID)
+            .variable(1, "sensors.temperature", 21.5)
+            .start()
+            .await;
+        let dh_provider_con =
+            DataHubProviderConnection::new(consumer.clone(), MOCK_PROVIDER_ID, true)
+                .await
+                .unwrap();
+        dh_provider_con
+            .wait_until_variable_keys_are_available(&["sensors.temperature"])
+            .await
+            .unwrap();
+
+        let mut change_stream = dh_provider_con
+            .subscribe_resilient(Some(vec!["sensors.temperature"]))
+            .await
+            .unwrap();
+
+        //the stream starts with the current state
+        let snapshot = change_stream.next().await.unwrap();
+        assert_eq!(snapshot.len(), 1);
+        assert_eq!(snapshot[0].1.value, VariableValue::Float64(21.5));
+
+        //change the value while the consumer reconnects, the change event may get lost
+        let nats_client = consumer.get_nats_consumer().get_nats_con().get_client();
+        let mut nats_events = consumer.get_nats_consumer().get_nats_con().get_events();
+        nats_client.force_reconnect().await.unwrap();
+        mock_provider.set_value("sensors.temperature", 23.0).await;
+        while !matches!(
+            nats_events.recv().await.unwrap(),
+            async_nats::Event::Connected
+        ) {}
+
+        //the change is yielded either via event or via the re-read after the reconnect, but only once
+        let change_evt = change_stream.next().await.unwrap();
+        assert_eq!(change_evt.len(), 1);
+        assert_eq!(change_evt[0].1.value, VariableValue::Float64(23.0));
+        assert!(timeout(Duration::from_millis(300), change_stream.next())
+            .await
+            .is_err());
+
+        //events are received again after the reconnect
+        mock_provider.set_value("sensors.temperature", 24.0).await;
+        let change_evt = change_stream.next().await.unwrap();
+        assert_eq!(change_evt[0].1.value, VariableValue::Float64(24.0));
+    }))
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn subscription_outlives_consumer_and_connection() {
+    run_with_timeout(Box::pin(async move {
+        let _fake_reg = FakeRegistry::new().await;
+
+        let auth_settings = consumer_auth_settings(NatsPermission::VariableHubRead);
+        let consumer = Arc::new(
+            DataHubConsumer::connect(NATS_HOSTNAME, &auth_settings)
+                .await
+                .unwrap(),
+        );
+
+        let dummy_provider = DummyProvider::new().await.unwrap();
+        let dh_provider_con = DataHubProviderConnection::new(consumer.clone(), PROVIDER_ID, true)
+            .await
+            .unwrap();
+
+        let mut change_stream = dh_provider_con
+            .subscribe_variables_with_filter(Some(vec!["my_folder.ro_float", "my_folder.ro_int2"]))
+            .await
+            .unwrap();
+        let mut provider_events = dh_provider_con.subscribe_provider_events().await.unwrap();
+
+        //drop the consumer first, then the connection, only the streams are left
+        drop(consumer);
+        drop(dh_provider_con);
+
+        let change_evt = change_stream.next().await.unwrap();
+        assert_eq!(change_evt.len(), 1);
+
+        //this will register a new variable and change IDs of existing vars
+        dummy_provider.change_variables();
+
+        assert!(matches!(
+            provider_events.next().await.unwrap(),
+            ProviderEvent::DefinitionChanged(_)
+        ));
+
+        //the filter set must still be rebuilt, so we receive the old and the new variable
+        let mut change_evt = change_stream.next().await.unwrap();
+        while change_evt.len() < 2 {
+            change_evt = change_stream.next().await.unwrap();
+        }
+        assert_eq!(change_evt.len(), 2);
+    }))
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn subscriptions_end_when_connection_closed() {
+    run_with_timeout(Box::pin(async move {
+        let _fake_reg = FakeRegistry::new().await;
+
+        let auth_settings = consumer_auth_settings(NatsPermission::VariableHubRead);
+        let consumer = Arc::new(
+            DataHubConsumer::connect(NATS_HOSTNAME, &auth_settings)
+                .await
+                .unwrap(),
+        );
+
+        let _dummy_provider = DummyProvider::new().await.unwrap();
+        let dh_provider_con = DataHubProviderConnection::new(consumer.clone(), PROVIDER_ID, true)
+            .await
+            .unwrap();
+
+        let mut change_stream = dh_provider_con
+            .subscribe_variables_with_filter(Some(vec!["my_folder.ro_int"]))
+            .await
+            .unwrap();
+        let mut provider_events = dh_provider_con.subscribe_provider_events().await.unwrap();
+        let mut adaptive_stream = dh_provider_con
+            .subscribe_adaptive(Some(vec!["my_folder.ro_int"]), Duration::from_secs(60))
+            .await
+            .unwrap();
+        let mut buffered_stream = dh_provider_con
+            .subscribe_variables_buffered(
+                Some(vec!["my_folder.ro_int"]),
+                SubscriptionBuffer::default(),
+            )
+            .await
+            .unwrap();
+
+        //the streams are alive while the connection is open
+        assert!(change_stream.next().await.is_some());
+
+        consumer
+            .get_nats_consumer()
+            .get_nats_con()
+            .get_client()
+            .drain()
+            .await
+            .unwrap();
+
+        //pending changes may still be yielded, but afterwards the streams must end instead of hanging
+        while change_stream.next().await.is_some() {}
+        while provider_events.next().await.is_some() {}
+        while adaptive_stream.next().await.is_some() {}
+        while buffered_stream.next().await.is_some() {}
+
+        //the streams are fused, so polling them again is fine
+        assert!(change_stream.is_terminated());
+        assert!(change_stream.next().await.is_none());
+        assert!(provider_events.is_terminated());
+        assert!(provider_events.next().await.is_none());
+        assert!(adaptive_stream.is_terminated());
+        assert!(adaptive_stream.next().await.is_none());
+        assert!(buffered_stream.is_terminated());
+        assert!(buffered_stream.next().await.is_none());
+    }))
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn mock_provider() {
+    run_with_timeout(Box::pin(async move {
+        const MOCK_PROVIDER_ID: &str = "mock_provider";
+
+        let _fake_reg = FakeRegistry::new().await;
+
+        let auth_settings = consumer_auth_settings(NatsPermission::VariableHubReadWrite);
+        let consumer = Arc::new(
+            DataHubConsumer::connect(NATS_HOSTNAME, &auth_settings)
+                .await
+                .unwrap(),
+        );
+
+        let mock_provider = MockProvider::builder(MOCK_PROVIDER_ID)
+            .variable(1, "sensors.temperature", 21.5)
+            .writable_variable(2, "control.setpoint", 20.0)
+            .start()
+            .await;
+        let dh_provider_con =
+            DataHubProviderConnection::new(consumer.clone(), MOCK_PROVIDER_ID, true)
+                .await
+                .unwrap();
+
+        //reads are answered with the declared values
+        let temperature = dh_provider_con
+            .read_single_variable("sensors.temperature")
+            .await
+            .unwrap();
+        assert_eq!(temperature.value, VariableValue::Float64(21.5));
+
+        //changes are published on demand
+        let mut change_stream = dh_provider_con
+            .subscribe_single_variable("sensors.temperature")
+            .await
+            .unwrap();
+        mock_provider.set_value("sensors.temperature", 22.0).await;
+        assert_eq!(
+            change_stream.next().await.unwrap().value,
+            VariableValue::Float64(22.0)
+        );
+
+        //writes are recorded and applied
+        let mut setpoint_stream = dh_provider_con
+            .subscribe_single_variable("control.setpoint")
+            .await
+            .unwrap();
+        dh_provider_con
+            .write_single_variable("control.setpoint", 18.0)
+            .await
+            .unwrap();
+        assert_eq!(
+            setpoint_stream.next().await.unwrap().value,
+            VariableValue::Float64(18.0)
+        );
+        assert_eq!(
+            mock_provider.received_writes(),
+            vec![("control.setpoint".to_owned(), VariableValue::Float64(18.0))]
+        );
+        assert_eq!(
+            mock_provider.value("control.setpoint"),
+            Some(VariableValue::Float64(18.0))
+        );
+    }))
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn wait_for_value() {
+    run_with_timeout(Box::pin(async move {
+        const MOCK_PROVIDER_ID: &str = "mock_provider";
+
+        let _fake_reg = FakeRegistry::new().await;
+
+        let auth_settings = consumer_auth_settings(NatsPermission::VariableHubRead);
+        let consumer = Arc::new(
+            DataHubConsumer::connect(NATS_HOSTNAME, &auth_settings)
+                .await
+                .unwrap(),
+        );
+
+        let mock_provider = MockProvider::builder(MOCK_PROVIDER_ID)
+            .variable(1, "sensors.temperature", 21.5)
+            .start()
+            .await;
+        let dh_provider_con =
+            DataHubProviderConnection::new(consumer.clone(), MOCK_PROVIDER_ID, true)
+                .await
+                .unwrap();
+        dh_provider_con
+            .wait_until_variable_keys_are_available(&["sensors.temperature"])
+            .await
+            .unwrap();
+
+        let is_hot =
+            |state: &VariableState| matches!(state.value, VariableValue::Float64(v) if v > 50.0);
+
+        //the current value does not satisfy the condition
+        let result = dh_provider_con
+            .wait_for_value("sensors.temperature", is_hot, Duration::from_millis(200))
+            .await;
+        assert!(matches!(result, Err(connected_dh_provider::Error::Timeout)));
+
+        //a later change satisfies the condition
+        let (result, ()) = tokio::join!(
+            dh_provider_con.wait_for_value("sensors.temperature", is_hot, Duration::from_secs(5)),
+            async {
+                tokio::time::sleep(Duration::from_millis(100)).await;
+                mock_provider.set_value("sensors.temperature", 40.0).await;
+                mock_provider.set_value("sensors.temperature", 55.0).await;
+            }
+        );
+        assert_eq!(result.unwrap().value, VariableValue::Float64(55.0));
+
+        //the current value already satisfies the condition
+        let state = dh_provider_con
+            .wait_for_value("sensors.temperature", is_hot, Duration::from_millis(200))
+            .await
+            .unwrap();
+        assert_eq!(state.value, VariableValue::Float64(55.0));
+    }))
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn provider_status() {
+    run_with_timeout(Box::pin(async move {
+        const MOCK_PROVIDER_ID: &str = "mock_provider";
+
+        let _fake_reg = FakeRegistry::new().await;
+
+        let auth_settings = consumer_auth_settings(NatsPermission::VariableHubRead);
+        let consumer = Arc::new(
+            DataHubConsumer::connect(NATS_HOSTNAME, &auth_settings)
+                .await
+                .unwrap(),
+        );
+
+        let mock_provider = MockProvider::builder(MOCK_PROVIDER_ID)
+            .variable(1, "sensors.temperature", 21.5)
+            .start()
+            .await;
+        let dh_provider_con =
+            DataHubProviderConnection::new(consumer.clone(), MOCK_PROVIDER_ID, true)
+                .await
+                .unwrap();
+
+        assert_eq!(
+            dh_provider_con.read_provider_status().await.unwrap(),
+            ProviderStatus::Ok
+        );
+
+        let mut provider_events = dh_provider_con.subscribe_provider_events().await.unwrap();
+        mock_provider
+            .provider()
+            .set_status(ProviderStatus::Maintenance)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            provider_events.next().await.unwrap(),
+            ProviderEvent::StatusChanged(ProviderStatus::Maintenance)
+        );
+        assert_eq!(
+            dh_provider_con.read_provider_status().await.unwrap(),
+            ProviderStatus::Maintenance
+        );
+
+        //setting the same status again publishes nothing
+        mock_provider
+            .provider()
+            .set_status(ProviderStatus::Maintenance)
+            .await
+            .unwrap();
+        assert!(timeout(Duration::from_millis(200), provider_events.next())
+            .await
+            .is_err());
+    }))
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn write_variables() {
+    run_with_timeout(Box::pin(async move {
+        let _fake_reg = FakeRegistry::new().await;
+
+        let auth_settings = consumer_auth_settings(NatsPermission::VariableHubReadWrite);
+        let consumer = Arc::new(
+            DataHubConsumer::connect(NATS_HOSTNAME, &auth_settings)
+                .await
+                .unwrap(),
+        );
+
+        let _dummy_provider = DummyProvider::new().await.unwrap();
+        let dh_provider_con = DataHubProviderConnection::new(consumer.clone(), PROVIDER_ID, true)
+            .await
+            .unwrap();
+
+        //subscribe to variable changes
+        let mut change_stream = dh_provider_con
+            .subscribe_variables_with_filter(Option::<Vec<VariableKey>>::None)
+            .await
+            .unwrap();
+
+        //Try to write invalid value type
+        assert!(dh_provider_con
+            .write_single_variable("my_folder.rw_string", 123)
+            .await
+            .is_err());
+
+        //Try to write to RO variable
+        assert!(dh_provider_con
+            .write_single_variable("my_folder.ro_float", 999.9)
+            .await
+            .is_err());
+
+        //Try to write non existant variable
+        assert!(dh_provider_con
+            .write_single_variable("doesntexist", VariableValue::String("wtf?".to_owned()))
+            .await
+            .is_err());
+
+        //write single variable
+        dh_provider_con
+            .write_single_variable("my_folder.rw_string", "Hello World!")
+            .await
+            .unwrap();
+
+        //write should have triggered a change event
+        let change_evt: HashMap<_, _> = change_stream.next().await.unwrap().into_iter().collect();
+        assert_eq!(change_evt.len(), 1);
+        assert_eq!(change_evt.get(&200).unwrap().value, "Hello World!".into());
+
+        //Write multiple variables at once
+        let var_changes = [
+            ("my_folder.rw_string", "Multi write!!!".into()),
+            ("my_folder.rw_int", 123.into()),
+        ];
+        dh_provider_con.write_variables(&var_changes).await.unwrap();
+
+        //write should have triggered a change event
+        let change_evt: HashMap<_, _> = change_stream.next().await.unwrap().into_iter().collect();
+        assert_eq!(change_evt.len(), 2);
+        assert_eq!(change_evt.get(&200).unwrap().value, "Multi write!!!".into());
+        assert_eq!(change_evt.get(&300).unwrap().value, 123.into());
+    }))
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn write_variables_with_ack() {
+    run_with_timeout(Box::pin(async move {
+        let _fake_reg = FakeRegistry::new().await;
+
+        let auth_settings = consumer_auth_settings(NatsPermission::VariableHubReadWrite);
+        let consumer = Arc::new(
+            DataHubConsumer::connect(NATS_HOSTNAME, &auth_settings)
+                .await
+                .unwrap(),
+        );
+
+        let _dummy_provider = DummyProvider::new().await.unwrap();
+        let dh_provider_con = DataHubProviderConnection::new(consumer.clone(), PROVIDER_ID, true)
             .await
             .unwrap();
+        let ll_provider = dh_provider_con.get_connected_nats_provider();
 
-        //wait for the some events
-        let change_evt = change_stream.next().await.unwrap();
-        assert_eq!(change_evt.len(), 1);
-        let changed_var = &change_evt.first().unwrap().1;
-        assert_eq!(changed_var.value, 0.0.into());
-        assert_eq!(changed_var.quality, VariableQuality::Good);
-
-        let change_evt = change_stream.next().await.unwrap();
-        assert_eq!(change_evt.len(), 1);
-        let changed_var = &change_evt.first().unwrap().1;
-        assert_eq!(changed_var.value, 123.0.into());
-        assert_eq!(changed_var.quality, VariableQuality::Good);
+        let mut write_command = WriteVariablesCommandT::default();
+        write_command.variables.provider_definition_fingerprint =
+            ll_provider.get_fingerprint().unwrap();
+        write_command.variables.items = Some(vec![
+            VariableUpdate {
+                id: 200,
+                value: (&VariableValue::from("Acknowledged!")).into(),
+            }
+            .into(),
+            VariableUpdate {
+                id: 300,
+                value: (&VariableValue::Int(7)).into(),
+            }
+            .into(),
+        ]);
 
-        //this will register a new variable and change IDs of existing vars
-        dummy_provider.change_variables();
+        let ack = ll_provider
+            .write_variables_with_ack(&write_command)
+            .await
+            .unwrap();
+        assert!(ack.all_accepted());
+        assert_eq!(
+            ack,
+            WriteAck {
+                accepted_ids: vec![200, 300],
+                rejected_ids: vec![],
+            }
+        );
 
-        //should now receive events for the old variable and the new variable,
-        //even though the id of the old variable has changed and the new variable didnt exist during the initial subscription call.
-        let change_evt = change_stream.next().await.unwrap();
-        assert_eq!(change_evt.len(), 2);
-    })
+        //the same checks as for fire and forget writes apply
+        write_command.variables.items = Some(vec![VariableUpdate {
+            id: 100,
+            value: (&VariableValue::Float64(1.0)).into(),
+        }
+        .into()]);
+        assert!(matches!(
+            ll_provider.write_variables_with_ack(&write_command).await,
+            Err(connected_nats_provider::Error::WritingToReadonly(_))
+        ));
+    }))
     .await;
 }
 
 #[tokio::test]
 #[serial]
-async fn write_variables() {
+async fn write_batching() {
     run_with_timeout(Box::pin(async move {
         let _fake_reg = FakeRegistry::new().await;
 
@@ -440,9 +2350,11 @@ async fn write_variables() {
         );
 
         let _dummy_provider = DummyProvider::new().await.unwrap();
-        let dh_provider_con = DataHubProviderConnection::new(consumer.clone(), PROVIDER_ID, true)
-            .await
-            .unwrap();
+        let mut dh_provider_con =
+            DataHubProviderConnection::new(consumer.clone(), PROVIDER_ID, true)
+                .await
+                .unwrap();
+        dh_provider_con.enable_write_batching(Duration::from_millis(100));
 
         //subscribe to variable changes
         let mut change_stream = dh_provider_con
@@ -450,47 +2362,44 @@ async fn write_variables() {
             .await
             .unwrap();
 
-        //Try to write invalid value type
+        //Checks are still performed immediately
         assert!(dh_provider_con
             .write_single_variable("my_folder.rw_string", 123)
             .await
             .is_err());
-
-        //Try to write to RO variable
         assert!(dh_provider_con
             .write_single_variable("my_folder.ro_float", 999.9)
             .await
             .is_err());
 
-        //Try to write non existant variable
-        assert!(dh_provider_con
-            .write_single_variable("doesntexist", VariableValue::String("wtf?".to_owned()))
-            .await
-            .is_err());
-
-        //write single variable
+        //Writes in quick succession are coalesced, last write wins
+        for value in 1..=3 {
+            dh_provider_con
+                .write_single_variable("my_folder.rw_int", value)
+                .await
+                .unwrap();
+        }
         dh_provider_con
-            .write_single_variable("my_folder.rw_string", "Hello World!")
+            .write_single_variable("my_folder.rw_string", "Batched!")
             .await
             .unwrap();
 
-        //write should have triggered a change event
+        //All writes should have been flushed with a single command
         let change_evt: HashMap<_, _> = change_stream.next().await.unwrap().into_iter().collect();
-        assert_eq!(change_evt.len(), 1);
-        assert_eq!(change_evt.get(&200).unwrap().value, "Hello World!".into());
+        assert_eq!(change_evt.len(), 2);
+        assert_eq!(change_evt.get(&200).unwrap().value, "Batched!".into());
+        assert_eq!(change_evt.get(&300).unwrap().value, 3.into());
 
-        //Write multiple variables at once
-        let var_changes = [
-            ("my_folder.rw_string", "Multi write!!!".into()),
-            ("my_folder.rw_int", 123.into()),
-        ];
-        dh_provider_con.write_variables(&var_changes).await.unwrap();
+        //Remaining writes are flushed when batching is disabled
+        dh_provider_con
+            .write_single_variable("my_folder.rw_int", 4)
+            .await
+            .unwrap();
+        dh_provider_con.disable_write_batching();
 
-        //write should have triggered a change event
         let change_evt: HashMap<_, _> = change_stream.next().await.unwrap().into_iter().collect();
-        assert_eq!(change_evt.len(), 2);
-        assert_eq!(change_evt.get(&200).unwrap().value, "Multi write!!!".into());
-        assert_eq!(change_evt.get(&300).unwrap().value, 123.into());
+        assert_eq!(change_evt.len(), 1);
+        assert_eq!(change_evt.get(&300).unwrap().value, 4.into());
     }))
     .await;
 }
@@ -633,6 +2542,164 @@ async fn change_var_defs() {
     .await;
 }
 
+#[tokio::test]
+#[serial]
+async fn wait_for_stable_definition() {
+    run_with_timeout(Box::pin(async move {
+        let _fake_reg = FakeRegistry::new().await;
+
+        let auth_settings = consumer_auth_settings(NatsPermission::VariableHubRead);
+        let consumer = Arc::new(
+            DataHubConsumer::connect(NATS_HOSTNAME, &auth_settings)
+                .await
+                .unwrap(),
+        );
+
+        let dummy_provider = DummyProvider::new().await.unwrap();
+        let dh_provider_con = DataHubProviderConnection::new(consumer.clone(), PROVIDER_ID, true)
+            .await
+            .unwrap();
+
+        //trigger a change of the provider def, which clears the vars first and adds the new ones afterwards
+        dummy_provider.change_variables();
+
+        dh_provider_con
+            .wait_for_stable_definition(Duration::from_millis(500))
+            .await
+            .unwrap();
+
+        //must not see the intermediate empty definition
+        let var_defs = dh_provider_con.get_all_variable_definitions().unwrap();
+        assert_eq!(var_defs.len(), 5);
+    }))
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn detect_id_remapping() {
+    run_with_timeout(Box::pin(async move {
+        const REMAPPING_PROVIDER_ID: &str = "remapping_provider";
+
+        let _fake_reg = FakeRegistry::new().await;
+
+        let var_a = VariableBuilder::new(1, "folder.a")
+            .initial_value(0)
+            .build()
+            .unwrap();
+        let var_b = VariableBuilder::new(2, "folder.b")
+            .initial_value(0)
+            .build()
+            .unwrap();
+        let provider = ProviderBuilder::new()
+            .add_variables(vec![var_a.clone(), var_b])
+            .unwrap()
+            .register_with_existing_connection(create_auth_con(REMAPPING_PROVIDER_ID).await)
+            .await
+            .unwrap();
+
+        let auth_settings = consumer_auth_settings(NatsPermission::VariableHubRead);
+        let consumer = Arc::new(
+            DataHubConsumer::connect(NATS_HOSTNAME, &auth_settings)
+                .await
+                .unwrap(),
+        );
+        let mut dh_provider_con =
+            DataHubProviderConnection::new(consumer.clone(), REMAPPING_PROVIDER_ID, true)
+                .await
+                .unwrap();
+        dh_provider_con.set_detect_id_remapping(true);
+
+        let mut provider_events_sub = dh_provider_con.subscribe_provider_events().await.unwrap();
+
+        //remove the variable and reuse its ID for a different key
+        provider.remove_variables(vec![var_a]).await.unwrap();
+        let ProviderEvent::DefinitionChanged(event_var_defs) =
+            provider_events_sub.next().await.unwrap()
+        else {
+            panic!("Expected ProviderEvent::DefinitionChanged");
+        };
+        assert_eq!(event_var_defs.len(), 1);
+
+        let var_c = VariableBuilder::new(1, "folder.c")
+            .initial_value(0)
+            .build()
+            .unwrap();
+        provider.add_variables(vec![var_c]).await.unwrap();
+
+        //the remapping is reported before the new definition
+        assert_eq!(
+            provider_events_sub.next().await.unwrap(),
+            ProviderEvent::IdRemapped {
+                id: 1,
+                old_key: "folder.a".to_string(),
+                new_key: "folder.c".to_string(),
+            }
+        );
+        let ProviderEvent::DefinitionChanged(event_var_defs) =
+            provider_events_sub.next().await.unwrap()
+        else {
+            panic!("Expected ProviderEvent::DefinitionChanged");
+        };
+        assert_eq!(event_var_defs.len(), 2);
+    }))
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn subscribe_provider_events_raw() {
+    run_with_timeout(Box::pin(async move {
+        let _fake_reg = FakeRegistry::new().await;
+
+        let auth_settings = consumer_auth_settings(NatsPermission::VariableHubRead);
+        let consumer = Arc::new(
+            DataHubConsumer::connect(NATS_HOSTNAME, &auth_settings)
+                .await
+                .unwrap(),
+        );
+
+        let dummy_provider = DummyProvider::new().await.unwrap();
+        let dh_provider_con = DataHubProviderConnection::new(consumer.clone(), PROVIDER_ID, true)
+            .await
+            .unwrap();
+
+        let mut provider_events_sub = dh_provider_con
+            .subscribe_provider_events_raw()
+            .await
+            .unwrap();
+
+        dummy_provider.change_variables();
+
+        //first event clears the vars, second event adds the new vars
+        let raw_event = provider_events_sub.next().await.unwrap();
+        assert_eq!(raw_event.event, ProviderEvent::DefinitionChanged(vec![]));
+
+        let raw_event = provider_events_sub.next().await.unwrap();
+        let ProviderEvent::DefinitionChanged(event_var_defs) = raw_event.event else {
+            panic!("Expected ProviderEvent::DefinitionChanged");
+        };
+        assert_eq!(event_var_defs.len(), 5);
+
+        //the raw definition contains the same variables and additional low level information
+        let raw_def = raw_event.provider_definition.unwrap();
+        assert_eq!(raw_def.state, ProviderDefinitionState::OK);
+        let raw_var_defs = raw_def.variable_definitions.unwrap();
+        assert_eq!(raw_var_defs.len(), 5);
+        for (raw_var_def, var_def) in raw_var_defs.into_iter().zip(event_var_defs) {
+            assert_eq!(VariableDefinition::from(raw_var_def), var_def);
+        }
+
+        //offline events have no raw definition
+        drop(dummy_provider);
+
+        let raw_event = provider_events_sub.next().await.unwrap();
+        assert_eq!(raw_event.event, ProviderEvent::Offline);
+        assert!(raw_event.provider_definition.is_none());
+    }))
+    .await;
+}
+
 #[tokio::test]
 #[serial]
 async fn provider_goes_offline() {
@@ -729,6 +2796,80 @@ async fn provider_goes_offline() {
     .await;
 }
 
+#[tokio::test]
+#[serial]
+async fn provider_events_on_nats_reconnect() {
+    run_with_timeout(Box::pin(async move {
+        let _fake_reg = FakeRegistry::new().await;
+
+        let auth_settings = consumer_auth_settings(NatsPermission::VariableHubRead);
+        let consumer = Arc::new(
+            DataHubConsumer::connect(NATS_HOSTNAME, &auth_settings)
+                .await
+                .unwrap(),
+        );
+
+        let _dummy_provider = DummyProvider::new().await.unwrap();
+        let dh_provider_con = DataHubProviderConnection::new(consumer.clone(), PROVIDER_ID, true)
+            .await
+            .unwrap();
+
+        let mut provider_events_sub = dh_provider_con.subscribe_provider_events().await.unwrap();
+
+        consumer
+            .get_nats_consumer()
+            .get_nats_con()
+            .get_client()
+            .force_reconnect()
+            .await
+            .unwrap();
+
+        //the reconnect is reported, but the provider is not considered offline
+        assert_eq!(
+            provider_events_sub.next().await.unwrap(),
+            ProviderEvent::NatsDisconnected
+        );
+        assert_eq!(
+            provider_events_sub.next().await.unwrap(),
+            ProviderEvent::NatsReconnected
+        );
+        assert!(dh_provider_con.get_connected_nats_provider().is_online());
+    }))
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn online_signal() {
+    run_with_timeout(async move {
+        let _fake_reg = FakeRegistry::new().await;
+
+        let auth_settings = consumer_auth_settings(NatsPermission::VariableHubRead);
+        let consumer = Arc::new(
+            DataHubConsumer::connect(NATS_HOSTNAME, &auth_settings)
+                .await
+                .unwrap(),
+        );
+
+        let dummy_provider = DummyProvider::new().await.unwrap();
+        let dh_provider_con = DataHubProviderConnection::new(consumer.clone(), PROVIDER_ID, true)
+            .await
+            .unwrap();
+
+        let mut online_signal = dh_provider_con.online_signal().await.unwrap();
+
+        //current state is emitted first
+        assert!(online_signal.next().await.unwrap());
+
+        drop(dummy_provider);
+        assert!(!online_signal.next().await.unwrap());
+
+        let _dummy_provider = DummyProvider::new().await.unwrap();
+        assert!(online_signal.next().await.unwrap());
+    })
+    .await;
+}
+
 #[tokio::test]
 #[serial]
 #[allow(clippy::cast_possible_wrap)]
@@ -775,6 +2916,53 @@ async fn read_incompatible_var_defs() {
     .await;
 }
 
+#[tokio::test]
+#[serial]
+async fn writable_incompatible_var_defs() {
+    run_with_timeout(async move {
+        let _fake_reg = FakeRegistry::new().await;
+
+        let auth_settings = consumer_auth_settings(NatsPermission::VariableHubRead);
+        let consumer = Arc::new(
+            DataHubConsumer::connect(NATS_HOSTNAME, &auth_settings)
+                .await
+                .unwrap(),
+        );
+
+        let _dummy_provider = IncompatibleProvider::new().await.unwrap();
+        let dh_provider_con = DataHubProviderConnection::new(
+            consumer.clone(),
+            incompatible_provider::PROVIDER_ID,
+            true,
+        )
+        .await
+        .unwrap();
+
+        //All variables except the one with the incompatible access type are read/write
+        let mut var_ids: Vec<_> = dh_provider_con
+            .writable_variables(false)
+            .unwrap()
+            .into_iter()
+            .map(|var_def| var_def.id)
+            .collect();
+        var_ids.sort_unstable();
+        assert_eq!(
+            var_ids,
+            vec![
+                VariableIDs::Valid as u32,
+                VariableIDs::InvalidDataType as u32,
+                VariableIDs::InvalidQuality as u32,
+                VariableIDs::InvalidValueType as u32,
+            ]
+        );
+
+        //Incompatible access types are treated as writable on request
+        let var_defs = dh_provider_con.writable_variables(true).unwrap();
+        assert_eq!(var_defs.len(), 5);
+    })
+    .await;
+}
+
 #[tokio::test]
 #[serial]
 async fn read_incompatible_var_states() {
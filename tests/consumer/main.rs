@@ -15,5 +15,7 @@ mod utils;
 
 mod dh_consumer;
 mod dh_provider_con;
-mod dummy_provider;
 mod incompatible_provider;
+mod proxy_variable;
+
+use u_os_hub_client::test_support::dummy_provider;
@@ -0,0 +1,144 @@
+// SPDX-FileCopyrightText: 2025 Weidmueller Interface GmbH & Co. KG <oss@weidmueller.com>
+//
+// SPDX-License-Identifier: MIT
+
+use std::sync::Arc;
+
+use futures::StreamExt;
+use serial_test::serial;
+use u_os_hub_client::{
+    authenticated_nats_con::NatsPermission,
+    consumer::{connected_dh_provider::DataHubProviderConnection, dh_consumer::DataHubConsumer},
+    dh_types::{VariableAccessType, VariableValue},
+    provider::{AddProxyVariableError, ProviderBuilder, VariableBuilder},
+};
+
+use crate::{
+    dummy_provider::{DummyProvider, PROVIDER_ID},
+    utils::{
+        create_auth_con, create_auth_con_with_perm, fake_registry::FakeRegistry, run_with_timeout,
+    },
+};
+
+const PROXY_PROVIDER_ID: &str = "proxy_provider";
+
+/// Connects a consumer with write permissions to `provider_id`.
+async fn connect_to_provider(client_name: &str, provider_id: &str) -> DataHubProviderConnection {
+    let auth_con =
+        create_auth_con_with_perm(client_name, NatsPermission::VariableHubReadWrite).await;
+    let consumer = Arc::new(
+        DataHubConsumer::from_existing_connection(auth_con)
+            .await
+            .unwrap(),
+    );
+
+    DataHubProviderConnection::new(consumer, provider_id, true)
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+#[serial]
+async fn proxy_variable_forwards_values_and_writes() {
+    run_with_timeout(Box::pin(async move {
+        let _fake_reg = FakeRegistry::new().await;
+        let _dummy_provider = DummyProvider::new().await.unwrap();
+
+        let upstream_con = Arc::new(connect_to_provider("proxy_upstream", PROVIDER_ID).await);
+
+        let local_var = VariableBuilder::new(1, "local.int")
+            .initial_value(0)
+            .build()
+            .unwrap();
+        let proxy_provider = ProviderBuilder::new()
+            .add_variables(vec![local_var])
+            .unwrap()
+            .register_with_existing_connection(create_auth_con(PROXY_PROVIDER_ID).await)
+            .await
+            .unwrap();
+
+        let proxy_rw_int = proxy_provider
+            .add_proxy_variable(2, "proxy.rw_int", upstream_con.clone(), "my_folder.rw_int")
+            .await
+            .unwrap();
+        let _proxy_ro_int = proxy_provider
+            .add_proxy_variable(3, "proxy.ro_int", upstream_con.clone(), "my_folder.ro_int")
+            .await
+            .unwrap();
+
+        let definition = proxy_rw_int.get_variable().get_definition();
+        assert_eq!(definition.access_type, VariableAccessType::ReadWrite);
+        assert_eq!(
+            proxy_rw_int.get_variable().get_state().get_value(),
+            &VariableValue::Int(1000)
+        );
+
+        let proxy_con = connect_to_provider("proxy_consumer", PROXY_PROVIDER_ID).await;
+        proxy_con
+            .wait_until_variable_keys_are_available(&["proxy.rw_int", "proxy.ro_int"])
+            .await
+            .unwrap();
+
+        //Upstream value changes are mirrored
+        let mut ro_int_stream = proxy_con
+            .subscribe_single_variable("proxy.ro_int")
+            .await
+            .unwrap();
+        let first = ro_int_stream.next().await.unwrap().value;
+        let second = ro_int_stream.next().await.unwrap().value;
+        assert_ne!(first, second);
+
+        //Writes are forwarded to the upstream provider and the change is mirrored back
+        let mut rw_int_stream = proxy_con
+            .subscribe_single_variable("proxy.rw_int")
+            .await
+            .unwrap();
+        proxy_con
+            .write_single_variable("proxy.rw_int", 42)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            rw_int_stream.next().await.unwrap().value,
+            VariableValue::Int(42)
+        );
+        assert_eq!(
+            upstream_con
+                .read_single_variable("my_folder.rw_int")
+                .await
+                .unwrap()
+                .value,
+            VariableValue::Int(42)
+        );
+    }))
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn proxy_variable_unknown_upstream_key() {
+    run_with_timeout(async move {
+        let _fake_reg = FakeRegistry::new().await;
+        let _dummy_provider = DummyProvider::new().await.unwrap();
+
+        let upstream_con = Arc::new(connect_to_provider("proxy_upstream", PROVIDER_ID).await);
+
+        let local_var = VariableBuilder::new(1, "local.int")
+            .initial_value(0)
+            .build()
+            .unwrap();
+        let proxy_provider = ProviderBuilder::new()
+            .add_variables(vec![local_var])
+            .unwrap()
+            .register_with_existing_connection(create_auth_con(PROXY_PROVIDER_ID).await)
+            .await
+            .unwrap();
+
+        let result = proxy_provider
+            .add_proxy_variable(2, "proxy.unknown", upstream_con, "my_folder.unknown")
+            .await;
+
+        assert!(matches!(result, Err(AddProxyVariableError::Upstream(_))));
+    })
+    .await;
+}
@@ -174,3 +174,105 @@ async fn test_add_variables_fail_on_duplicates() {
 
     drop(provider);
 }
+
+#[tokio::test]
+#[serial]
+async fn test_current_definition() {
+    // Prepare
+    let _fake_registry = FakeRegistry::new().await;
+    let auth_nats_con = utils::create_auth_con(PROVIDER_ID).await;
+
+    let var1 = VariableBuilder::new(0, "my_folder.my_variable_1")
+        .initial_value(true)
+        .build()
+        .expect("variable should build");
+
+    let var2 = VariableBuilder::new(1, "my_folder.my_variable_2")
+        .initial_value(true)
+        .build()
+        .expect("variable should build");
+
+    let provider = ProviderBuilder::new()
+        .add_variables(vec![var1.clone(), var2.clone()])
+        .expect("Variables should be added")
+        .register_with_existing_connection(auth_nats_con)
+        .await
+        .expect("provider should register");
+
+    // act
+    let var3 = VariableBuilder::new(2, "my_folder.my_variable_3")
+        .initial_value(1)
+        .build()
+        .expect("variable should build");
+
+    provider
+        .add_variables(vec![var3.clone()])
+        .await
+        .expect("should add a new variable");
+    provider
+        .remove_variables(vec![var1])
+        .await
+        .expect("should remove a variable");
+
+    let definition = provider
+        .current_definition()
+        .await
+        .expect("should return the definition");
+
+    // assert
+    assert_eq!(
+        definition,
+        vec![var2.get_definition().clone(), var3.get_definition().clone()]
+    );
+
+    drop(provider);
+}
+
+#[tokio::test]
+#[serial]
+async fn test_variable_capacity() {
+    // Prepare
+    let _fake_registry = FakeRegistry::new().await;
+    let auth_nats_con = utils::create_auth_con(PROVIDER_ID).await;
+
+    let var1 = VariableBuilder::new(0, "my_folder.my_variable_1")
+        .initial_value(true)
+        .build()
+        .expect("variable should build");
+
+    let var2 = VariableBuilder::new(1, "my_folder.my_variable_2")
+        .initial_value(true)
+        .build()
+        .expect("variable should build");
+
+    let provider = ProviderBuilder::new()
+        .max_variables(2)
+        .add_variables(vec![var1])
+        .expect("Variables should be added")
+        .register_with_existing_connection(auth_nats_con)
+        .await
+        .expect("provider should register");
+
+    // act
+    let capacity = provider
+        .variable_capacity()
+        .await
+        .expect("should return the capacity");
+
+    // assert
+    assert_eq!(capacity.count, 1);
+    assert_eq!(capacity.max_variables, 2);
+    assert_eq!(capacity.remaining(), 1);
+
+    provider
+        .add_variables(vec![var2])
+        .await
+        .expect("should add a new variable");
+
+    assert_eq!(provider.variable_count().await.unwrap(), 2);
+    let capacity = provider.variable_capacity().await.unwrap();
+    assert_eq!(capacity.remaining(), 0);
+    assert!((capacity.utilization() - 1.0).abs() < f64::EPSILON);
+
+    drop(provider);
+}
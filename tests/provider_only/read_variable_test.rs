@@ -2,12 +2,22 @@
 //
 // SPDX-License-Identifier: MIT
 
+use std::{sync::Arc, time::Duration};
+
+use bytes::Bytes;
 use serial_test::serial;
+use tokio::time::timeout;
 use u_os_hub_client::{
+    authenticated_nats_con::NatsPermission,
+    consumer::{connected_nats_provider::ConnectedNatsProvider, nats_consumer::NatsConsumer},
     dh_types::VariableValue,
-    generated::weidmueller::ucontrol::hub::root_as_read_variables_query_response,
+    generated::weidmueller::ucontrol::hub::{
+        root_as_read_variables_query_response, ReadVariablesQueryRequestT,
+        ReadVariablesQueryResponseT, VariablesChangedEventT, WriteVariablesCommandT,
+    },
     nats_subjects,
     payload_builders::build_read_variables_query_request,
+    payload_codec::{FlatbufferCodec, PayloadCodec, PayloadDecodeError},
     provider::{ProviderBuilder, VariableBuilder},
 };
 
@@ -172,3 +182,119 @@ async fn test_read_one_variable() {
         }
     }
 }
+
+/// Flatbuffer codec that reverses all payloads, so they are no valid flatbuffers on the wire.
+#[derive(Debug)]
+struct ReversedFlatbufferCodec;
+
+fn reversed(payload: &[u8]) -> Vec<u8> {
+    payload.iter().rev().copied().collect()
+}
+
+impl PayloadCodec for ReversedFlatbufferCodec {
+    fn encode_read_variables_query_request(&self, request: &ReadVariablesQueryRequestT) -> Bytes {
+        reversed(&FlatbufferCodec.encode_read_variables_query_request(request)).into()
+    }
+
+    fn decode_read_variables_query_request(
+        &self,
+        payload: &[u8],
+    ) -> Result<ReadVariablesQueryRequestT, PayloadDecodeError> {
+        FlatbufferCodec.decode_read_variables_query_request(&reversed(payload))
+    }
+
+    fn encode_read_variables_query_response(
+        &self,
+        response: &ReadVariablesQueryResponseT,
+    ) -> Bytes {
+        reversed(&FlatbufferCodec.encode_read_variables_query_response(response)).into()
+    }
+
+    fn decode_read_variables_query_response(
+        &self,
+        payload: &[u8],
+    ) -> Result<ReadVariablesQueryResponseT, PayloadDecodeError> {
+        FlatbufferCodec.decode_read_variables_query_response(&reversed(payload))
+    }
+
+    fn encode_variables_changed_event(&self, event: &VariablesChangedEventT) -> Bytes {
+        reversed(&FlatbufferCodec.encode_variables_changed_event(event)).into()
+    }
+
+    fn decode_variables_changed_event(
+        &self,
+        payload: &[u8],
+    ) -> Result<VariablesChangedEventT, PayloadDecodeError> {
+        FlatbufferCodec.decode_variables_changed_event(&reversed(payload))
+    }
+
+    fn encode_write_variables_command(&self, command: &WriteVariablesCommandT) -> Bytes {
+        reversed(&FlatbufferCodec.encode_write_variables_command(command)).into()
+    }
+
+    fn decode_write_variables_command(
+        &self,
+        payload: &[u8],
+    ) -> Result<WriteVariablesCommandT, PayloadDecodeError> {
+        FlatbufferCodec.decode_write_variables_command(&reversed(payload))
+    }
+}
+
+#[tokio::test]
+#[serial]
+async fn test_read_variables_with_custom_payload_codec() {
+    // Prepare
+    let _fake_registry = FakeRegistry::new().await;
+    let auth_nats_con = utils::create_auth_con(PROVIDER_ID).await;
+    let test_nats_client = auth_nats_con.get_client().clone();
+
+    let var1 = VariableBuilder::new(0, "my_folder.my_variable_1")
+        .initial_value(123)
+        .build()
+        .expect("variable should build");
+
+    let _provider = ProviderBuilder::new()
+        .with_payload_codec(Arc::new(ReversedFlatbufferCodec))
+        .add_variables(vec![var1])
+        .expect("Variables should be added")
+        .register_with_existing_connection(auth_nats_con)
+        .await
+        .expect("provider should register");
+
+    let consumer_nats_con =
+        utils::create_auth_con_with_perm("test-consumer", NatsPermission::VariableHubRead).await;
+    let nats_consumer = Arc::new(
+        NatsConsumer::new(consumer_nats_con)
+            .await
+            .expect("consumer should be created")
+            .with_payload_codec(Arc::new(ReversedFlatbufferCodec)),
+    );
+    let connected_nats_provider = ConnectedNatsProvider::new(nats_consumer, PROVIDER_ID)
+        .await
+        .unwrap();
+
+    // act
+    let response = connected_nats_provider
+        .read_variables(&ReadVariablesQueryRequestT::default())
+        .await
+        .expect("the response should be decoded with the same codec");
+
+    // assert
+    let variables = response.variables.items.expect("there should be variables");
+    assert_eq!(variables.len(), 1);
+    assert_eq!(
+        Option::<VariableValue>::from(variables[0].value.clone()),
+        Some(VariableValue::Int(123))
+    );
+
+    //A client with the default codec can't read the provider, as it ignores the invalid request
+    let result = timeout(
+        Duration::from_millis(500),
+        test_nats_client.request(
+            nats_subjects::read_variables_query(PROVIDER_ID),
+            build_read_variables_query_request(None),
+        ),
+    )
+    .await;
+    assert!(!matches!(result, Ok(Ok(_))));
+}
@@ -2,18 +2,31 @@
 //
 // SPDX-License-Identifier: MIT
 
-use std::time::Duration;
+use std::{
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
 
 use futures::StreamExt;
 use serial_test::serial;
 use tokio::time::timeout;
 use u_os_hub_client::{
+    authenticated_nats_con::{AuthenticationSettingsBuilder, NatsPermission},
     generated::weidmueller::ucontrol::hub::{
-        root_as_read_provider_definition_query_response, State,
+        root_as_read_provider_definition_query_response, ProviderDefinitionState,
+        ProviderDefinitionT, State,
     },
     nats_subjects,
-    payload_builders::build_state_changed_event_payload,
-    provider::{ProviderBuilder, VariableBuilder},
+    payload_builders::{
+        build_provider_definition_changed_event, build_state_changed_event_payload,
+    },
+    provider::{
+        provider_builder::{ConnectBackoff, ConnectError, RegistrationFailurePolicy},
+        AddVariablesError, FatalProviderError, ProviderBuilder, VariableBuilder,
+    },
 };
 
 use crate::utils::{self, fake_registry::FakeRegistry};
@@ -164,3 +177,238 @@ async fn test_resend_provider_definition_on_registry_up_event() {
     }
     drop(provider);
 }
+
+#[tokio::test]
+#[serial]
+async fn test_register_provider_with_explicit_provider_id() {
+    const EXPLICIT_PROVIDER_ID: &str = "explicit_provider_id";
+
+    // Prepare
+    let _fake_registry = FakeRegistry::new().await;
+    let auth_nats_con = utils::create_auth_con(PROVIDER_ID).await;
+    let test_nats_client = auth_nats_con.get_client();
+
+    let mut registry_def_changed_subscription = test_nats_client
+        .subscribe(nats_subjects::registry_provider_definition_changed_event(
+            EXPLICIT_PROVIDER_ID,
+        ))
+        .await
+        .expect("should subscribe to def changed event from registry");
+
+    // act
+    let var1 = VariableBuilder::new(0, "my_folder.my_variable_1")
+        .initial_value(true)
+        .build()
+        .expect("variable should build");
+
+    let _provider = ProviderBuilder::new()
+        .with_provider_id(EXPLICIT_PROVIDER_ID)
+        .add_variables(vec![var1])
+        .expect("Variables should be added")
+        .register_with_existing_connection(auth_nats_con)
+        .await
+        .expect("provider should register");
+
+    // assert
+    let msg = timeout(
+        Duration::from_secs(1),
+        registry_def_changed_subscription.next(),
+    )
+    .await
+    .expect("definition changed message should have been sended")
+    .expect("subscription should be open");
+
+    assert_eq!(
+        nats_subjects::get_provider_name_from_subject(&msg.subject).expect("should be there set"),
+        EXPLICIT_PROVIDER_ID
+    );
+}
+
+/// Starts a registry replacement that marks every provider definition of [`PROVIDER_ID`] as invalid.
+/// Returns the number of received registration attempts.
+async fn start_rejecting_registry() -> (tokio::task::JoinHandle<()>, Arc<AtomicU32>) {
+    let registry_con = utils::create_auth_con("fake-registry").await;
+    let mut def_changed_subscription = registry_con
+        .get_client()
+        .subscribe(nats_subjects::provider_changed_event(PROVIDER_ID))
+        .await
+        .expect("should subscribe to provider def changed event");
+
+    let attempts = Arc::new(AtomicU32::new(0));
+    let attempts_clone = attempts.clone();
+    let task = tokio::spawn(async move {
+        let client = registry_con.get_client();
+        while def_changed_subscription.next().await.is_some() {
+            attempts_clone.fetch_add(1, Ordering::SeqCst);
+
+            let mut provider_def = ProviderDefinitionT::default();
+            provider_def.state = ProviderDefinitionState::INVALID;
+            client
+                .publish(
+                    nats_subjects::registry_provider_definition_changed_event(PROVIDER_ID),
+                    build_provider_definition_changed_event(Some(provider_def)),
+                )
+                .await
+                .unwrap();
+            client.flush().await.unwrap();
+        }
+    });
+
+    (task, attempts)
+}
+
+#[tokio::test]
+#[serial]
+async fn test_register_provider_gives_up_after_retries() {
+    // Prepare
+    let (registry_task, attempts) = start_rejecting_registry().await;
+    let auth_nats_con = utils::create_auth_con(PROVIDER_ID).await;
+
+    // act
+    let result = timeout(
+        Duration::from_secs(5),
+        ProviderBuilder::new()
+            .registration_failure_policy(RegistrationFailurePolicy::Retry {
+                backoff: Duration::from_millis(10),
+                max_attempts: 3,
+            })
+            .register_with_existing_connection(auth_nats_con),
+    )
+    .await
+    .expect("registration should give up before timeout");
+
+    // assert
+    assert!(matches!(result, Err(ConnectError::RegistrationFailed)));
+    assert_eq!(attempts.load(Ordering::SeqCst), 3);
+    registry_task.abort();
+}
+
+#[tokio::test]
+#[serial]
+async fn test_register_provider_abort_policy() {
+    // Prepare
+    let (registry_task, attempts) = start_rejecting_registry().await;
+    let auth_nats_con = utils::create_auth_con(PROVIDER_ID).await;
+
+    // act
+    let result = timeout(
+        Duration::from_secs(5),
+        ProviderBuilder::new()
+            .registration_failure_policy(RegistrationFailurePolicy::Abort)
+            .register_with_existing_connection(auth_nats_con),
+    )
+    .await
+    .expect("registration should give up before timeout");
+
+    // assert
+    assert!(matches!(result, Err(ConnectError::RegistrationFailed)));
+    assert_eq!(attempts.load(Ordering::SeqCst), 1);
+    registry_task.abort();
+}
+
+#[tokio::test]
+async fn test_register_with_backoff_gives_up_after_max_attempts() {
+    // Prepare
+    let auth_settings =
+        AuthenticationSettingsBuilder::new(NatsPermission::VariableHubProvide).build();
+    let backoff = ConnectBackoff {
+        initial_delay: Duration::from_millis(10),
+        max_delay: Duration::from_millis(10),
+        max_attempts: Some(2),
+    };
+
+    // act
+    let result = timeout(
+        Duration::from_secs(30),
+        ProviderBuilder::new().register_with_backoff(
+            "nats://localhost:4223",
+            &auth_settings,
+            backoff,
+        ),
+    )
+    .await
+    .expect("connecting should give up before timeout");
+
+    // assert
+    assert!(matches!(result, Err(ConnectError::Nats(_))));
+}
+
+#[tokio::test]
+async fn test_register_with_backoff_invalid_address() {
+    // Prepare
+    let auth_settings =
+        AuthenticationSettingsBuilder::new(NatsPermission::VariableHubProvide).build();
+
+    // act
+    //Must fail immediately, although the backoff retries forever
+    let result = timeout(
+        Duration::from_millis(100),
+        ProviderBuilder::new().register_with_backoff(
+            "localhost:4222",
+            &auth_settings,
+            ConnectBackoff::default(),
+        ),
+    )
+    .await
+    .expect("an invalid address should not be retried");
+
+    // assert
+    assert!(matches!(result, Err(ConnectError::Nats(_))));
+}
+
+#[tokio::test]
+#[serial]
+async fn test_registration_timeout() {
+    // Prepare
+    //No registry is running, so the definition is never accepted
+    let auth_nats_con = utils::create_auth_con(PROVIDER_ID).await;
+
+    // act
+    let result = timeout(
+        Duration::from_secs(5),
+        ProviderBuilder::new()
+            .with_registration_timeout(Duration::from_millis(200))
+            .register_with_existing_connection(auth_nats_con),
+    )
+    .await
+    .expect("registration should time out before the test timeout");
+
+    // assert
+    assert!(matches!(result, Err(ConnectError::Timeout)));
+}
+
+#[tokio::test]
+#[serial]
+async fn test_fatal_error_on_closed_connection() {
+    // Prepare
+    let _fake_registry = FakeRegistry::new().await;
+    let auth_nats_con = utils::create_auth_con(PROVIDER_ID).await;
+    let test_nats_client = auth_nats_con.get_client().clone();
+
+    let provider = ProviderBuilder::new()
+        .register_with_existing_connection(auth_nats_con)
+        .await
+        .expect("provider should register");
+
+    // act
+    test_nats_client
+        .drain()
+        .await
+        .expect("connection should drain");
+
+    // assert
+    let fatal_error = timeout(Duration::from_secs(5), provider.wait_for_fatal_error())
+        .await
+        .expect("fatal error should be reported");
+    assert!(matches!(fatal_error, FatalProviderError::ConnectionClosed));
+
+    //the error stays available and the provider is unusable afterwards
+    assert!(matches!(
+        provider.wait_for_fatal_error().await,
+        FatalProviderError::ConnectionClosed
+    ));
+    assert!(matches!(
+        provider.add_variables(vec![]).await,
+        Err(AddVariablesError::ProviderThreadCrashed)
+    ));
+}
@@ -15,7 +15,11 @@ use u_os_hub_client::{
         nats_consumer::NatsConsumer, variable_key::VariableKey,
     },
     dh_types::{DurationValue, TimestampValue, VariableQuality, VariableValue},
-    provider::{ProviderBuilder, UpdateVariableValuesError, VariableBuilder},
+    generated::weidmueller::ucontrol::hub::ReadVariablesQueryRequestT,
+    provider::{
+        provider_builder::{AggregateOp, UnchangedUpdates},
+        GetVariableStatesError, ProviderBuilder, UpdateVariableValuesError, VariableBuilder,
+    },
 };
 
 use crate::utils::{self, fake_registry::FakeRegistry};
@@ -187,6 +191,255 @@ async fn test_update_variable_fingerprint() {
     }
 }
 
+#[tokio::test]
+#[serial]
+async fn test_pause_publishing() {
+    // Prepare
+    let _fake_registry = FakeRegistry::new().await;
+    let auth_nats_con = utils::create_auth_con(PROVIDER_ID).await;
+
+    let auth_nats_con_consumer =
+        utils::create_auth_con_with_perm("test-consumer", NatsPermission::VariableHubRead).await;
+    let nats_consumer = Arc::new(
+        NatsConsumer::new(auth_nats_con_consumer)
+            .await
+            .expect("consumer should be created"),
+    );
+
+    let mut var1 = VariableBuilder::new(0, "my_folder.my_variable_1")
+        .initial_value(0)
+        .build()
+        .expect("variable should build");
+
+    let mut var2 = VariableBuilder::new(1, "my_folder.my_variable_2")
+        .initial_value("Test_it")
+        .build()
+        .expect("variable should build");
+
+    let provider = ProviderBuilder::new()
+        .add_variables(vec![var1.clone(), var2.clone()])
+        .expect("Variables should be added")
+        .register_with_existing_connection(auth_nats_con)
+        .await
+        .expect("provider should register");
+
+    nats_consumer.wait_for_provider(PROVIDER_ID).await.unwrap();
+    let connected_nats_provider = ConnectedNatsProvider::new(nats_consumer, PROVIDER_ID)
+        .await
+        .unwrap();
+    let mut var_change_sub = connected_nats_provider.subscribe_variables().await.unwrap();
+
+    // act
+    provider.pause_publishing().await.unwrap();
+
+    for value in 1..=3 {
+        var1.get_mut_state().set_value(value);
+        provider
+            .update_variable_states(vec![var1.get_state().clone()])
+            .await
+            .unwrap();
+    }
+    var2.get_mut_state().set_value("Test_String123");
+    provider
+        .update_variable_states(vec![var2.get_state().clone()])
+        .await
+        .unwrap();
+
+    // assert
+    //nothing is published while paused
+    assert!(timeout(Duration::from_millis(500), var_change_sub.next())
+        .await
+        .is_err());
+
+    provider.resume_publishing().await.unwrap();
+
+    //all changes are published in a single event with the latest states
+    let var_changed_event = timeout(Duration::from_secs(1), var_change_sub.next())
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap();
+    let items = var_changed_event.changed_variables.items.unwrap();
+    assert_eq!(items.len(), 2);
+    assert_eq!(items[0].id, 0);
+    assert_eq!(
+        Option::<VariableValue>::from(items[0].value.clone()),
+        Some(VariableValue::Int(3))
+    );
+    assert_eq!(items[1].id, 1);
+
+    //resuming again does not publish anything
+    provider.resume_publishing().await.unwrap();
+    assert!(timeout(Duration::from_millis(500), var_change_sub.next())
+        .await
+        .is_err());
+}
+
+#[tokio::test]
+#[serial]
+async fn test_unchanged_updates() {
+    // Prepare
+    let _fake_registry = FakeRegistry::new().await;
+    let auth_nats_con = utils::create_auth_con(PROVIDER_ID).await;
+
+    let auth_nats_con_consumer =
+        utils::create_auth_con_with_perm("test-consumer", NatsPermission::VariableHubRead).await;
+    let nats_consumer = Arc::new(
+        NatsConsumer::new(auth_nats_con_consumer)
+            .await
+            .expect("consumer should be created"),
+    );
+
+    let mut var1 = VariableBuilder::new(0, "my_folder.my_variable_1")
+        .initial_value(0)
+        .build()
+        .expect("variable should build");
+
+    let provider = ProviderBuilder::new()
+        .unchanged_updates(UnchangedUpdates::PublishRefreshes)
+        .add_variables(vec![var1.clone()])
+        .expect("Variables should be added")
+        .register_with_existing_connection(auth_nats_con)
+        .await
+        .expect("provider should register");
+
+    nats_consumer.wait_for_provider(PROVIDER_ID).await.unwrap();
+    let connected_nats_provider = ConnectedNatsProvider::new(nats_consumer, PROVIDER_ID)
+        .await
+        .unwrap();
+    let mut var_change_sub = connected_nats_provider.subscribe_variables().await.unwrap();
+
+    // act
+    var1.get_mut_state().set_all(
+        0,
+        VariableQuality::Good,
+        Some(TimestampValue::from_unix_timestamp(1).unwrap()),
+    );
+    provider
+        .update_variable_states(vec![var1.get_state().clone()])
+        .await
+        .unwrap();
+    //the quality and timestamp changed, so the update is published
+    assert!(timeout(Duration::from_secs(1), var_change_sub.next())
+        .await
+        .is_ok());
+
+    // assert
+    //an identical update is a no-op and suppressed
+    provider
+        .update_variable_states(vec![var1.get_state().clone()])
+        .await
+        .unwrap();
+    assert!(timeout(Duration::from_millis(500), var_change_sub.next())
+        .await
+        .is_err());
+
+    //a timestamp-only update is published as liveness refresh
+    var1.get_mut_state().set_all(
+        0,
+        VariableQuality::Good,
+        Some(TimestampValue::from_unix_timestamp(2).unwrap()),
+    );
+    provider
+        .update_variable_states(vec![var1.get_state().clone()])
+        .await
+        .unwrap();
+    let var_changed_event = timeout(Duration::from_secs(1), var_change_sub.next())
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap();
+    let items = var_changed_event.changed_variables.items.unwrap();
+    assert_eq!(items.len(), 1);
+    assert_eq!(items[0].id, 0);
+}
+
+#[tokio::test]
+#[serial]
+async fn test_update_variable_states_if_changed() {
+    // Prepare
+    let _fake_registry = FakeRegistry::new().await;
+    let auth_nats_con = utils::create_auth_con(PROVIDER_ID).await;
+
+    let auth_nats_con_consumer =
+        utils::create_auth_con_with_perm("test-consumer", NatsPermission::VariableHubRead).await;
+    let nats_consumer = Arc::new(
+        NatsConsumer::new(auth_nats_con_consumer)
+            .await
+            .expect("consumer should be created"),
+    );
+
+    let mut var1 = VariableBuilder::new(0, "my_folder.my_variable_1")
+        .initial_value(0)
+        .build()
+        .expect("variable should build");
+    let mut var2 = VariableBuilder::new(1, "my_folder.my_variable_2")
+        .initial_value(0)
+        .build()
+        .expect("variable should build");
+
+    //the builder keeps publishing every update
+    let provider = ProviderBuilder::new()
+        .add_variables(vec![var1.clone(), var2.clone()])
+        .expect("Variables should be added")
+        .register_with_existing_connection(auth_nats_con)
+        .await
+        .expect("provider should register");
+
+    nats_consumer.wait_for_provider(PROVIDER_ID).await.unwrap();
+    let connected_nats_provider = ConnectedNatsProvider::new(nats_consumer, PROVIDER_ID)
+        .await
+        .unwrap();
+    let mut var_change_sub = connected_nats_provider.subscribe_variables().await.unwrap();
+
+    // act
+    let timestamp = var2.get_state().get_timestamp().unwrap() + DurationValue::seconds(1);
+    var1.get_mut_state()
+        .set_all(1, VariableQuality::Good, Some(timestamp));
+    var2.get_mut_state()
+        .set_all(0, VariableQuality::Good, Some(timestamp));
+    provider
+        .update_variable_states_if_changed(
+            vec![var1.get_state().clone(), var2.get_state().clone()],
+            true,
+        )
+        .await
+        .unwrap();
+
+    // assert
+    //only the value of var1 changed, the timestamp-only change of var2 is ignored
+    let var_changed_event = timeout(Duration::from_secs(1), var_change_sub.next())
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap();
+    let items = var_changed_event.changed_variables.items.unwrap();
+    assert_eq!(items.len(), 1);
+    assert_eq!(items[0].id, 0);
+
+    //the timestamp of var2 was applied nonetheless
+    let states = provider.get_variable_states(vec![1]).await.unwrap();
+    assert_eq!(states, vec![var2.get_state().clone()]);
+
+    //an identical update is suppressed
+    provider
+        .update_variable_states_if_changed(vec![var1.get_state().clone()], false)
+        .await
+        .unwrap();
+    assert!(timeout(Duration::from_millis(500), var_change_sub.next())
+        .await
+        .is_err());
+
+    //other updates still follow the policy of the builder
+    provider
+        .update_variable_states(vec![var1.get_state().clone()])
+        .await
+        .unwrap();
+    assert!(timeout(Duration::from_secs(1), var_change_sub.next())
+        .await
+        .is_ok());
+}
+
 /// Tests some advanced cases of the variable state and consumer interaction.
 #[tokio::test]
 #[serial]
@@ -307,3 +560,247 @@ async fn test_quality_and_timestamp() {
     //Variable timestamp should be recent
     assert!((TimestampValue::now() - var_state.timestamp) < DurationValue::seconds(1));
 }
+
+#[tokio::test]
+#[serial]
+async fn test_heartbeat() {
+    // Prepare
+    let _fake_registry = FakeRegistry::new().await;
+    let auth_nats_con = utils::create_auth_con(PROVIDER_ID).await;
+
+    let auth_nats_con_consumer =
+        utils::create_auth_con_with_perm("test-consumer", NatsPermission::VariableHubRead).await;
+    let nats_consumer = Arc::new(
+        NatsConsumer::new(auth_nats_con_consumer)
+            .await
+            .expect("consumer should be created"),
+    );
+
+    // act
+    let _provider = ProviderBuilder::new()
+        .with_heartbeat(7, "heartbeat", Duration::from_millis(100))
+        .expect("heartbeat should be added")
+        .register_with_existing_connection(auth_nats_con)
+        .await
+        .expect("provider should register");
+
+    nats_consumer.wait_for_provider(PROVIDER_ID).await.unwrap();
+    let connected_nats_provider = ConnectedNatsProvider::new(nats_consumer, PROVIDER_ID)
+        .await
+        .unwrap();
+    let mut var_change_sub = connected_nats_provider.subscribe_variables().await.unwrap();
+
+    // assert
+    //the heartbeat is updated periodically with the current time
+    let mut heartbeats = Vec::new();
+    for _ in 0..2 {
+        let var_changed_event = timeout(Duration::from_secs(1), var_change_sub.next())
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        let items = var_changed_event.changed_variables.items.unwrap();
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].id, 7);
+
+        let Some(VariableValue::Timestamp(heartbeat)) =
+            Option::<VariableValue>::from(items[0].value.clone())
+        else {
+            panic!("heartbeat should be a timestamp");
+        };
+        heartbeats.push(heartbeat);
+    }
+    assert!(heartbeats[1] > heartbeats[0]);
+}
+
+#[tokio::test]
+#[serial]
+async fn test_aggregate() {
+    // Prepare
+    let _fake_registry = FakeRegistry::new().await;
+    let auth_nats_con = utils::create_auth_con(PROVIDER_ID).await;
+
+    let auth_nats_con_consumer =
+        utils::create_auth_con_with_perm("test-consumer", NatsPermission::VariableHubRead).await;
+    let nats_consumer = Arc::new(
+        NatsConsumer::new(auth_nats_con_consumer)
+            .await
+            .expect("consumer should be created"),
+    );
+
+    let mut var1 = VariableBuilder::new(0, "machine.power_1")
+        .initial_value(1.5)
+        .build()
+        .expect("variable should build");
+    let var2 = VariableBuilder::new(1, "machine.power_2")
+        .initial_value(2)
+        .build()
+        .expect("variable should build");
+
+    let provider = ProviderBuilder::new()
+        .add_variables(vec![var1.clone(), var2])
+        .expect("Variables should be added")
+        .add_aggregate(10, "machine_total", "machine", AggregateOp::Sum)
+        .expect("aggregate should be added")
+        .register_with_existing_connection(auth_nats_con)
+        .await
+        .expect("provider should register");
+
+    nats_consumer.wait_for_provider(PROVIDER_ID).await.unwrap();
+    let connected_nats_provider = ConnectedNatsProvider::new(nats_consumer, PROVIDER_ID)
+        .await
+        .unwrap();
+    let mut var_change_sub = connected_nats_provider.subscribe_variables().await.unwrap();
+
+    // assert
+    //the initial aggregate is calculated on registration
+    let mut read_request = ReadVariablesQueryRequestT::default();
+    read_request.ids = Some(vec![10]);
+    let response = connected_nats_provider
+        .read_variables(&read_request)
+        .await
+        .unwrap();
+    let items = response.variables.items.unwrap();
+    assert_eq!(
+        Option::<VariableValue>::from(items[0].value.clone()),
+        Some(VariableValue::Float64(3.5))
+    );
+
+    //the aggregate is published together with the changed member
+    var1.get_mut_state().set_value(5.0);
+    provider
+        .update_variable_states(vec![var1.get_state().clone()])
+        .await
+        .unwrap();
+
+    let var_changed_event = timeout(Duration::from_secs(1), var_change_sub.next())
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap();
+    let items = var_changed_event.changed_variables.items.unwrap();
+    assert_eq!(items.len(), 2);
+    assert_eq!(items[1].id, 10);
+    assert_eq!(
+        Option::<VariableValue>::from(items[1].value.clone()),
+        Some(VariableValue::Float64(7.0))
+    );
+}
+
+#[cfg(feature = "compression")]
+#[tokio::test]
+#[serial]
+async fn test_compressed_change_events() {
+    use u_os_hub_client::payload_compression::PayloadCompression;
+
+    // Prepare
+    let _fake_registry = FakeRegistry::new().await;
+    let auth_nats_con = utils::create_auth_con(PROVIDER_ID).await;
+
+    let auth_nats_con_consumer =
+        utils::create_auth_con_with_perm("test-consumer", NatsPermission::VariableHubRead).await;
+    let nats_consumer = Arc::new(
+        NatsConsumer::new(auth_nats_con_consumer)
+            .await
+            .expect("consumer should be created"),
+    );
+
+    let mut var1 = VariableBuilder::new(0, "my_folder.my_variable_1")
+        .initial_value("x".repeat(2048))
+        .build()
+        .expect("variable should build");
+
+    let provider = ProviderBuilder::new()
+        .add_variables(vec![var1.clone()])
+        .expect("Variables should be added")
+        .with_payload_compression(PayloadCompression::default())
+        .register_with_existing_connection(auth_nats_con)
+        .await
+        .expect("provider should register");
+
+    nats_consumer.wait_for_provider(PROVIDER_ID).await.unwrap();
+    let connected_nats_provider = ConnectedNatsProvider::new(nats_consumer, PROVIDER_ID)
+        .await
+        .unwrap();
+    let mut var_change_sub = connected_nats_provider.subscribe_variables().await.unwrap();
+
+    // act
+    var1.get_mut_state().set_value("y".repeat(2048));
+    provider
+        .update_variable_states(vec![var1.get_state().clone()])
+        .await
+        .unwrap();
+
+    // assert
+    //the event is decompressed transparently
+    let var_changed_event = timeout(Duration::from_secs(1), var_change_sub.next())
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap();
+    let items = var_changed_event.changed_variables.items.unwrap();
+    assert_eq!(
+        Option::<VariableValue>::from(items[0].value.clone()),
+        Some(VariableValue::String("y".repeat(2048)))
+    );
+}
+
+#[tokio::test]
+#[serial]
+async fn test_get_variable_states() {
+    // Prepare
+    let _fake_registry = FakeRegistry::new().await;
+    let auth_nats_con = utils::create_auth_con(PROVIDER_ID).await;
+
+    let var1 = VariableBuilder::new(0, "my_folder.my_variable_1")
+        .initial_value(true)
+        .build()
+        .expect("variable should build");
+
+    let mut var2 = VariableBuilder::new(1, "my_folder.my_variable_2")
+        .initial_value("Test_it")
+        .build()
+        .expect("variable should build");
+
+    let provider = ProviderBuilder::new()
+        .add_variables(vec![var1.clone(), var2.clone()])
+        .expect("Variables should be added")
+        .register_with_existing_connection(auth_nats_con)
+        .await
+        .expect("provider should register");
+
+    var2.get_mut_state().set_all(
+        "Test_String123",
+        VariableQuality::UncertainLastUsableValue,
+        Some(TimestampValue::now()),
+    );
+    provider
+        .update_variable_states(vec![var2.get_state().clone()])
+        .await
+        .expect("should update the variable");
+
+    // act
+    let states = provider
+        .get_variable_states(vec![1, 0])
+        .await
+        .expect("should read the states");
+    let all_states = provider
+        .get_variable_states(vec![])
+        .await
+        .expect("should read all states");
+    let unknown = provider.get_variable_states(vec![0, 42]).await;
+
+    // assert
+    assert_eq!(
+        states,
+        vec![var2.get_state().clone(), var1.get_state().clone()]
+    );
+    assert_eq!(
+        all_states,
+        vec![var1.get_state().clone(), var2.get_state().clone()]
+    );
+    assert!(matches!(
+        unknown,
+        Err(GetVariableStatesError::VariableNotFound(_))
+    ));
+}
@@ -8,11 +8,17 @@ use futures::StreamExt;
 use serial_test::serial;
 use tokio::time::timeout;
 use u_os_hub_client::{
-    dh_types::VariableAccessType,
+    consumer::connected_nats_provider::{
+        WRITE_ACCEPTED_IDS_HEADER, WRITE_INVALID_COMMAND_HEADER, WRITE_REJECTED_IDS_HEADER,
+    },
+    dh_types::{VariableAccessType, VariableValue},
     generated::weidmueller::ucontrol::hub::root_as_read_provider_definition_query_response,
     nats_subjects,
     payload_builders::{build_write_variables_command, VariableUpdate},
-    provider::{ProviderBuilder, VariableBuilder},
+    provider::{
+        provider_types::OutOfBoundsWrite, AddAndSubscribeError, DrainWritesError, ProviderBuilder,
+        SubscribeToWriteCommandError, VariableBuilder,
+    },
 };
 
 use crate::utils::{self, fake_registry::FakeRegistry};
@@ -99,3 +105,596 @@ async fn test_write_variable_command() {
         panic!("should received write command")
     }
 }
+
+#[tokio::test]
+#[serial]
+async fn test_write_variable_command_ack() {
+    // Prepare
+    let _fake_registry = FakeRegistry::new().await;
+    let auth_nats_con = utils::create_auth_con(PROVIDER_ID).await;
+    let test_nats_client = auth_nats_con.get_client().clone();
+
+    let mut def_changed_subscription = test_nats_client
+        .subscribe(nats_subjects::provider_changed_event(PROVIDER_ID))
+        .await
+        .unwrap();
+
+    let rw_var = VariableBuilder::new(0, "my_folder.my_variable_rw")
+        .access_type(VariableAccessType::ReadWrite)
+        .initial_value(true)
+        .build()
+        .expect("variable should build");
+    let ro_var = VariableBuilder::new(1, "my_folder.my_variable_ro")
+        .initial_value(true)
+        .build()
+        .expect("variable should build");
+
+    let provider = ProviderBuilder::new()
+        .add_variables(vec![rw_var.clone(), ro_var.clone()])
+        .expect("Variables should be added")
+        .register_with_existing_connection(auth_nats_con)
+        .await
+        .expect("provider should register");
+
+    let mut subscription_to_write_cmd = provider
+        .subscribe_to_write_command(vec![rw_var.clone()])
+        .await
+        .expect("should work");
+
+    let msg = timeout(Duration::from_secs(1), def_changed_subscription.next())
+        .await
+        .expect("should receive a provider definition from register")
+        .unwrap();
+    let fingerprint = root_as_read_provider_definition_query_response(&msg.payload)
+        .unwrap()
+        .unpack()
+        .provider_definition
+        .expect("there should be a provider definition")
+        .fingerprint;
+
+    let updates = || {
+        [0, 1, 99]
+            .into_iter()
+            .map(|id| VariableUpdate {
+                id,
+                value: (&VariableValue::Boolean(false)).into(),
+            })
+            .collect()
+    };
+
+    // act
+    let reply = test_nats_client
+        .request(
+            nats_subjects::write_variables_command(PROVIDER_ID),
+            build_write_variables_command(updates(), fingerprint),
+        )
+        .await
+        .expect("provider should reply to the write request");
+
+    // assert
+    assert!(reply.payload.is_empty());
+    let headers = reply.headers.expect("ack should contain headers");
+    assert_eq!(
+        headers.get(WRITE_ACCEPTED_IDS_HEADER).unwrap().as_str(),
+        "0"
+    );
+    assert_eq!(
+        headers.get(WRITE_REJECTED_IDS_HEADER).unwrap().as_str(),
+        "1,99"
+    );
+
+    let write_commands = timeout(Duration::from_secs(1), subscription_to_write_cmd.recv())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(write_commands.len(), 1);
+    assert_eq!(write_commands[0].id, 0);
+
+    //writes based on an outdated definition are rejected completely
+    let reply = test_nats_client
+        .request(
+            nats_subjects::write_variables_command(PROVIDER_ID),
+            build_write_variables_command(updates(), fingerprint + 1),
+        )
+        .await
+        .expect("provider should reply to the write request");
+
+    let headers = reply.headers.expect("ack should contain headers");
+    assert!(headers.get(WRITE_ACCEPTED_IDS_HEADER).is_none());
+    assert_eq!(
+        headers.get(WRITE_REJECTED_IDS_HEADER).unwrap().as_str(),
+        "0,1,99"
+    );
+}
+
+#[tokio::test]
+#[serial]
+async fn test_write_variable_command_ack_for_invalid_payload() {
+    // Prepare
+    let _fake_registry = FakeRegistry::new().await;
+    let auth_nats_con = utils::create_auth_con(PROVIDER_ID).await;
+    let test_nats_client = auth_nats_con.get_client().clone();
+
+    let rw_var = VariableBuilder::new(0, "my_folder.my_variable_rw")
+        .access_type(VariableAccessType::ReadWrite)
+        .initial_value(true)
+        .build()
+        .expect("variable should build");
+
+    let _provider = ProviderBuilder::new()
+        .add_variables(vec![rw_var])
+        .expect("Variables should be added")
+        .register_with_existing_connection(auth_nats_con)
+        .await
+        .expect("provider should register");
+
+    // act
+    let reply = test_nats_client
+        .request(
+            nats_subjects::write_variables_command(PROVIDER_ID),
+            vec![1, 2, 3].into(),
+        )
+        .await
+        .expect("provider should reply to the invalid write request");
+
+    // assert
+    assert!(reply.payload.is_empty());
+    let headers = reply.headers.expect("ack should contain headers");
+    assert!(headers.get(WRITE_INVALID_COMMAND_HEADER).is_some());
+}
+
+#[tokio::test]
+#[serial]
+async fn test_write_variable_command_after_reconnect() {
+    // Prepare
+    let _fake_registry = FakeRegistry::new().await;
+    let auth_nats_con = utils::create_auth_con(PROVIDER_ID).await;
+    let test_nats_client = auth_nats_con.get_client().clone();
+
+    let mut def_changed_subscription = test_nats_client
+        .subscribe(nats_subjects::provider_changed_event(PROVIDER_ID))
+        .await
+        .unwrap();
+
+    let mut var1 = VariableBuilder::new(0, "my_folder.my_variable_1_rw")
+        .access_type(VariableAccessType::ReadWrite)
+        .initial_value(true)
+        .build()
+        .expect("variable should build");
+
+    let provider = ProviderBuilder::new()
+        .add_variables(vec![var1.clone()])
+        .expect("Variables should be added")
+        .register_with_existing_connection(auth_nats_con)
+        .await
+        .expect("provider should register");
+
+    let mut subscription_to_write_cmd = provider
+        .subscribe_to_write_command(vec![var1.clone()])
+        .await
+        .expect("should work");
+
+    timeout(Duration::from_secs(1), def_changed_subscription.next())
+        .await
+        .expect("should receive a provider definition from register");
+
+    // act
+    test_nats_client
+        .force_reconnect()
+        .await
+        .expect("should reconnect");
+
+    //the provider registers again after the reconnect
+    let msg = timeout(Duration::from_secs(5), def_changed_subscription.next())
+        .await
+        .expect("should receive a provider definition after reconnect")
+        .unwrap();
+    let fingerprint = root_as_read_provider_definition_query_response(&msg.payload)
+        .unwrap()
+        .unpack()
+        .provider_definition
+        .expect("there should be a provider definition")
+        .fingerprint;
+
+    let var1_state = var1.get_mut_state();
+    var1_state.set_value(false);
+    let write_cmd_payload = build_write_variables_command(
+        vec![VariableUpdate {
+            id: var1_state.get_id(),
+            value: var1_state.get_value().into(),
+        }],
+        fingerprint,
+    );
+
+    test_nats_client
+        .publish(
+            nats_subjects::write_variables_command(PROVIDER_ID),
+            write_cmd_payload,
+        )
+        .await
+        .expect("should publish write command");
+
+    // assert
+    //the notifier that was created before the reconnect still receives the write command
+    let write_commands = timeout(Duration::from_secs(5), subscription_to_write_cmd.recv())
+        .await
+        .expect("should receive write command")
+        .expect("write notifier should still be open");
+
+    assert_eq!(write_commands.len(), 1);
+    assert_eq!(write_commands[0].id, var1.get_definition().id);
+    assert_eq!(&write_commands[0].value, var1.get_state().get_value());
+}
+
+#[tokio::test]
+#[serial]
+async fn test_drain_writes() {
+    // Prepare
+    let _fake_registry = FakeRegistry::new().await;
+    let auth_nats_con = utils::create_auth_con(PROVIDER_ID).await;
+    let test_nats_client = auth_nats_con.get_client().clone();
+
+    let mut def_changed_subscription = test_nats_client
+        .subscribe(nats_subjects::provider_changed_event(PROVIDER_ID))
+        .await
+        .unwrap();
+
+    let var1 = VariableBuilder::new(0, "my_folder.my_variable_1_rw")
+        .access_type(VariableAccessType::ReadWrite)
+        .initial_value(true)
+        .build()
+        .expect("variable should build");
+
+    let provider = ProviderBuilder::new()
+        .add_variables(vec![var1.clone()])
+        .expect("Variables should be added")
+        .register_with_existing_connection(auth_nats_con)
+        .await
+        .expect("provider should register");
+
+    let mut subscription_to_write_cmd = provider
+        .subscribe_to_write_command(vec![var1.clone()])
+        .await
+        .expect("should work");
+
+    let msg = timeout(Duration::from_secs(1), def_changed_subscription.next())
+        .await
+        .expect("should receive a provider definition from register")
+        .unwrap();
+    let fingerprint = root_as_read_provider_definition_query_response(&msg.payload)
+        .unwrap()
+        .unpack()
+        .provider_definition
+        .expect("there should be a provider definition")
+        .fingerprint;
+
+    let write_cmd_payload = build_write_variables_command(
+        vec![VariableUpdate {
+            id: var1.get_definition().id,
+            value: (&VariableValue::Boolean(false)).into(),
+        }],
+        fingerprint,
+    );
+
+    test_nats_client
+        .publish(
+            nats_subjects::write_variables_command(PROVIDER_ID),
+            write_cmd_payload.clone(),
+        )
+        .await
+        .expect("should publish write command");
+    test_nats_client.flush().await.unwrap();
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    // act & assert
+    //the pending write command is not consumed yet
+    let result = provider.drain_writes(Duration::from_millis(100)).await;
+    assert!(matches!(result, Err(DrainWritesError::Timeout)));
+
+    let write_commands = subscription_to_write_cmd
+        .try_recv()
+        .expect("pending write command should still be delivered");
+    assert_eq!(write_commands.len(), 1);
+
+    provider
+        .drain_writes(Duration::from_millis(100))
+        .await
+        .expect("write channels should be empty");
+
+    //new write commands are ignored after draining
+    test_nats_client
+        .publish(
+            nats_subjects::write_variables_command(PROVIDER_ID),
+            write_cmd_payload,
+        )
+        .await
+        .expect("should publish write command");
+
+    assert!(
+        timeout(Duration::from_millis(500), subscription_to_write_cmd.recv())
+            .await
+            .is_err(),
+        "write command after draining should be ignored"
+    );
+}
+
+#[tokio::test]
+#[serial]
+async fn test_add_and_subscribe() {
+    // Prepare
+    let _fake_registry = FakeRegistry::new().await;
+    let auth_nats_con = utils::create_auth_con(PROVIDER_ID).await;
+    let test_nats_client = auth_nats_con.get_client().clone();
+
+    let var1 = VariableBuilder::new(0, "my_folder.my_variable_1_rw")
+        .access_type(VariableAccessType::ReadWrite)
+        .initial_value(true)
+        .build()
+        .expect("variable should build");
+    let var2 = VariableBuilder::new(1, "my_folder.my_variable_2_rw")
+        .access_type(VariableAccessType::ReadWrite)
+        .initial_value(1)
+        .build()
+        .expect("variable should build");
+
+    let provider = ProviderBuilder::new()
+        .add_variables(vec![var1.clone()])
+        .expect("Variables should be added")
+        .register_with_existing_connection(auth_nats_con)
+        .await
+        .expect("provider should register");
+
+    let mut def_changed_subscription = test_nats_client
+        .subscribe(nats_subjects::provider_changed_event(PROVIDER_ID))
+        .await
+        .unwrap();
+
+    // act
+    let mut subscription_to_write_cmd = provider
+        .add_and_subscribe(vec![var2.clone()])
+        .await
+        .expect("should add and subscribe");
+
+    // assert
+    let msg = timeout(Duration::from_secs(1), def_changed_subscription.next())
+        .await
+        .expect("should receive the provider definition with the new variable")
+        .unwrap();
+    let provider_definition = root_as_read_provider_definition_query_response(&msg.payload)
+        .unwrap()
+        .unpack()
+        .provider_definition
+        .expect("there should be a provider definition");
+    assert_eq!(
+        provider_definition
+            .variable_definitions
+            .unwrap_or_default()
+            .len(),
+        2
+    );
+
+    let write_cmd_payload = build_write_variables_command(
+        vec![VariableUpdate {
+            id: var2.get_definition().id,
+            value: (&VariableValue::Int(2)).into(),
+        }],
+        provider_definition.fingerprint,
+    );
+    test_nats_client
+        .publish(
+            nats_subjects::write_variables_command(PROVIDER_ID),
+            write_cmd_payload,
+        )
+        .await
+        .expect("should publish write command");
+
+    let write_commands = timeout(Duration::from_secs(1), subscription_to_write_cmd.recv())
+        .await
+        .expect("should receive write command")
+        .expect("write notifier should be open");
+    assert_eq!(write_commands.len(), 1);
+    assert_eq!(write_commands[0].id, var2.get_definition().id);
+    assert_eq!(write_commands[0].value, VariableValue::Int(2));
+
+    //a second subscription of the same variable conflicts, so nothing is added
+    let result = provider.add_and_subscribe(vec![var2]).await;
+    assert!(matches!(
+        result,
+        Err(AddAndSubscribeError::SubscribeToWriteCommand(
+            SubscribeToWriteCommandError::AlreadySubscribed(_)
+        ))
+    ));
+}
+
+#[tokio::test]
+#[serial]
+async fn test_subscribe_to_all_write_commands() {
+    // Prepare
+    let _fake_registry = FakeRegistry::new().await;
+    let auth_nats_con = utils::create_auth_con(PROVIDER_ID).await;
+    let test_nats_client = auth_nats_con.get_client().clone();
+
+    let var1 = VariableBuilder::new(0, "my_folder.my_variable_1_rw")
+        .access_type(VariableAccessType::ReadWrite)
+        .initial_value(true)
+        .build()
+        .expect("variable should build");
+    let var2 = VariableBuilder::new(1, "my_folder.my_variable_2_rw")
+        .access_type(VariableAccessType::ReadWrite)
+        .initial_value(1)
+        .build()
+        .expect("variable should build");
+
+    let provider = ProviderBuilder::new()
+        .add_variables(vec![var1.clone()])
+        .expect("Variables should be added")
+        .register_with_existing_connection(auth_nats_con)
+        .await
+        .expect("provider should register");
+
+    let mut def_changed_subscription = test_nats_client
+        .subscribe(nats_subjects::provider_changed_event(PROVIDER_ID))
+        .await
+        .unwrap();
+
+    // act
+    let mut subscription_to_write_cmd = provider
+        .subscribe_to_all_write_commands()
+        .await
+        .expect("should subscribe to all variables");
+
+    // variables added afterwards are covered as well
+    provider
+        .add_variables(vec![var2.clone()])
+        .await
+        .expect("should add variable");
+
+    // assert
+    let msg = timeout(Duration::from_secs(1), def_changed_subscription.next())
+        .await
+        .expect("should receive the provider definition with the new variable")
+        .unwrap();
+    let provider_definition = root_as_read_provider_definition_query_response(&msg.payload)
+        .unwrap()
+        .unpack()
+        .provider_definition
+        .expect("there should be a provider definition");
+
+    let write_cmd_payload = build_write_variables_command(
+        vec![
+            VariableUpdate {
+                id: var1.get_definition().id,
+                value: (&VariableValue::Boolean(false)).into(),
+            },
+            VariableUpdate {
+                id: var2.get_definition().id,
+                value: (&VariableValue::Int(2)).into(),
+            },
+        ],
+        provider_definition.fingerprint,
+    );
+    test_nats_client
+        .publish(
+            nats_subjects::write_variables_command(PROVIDER_ID),
+            write_cmd_payload,
+        )
+        .await
+        .expect("should publish write command");
+
+    let write_commands = timeout(Duration::from_secs(1), subscription_to_write_cmd.recv())
+        .await
+        .expect("should receive write command")
+        .expect("write notifier should be open");
+    assert_eq!(write_commands.len(), 2);
+    assert_eq!(write_commands[0].value, VariableValue::Boolean(false));
+    assert_eq!(write_commands[1].value, VariableValue::Int(2));
+
+    //other write subscriptions conflict with the subscription of all variables
+    let result = provider.subscribe_to_all_write_commands().await;
+    assert!(matches!(
+        result,
+        Err(SubscribeToWriteCommandError::AllVariablesSubscribed)
+    ));
+    let result = provider
+        .subscribe_to_write_command(vec![var1.clone()])
+        .await;
+    assert!(matches!(
+        result,
+        Err(SubscribeToWriteCommandError::AllVariablesSubscribed)
+    ));
+
+    //dropping the receiver ends the subscription
+    drop(subscription_to_write_cmd);
+    provider
+        .subscribe_to_write_command(vec![var1])
+        .await
+        .expect("should subscribe after the subscription of all variables ended");
+    let result = provider.subscribe_to_all_write_commands().await;
+    assert!(matches!(
+        result,
+        Err(SubscribeToWriteCommandError::AlreadySubscribed(_))
+    ));
+}
+
+#[tokio::test]
+#[serial]
+async fn test_write_bounds() {
+    // Prepare
+    let _fake_registry = FakeRegistry::new().await;
+    let auth_nats_con = utils::create_auth_con(PROVIDER_ID).await;
+    let test_nats_client = auth_nats_con.get_client().clone();
+
+    let mut def_changed_subscription = test_nats_client
+        .subscribe(nats_subjects::provider_changed_event(PROVIDER_ID))
+        .await
+        .unwrap();
+
+    let rejecting_var = VariableBuilder::new(0, "my_folder.rejecting_rw")
+        .access_type(VariableAccessType::ReadWrite)
+        .initial_value(5)
+        .min(0)
+        .max(10)
+        .build()
+        .expect("variable should build");
+    let clamping_var = VariableBuilder::new(1, "my_folder.clamping_rw")
+        .access_type(VariableAccessType::ReadWrite)
+        .initial_value(0.5)
+        .min(0.0)
+        .max(1.0)
+        .out_of_bounds_writes(OutOfBoundsWrite::Clamp)
+        .build()
+        .expect("variable should build");
+
+    let provider = ProviderBuilder::new()
+        .add_variables(vec![rejecting_var.clone(), clamping_var.clone()])
+        .expect("Variables should be added")
+        .register_with_existing_connection(auth_nats_con)
+        .await
+        .expect("provider should register");
+
+    let mut subscription_to_write_cmd = provider
+        .subscribe_to_write_command(vec![rejecting_var.clone(), clamping_var.clone()])
+        .await
+        .expect("should subscribe");
+
+    let msg = timeout(Duration::from_secs(1), def_changed_subscription.next())
+        .await
+        .expect("should receive the provider definition")
+        .unwrap();
+    let provider_definition = root_as_read_provider_definition_query_response(&msg.payload)
+        .unwrap()
+        .unpack()
+        .provider_definition
+        .expect("there should be a provider definition");
+
+    // act
+    let write_cmd_payload = build_write_variables_command(
+        vec![
+            VariableUpdate {
+                id: rejecting_var.get_definition().id,
+                value: (&VariableValue::Int(11)).into(),
+            },
+            VariableUpdate {
+                id: clamping_var.get_definition().id,
+                value: (&VariableValue::Float64(-3.0)).into(),
+            },
+        ],
+        provider_definition.fingerprint,
+    );
+    test_nats_client
+        .publish(
+            nats_subjects::write_variables_command(PROVIDER_ID),
+            write_cmd_payload,
+        )
+        .await
+        .expect("should publish write command");
+
+    // assert
+    let write_commands = timeout(Duration::from_secs(1), subscription_to_write_cmd.recv())
+        .await
+        .expect("should receive write command")
+        .expect("write notifier should be open");
+    assert_eq!(write_commands.len(), 1);
+    assert_eq!(write_commands[0].id, clamping_var.get_definition().id);
+    assert_eq!(write_commands[0].value, VariableValue::Float64(0.0));
+}
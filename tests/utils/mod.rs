@@ -9,56 +9,9 @@
     clippy::panic,
     clippy::indexing_slicing
 )]
-#![allow(dead_code)]
-///As integration tests are compiled into their own crates, this leads to unused warnings they dont use all functions of the utils module
-pub mod fake_registry;
-
-use std::{future::IntoFuture, sync::Arc, time::Duration};
-
-use tokio::time::timeout;
-use u_os_hub_client::{
-    authenticated_nats_con::{
-        AuthenticatedNatsConnection, AuthenticationSettingsBuilder, NatsPermission,
-    },
-    oauth2::OAuth2Credentials,
+#![allow(unused_imports)]
+// As integration tests are compiled into their own crates, this leads to unused warnings if they dont use all items of the utils module
+pub use u_os_hub_client::test_support::{
+    create_auth_con, create_auth_con_with_perm, fake_registry, run_with_timeout, DEFAULT_TIMEOUT,
+    NATS_HOSTNAME,
 };
-
-pub const NATS_HOSTNAME: &str = "nats://localhost:4222";
-
-/// As some nats operations are async and may wait for a long time, we define a default timeout to avoid blocking tests forever.
-pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
-
-pub async fn create_auth_con(client_name: &str) -> Arc<AuthenticatedNatsConnection> {
-    create_auth_con_with_perm(client_name, NatsPermission::VariableHubProvide).await
-}
-
-pub async fn create_auth_con_with_perm(
-    client_name: &str,
-    perm: NatsPermission,
-) -> Arc<AuthenticatedNatsConnection> {
-    let auth_settings = AuthenticationSettingsBuilder::new(perm)
-        .with_credentials(OAuth2Credentials {
-            client_name: client_name.to_owned(),
-            client_id: String::new(),
-            client_secret: String::new(),
-        })
-        .build();
-
-    tracing::info!("Creating AuthenticatedNatsConnection");
-    let con = timeout(
-        Duration::from_secs(10),
-        AuthenticatedNatsConnection::new(NATS_HOSTNAME, &auth_settings),
-    )
-    .await
-    .unwrap()
-    .unwrap();
-
-    Arc::new(con)
-}
-
-pub async fn run_with_timeout<F>(future: F) -> <F as IntoFuture>::Output
-where
-    F: IntoFuture,
-{
-    timeout(DEFAULT_TIMEOUT, future).await.expect("Timeout")
-}